@@ -2,6 +2,17 @@ fn main() {
     // Load .env file for WiFi configuration
     load_env_config();
 
+    // Board settings from board.toml, also needed by host builds
+    generate_board_config();
+
+    // Commit hash reported by the firmware info query
+    emit_build_hash();
+
+    // Host builds (unit tests) link against std without the chip linker scripts
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("none") {
+        return;
+    }
+
     linker_be_nice();
     // make sure linkall.x is the last linker script (otherwise might cause problems with flip-link)
     println!("cargo:rustc-link-arg=-Tlinkall.x");
@@ -19,6 +30,11 @@ fn load_env_config() {
     // Tell cargo to rerun if atmosphere variables change
     println!("cargo:rerun-if-env-changed=WIFI_SSID");
     println!("cargo:rerun-if-env-changed=WIFI_PASSWORD");
+    println!("cargo:rerun-if-env-changed=LED_PSK");
+    println!("cargo:rerun-if-env-changed=MQTT_USERNAME");
+    println!("cargo:rerun-if-env-changed=MQTT_PASSWORD");
+    println!("cargo:rerun-if-env-changed=CONFIG_AP_PASSWORD");
+    println!("cargo:rerun-if-env-changed=OTA_URL");
 
     // Try to load .env file if it exists
     if Path::new(".env").exists() {
@@ -43,9 +59,65 @@ fn load_env_config() {
     println!("cargo:rustc-env=WIFI_SSID={}", wifi_ssid);
     println!("cargo:rustc-env=WIFI_PASSWORD={}", wifi_password);
 
+    // Pre-shared key for encrypted LED data (empty disables encryption)
+    let led_psk = env::var("LED_PSK")
+        .unwrap_or_else(|_| String::new())
+        .trim()
+        .to_string();
+    println!("cargo:rustc-env=LED_PSK={}", led_psk);
+
+    if !led_psk.is_empty() {
+        if led_psk.len() == 64 && led_psk.chars().all(|c| c.is_ascii_hexdigit()) {
+            println!("cargo:warning=LED_PSK configured - plaintext LED data will be rejected");
+        } else {
+            println!("cargo:warning=LED_PSK must be 64 hex digits - encryption disabled");
+        }
+    }
+
+    // MQTT broker credentials (empty connects anonymously)
+    for name in ["MQTT_USERNAME", "MQTT_PASSWORD"] {
+        let value = env::var(name)
+            .unwrap_or_else(|_| String::new())
+            .trim()
+            .to_string();
+        println!("cargo:rustc-env={}={}", name, value);
+    }
+
+    // Configuration access point password (empty keeps it off)
+    let config_ap_password = env::var("CONFIG_AP_PASSWORD")
+        .unwrap_or_else(|_| String::new())
+        .trim()
+        .to_string();
+    println!("cargo:rustc-env=CONFIG_AP_PASSWORD={}", config_ap_password);
+
+    if !config_ap_password.is_empty() {
+        if (8..=63).contains(&config_ap_password.len()) {
+            println!("cargo:warning=CONFIG_AP_PASSWORD configured - configuration AP enabled");
+        } else {
+            println!(
+                "cargo:warning=CONFIG_AP_PASSWORD must be 8-63 characters - configuration AP disabled"
+            );
+        }
+    }
+
+    // Firmware update source (empty means updates need a URL in the command)
+    let ota_url = env::var("OTA_URL")
+        .unwrap_or_else(|_| String::new())
+        .trim()
+        .to_string();
+    println!("cargo:rustc-env=OTA_URL={}", ota_url);
+
+    if !ota_url.is_empty() && !ota_url.starts_with("http://") {
+        println!(
+            "cargo:warning=OTA_URL must start with http:// - updates need a URL in the command"
+        );
+    }
+
     // Print status
     if wifi_ssid.is_empty() {
-        println!("cargo:warning=WIFI_SSID is empty - WiFi will not be configured");
+        println!(
+            "cargo:warning=WIFI_SSID is empty - the board opens a setup access point until provisioned"
+        );
     } else {
         println!("cargo:warning=WIFI_SSID configured: {}", wifi_ssid);
     }
@@ -60,6 +132,162 @@ fn load_env_config() {
     }
 }
 
+/// Set `BUILD_HASH` to the short commit hash, `-dirty` with uncommitted changes
+fn emit_build_hash() {
+    use std::process::Command;
+
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    let hash = match git(&["rev-parse", "--short=12", "HEAD"]) {
+        Some(hash) if git(&["status", "--porcelain"]).is_some_and(|s| !s.is_empty()) => {
+            format!("{}-dirty", hash)
+        }
+        Some(hash) => hash,
+        None => String::from("unknown"),
+    };
+    println!("cargo:rustc-env=BUILD_HASH={}", hash);
+
+    // A new commit or checkout moves HEAD or the branch it points to
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    if let Some(reference) = std::fs::read_to_string(".git/HEAD")
+        .ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|r| r.trim().to_string()))
+    {
+        println!("cargo:rerun-if-changed=.git/{}", reference);
+    }
+    println!("cargo:rerun-if-changed=.git/index");
+}
+
+/// Turn the optional `board.toml` into `$OUT_DIR/board_config.rs`
+///
+/// Every setting is an `Option`, `None` when the file or the key is absent, so
+/// the defaults stay in `src/lib.rs`. `BOARD_CONFIG` points at another file.
+fn generate_board_config() {
+    use std::env;
+    use std::fmt::Write;
+    use std::path::Path;
+
+    println!("cargo:rerun-if-env-changed=BOARD_CONFIG");
+    let path = env::var("BOARD_CONFIG").unwrap_or_else(|_| "board.toml".to_string());
+    println!("cargo:rerun-if-changed={}", path);
+
+    let table = if Path::new(&path).exists() {
+        let text = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e));
+        let table: toml::Table = text
+            .parse()
+            .unwrap_or_else(|e| panic!("Invalid {}: {}", path, e));
+        println!("cargo:warning=Loaded board configuration from {}", path);
+        table
+    } else {
+        toml::Table::new()
+    };
+
+    let section = |name: &str| -> toml::Table {
+        match table.get(name) {
+            Some(toml::Value::Table(section)) => section.clone(),
+            Some(_) => panic!("{}: [{}] must be a table", path, name),
+            None => toml::Table::new(),
+        }
+    };
+    let strip = section("strip");
+    let defaults = section("defaults");
+
+    let integer = |section: &toml::Table, key: &str, max: i64| -> Option<i64> {
+        let value = section.get(key)?;
+        match value.as_integer() {
+            Some(n) if (0..=max).contains(&n) => Some(n),
+            _ => panic!("{}: {} must be a number from 0 to {}", path, key, max),
+        }
+    };
+    let string = |section: &toml::Table, key: &str| -> Option<String> {
+        let value = section.get(key)?;
+        match value.as_str() {
+            Some(s) => Some(s.to_string()),
+            None => panic!("{}: {} must be a string", path, key),
+        }
+    };
+
+    // An explicit pixel format wins over the one implied by the chipset
+    let pixel_format = string(&strip, "pixel_format")
+        .map(|format| match format.to_ascii_lowercase().as_str() {
+            "rgb" => "Rgb",
+            "grb" => "Grb",
+            "rgbw" => "Rgbw",
+            "grbw" => "Grbw",
+            _ => panic!("{}: unknown pixel_format {:?} (rgb, grb, rgbw, grbw)", path, format),
+        })
+        .or_else(|| {
+            string(&strip, "chipset").map(|chipset| {
+                match chipset.to_ascii_lowercase().as_str() {
+                    "ws2812" | "ws2812b" | "ws2813" | "ws2815" | "sk6812" => "Grb",
+                    "ws2811" => "Rgb",
+                    "sk6812-rgbw" | "sk6812rgbw" => "Grbw",
+                    _ => panic!(
+                        "{}: unknown chipset {:?} (ws2811, ws2812b, ws2813, ws2815, sk6812, sk6812-rgbw)",
+                        path, chipset
+                    ),
+                }
+            })
+        });
+
+    let device_name = string(&defaults, "device_name");
+    if let Some(name) = &device_name
+        && (name.len() > 63 || name.contains('.'))
+    {
+        panic!(
+            "{}: device_name must be at most 63 bytes, without dots",
+            path
+        );
+    }
+
+    let option = |value: Option<String>| match value {
+        Some(value) => format!("Some({})", value),
+        None => "None".to_string(),
+    };
+    let mut out = String::from("// Generated by build.rs from board.toml - do not edit\n");
+    let _ = writeln!(
+        out,
+        "pub const LED_COUNT: Option<usize> = {};",
+        option(integer(&strip, "led_count", u16::MAX as i64).map(|n| n.to_string()))
+    );
+    let _ = writeln!(
+        out,
+        "pub const PIXEL_FORMAT: Option<crate::protocol::PixelFormat> = {};",
+        option(pixel_format.map(|f| format!("crate::protocol::PixelFormat::{}", f)))
+    );
+    let _ = writeln!(
+        out,
+        "pub const LED_DATA_PIN: Option<u8> = {};",
+        option(integer(&strip, "data_pin", 48).map(|n| n.to_string()))
+    );
+    let _ = writeln!(
+        out,
+        "pub const BRIGHTNESS: Option<u8> = {};",
+        option(integer(&defaults, "brightness", 255).map(|n| n.to_string()))
+    );
+    let _ = writeln!(
+        out,
+        "pub const UDP_PORT: Option<u16> = {};",
+        option(integer(&defaults, "udp_port", u16::MAX as i64).map(|n| n.to_string()))
+    );
+    let _ = writeln!(
+        out,
+        "pub const DEVICE_NAME: Option<&str> = {};",
+        option(device_name.map(|name| format!("{:?}", name)))
+    );
+
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    std::fs::write(Path::new(&out_dir).join("board_config.rs"), out)
+        .expect("Failed to write board_config.rs");
+}
+
 fn linker_be_nice() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() > 1 {
@@ -4,7 +4,7 @@
 //! White, Yellow, Cyan, Green, Magenta, Red, Blue, Black (repeating)
 //! This test checks if the LED driver has flickering issues with repeated refreshes.
 //!
-//! Hardware: SK6812-RGBW LEDs, GPIO4, G,R,B,W channel order
+//! Hardware: SK6812-RGBW LEDs on the board profile's data pin, G,R,B,W channel order
 
 #![no_std]
 #![no_main]
@@ -14,7 +14,7 @@ use esp_alloc as _;
 
 use esp_hal::{
     delay::Delay,
-    gpio::{Level, Output, OutputConfig},
+    gpio::{AnyPin, Level, Output, OutputConfig},
     rmt::{PulseCode, Rmt, TxChannelConfig, TxChannelCreator},
     time::Rate,
 };
@@ -90,12 +90,18 @@ fn main() -> ! {
 
     println!("🚀 500 LEDs Refresh Test - Every 500ms");
 
-    let led_pin = Output::new(peripherals.GPIO4, Level::Low, OutputConfig::default())
-        .into_peripheral_output();
+    // SAFETY: nothing else uses the data pin in this test
+    let data_pin = unsafe { AnyPin::steal(board_rs::board_profile::PROFILE.led_data_pin) };
+    let led_pin =
+        Output::new(data_pin, Level::Low, OutputConfig::default()).into_peripheral_output();
 
-    let rmt = Rmt::new(peripherals.RMT, Rate::from_mhz(10)).unwrap();
+    let rmt = Rmt::new(
+        peripherals.RMT,
+        Rate::from_mhz(board_rs::config::RMT_FREQUENCY_MHZ),
+    )
+    .unwrap();
     let tx_config = TxChannelConfig::default()
-        .with_clk_divider(1)
+        .with_clk_divider(board_rs::config::RMT_CLK_DIVIDER)
         .with_idle_output_level(Level::Low)
         .with_idle_output(false)
         .with_carrier_modulation(false);
@@ -10,7 +10,7 @@ extern crate alloc;
 
 use esp_hal::{
     delay::Delay,
-    gpio::{Level, Output, OutputConfig},
+    gpio::{AnyPin, Level, Output, OutputConfig},
     rmt::{PulseCode, Rmt, TxChannelConfig, TxChannelCreator},
     time::Rate,
 };
@@ -40,10 +40,10 @@ fn byte_to_pulses(byte: u8) -> [u32; 8] {
     for i in 0..8 {
         let bit = (byte >> (7 - i)) & 1;
         if bit == 1 {
-            // 1位: 高电平0.8us, 低电平0.45us (在10MHz时钟下: 8个周期高, 4个周期低)
+            // 1位: 高电平0.8us, 低电平0.45us (100ns时钟周期: 8个周期高, 4个周期低)
             pulses[i] = PulseCode::new(Level::High, 8, Level::Low, 4);
         } else {
-            // 0位: 高电平0.4us, 低电平0.85us (在10MHz时钟下: 4个周期高, 8个周期低)
+            // 0位: 高电平0.4us, 低电平0.85us (100ns时钟周期: 4个周期高, 8个周期低)
             pulses[i] = PulseCode::new(Level::High, 4, Level::Low, 8);
         }
     }
@@ -67,7 +67,7 @@ where
         }
     }
 
-    // 添加复位脉冲 (低电平50us = 500个周期在10MHz)
+    // 添加复位脉冲 (低电平50us = 500个100ns周期)
     pulses.push(PulseCode::new(Level::Low, 500, Level::Low, 0));
 
     channel.transmit(&pulses)?.wait()
@@ -84,37 +84,45 @@ fn main() -> ! {
     let peripherals = esp_hal::init(config);
 
     println!("🚀 最小LED测试程序启动");
-    println!("📍 使用GPIO4作为LED数据引脚");
+    // 数据引脚来自板级配置 (board_profile)
+    let pin = board_rs::board_profile::PROFILE.led_data_pin;
+    println!("📍 使用GPIO{}作为LED数据引脚", pin);
 
     // 基本GPIO测试
-    println!("🔧 测试GPIO4基本功能...");
-    let mut gpio_test = Output::new(peripherals.GPIO4, Level::Low, OutputConfig::default());
+    println!("🔧 测试GPIO{}基本功能...", pin);
+    // SAFETY: 测试程序中没有其他驱动使用该引脚
+    let data_pin = unsafe { AnyPin::steal(pin) };
+    let mut gpio_test = Output::new(data_pin, Level::Low, OutputConfig::default());
     for i in 1..=5 {
         gpio_test.set_high();
-        println!("GPIO4 HIGH ({})", i);
+        println!("GPIO{} HIGH ({})", pin, i);
         // 简单延时
         for _ in 0..1000000 {
             core::hint::spin_loop();
         }
 
         gpio_test.set_low();
-        println!("GPIO4 LOW ({})", i);
+        println!("GPIO{} LOW ({})", pin, i);
         for _ in 0..1000000 {
             core::hint::spin_loop();
         }
     }
-    println!("✅ GPIO4基本测试完成");
+    println!("✅ GPIO{}基本测试完成", pin);
 
     // 转换引脚用于RMT
     let led_pin = gpio_test.into_peripheral_output();
 
     // 初始化RMT外设
     println!("🔧 初始化RMT外设...");
-    let rmt = Rmt::new(peripherals.RMT, Rate::from_mhz(10)).unwrap();
+    let rmt = Rmt::new(
+        peripherals.RMT,
+        Rate::from_mhz(board_rs::config::RMT_FREQUENCY_MHZ),
+    )
+    .unwrap();
 
     // 配置RMT通道用于RGBW LED控制
     let tx_config = TxChannelConfig::default()
-        .with_clk_divider(1)
+        .with_clk_divider(board_rs::config::RMT_CLK_DIVIDER)
         .with_idle_output_level(Level::Low)
         .with_idle_output(false)
         .with_carrier_modulation(false);
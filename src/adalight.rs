@@ -0,0 +1,139 @@
+//! Adalight serial input module
+//!
+//! Wired fallback ingest path for when Wi-Fi is congested: a UART speaking the
+//! Adalight protocol used by Prismatik, Hyperion and similar tools.
+//!
+//! Frame: `"Ada"[count-1 hi][count-1 lo][hi ^ lo ^ 0x55]` followed by RGB
+//! triples for `count` LEDs.
+
+use crate::config;
+use crate::led_control::{LedData, LedDataSignal};
+use crate::println;
+use alloc::vec::Vec;
+use embassy_time::Instant;
+use esp_hal::Async;
+use esp_hal::uart::Uart;
+
+/// Adalight header magic
+const MAGIC: &[u8; 3] = b"Ada";
+
+/// Parser position within an Adalight frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseState {
+    /// Matched this many magic bytes
+    Magic(usize),
+    CountHigh,
+    CountLow(u8),
+    Checksum(u8, u8),
+    /// Collecting RGB data
+    Data,
+}
+
+/// Incremental Adalight stream parser
+#[derive(Debug)]
+pub struct AdalightParser {
+    state: ParseState,
+    expected: usize,
+    rgb: Vec<u8>,
+}
+
+impl Default for AdalightParser {
+    fn default() -> Self {
+        Self {
+            state: ParseState::Magic(0),
+            expected: 0,
+            rgb: Vec::new(),
+        }
+    }
+}
+
+impl AdalightParser {
+    /// Feed one byte, returning the RGB data once a whole frame has arrived
+    pub fn push(&mut self, byte: u8) -> Option<&[u8]> {
+        self.state = match self.state {
+            ParseState::Magic(matched) => {
+                if byte == MAGIC[matched] {
+                    if matched + 1 == MAGIC.len() {
+                        ParseState::CountHigh
+                    } else {
+                        ParseState::Magic(matched + 1)
+                    }
+                } else if byte == MAGIC[0] {
+                    ParseState::Magic(1)
+                } else {
+                    ParseState::Magic(0)
+                }
+            }
+            ParseState::CountHigh => ParseState::CountLow(byte),
+            ParseState::CountLow(high) => ParseState::Checksum(high, byte),
+            ParseState::Checksum(high, low) => {
+                if byte != high ^ low ^ 0x55 {
+                    // Corrupt header - resynchronise on the next magic
+                    ParseState::Magic(0)
+                } else {
+                    let count = u16::from_be_bytes([high, low]) as usize + 1;
+                    if count > config::MAX_LEDS {
+                        ParseState::Magic(0)
+                    } else {
+                        self.expected = count * 3;
+                        self.rgb.clear();
+                        ParseState::Data
+                    }
+                }
+            }
+            ParseState::Data => {
+                self.rgb.push(byte);
+                if self.rgb.len() < self.expected {
+                    ParseState::Data
+                } else {
+                    self.state = ParseState::Magic(0);
+                    return Some(&self.rgb);
+                }
+            }
+        };
+        None
+    }
+}
+
+/// Adalight UART receiver task
+#[embassy_executor::task]
+pub async fn adalight_task(
+    mut uart: Uart<'static, Async>,
+    led_data_sender: &'static LedDataSignal,
+) {
+    println!(
+        "[ADALIGHT] Listening on UART at {} baud",
+        config::ADALIGHT_BAUD_RATE
+    );
+
+    let mut parser = AdalightParser::default();
+    let mut buffer = [0u8; 256];
+
+    loop {
+        crate::memory::checkpoint(crate::memory::Subsystem::Adalight);
+
+        let len = match uart.read_async(&mut buffer).await {
+            Ok(len) => len,
+            Err(e) => {
+                println!("[ADALIGHT] UART error: {:?}", e);
+                continue;
+            }
+        };
+
+        for &byte in &buffer[..len] {
+            if let Some(rgb) = parser.push(byte) {
+                let rgb_len = rgb.len().min(crate::config_store::led_count() * 3);
+                let mut data = crate::led_control::take_recycled_frame().unwrap_or_default();
+                crate::led_control::rgb_to_pixel_format(
+                    &rgb[..rgb_len],
+                    crate::config_store::pixel_format(),
+                    &mut data,
+                );
+                led_data_sender.signal(LedData {
+                    data,
+                    timestamp: Instant::now(),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Throughput self-benchmark module
+//!
+//! While a benchmark window is open the UDP server and LED task record how
+//! many packets and frames pass through each stage and how long the stages
+//! take. The host blasts ordinary LED data packets (0x02/0x05) during the
+//! window, so the figures reflect the real ingest path.
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+use embassy_time::Instant;
+
+/// Timing of one pipeline stage
+#[derive(Debug, Clone, Copy)]
+pub struct StageTiming {
+    pub count: u32,
+    pub total_us: u64,
+    pub max_us: u32,
+}
+
+impl StageTiming {
+    pub(crate) const EMPTY: Self = Self {
+        count: 0,
+        total_us: 0,
+        max_us: 0,
+    };
+
+    pub(crate) fn record(&mut self, us: u32) {
+        self.count = self.count.saturating_add(1);
+        self.total_us += us as u64;
+        self.max_us = self.max_us.max(us);
+    }
+
+    /// Average stage time in microseconds
+    pub fn average_us(&self) -> u32 {
+        if self.count == 0 {
+            0
+        } else {
+            (self.total_us / self.count as u64) as u32
+        }
+    }
+}
+
+/// Counters collected during a benchmark window
+#[derive(Debug, Clone, Copy)]
+pub struct BenchmarkStats {
+    pub elapsed_ms: u32,
+    /// LED data packets accepted by the UDP server
+    pub packets: u32,
+    pub bytes: u32,
+    /// Sequenced packets lost in transit (sequence gaps)
+    pub lost: u32,
+    /// Frames handed to the LED task
+    pub frames_committed: u32,
+    /// Frames actually sent to the strip
+    pub frames_rendered: u32,
+    /// Receive-to-framebuffer time per packet
+    pub parse: StageTiming,
+    /// RMT transmit time per rendered frame
+    pub render: StageTiming,
+}
+
+impl BenchmarkStats {
+    const EMPTY: Self = Self {
+        elapsed_ms: 0,
+        packets: 0,
+        bytes: 0,
+        lost: 0,
+        frames_committed: 0,
+        frames_rendered: 0,
+        parse: StageTiming::EMPTY,
+        render: StageTiming::EMPTY,
+    };
+
+    /// Frames committed but overwritten before the LED task rendered them
+    pub fn frames_dropped(&self) -> u32 {
+        self.frames_committed.saturating_sub(self.frames_rendered)
+    }
+
+    /// Rendered frames per second, times 100
+    pub fn fps_x100(&self) -> u32 {
+        if self.elapsed_ms == 0 {
+            0
+        } else {
+            (self.frames_rendered as u64 * 100_000 / self.elapsed_ms as u64) as u32
+        }
+    }
+
+    /// Encode as a benchmark report:
+    /// `[header][elapsed ms u32][packets u32][bytes u32][lost u32][committed u32]`
+    /// `[rendered u32][dropped u32][fps x100 u32]`
+    /// `[parse avg us u32][parse max us u32][render avg us u32][render max us u32]`
+    pub fn to_response(&self) -> [u8; 49] {
+        let fields = [
+            self.elapsed_ms,
+            self.packets,
+            self.bytes,
+            self.lost,
+            self.frames_committed,
+            self.frames_rendered,
+            self.frames_dropped(),
+            self.fps_x100(),
+            self.parse.average_us(),
+            self.parse.max_us,
+            self.render.average_us(),
+            self.render.max_us,
+        ];
+
+        let mut response = [0u8; 49];
+        response[0] = crate::config::BENCHMARK_HEADER;
+        for (chunk, value) in response[1..].chunks_exact_mut(4).zip(fields) {
+            chunk.copy_from_slice(&value.to_be_bytes());
+        }
+        response
+    }
+}
+
+struct Benchmark {
+    started: Option<Instant>,
+    stats: BenchmarkStats,
+}
+
+static BENCHMARK: Mutex<RefCell<Benchmark>> = Mutex::new(RefCell::new(Benchmark {
+    started: None,
+    stats: BenchmarkStats::EMPTY,
+}));
+
+/// Open a new benchmark window, discarding previous results
+pub fn start() {
+    critical_section::with(|cs| {
+        let mut bench = BENCHMARK.borrow_ref_mut(cs);
+        bench.started = Some(Instant::now());
+        bench.stats = BenchmarkStats::EMPTY;
+    });
+}
+
+/// Close the benchmark window and return its results
+pub fn stop() -> BenchmarkStats {
+    critical_section::with(|cs| {
+        let mut bench = BENCHMARK.borrow_ref_mut(cs);
+        if let Some(started) = bench.started.take() {
+            bench.stats.elapsed_ms = started.elapsed().as_millis() as u32;
+        }
+        bench.stats
+    })
+}
+
+/// Record an accepted LED data packet and its processing time
+pub fn record_packet(bytes: usize, since: Instant) {
+    let us = since.elapsed().as_micros() as u32;
+    critical_section::with(|cs| {
+        let mut bench = BENCHMARK.borrow_ref_mut(cs);
+        if bench.started.is_some() {
+            bench.stats.packets = bench.stats.packets.saturating_add(1);
+            bench.stats.bytes = bench.stats.bytes.saturating_add(bytes as u32);
+            bench.stats.parse.record(us);
+        }
+    });
+}
+
+/// Record packets lost to sequence gaps
+pub fn record_lost(count: u32) {
+    critical_section::with(|cs| {
+        let mut bench = BENCHMARK.borrow_ref_mut(cs);
+        if bench.started.is_some() {
+            bench.stats.lost = bench.stats.lost.saturating_add(count);
+        }
+    });
+}
+
+/// Record a frame handed to the LED task
+pub fn record_commit() {
+    critical_section::with(|cs| {
+        let mut bench = BENCHMARK.borrow_ref_mut(cs);
+        if bench.started.is_some() {
+            bench.stats.frames_committed = bench.stats.frames_committed.saturating_add(1);
+        }
+    });
+}
+
+/// Record a new frame sent to the strip and its transmit time
+pub fn record_render(since: Instant) {
+    let us = since.elapsed().as_micros() as u32;
+    critical_section::with(|cs| {
+        let mut bench = BENCHMARK.borrow_ref_mut(cs);
+        if bench.started.is_some() {
+            bench.stats.frames_rendered = bench.stats.frames_rendered.saturating_add(1);
+            bench.stats.render.record(us);
+        }
+    });
+}
@@ -0,0 +1,239 @@
+//! BLE provisioning module
+//!
+//! Factory-fresh boards have no network to be reached on, so while the board
+//! is in provisioning mode it also advertises a GATT service over BLE. The
+//! companion app connects, writes the Wi-Fi credentials and an optional device
+//! name, then writes `1` to the apply characteristic; the board stores them
+//! and restarts to join the network, exactly like the captive portal.
+//!
+//! Service `4c0a0001-6f1b-4c4e-9a7e-1a5b2d3c4e5f`:
+//!
+//! - `...0002` SSID (write, UTF-8, up to 32 bytes)
+//! - `...0003` password (write, UTF-8, up to 64 bytes, empty for open networks)
+//! - `...0004` device name (write, UTF-8, up to 63 bytes, becomes the mDNS instance name)
+//! - `...0005` apply (write `1` to save and restart; read gives the last result)
+
+use crate::println;
+use crate::provisioning::{self, WifiCredentials};
+use bt_hci::controller::ExternalController;
+use embassy_futures::join::join;
+use embassy_time::{Duration, Timer};
+use esp_wifi::ble::controller::BleConnector;
+use heapless::{String, Vec};
+use trouble_host::prelude::*;
+
+/// Apply result: nothing applied yet
+pub const APPLY_IDLE: u8 = 0;
+/// Apply result: credentials missing or invalid
+pub const APPLY_INVALID: u8 = 2;
+/// Apply result: credentials couldn't be stored
+pub const APPLY_STORAGE_FAILED: u8 = 3;
+
+/// Command written to the apply characteristic
+const APPLY_COMMAND: u8 = 1;
+
+/// HCI command slots shared with the controller
+const HCI_SLOTS: usize = 20;
+
+/// A single app connects at a time
+const CONNECTIONS_MAX: usize = 1;
+
+/// L2CAP channels: signalling and ATT
+const L2CAP_CHANNELS_MAX: usize = 2;
+
+#[gatt_server]
+struct Server {
+    provisioning: ProvisioningService,
+}
+
+#[gatt_service(uuid = "4c0a0001-6f1b-4c4e-9a7e-1a5b2d3c4e5f")]
+struct ProvisioningService {
+    #[characteristic(uuid = "4c0a0002-6f1b-4c4e-9a7e-1a5b2d3c4e5f", write)]
+    ssid: Vec<u8, 32>,
+    #[characteristic(uuid = "4c0a0003-6f1b-4c4e-9a7e-1a5b2d3c4e5f", write)]
+    password: Vec<u8, 64>,
+    #[characteristic(uuid = "4c0a0004-6f1b-4c4e-9a7e-1a5b2d3c4e5f", write)]
+    name: Vec<u8, 63>,
+    #[characteristic(uuid = "4c0a0005-6f1b-4c4e-9a7e-1a5b2d3c4e5f", read, write)]
+    apply: u8,
+}
+
+/// Values written by the app during one connection
+#[derive(Default)]
+struct Submission {
+    ssid: String<32>,
+    password: String<64>,
+    name: String<63>,
+}
+
+impl Submission {
+    /// Store a write into the matching field (invalid UTF-8 or overlong values are ignored)
+    fn write<const N: usize>(field: &mut String<N>, data: &[u8]) {
+        field.clear();
+        if let Ok(value) = core::str::from_utf8(data) {
+            field.push_str(value).ok();
+        }
+    }
+
+    /// Store the submission and restart; only returns on failure
+    async fn apply(&self) -> u8 {
+        let Some(credentials) = WifiCredentials::new(&self.ssid, &self.password) else {
+            return APPLY_INVALID;
+        };
+        if !self.name.is_empty()
+            && let Err(e) = provisioning::store_device_name(&self.name)
+        {
+            println!("[BLE] Rejected device name: {:?}", e);
+            return APPLY_INVALID;
+        }
+        match provisioning::apply(&credentials).await {
+            Ok(()) => APPLY_IDLE,
+            Err(e) => {
+                println!("[BLE] Failed to save credentials: {:?}", e);
+                APPLY_STORAGE_FAILED
+            }
+        }
+    }
+}
+
+/// Advertise until an app connects
+async fn advertise<'values, 'server, C: Controller>(
+    name: &'values str,
+    peripheral: &mut Peripheral<'values, C, DefaultPacketPool>,
+    server: &'server Server<'values>,
+) -> Result<GattConnection<'values, 'server, DefaultPacketPool>, BleHostError<C::Error>> {
+    let mut adv_data = [0; 31];
+    let len = AdStructure::encode_slice(
+        &[
+            AdStructure::Flags(LE_GENERAL_DISCOVERABLE | BR_EDR_NOT_SUPPORTED),
+            AdStructure::CompleteLocalName(name.as_bytes()),
+        ],
+        &mut adv_data[..],
+    )?;
+    let advertiser = peripheral
+        .advertise(
+            &Default::default(),
+            Advertisement::ConnectableScannableUndirected {
+                adv_data: &adv_data[..len],
+                scan_data: &[],
+            },
+        )
+        .await?;
+    Ok(advertiser.accept().await?.with_attribute_server(server)?)
+}
+
+/// Handle GATT requests until the app disconnects
+async fn serve(server: &Server<'_>, conn: &GattConnection<'_, '_, DefaultPacketPool>) {
+    let service = &server.provisioning;
+    let mut submission = Submission::default();
+
+    loop {
+        crate::memory::checkpoint(crate::memory::Subsystem::Provisioning);
+
+        match conn.next().await {
+            GattConnectionEvent::Disconnected { reason } => {
+                println!("[BLE] App disconnected: {:?}", reason);
+                return;
+            }
+            GattConnectionEvent::Gatt { event } => {
+                provisioning::mark_portal_activity();
+                let mut apply = false;
+                if let GattEvent::Write(write) = &event {
+                    let handle = write.handle();
+                    let data = write.data();
+                    if handle == service.ssid.handle {
+                        Submission::write(&mut submission.ssid, data);
+                    } else if handle == service.password.handle {
+                        Submission::write(&mut submission.password, data);
+                    } else if handle == service.name.handle {
+                        Submission::write(&mut submission.name, data);
+                    } else if handle == service.apply.handle {
+                        apply = data == [APPLY_COMMAND];
+                    }
+                }
+
+                match event.accept() {
+                    Ok(reply) => reply.send().await,
+                    Err(e) => println!("[BLE] Failed to answer request: {:?}", e),
+                }
+
+                if apply {
+                    println!(
+                        "[BLE] Applying credentials for '{}'",
+                        submission.ssid.as_str()
+                    );
+                    let result = submission.apply().await;
+                    service.apply.set(server, &result).ok();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// BLE provisioning task
+///
+/// Runs the host stack for the whole uptime but only advertises while the
+/// board is in provisioning mode.
+#[embassy_executor::task]
+pub async fn ble_provisioning_task(connector: BleConnector<'static>) {
+    let controller: ExternalController<_, HCI_SLOTS> = ExternalController::new(connector);
+    let mac = esp_hal::efuse::Efuse::read_base_mac_address();
+    let address = Address::random([mac[5], mac[4], mac[3], mac[2], mac[1], mac[0] | 0xC0]);
+
+    let mut resources: HostResources<DefaultPacketPool, CONNECTIONS_MAX, L2CAP_CHANNELS_MAX> =
+        HostResources::new();
+    let stack = trouble_host::new(controller, &mut resources).set_random_address(address);
+    let Host {
+        mut peripheral,
+        mut runner,
+        ..
+    } = stack.build();
+
+    let name = provisioning::ap_ssid();
+    let server = match Server::new_with_config(GapConfig::Peripheral(PeripheralConfig {
+        name: &name,
+        appearance: &appearance::UNKNOWN,
+    })) {
+        Ok(server) => server,
+        Err(e) => {
+            println!("[BLE] Failed to create GATT server: {:?}", e);
+            return;
+        }
+    };
+
+    let host = async {
+        loop {
+            if let Err(e) = runner.run().await {
+                println!("[BLE] Host stack error: {:?}", e);
+                Timer::after(Duration::from_secs(1)).await;
+            }
+        }
+    };
+
+    let provisioning = async {
+        loop {
+            if !provisioning::is_active() {
+                Timer::after(Duration::from_secs(1)).await;
+                continue;
+            }
+
+            println!(
+                "[BLE] Advertising provisioning service as '{}'",
+                name.as_str()
+            );
+            match advertise(&name, &mut peripheral, &server).await {
+                Ok(conn) => {
+                    println!("[BLE] App connected");
+                    serve(&server, &conn).await;
+                }
+                Err(e) => {
+                    println!("[BLE] Advertising failed: {:?}", e);
+                    Timer::after(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    };
+
+    join(host, provisioning).await;
+}
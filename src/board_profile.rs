@@ -0,0 +1,85 @@
+//! Board profiles
+//!
+//! Each hardware revision wires the strip, the button and the other board
+//! functions to its own GPIOs. A [`BoardProfile`] lists them, and the profile
+//! the firmware runs with is picked at build time with a cargo feature:
+//!
+//! - default: [`DEVKIT`], a bare dev board with the strip on GPIO4
+//! - `driver-board`: [`DRIVER_BOARD`], the driver board with PSU switch,
+//!   button and indicator LED
+//!
+//! To support another revision, add a profile here and a feature selecting it.
+//! A `board.toml` `data_pin` moves the strip of any profile.
+
+/// Pin assignments of one board revision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardProfile {
+    /// Profile name, printed at boot
+    pub name: &'static str,
+    /// LED strip data line (RMT output)
+    pub led_data_pin: u8,
+    /// Button to ground, used for the factory reset (None if not fitted)
+    pub button_pin: Option<u8>,
+    /// Single-color indicator LED, active high, mirroring the status LEDs on
+    /// the strip (None if not fitted)
+    pub status_led_pin: Option<u8>,
+    /// LED power supply enable output, driven by `HookAction::SetPower` (None
+    /// if the supply is always on)
+    pub power_enable_pin: Option<u8>,
+    /// Adalight UART receive pin
+    pub adalight_rx_pin: u8,
+}
+
+impl BoardProfile {
+    /// Whether `pin` is taken by one of the profile's functions
+    ///
+    /// The Adalight pin only counts when `adalight` input is enabled.
+    pub fn uses_pin(&self, pin: u8, adalight: bool) -> bool {
+        pin == self.led_data_pin
+            || self.button_pin == Some(pin)
+            || self.status_led_pin == Some(pin)
+            || self.power_enable_pin == Some(pin)
+            || (adalight && pin == self.adalight_rx_pin)
+    }
+}
+
+/// Bare dev board: only the strip is wired up
+pub const DEVKIT: BoardProfile = BoardProfile {
+    name: "devkit",
+    led_data_pin: 4,
+    button_pin: None,
+    status_led_pin: None,
+    power_enable_pin: None,
+    // GPIO6 is a flash pin on the ESP32
+    #[cfg(not(feature = "esp32"))]
+    adalight_rx_pin: 6,
+    #[cfg(feature = "esp32")]
+    adalight_rx_pin: 13,
+};
+
+/// Driver board: strip on GPIO4, PSU enable on GPIO5, the BOOT button
+/// (GPIO9) for the factory reset and an indicator LED on GPIO10 (ESP32-C3)
+pub const DRIVER_BOARD: BoardProfile = BoardProfile {
+    name: "driver-board",
+    led_data_pin: 4,
+    button_pin: Some(9),
+    status_led_pin: Some(10),
+    power_enable_pin: Some(5),
+    adalight_rx_pin: 6,
+};
+
+/// Profile selected by the cargo features
+#[cfg(not(feature = "driver-board"))]
+const SELECTED: BoardProfile = DEVKIT;
+#[cfg(feature = "driver-board")]
+const SELECTED: BoardProfile = DRIVER_BOARD;
+
+/// Profile of the board this firmware is built for, with the data pin from
+/// `board.toml` if it sets one
+pub const PROFILE: BoardProfile = match crate::board_config::LED_DATA_PIN {
+    Some(led_data_pin) => BoardProfile {
+        led_data_pin,
+        ..SELECTED
+    },
+    None => SELECTED,
+};
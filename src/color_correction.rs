@@ -0,0 +1,140 @@
+//! Gamma and white balance correction
+//!
+//! Applied to every byte sent to the strip, before the global brightness.
+//! Gamma is stored in tenths (`10` is linear, `22` the usual 2.2) and the
+//! white balance as a scale per color channel (`255` leaves it unchanged).
+//! Both are folded into one lookup table per byte position of a pixel, so
+//! the channel order of the pixel format is taken care of once, when the
+//! tables are built.
+
+use crate::protocol::PixelFormat;
+
+/// Linear response (no gamma correction)
+pub const GAMMA_LINEAR: u8 = 10;
+
+/// Accepted gamma range, in tenths
+pub const GAMMA_RANGE: core::ops::RangeInclusive<u8> = 10..=30;
+
+/// White balance leaving every channel unchanged (red, green, blue, white)
+pub const WHITE_BALANCE_NEUTRAL: [u8; 4] = [u8::MAX; 4];
+
+/// Lookup tables for each byte position of a pixel
+#[derive(Clone)]
+pub struct ColorCorrection {
+    tables: [[u8; 256]; 4],
+    identity: bool,
+}
+
+impl ColorCorrection {
+    /// No correction
+    pub const IDENTITY: Self = Self {
+        tables: [[0; 256]; 4],
+        identity: true,
+    };
+
+    /// Tables for a gamma (in tenths), a white balance (red, green, blue,
+    /// white scales) and the strip's channel order
+    pub fn new(gamma: u8, white_balance: [u8; 4], format: PixelFormat) -> Self {
+        if gamma == GAMMA_LINEAR && white_balance == WHITE_BALANCE_NEUTRAL {
+            return Self::IDENTITY;
+        }
+
+        let mut curve = [0u8; 256];
+        for (value, out) in curve.iter_mut().enumerate() {
+            *out = gamma_corrected(value as u8, gamma);
+        }
+
+        // Red, green, blue, white index into the white balance
+        let order: [usize; 4] = match format {
+            PixelFormat::Rgb | PixelFormat::Rgbw => [0, 1, 2, 3],
+            PixelFormat::Grb | PixelFormat::Grbw => [1, 0, 2, 3],
+        };
+        let mut tables = [[0u8; 256]; 4];
+        for (table, channel) in tables.iter_mut().zip(order) {
+            let scale = white_balance[channel] as u16 + 1;
+            for (out, &value) in table.iter_mut().zip(curve.iter()) {
+                *out = ((value as u16 * scale) >> 8) as u8;
+            }
+        }
+        Self {
+            tables,
+            identity: false,
+        }
+    }
+
+    /// Corrected value of the byte at `position` within its pixel
+    #[inline]
+    pub fn apply(&self, position: usize, value: u8) -> u8 {
+        if self.identity {
+            value
+        } else {
+            self.tables[position][value as usize]
+        }
+    }
+}
+
+/// `255 * (value / 255) ^ (gamma / 10)`, rounded
+///
+/// There is no `powf` without std, so the fractional power is taken as the
+/// tenth root of an integer power, found by bisection.
+fn gamma_corrected(value: u8, gamma: u8) -> u8 {
+    if gamma == GAMMA_LINEAR || value == 0 || value == u8::MAX {
+        return value;
+    }
+    let mut target = 1.0f64;
+    for _ in 0..gamma {
+        target *= value as f64 / 255.0;
+    }
+
+    let (mut low, mut high) = (0.0f64, 1.0f64);
+    for _ in 0..32 {
+        let mid = (low + high) / 2.0;
+        let mut power = 1.0;
+        for _ in 0..GAMMA_LINEAR {
+            power *= mid;
+        }
+        if power < target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low * 255.0 + 0.5) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_neutral_correction_is_identity() {
+        let correction =
+            ColorCorrection::new(GAMMA_LINEAR, WHITE_BALANCE_NEUTRAL, PixelFormat::Grbw);
+        for value in 0..=255u8 {
+            assert_eq!(correction.apply(1, value), value);
+        }
+    }
+
+    #[test]
+    fn gamma_darkens_midtones_and_keeps_ends() {
+        assert_eq!(gamma_corrected(0, 22), 0);
+        assert_eq!(gamma_corrected(255, 22), 255);
+        assert_eq!(gamma_corrected(128, 22), 56);
+        assert_eq!(gamma_corrected(128, 20), 64);
+        assert_eq!(gamma_corrected(77, 10), 77);
+    }
+
+    #[test]
+    fn white_balance_follows_channel_order() {
+        // Halve red only
+        let balance = [127, 255, 255, 255];
+        let rgb = ColorCorrection::new(GAMMA_LINEAR, balance, PixelFormat::Rgb);
+        assert_eq!(rgb.apply(0, 200), 100);
+        assert_eq!(rgb.apply(1, 200), 200);
+
+        let grbw = ColorCorrection::new(GAMMA_LINEAR, balance, PixelFormat::Grbw);
+        assert_eq!(grbw.apply(0, 200), 200);
+        assert_eq!(grbw.apply(1, 200), 100);
+        assert_eq!(grbw.apply(3, 200), 200);
+    }
+}
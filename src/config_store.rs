@@ -0,0 +1,835 @@
+//! Persistent configuration store
+//!
+//! Settings that can change after the firmware is flashed live in one record
+//! in the `nvs` partition (`config::CONFIG_STORE_FLASH_OFFSET`): LED count,
+//! pixel format, brightness and its limit, gamma, white balance, UDP port,
+//! device name and the provisioned Wi-Fi networks. The record is read once at
+//! boot into a cached copy; features read the cache and write through
+//! `update`, which validates the new settings, writes the record and then
+//! swaps the cache. Settings adjusted in quick succession (the runtime
+//! brightness) go through `update_deferred` instead, which swaps the cache at
+//! once and leaves the flash write to `persist_task`.
+//!
+//! Record layout: [magic:4][schema version][payload len:2][payload][crc32:4]
+//!
+//! Payload (version 3): [led count:2][pixel format][brightness][udp port:2]
+//! [name len][name...][network count] then per network
+//! [ssid len][ssid...][password len][password...], then [brightness limit]
+//! [gamma][white balance:4][keepalive timeout ms:4][keepalive max missed]
+//! [keepalive action][hook table:24] (see `hooks`)
+//!
+//! Records written by older firmware are upgraded on load by `MIGRATIONS`,
+//! one schema version at a time, and written back in the current layout. A
+//! record from newer firmware is left untouched and the defaults are used,
+//! rather than guessing at its layout. New fields are appended to the
+//! payload, each with a migration step supplying its value for older records.
+//!
+//! The same record is the export blob of config key `0x00`, so a configuration
+//! can be cloned onto another board. Exports leave the Wi-Fi passwords out; on
+//! import a network without a password keeps the one stored for its SSID.
+//!
+//! Without a record the build-time defaults apply. Boards provisioned before
+//! the store existed have their networks and device name imported from the
+//! older per-item records at boot.
+
+use crate::color_correction::{GAMMA_LINEAR, GAMMA_RANGE, WHITE_BALANCE_NEUTRAL};
+use crate::hooks::HookTable;
+use crate::protocol::{ConfigKey, PixelFormat};
+use crate::{BoardError, config};
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicU32, Ordering};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use heapless::{String, Vec};
+
+/// Record magic
+const RECORD_MAGIC: [u8; 4] = *b"BCFG";
+
+/// Schema version written by this firmware
+pub const SCHEMA_VERSION: u8 = 5;
+
+/// Upgrades a payload by one schema version in place
+type Migration = fn(&mut Vec<u8, MAX_PAYLOAD>) -> Option<()>;
+
+/// Forward migrations: entry `n` turns a version `n + 1` payload into version `n + 2`
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize - 1] = [
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+    migrate_v4_to_v5,
+];
+
+/// v2 adds the brightness limit; older boards had none
+fn migrate_v1_to_v2(payload: &mut Vec<u8, MAX_PAYLOAD>) -> Option<()> {
+    payload.push(u8::MAX).ok()
+}
+
+/// v3 adds gamma and white balance; older boards sent the data uncorrected
+fn migrate_v2_to_v3(payload: &mut Vec<u8, MAX_PAYLOAD>) -> Option<()> {
+    payload.push(GAMMA_LINEAR).ok()?;
+    payload.extend_from_slice(&WHITE_BALANCE_NEUTRAL).ok()
+}
+
+/// v4 adds the keepalive supervision settings, which were build-time only
+fn migrate_v3_to_v4(payload: &mut Vec<u8, MAX_PAYLOAD>) -> Option<()> {
+    payload
+        .extend_from_slice(&config::KEEPALIVE_TIMEOUT_MS.to_be_bytes())
+        .ok()?;
+    payload.push(config::KEEPALIVE_MAX_MISSED).ok()?;
+    payload.push(config::KEEPALIVE_BLANK_ON_TIMEOUT as u8).ok()
+}
+
+/// v5 adds the hook table, which was build-time only
+fn migrate_v4_to_v5(payload: &mut Vec<u8, MAX_PAYLOAD>) -> Option<()> {
+    payload.extend_from_slice(&HookTable::DEFAULT.encode()).ok()
+}
+
+/// Magic, version and payload length
+const HEADER_SIZE: usize = 4 + 1 + 2;
+
+/// Flash space reserved for the record
+pub const RECORD_CAPACITY: usize = 512;
+
+/// Largest payload that fits the record
+const MAX_PAYLOAD: usize = RECORD_CAPACITY - HEADER_SIZE - 4;
+
+/// Wi-Fi station credentials
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WifiCredentials {
+    pub ssid: String<32>,
+    pub password: String<64>,
+}
+
+impl WifiCredentials {
+    /// Credentials from an SSID and password, if both fit (the SSID must not be empty)
+    pub fn new(ssid: &str, password: &str) -> Option<Self> {
+        if ssid.is_empty() {
+            return None;
+        }
+        Some(Self {
+            ssid: String::try_from(ssid).ok()?,
+            password: String::try_from(password).ok()?,
+        })
+    }
+}
+
+/// Settings kept across reboots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredConfig {
+    /// LEDs on the strip
+    pub led_count: u16,
+    /// Channel order of the strip (same bytes per LED as the build)
+    pub pixel_format: PixelFormat,
+    /// Global brightness applied at boot
+    pub brightness: u8,
+    /// Highest global brightness accepted from any source
+    pub max_brightness: u8,
+    /// Gamma in tenths (10 is linear)
+    pub gamma: u8,
+    /// Red, green, blue and white channel scales (255 leaves a channel unchanged)
+    pub white_balance: [u8; 4],
+    /// UDP port for LED data
+    pub udp_port: u16,
+    /// mDNS instance name (empty uses the default name)
+    pub device_name: String<63>,
+    /// Provisioned networks, in priority order
+    pub networks: Vec<WifiCredentials, { config::MAX_STORED_NETWORKS }>,
+    /// Time without a keepalive that counts as one miss
+    pub keepalive_timeout_ms: u32,
+    /// Consecutive missed keepalives tolerated before the timeout action
+    pub keepalive_max_missed: u8,
+    /// Blank the strip (instead of only changing state) on keepalive timeout
+    pub keepalive_blank_on_timeout: bool,
+    /// Actions run on lifecycle events
+    pub hooks: HookTable,
+}
+
+impl StoredConfig {
+    /// Build-time defaults
+    pub const DEFAULT: Self = Self {
+        led_count: config::LED_COUNT as u16,
+        pixel_format: config::PIXEL_FORMAT,
+        brightness: config::DEFAULT_BRIGHTNESS,
+        max_brightness: u8::MAX,
+        gamma: GAMMA_LINEAR,
+        white_balance: WHITE_BALANCE_NEUTRAL,
+        udp_port: config::UDP_PORT,
+        device_name: String::new(),
+        networks: Vec::new(),
+        keepalive_timeout_ms: config::KEEPALIVE_TIMEOUT_MS,
+        keepalive_max_missed: config::KEEPALIVE_MAX_MISSED,
+        keepalive_blank_on_timeout: config::KEEPALIVE_BLANK_ON_TIMEOUT,
+        hooks: HookTable::DEFAULT,
+    };
+
+    /// Check the settings can be applied to this build
+    ///
+    /// The LED count is limited by what the RMT output can send in one frame.
+    pub fn validate(&self) -> Result<(), BoardError> {
+        let frame_bytes = self.led_count as usize * self.pixel_format.bytes_per_led();
+        let valid = self.led_count > 0
+            && frame_bytes <= config::MAX_FRAME_BYTES
+            && self.pixel_format.bytes_per_led() == config::BYTES_PER_LED
+            && GAMMA_RANGE.contains(&self.gamma)
+            && self.udp_port != 0
+            && self.keepalive_timeout_ms > 0
+            && !self.device_name.contains('.');
+        if valid {
+            Ok(())
+        } else {
+            Err(BoardError::ProtocolError)
+        }
+    }
+
+    /// Add a network at the highest priority
+    ///
+    /// A stored network with the same SSID is replaced; with every slot taken
+    /// the lowest priority one is dropped.
+    pub fn add_network(&mut self, network: WifiCredentials) {
+        self.networks.retain(|n| n.ssid != network.ssid);
+        self.networks.truncate(config::MAX_STORED_NETWORKS - 1);
+        let _ = self.networks.insert(0, network);
+    }
+
+    /// Copy with the network passwords cleared, for exports
+    fn without_passwords(&self) -> Self {
+        let mut copy = self.clone();
+        for network in copy.networks.iter_mut() {
+            network.password.clear();
+        }
+        copy
+    }
+
+    /// Current value of a setting, as sent in config replies
+    pub fn value(&self, key: ConfigKey) -> Vec<u8, RECORD_CAPACITY> {
+        let mut value = Vec::new();
+        let _ = match key {
+            ConfigKey::All => value.extend_from_slice(&self.without_passwords().encode()),
+            ConfigKey::LedCount => value.extend_from_slice(&self.led_count.to_be_bytes()),
+            ConfigKey::PixelFormat => value.extend_from_slice(&[self.pixel_format as u8]),
+            ConfigKey::Brightness => value.extend_from_slice(&[self.brightness]),
+            ConfigKey::MaxBrightness => value.extend_from_slice(&[self.max_brightness]),
+            ConfigKey::Gamma => value.extend_from_slice(&[self.gamma]),
+            ConfigKey::WhiteBalance => value.extend_from_slice(&self.white_balance),
+            ConfigKey::DeviceName => value.extend_from_slice(self.device_name.as_bytes()),
+            ConfigKey::UdpPort => value.extend_from_slice(&self.udp_port.to_be_bytes()),
+            ConfigKey::KeepaliveTimeout => {
+                value.extend_from_slice(&self.keepalive_timeout_ms.to_be_bytes())
+            }
+            ConfigKey::KeepaliveMaxMissed => value.extend_from_slice(&[self.keepalive_max_missed]),
+            ConfigKey::KeepaliveAction => {
+                value.extend_from_slice(&[self.keepalive_blank_on_timeout as u8])
+            }
+            ConfigKey::Hooks => value.extend_from_slice(&self.hooks.encode()),
+        };
+        value
+    }
+
+    /// Set a setting from a config packet value (checked by `validate` on update)
+    pub fn set_value(&mut self, key: ConfigKey, value: &[u8]) -> Result<(), BoardError> {
+        match (key, value) {
+            (ConfigKey::All, blob) => {
+                let mut imported = Self::decode(blob).ok_or(BoardError::ProtocolError)?;
+                for network in imported.networks.iter_mut() {
+                    if network.password.is_empty()
+                        && let Some(known) = self.networks.iter().find(|n| n.ssid == network.ssid)
+                    {
+                        network.password = known.password.clone();
+                    }
+                }
+                *self = imported
+            }
+            (ConfigKey::LedCount, &[hi, lo]) => self.led_count = u16::from_be_bytes([hi, lo]),
+            (ConfigKey::PixelFormat, &[code]) => {
+                self.pixel_format = PixelFormat::from_code(code).ok_or(BoardError::ProtocolError)?
+            }
+            (ConfigKey::Brightness, &[brightness]) => self.brightness = brightness,
+            (ConfigKey::MaxBrightness, &[limit]) => self.max_brightness = limit,
+            (ConfigKey::Gamma, &[gamma]) => self.gamma = gamma,
+            (ConfigKey::WhiteBalance, &[red, green, blue, white]) => {
+                self.white_balance = [red, green, blue, white]
+            }
+            (ConfigKey::DeviceName, name) => {
+                self.device_name = core::str::from_utf8(name)
+                    .ok()
+                    .and_then(|name| String::try_from(name).ok())
+                    .ok_or(BoardError::ProtocolError)?
+            }
+            (ConfigKey::UdpPort, &[hi, lo]) => self.udp_port = u16::from_be_bytes([hi, lo]),
+            (ConfigKey::KeepaliveTimeout, &[a, b, c, d]) => {
+                self.keepalive_timeout_ms = u32::from_be_bytes([a, b, c, d])
+            }
+            (ConfigKey::KeepaliveMaxMissed, &[missed]) => self.keepalive_max_missed = missed,
+            (ConfigKey::KeepaliveAction, &[action @ (0 | 1)]) => {
+                self.keepalive_blank_on_timeout = action == 1
+            }
+            (ConfigKey::Hooks, table) => {
+                self.hooks = HookTable::decode(table).ok_or(BoardError::ProtocolError)?
+            }
+            _ => return Err(BoardError::ProtocolError),
+        }
+        Ok(())
+    }
+
+    /// Encode as a flash record
+    pub fn encode(&self) -> Vec<u8, RECORD_CAPACITY> {
+        let mut payload: Vec<u8, MAX_PAYLOAD> = Vec::new();
+        payload
+            .extend_from_slice(&self.led_count.to_be_bytes())
+            .ok();
+        payload.push(self.pixel_format as u8).ok();
+        payload.push(self.brightness).ok();
+        payload.extend_from_slice(&self.udp_port.to_be_bytes()).ok();
+        push_str(&mut payload, &self.device_name);
+        payload.push(self.networks.len() as u8).ok();
+        for network in &self.networks {
+            push_str(&mut payload, &network.ssid);
+            push_str(&mut payload, &network.password);
+        }
+        payload.push(self.max_brightness).ok();
+        payload.push(self.gamma).ok();
+        payload.extend_from_slice(&self.white_balance).ok();
+        payload
+            .extend_from_slice(&self.keepalive_timeout_ms.to_be_bytes())
+            .ok();
+        payload.push(self.keepalive_max_missed).ok();
+        payload.push(self.keepalive_blank_on_timeout as u8).ok();
+        payload.extend_from_slice(&self.hooks.encode()).ok();
+
+        let mut record = Vec::new();
+        record.extend_from_slice(&RECORD_MAGIC).ok();
+        record.push(SCHEMA_VERSION).ok();
+        record
+            .extend_from_slice(&(payload.len() as u16).to_be_bytes())
+            .ok();
+        record.extend_from_slice(&payload).ok();
+        let crc = crate::crc::crc32(&record);
+        record.extend_from_slice(&crc.to_be_bytes()).ok();
+        record
+    }
+
+    /// Schema version of a flash record, None if there is no record
+    pub fn record_version(record: &[u8]) -> Option<u8> {
+        (record.len() >= HEADER_SIZE && record[..4] == RECORD_MAGIC).then(|| record[4])
+    }
+
+    /// Decode a flash record, migrating older schema versions
+    ///
+    /// None if it's missing, corrupt or from newer firmware.
+    pub fn decode(record: &[u8]) -> Option<Self> {
+        let version = Self::record_version(record)?;
+        if version == 0 || version > SCHEMA_VERSION {
+            return None;
+        }
+        let len = u16::from_be_bytes([record[5], record[6]]) as usize;
+        if len > MAX_PAYLOAD || record.len() < HEADER_SIZE + len + 4 {
+            return None;
+        }
+        let (body, rest) = record.split_at(HEADER_SIZE + len);
+        if crate::crc::crc32(body) != u32::from_be_bytes(rest[..4].try_into().ok()?) {
+            return None;
+        }
+
+        let mut payload = Vec::from_slice(&body[HEADER_SIZE..]).ok()?;
+        for migrate in &MIGRATIONS[version as usize - 1..] {
+            migrate(&mut payload)?;
+        }
+        Self::decode_payload(&payload)
+    }
+
+    /// Decode a payload in the current schema
+    fn decode_payload(payload: &[u8]) -> Option<Self> {
+        let mut reader = Reader(payload);
+        let led_count = u16::from_be_bytes([reader.byte()?, reader.byte()?]);
+        let pixel_format = PixelFormat::from_code(reader.byte()?)?;
+        let brightness = reader.byte()?;
+        let udp_port = u16::from_be_bytes([reader.byte()?, reader.byte()?]);
+        let device_name = String::try_from(reader.str()?).ok()?;
+        let mut networks = Vec::new();
+        for _ in 0..reader.byte()? {
+            let network = WifiCredentials::new(reader.str()?, reader.str()?)?;
+            networks.push(network).ok()?;
+        }
+        let max_brightness = reader.byte()?;
+        let gamma = reader.byte()?;
+        let white_balance = [
+            reader.byte()?,
+            reader.byte()?,
+            reader.byte()?,
+            reader.byte()?,
+        ];
+        let keepalive_timeout_ms = u32::from_be_bytes([
+            reader.byte()?,
+            reader.byte()?,
+            reader.byte()?,
+            reader.byte()?,
+        ]);
+        let keepalive_max_missed = reader.byte()?;
+        let keepalive_blank_on_timeout = match reader.byte()? {
+            0 => false,
+            1 => true,
+            _ => return None,
+        };
+        let hooks = HookTable::decode(reader.bytes(crate::hooks::HOOK_TABLE_SIZE)?)?;
+        Some(Self {
+            led_count,
+            pixel_format,
+            brightness,
+            max_brightness,
+            gamma,
+            white_balance,
+            udp_port,
+            device_name,
+            networks,
+            keepalive_timeout_ms,
+            keepalive_max_missed,
+            keepalive_blank_on_timeout,
+            hooks,
+        })
+    }
+}
+
+/// Append a length-prefixed string
+fn push_str<const N: usize>(out: &mut Vec<u8, N>, value: &str) {
+    out.push(value.len() as u8).ok();
+    out.extend_from_slice(value.as_bytes()).ok();
+}
+
+/// Cursor over a record payload
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        let (&first, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(first)
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (value, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(value)
+    }
+
+    fn str(&mut self) -> Option<&'a str> {
+        let len = self.byte()? as usize;
+        core::str::from_utf8(self.bytes(len)?).ok()
+    }
+}
+
+/// Settings in effect
+static CONFIG: critical_section::Mutex<RefCell<StoredConfig>> =
+    critical_section::Mutex::new(RefCell::new(StoredConfig::DEFAULT));
+
+/// Bumped whenever the settings in effect change
+static GENERATION: AtomicU32 = AtomicU32::new(0);
+
+/// Raised by `update_deferred` for `persist_task`
+static SAVE_REQUEST: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Swap the settings in effect
+fn replace(stored: StoredConfig) {
+    // No atomic read-modify-write on the C3, so bump the generation in the
+    // same critical section
+    critical_section::with(|cs| {
+        *CONFIG.borrow_ref_mut(cs) = stored;
+        let generation = GENERATION.load(Ordering::Relaxed);
+        GENERATION.store(generation.wrapping_add(1), Ordering::Relaxed);
+    });
+}
+
+/// Load the stored settings (call once at boot, before anything reads them)
+#[cfg(target_os = "none")]
+pub fn init() {
+    let record = read_flash();
+    let version = StoredConfig::record_version(&record);
+    let stored = match StoredConfig::decode(&record) {
+        Some(stored) => {
+            if version != Some(SCHEMA_VERSION) {
+                crate::println!(
+                    "[CONFIG] Migrating schema v{} record to v{}",
+                    version.unwrap_or(0),
+                    SCHEMA_VERSION
+                );
+                write_flash(&stored).ok();
+            }
+            stored
+        }
+        None if version.is_some_and(|v| v > SCHEMA_VERSION) => {
+            crate::println!(
+                "[CONFIG] Record has schema v{}, newer than this firmware (v{}) - left untouched, using defaults",
+                version.unwrap_or(0),
+                SCHEMA_VERSION
+            );
+            StoredConfig::DEFAULT
+        }
+        None => {
+            let mut stored = StoredConfig::DEFAULT;
+            stored.networks = crate::provisioning::legacy_networks();
+            stored.device_name = crate::provisioning::legacy_device_name().unwrap_or_default();
+            if stored != StoredConfig::DEFAULT {
+                crate::println!("[CONFIG] Importing provisioned settings");
+                write_flash(&stored).ok();
+            }
+            stored
+        }
+    };
+
+    let stored = match stored.validate() {
+        Ok(()) => stored,
+        Err(_) => {
+            crate::println!("[CONFIG] Stored settings don't fit this build - using defaults");
+            StoredConfig {
+                networks: stored.networks,
+                device_name: stored.device_name,
+                ..StoredConfig::DEFAULT
+            }
+        }
+    };
+    replace(stored);
+}
+
+/// Copy of the settings in effect
+pub fn get() -> StoredConfig {
+    critical_section::with(|cs| CONFIG.borrow_ref(cs).clone())
+}
+
+/// Change the settings and persist them
+///
+/// `change` edits a copy; nothing is written unless it succeeds and the
+/// result passes `StoredConfig::validate`. Some settings (LED count, UDP
+/// port) only take full effect after a restart.
+pub fn update(
+    change: impl FnOnce(&mut StoredConfig) -> Result<(), BoardError>,
+) -> Result<(), BoardError> {
+    let mut stored = get();
+    change(&mut stored)?;
+    stored.validate()?;
+    #[cfg(target_os = "none")]
+    write_flash(&stored)?;
+    replace(stored);
+    Ok(())
+}
+
+/// Change the settings now and persist them once they stop changing
+///
+/// Like `update`, but the flash is only written by `persist_task`, after
+/// `config::CONFIG_SAVE_DELAY_MS` without further changes, so a slider
+/// dragged across its range costs one write.
+pub fn update_deferred(
+    change: impl FnOnce(&mut StoredConfig) -> Result<(), BoardError>,
+) -> Result<(), BoardError> {
+    let mut stored = get();
+    change(&mut stored)?;
+    stored.validate()?;
+    replace(stored);
+    SAVE_REQUEST.signal(());
+    Ok(())
+}
+
+/// Counter that changes whenever the settings in effect do, so users of a
+/// setting can tell when to pick it up again
+pub fn generation() -> u32 {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+/// Writes the changes made through `update_deferred` to flash
+#[cfg(target_os = "none")]
+#[embassy_executor::task]
+pub async fn persist_task() {
+    use embassy_futures::select::{Either, select};
+    use embassy_time::{Duration, Timer};
+
+    let delay = Duration::from_millis(config::CONFIG_SAVE_DELAY_MS as u64);
+    loop {
+        SAVE_REQUEST.wait().await;
+        while let Either::First(()) = select(SAVE_REQUEST.wait(), Timer::after(delay)).await {}
+        match write_flash(&get()) {
+            Ok(()) => crate::println!("[CONFIG] Settings saved"),
+            Err(e) => crate::println!("[CONFIG] Saving settings failed: {:?}", e),
+        }
+    }
+}
+
+/// Go back to the build-time defaults, forgetting the networks and device name
+pub fn reset() -> Result<(), BoardError> {
+    update(|stored| {
+        *stored = StoredConfig::DEFAULT;
+        Ok(())
+    })
+}
+
+/// LEDs on the strip
+pub fn led_count() -> usize {
+    critical_section::with(|cs| CONFIG.borrow_ref(cs).led_count as usize)
+}
+
+/// Channel order of the strip
+pub fn pixel_format() -> PixelFormat {
+    critical_section::with(|cs| CONFIG.borrow_ref(cs).pixel_format)
+}
+
+/// Actions run on lifecycle events
+pub fn hooks() -> HookTable {
+    critical_section::with(|cs| CONFIG.borrow_ref(cs).hooks)
+}
+
+/// Highest global brightness accepted
+pub fn max_brightness() -> u8 {
+    critical_section::with(|cs| CONFIG.borrow_ref(cs).max_brightness)
+}
+
+/// UDP port for LED data
+pub fn udp_port() -> u16 {
+    critical_section::with(|cs| CONFIG.borrow_ref(cs).udp_port)
+}
+
+/// Provisioned device name, if any
+pub fn device_name() -> Option<String<63>> {
+    critical_section::with(|cs| {
+        let name = &CONFIG.borrow_ref(cs).device_name;
+        (!name.is_empty()).then(|| name.clone())
+    })
+}
+
+/// Raw record (erased flash if it can't be read)
+#[cfg(target_os = "none")]
+fn read_flash() -> [u8; RECORD_CAPACITY] {
+    use embedded_storage::ReadStorage;
+
+    let mut record = [0u8; RECORD_CAPACITY];
+    if esp_storage::FlashStorage::new()
+        .read(config::CONFIG_STORE_FLASH_OFFSET, &mut record)
+        .is_err()
+    {
+        record.fill(0xFF);
+    }
+    record
+}
+
+#[cfg(target_os = "none")]
+fn write_flash(stored: &StoredConfig) -> Result<(), BoardError> {
+    use embedded_storage::Storage;
+
+    esp_storage::FlashStorage::new()
+        .write(config::CONFIG_STORE_FLASH_OFFSET, &stored.encode())
+        .map_err(|_| BoardError::StorageError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hooks::HookAction;
+    use crate::protocol::LedMode;
+
+    fn sample() -> StoredConfig {
+        let mut stored = StoredConfig::DEFAULT;
+        stored.led_count = 144;
+        stored.brightness = 128;
+        stored.device_name = String::try_from("Desk").unwrap();
+        stored
+            .networks
+            .push(WifiCredentials::new("home", "secret").unwrap())
+            .unwrap();
+        stored
+    }
+
+    #[test]
+    fn record_round_trips() {
+        let stored = sample();
+        assert_eq!(StoredConfig::decode(&stored.encode()), Some(stored));
+    }
+
+    /// Re-encode a record under another schema version with its payload cut short
+    fn reframe(record: &[u8], version: u8, drop: usize) -> std::vec::Vec<u8> {
+        let payload_end = record.len() - 4 - drop;
+        let mut old = record[..payload_end].to_vec();
+        old[4] = version;
+        old[5..7].copy_from_slice(&((payload_end - HEADER_SIZE) as u16).to_be_bytes());
+        let crc = crate::crc::crc32(&old);
+        old.extend_from_slice(&crc.to_be_bytes());
+        old
+    }
+
+    #[test]
+    fn v1_records_are_migrated() {
+        let mut stored = sample();
+        stored.max_brightness = 200;
+        // v1 payloads end before the brightness limit
+        let v1 = reframe(&stored.encode(), 1, 36);
+        assert_eq!(StoredConfig::record_version(&v1), Some(1));
+
+        let migrated = StoredConfig::decode(&v1).unwrap();
+        assert_eq!(migrated.max_brightness, u8::MAX);
+        assert_eq!(migrated.gamma, GAMMA_LINEAR);
+        assert_eq!(migrated.led_count, stored.led_count);
+        assert_eq!(migrated.networks, stored.networks);
+    }
+
+    #[test]
+    fn v2_records_are_migrated() {
+        let mut stored = sample();
+        stored.max_brightness = 200;
+        stored.gamma = 22;
+        stored.white_balance = [255, 230, 200, 255];
+        // v2 payloads end before gamma and white balance
+        let v2 = reframe(&stored.encode(), 2, 35);
+
+        let migrated = StoredConfig::decode(&v2).unwrap();
+        assert_eq!(migrated.max_brightness, 200);
+        assert_eq!(migrated.gamma, GAMMA_LINEAR);
+        assert_eq!(migrated.white_balance, WHITE_BALANCE_NEUTRAL);
+    }
+
+    #[test]
+    fn v3_records_are_migrated() {
+        let mut stored = sample();
+        stored.gamma = 22;
+        stored.keepalive_timeout_ms = 5000;
+        stored.keepalive_blank_on_timeout = !config::KEEPALIVE_BLANK_ON_TIMEOUT;
+        // v3 payloads end before the keepalive settings
+        let v3 = reframe(&stored.encode(), 3, 30);
+
+        let migrated = StoredConfig::decode(&v3).unwrap();
+        assert_eq!(migrated.gamma, 22);
+        assert_eq!(migrated.keepalive_timeout_ms, config::KEEPALIVE_TIMEOUT_MS);
+        assert_eq!(migrated.keepalive_max_missed, config::KEEPALIVE_MAX_MISSED);
+        assert_eq!(
+            migrated.keepalive_blank_on_timeout,
+            config::KEEPALIVE_BLANK_ON_TIMEOUT
+        );
+        assert_eq!(migrated.hooks, HookTable::DEFAULT);
+    }
+
+    #[test]
+    fn v4_records_are_migrated() {
+        let mut stored = sample();
+        stored.keepalive_timeout_ms = 5000;
+        stored.hooks.on_boot[0] = Some(HookAction::SetPower(true));
+        // v4 payloads end before the hook table
+        let v4 = reframe(&stored.encode(), 4, crate::hooks::HOOK_TABLE_SIZE);
+
+        let migrated = StoredConfig::decode(&v4).unwrap();
+        assert_eq!(migrated.keepalive_timeout_ms, 5000);
+        assert_eq!(migrated.hooks, HookTable::DEFAULT);
+    }
+
+    #[test]
+    fn largest_config_fits_the_record() {
+        let mut stored = sample();
+        stored.device_name = String::try_from("n".repeat(63).as_str()).unwrap();
+        stored.networks.clear();
+        for i in 0..config::MAX_STORED_NETWORKS {
+            let ssid = std::format!("{i}").repeat(32);
+            let network = WifiCredentials::new(&ssid, &"p".repeat(64)).unwrap();
+            stored.networks.push(network).unwrap();
+        }
+        for action in stored.hooks.on_data_stop.iter_mut() {
+            *action = Some(HookAction::SetBrightness(10));
+        }
+
+        assert_eq!(StoredConfig::decode(&stored.encode()), Some(stored));
+    }
+
+    #[test]
+    fn corrupt_or_unknown_records_are_rejected() {
+        let mut record = sample().encode();
+        record[10] ^= 0xFF;
+        assert_eq!(StoredConfig::decode(&record), None);
+
+        let newer = reframe(&sample().encode(), SCHEMA_VERSION + 1, 0);
+        assert_eq!(
+            StoredConfig::record_version(&newer),
+            Some(SCHEMA_VERSION + 1)
+        );
+        assert_eq!(StoredConfig::decode(&newer), None);
+
+        assert_eq!(StoredConfig::decode(&[0xFF; RECORD_CAPACITY]), None);
+    }
+
+    #[test]
+    fn config_values_round_trip() {
+        let mut stored = StoredConfig::DEFAULT;
+        stored
+            .set_value(ConfigKey::LedCount, &[0x01, 0x2C])
+            .unwrap();
+        stored.set_value(ConfigKey::DeviceName, b"Shelf").unwrap();
+        assert_eq!(stored.led_count, 300);
+        assert_eq!(stored.value(ConfigKey::LedCount).as_slice(), &[0x01, 0x2C]);
+        assert_eq!(stored.value(ConfigKey::DeviceName).as_slice(), b"Shelf");
+
+        let mut clone = StoredConfig::DEFAULT;
+        clone
+            .set_value(ConfigKey::All, &stored.value(ConfigKey::All))
+            .unwrap();
+        assert_eq!(clone, stored);
+
+        assert!(stored.set_value(ConfigKey::All, &[0xFF; 16]).is_err());
+        assert!(stored.set_value(ConfigKey::Brightness, &[1, 2]).is_err());
+        assert!(stored.set_value(ConfigKey::PixelFormat, &[9]).is_err());
+
+        stored
+            .set_value(ConfigKey::KeepaliveTimeout, &[0, 0, 0x13, 0x88])
+            .unwrap();
+        stored.set_value(ConfigKey::KeepaliveAction, &[1]).unwrap();
+        assert_eq!(stored.keepalive_timeout_ms, 5000);
+        assert!(stored.keepalive_blank_on_timeout);
+        assert_eq!(stored.value(ConfigKey::KeepaliveAction).as_slice(), &[1]);
+        assert!(stored.set_value(ConfigKey::KeepaliveAction, &[2]).is_err());
+
+        let mut hooks = HookTable::DEFAULT;
+        hooks.on_data_start[0] = Some(HookAction::SetMode(LedMode::Ambient));
+        stored.set_value(ConfigKey::Hooks, &hooks.encode()).unwrap();
+        assert_eq!(stored.hooks, hooks);
+        assert_eq!(stored.value(ConfigKey::Hooks).as_slice(), &hooks.encode());
+        assert!(stored.set_value(ConfigKey::Hooks, &[1, 9]).is_err());
+    }
+
+    #[test]
+    fn exports_leave_passwords_out() {
+        let mut stored = StoredConfig::DEFAULT;
+        stored.add_network(WifiCredentials::new("Home", "secret").unwrap());
+        let export = stored.value(ConfigKey::All);
+        assert!(!export.windows(6).any(|w| w == b"secret"));
+
+        // A board that knows the network keeps its password
+        let mut known = stored.clone();
+        known.set_value(ConfigKey::All, &export).unwrap();
+        assert_eq!(known, stored);
+
+        let mut other = StoredConfig::DEFAULT;
+        other.set_value(ConfigKey::All, &export).unwrap();
+        assert_eq!(other.networks[0].ssid.as_str(), "Home");
+        assert!(other.networks[0].password.is_empty());
+    }
+
+    #[test]
+    fn validation_rejects_unusable_settings() {
+        assert!(StoredConfig::DEFAULT.validate().is_ok());
+
+        let mut stored = StoredConfig::DEFAULT;
+        stored.led_count = 0;
+        assert!(stored.validate().is_err());
+
+        let mut stored = StoredConfig::DEFAULT;
+        stored.led_count = config::MAX_LEDS as u16;
+        assert!(stored.validate().is_ok());
+        stored.led_count += 1;
+        assert!(stored.validate().is_err());
+
+        let mut stored = StoredConfig::DEFAULT;
+        stored.device_name = String::try_from("a.b").unwrap();
+        assert!(stored.validate().is_err());
+
+        let mut stored = StoredConfig::DEFAULT;
+        stored.gamma = 5;
+        assert!(stored.validate().is_err());
+
+        let mut stored = StoredConfig::DEFAULT;
+        stored.keepalive_timeout_ms = 0;
+        assert!(stored.validate().is_err());
+    }
+}
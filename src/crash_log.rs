@@ -0,0 +1,460 @@
+//! Crash log module
+//!
+//! Keeps the last error state, error and retry counts and the last abnormal
+//! chip reset reason across reboots, so field failures can be investigated
+//! afterwards. The live record sits in persistent RTC fast RAM, which survives
+//! software, panic and watchdog resets for free; a copy goes to flash
+//! whenever the error state changes or the chip resets abnormally, so the
+//! record also survives a power cycle (its counts may lag the RTC copy).
+//!
+//! The record also counts consecutive boots that ended (by a crash, watchdog
+//! or software reset) before the system came up. After
+//! `config::SAFE_MODE_BOOT_FAILURES` of them the board boots into safe mode:
+//! networking only, no LED output, and the setup access point kept open, so a
+//! bad configuration can't brick it. A power cycle starts counting afresh.
+//!
+//! It also counts every boot, so an unexpected reboot shows up as a jump in
+//! the count (and a short uptime) in the `0x10` device info and the MQTT
+//! state. The count lives in the record because that is written to flash at
+//! every boot anyway.
+//!
+//! A panic is kept too: the panic handler writes the message and location
+//! to a second record in RTC RAM before it resets the board, and the next
+//! boot prints it and copies it to flash, so the last panic stays available
+//! until the next one.
+//!
+//! The record is printed at boot and returned by the `0x1C` boot report query.
+
+use crate::config;
+use crate::println;
+use crate::state_machine::SystemState;
+use core::cell::RefCell;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use embedded_storage::{ReadStorage, Storage};
+use esp_hal::rtc_cntl::SocResetReason;
+use esp_storage::FlashStorage;
+
+/// Record layout: [magic:4][last error][previous state][last reset reason]
+/// [failed boots][error count:4][retry count:4][uptime s:4][boot count:4][crc32:4]
+const RECORD_MAGIC: [u8; 4] = *b"ELOG";
+const RECORD_SIZE: usize = 28;
+
+/// Code for "no error recorded"
+const NO_STATE: u8 = 0xFF;
+
+/// Longest panic message kept (`file:line: message`, cut off beyond)
+pub const PANIC_MESSAGE_MAX: usize = 96;
+
+/// Panic record layout: [magic:4][boot count:4][uptime s:4][message length]
+/// [message...][crc32:4]
+const PANIC_MAGIC: [u8; 4] = *b"PNIC";
+const PANIC_RECORD_SIZE: usize = 13 + PANIC_MESSAGE_MAX + 4;
+
+/// Offset of the panic record in the crash log flash sector
+const PANIC_FLASH_OFFSET: u32 = 0x100;
+
+/// Boot report up to the panic fields
+const REPORT_FIXED_SIZE: usize = 1 + 4 + 12 + 2 + 8;
+
+/// Largest boot report response
+pub const RESPONSE_SIZE: usize = REPORT_FIXED_SIZE + 1 + 8 + 1 + PANIC_MESSAGE_MAX;
+
+/// Live record, kept across resets other than power loss
+#[esp_hal::ram(rtc_fast, persistent)]
+static mut RTC_RECORD: [u8; RECORD_SIZE] = [0; RECORD_SIZE];
+
+/// Panic written by the panic handler, picked up by the next boot
+#[esp_hal::ram(rtc_fast, persistent)]
+static mut RTC_PANIC: [u8; PANIC_RECORD_SIZE] = [0; PANIC_RECORD_SIZE];
+
+/// Last recorded panic, and whether it ended the previous boot
+static LAST_PANIC: critical_section::Mutex<RefCell<(Option<PanicRecord>, bool)>> =
+    critical_section::Mutex::new(RefCell::new((None, false)));
+
+/// Reset reason of this boot and the record as found at boot
+static BOOT: critical_section::Mutex<RefCell<(u8, ErrorRecord)>> =
+    critical_section::Mutex::new(RefCell::new((0, ErrorRecord::EMPTY)));
+
+/// Boots counted so far, including this one
+static BOOT_COUNT: AtomicU32 = AtomicU32::new(0);
+
+/// Whether this boot runs in safe mode
+static SAFE_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Error history carried across reboots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorRecord {
+    /// Last error state entered
+    pub last_error: Option<SystemState>,
+    /// State the system was in before that error
+    pub previous_state: Option<SystemState>,
+    /// Reset reason code of the last reset other than power-on (0 = none)
+    pub last_reset_reason: u8,
+    /// Error states entered since the record was created
+    pub error_count: u32,
+    /// State machine retry count when the last error was entered
+    pub retry_count: u32,
+    /// Uptime in seconds when the last error was entered
+    pub uptime_s: u32,
+    /// Consecutive boots that reset before the system came up
+    pub failed_boots: u8,
+    /// Boots since the record was created
+    pub boot_count: u32,
+}
+
+impl ErrorRecord {
+    const EMPTY: Self = Self {
+        last_error: None,
+        previous_state: None,
+        last_reset_reason: 0,
+        error_count: 0,
+        retry_count: 0,
+        uptime_s: 0,
+        failed_boots: 0,
+        boot_count: 0,
+    };
+
+    fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut record = [0u8; RECORD_SIZE];
+        record[..4].copy_from_slice(&RECORD_MAGIC);
+        record[4] = self.last_error.map_or(NO_STATE, |s| s as u8);
+        record[5] = self.previous_state.map_or(NO_STATE, |s| s as u8);
+        record[6] = self.last_reset_reason;
+        record[7] = self.failed_boots;
+        record[8..12].copy_from_slice(&self.error_count.to_be_bytes());
+        record[12..16].copy_from_slice(&self.retry_count.to_be_bytes());
+        record[16..20].copy_from_slice(&self.uptime_s.to_be_bytes());
+        record[20..24].copy_from_slice(&self.boot_count.to_be_bytes());
+        let crc = crate::crc::crc32(&record[..RECORD_SIZE - 4]);
+        record[RECORD_SIZE - 4..].copy_from_slice(&crc.to_be_bytes());
+        record
+    }
+
+    fn decode(record: &[u8; RECORD_SIZE]) -> Option<Self> {
+        let (body, crc) = record.split_at(RECORD_SIZE - 4);
+        if body[..4] != RECORD_MAGIC
+            || crate::crc::crc32(body) != u32::from_be_bytes(crc.try_into().ok()?)
+        {
+            return None;
+        }
+        let word =
+            |at: usize| u32::from_be_bytes([body[at], body[at + 1], body[at + 2], body[at + 3]]);
+        Some(Self {
+            last_error: SystemState::from_code(body[4]),
+            previous_state: SystemState::from_code(body[5]),
+            last_reset_reason: body[6],
+            failed_boots: body[7],
+            error_count: word(8),
+            retry_count: word(12),
+            uptime_s: word(16),
+            boot_count: word(20),
+        })
+    }
+
+    fn load_rtc() -> Option<Self> {
+        Self::decode(&unsafe { core::ptr::read_volatile(&raw const RTC_RECORD) })
+    }
+
+    fn store_rtc(&self) {
+        unsafe { core::ptr::write_volatile(&raw mut RTC_RECORD, self.encode()) };
+    }
+
+    fn load_flash() -> Option<Self> {
+        let mut record = [0u8; RECORD_SIZE];
+        FlashStorage::new()
+            .read(config::CRASH_LOG_FLASH_OFFSET, &mut record)
+            .ok()?;
+        Self::decode(&record)
+    }
+
+    fn store_flash(&self) {
+        if let Err(e) = FlashStorage::new().write(config::CRASH_LOG_FLASH_OFFSET, &self.encode()) {
+            println!("[CRASH] Failed to save the crash log: {:?}", e);
+        }
+    }
+}
+
+/// A panic, kept across reboots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicRecord {
+    /// Boot that panicked (see `boot_count`)
+    pub boot_count: u32,
+    /// Uptime in seconds at the panic
+    pub uptime_s: u32,
+    /// `file:line: message`, cut off at `PANIC_MESSAGE_MAX` bytes
+    pub message: heapless::String<PANIC_MESSAGE_MAX>,
+}
+
+/// Formats into a panic message, dropping whatever doesn't fit
+struct Truncating<'a>(&'a mut heapless::String<PANIC_MESSAGE_MAX>);
+
+impl Write for Truncating<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.0.push(c).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PanicRecord {
+    fn encode(&self) -> [u8; PANIC_RECORD_SIZE] {
+        let mut record = [0u8; PANIC_RECORD_SIZE];
+        let message = self.message.as_bytes();
+        record[..4].copy_from_slice(&PANIC_MAGIC);
+        record[4..8].copy_from_slice(&self.boot_count.to_be_bytes());
+        record[8..12].copy_from_slice(&self.uptime_s.to_be_bytes());
+        record[12] = message.len() as u8;
+        record[13..13 + message.len()].copy_from_slice(message);
+        let crc = crate::crc::crc32(&record[..PANIC_RECORD_SIZE - 4]);
+        record[PANIC_RECORD_SIZE - 4..].copy_from_slice(&crc.to_be_bytes());
+        record
+    }
+
+    fn decode(record: &[u8; PANIC_RECORD_SIZE]) -> Option<Self> {
+        let (body, crc) = record.split_at(PANIC_RECORD_SIZE - 4);
+        if body[..4] != PANIC_MAGIC
+            || crate::crc::crc32(body) != u32::from_be_bytes(crc.try_into().ok()?)
+        {
+            return None;
+        }
+        let length = (body[12] as usize).min(PANIC_MESSAGE_MAX);
+        let message = core::str::from_utf8(&body[13..13 + length]).ok()?;
+        Some(Self {
+            boot_count: u32::from_be_bytes([body[4], body[5], body[6], body[7]]),
+            uptime_s: u32::from_be_bytes([body[8], body[9], body[10], body[11]]),
+            message: heapless::String::try_from(message).ok()?,
+        })
+    }
+
+    fn take_rtc() -> Option<Self> {
+        let record = Self::decode(&unsafe { core::ptr::read_volatile(&raw const RTC_PANIC) });
+        unsafe { core::ptr::write_volatile(&raw mut RTC_PANIC, [0; PANIC_RECORD_SIZE]) };
+        record
+    }
+
+    fn load_flash() -> Option<Self> {
+        let mut record = [0u8; PANIC_RECORD_SIZE];
+        FlashStorage::new()
+            .read(
+                config::CRASH_LOG_FLASH_OFFSET + PANIC_FLASH_OFFSET,
+                &mut record,
+            )
+            .ok()?;
+        Self::decode(&record)
+    }
+
+    fn store_flash(&self) {
+        if let Err(e) = FlashStorage::new().write(
+            config::CRASH_LOG_FLASH_OFFSET + PANIC_FLASH_OFFSET,
+            &self.encode(),
+        ) {
+            println!("[CRASH] Failed to save the panic: {:?}", e);
+        }
+    }
+}
+
+/// Keep a panic for the next boot
+///
+/// Called from the panic handler: only RTC RAM is written, as the flash may
+/// be what failed. The next `init` moves the record to flash.
+pub fn record_panic(info: &core::panic::PanicInfo) {
+    let mut message = heapless::String::new();
+    let mut writer = Truncating(&mut message);
+    let _ = match info.location() {
+        Some(location) => write!(
+            writer,
+            "{}:{}: {}",
+            location.file(),
+            location.line(),
+            info.message()
+        ),
+        None => write!(writer, "{}", info.message()),
+    };
+    let record = PanicRecord {
+        boot_count: boot_count(),
+        uptime_s: embassy_time::Instant::now().as_secs() as u32,
+        message,
+    };
+    unsafe { core::ptr::write_volatile(&raw mut RTC_PANIC, record.encode()) };
+}
+
+/// Last recorded panic, and whether it ended the previous boot
+pub fn last_panic() -> (Option<PanicRecord>, bool) {
+    critical_section::with(|cs| LAST_PANIC.borrow_ref(cs).clone())
+}
+
+/// Reset reason code of this boot (0 = unknown)
+fn reset_reason_code() -> (u8, bool) {
+    match esp_hal::rtc_cntl::reset_reason(esp_hal::system::Cpu::ProCpu) {
+        Some(SocResetReason::ChipPowerOn) => (SocResetReason::ChipPowerOn as u8, true),
+        Some(reason) => (reason as u8, false),
+        None => (0, false),
+    }
+}
+
+/// Load the record and note this boot's reset reason
+///
+/// Call once, early in `main`.
+pub fn init() {
+    let (reason, power_on) = reset_reason_code();
+
+    // RTC RAM is lost on power loss; fall back to the flash copy
+    let mut record = ErrorRecord::load_rtc()
+        .filter(|_| !power_on)
+        .or_else(ErrorRecord::load_flash)
+        .unwrap_or(ErrorRecord::EMPTY);
+    let at_boot = record;
+    record.boot_count = record.boot_count.saturating_add(1);
+    BOOT_COUNT.store(record.boot_count, Ordering::Relaxed);
+
+    // Every boot counts as failed until `clear_failed_boots` is called
+    if power_on {
+        record.failed_boots = 0;
+    } else {
+        record.failed_boots = record.failed_boots.saturating_add(1);
+        if reason != 0 {
+            record.last_reset_reason = reason;
+        }
+    }
+    // The boot count changes every time, so this is one flash write per boot
+    record.store_flash();
+    record.store_rtc();
+    critical_section::with(|cs| *BOOT.borrow_ref_mut(cs) = (reason, at_boot));
+
+    let limit = config::SAFE_MODE_BOOT_FAILURES;
+    if limit > 0 && record.failed_boots > limit {
+        SAFE_MODE.store(true, Ordering::Relaxed);
+        println!(
+            "[CRASH] {} boots in a row failed - starting in safe mode",
+            record.failed_boots - 1
+        );
+    }
+
+    println!(
+        "[CRASH] Boot #{}, reset reason {:?}",
+        record.boot_count,
+        esp_hal::rtc_cntl::reset_reason(esp_hal::system::Cpu::ProCpu)
+    );
+    if let Some(error) = at_boot.last_error {
+        println!(
+            "[CRASH] Last error {:?} (from {:?}) at {}s uptime, {} retries, {} errors total",
+            error,
+            at_boot.previous_state,
+            at_boot.uptime_s,
+            at_boot.retry_count,
+            at_boot.error_count
+        );
+    }
+    if at_boot.last_reset_reason != 0 {
+        println!(
+            "[CRASH] Last abnormal reset reason code {}",
+            at_boot.last_reset_reason
+        );
+    }
+
+    // RTC RAM holds garbage after a power cycle
+    let fresh = if power_on {
+        None
+    } else {
+        PanicRecord::take_rtc()
+    };
+    if let Some(panic) = &fresh {
+        println!(
+            "[CRASH] ❌ Boot #{} panicked after {}s: {}",
+            panic.boot_count, panic.uptime_s, panic.message
+        );
+        panic.store_flash();
+    }
+    let panicked = fresh.is_some();
+    let last = fresh.or_else(PanicRecord::load_flash);
+    critical_section::with(|cs| *LAST_PANIC.borrow_ref_mut(cs) = (last, panicked));
+}
+
+/// Boots counted so far, including this one (0 before `init`)
+pub fn boot_count() -> u32 {
+    BOOT_COUNT.load(Ordering::Relaxed)
+}
+
+/// Whether this boot started from power-on rather than a reset
+pub fn is_power_on_boot() -> bool {
+    reset_reason_code().1
+}
+
+/// Whether this boot runs in safe mode (networking only, no LED output)
+pub fn is_safe_mode() -> bool {
+    SAFE_MODE.load(Ordering::Relaxed)
+}
+
+/// Mark this boot as successful, or a new configuration as worth a normal boot
+pub fn clear_failed_boots() {
+    let mut record = ErrorRecord::load_rtc().unwrap_or(ErrorRecord::EMPTY);
+    if record.failed_boots == 0 {
+        return;
+    }
+    record.failed_boots = 0;
+    record.store_rtc();
+    record.store_flash();
+}
+
+/// Record that the state machine entered an error state
+pub fn record_error(error: SystemState, previous_state: Option<SystemState>, retry_count: u32) {
+    let mut record = ErrorRecord::load_rtc().unwrap_or(ErrorRecord::EMPTY);
+    let changed = record.last_error != Some(error);
+    record.last_error = Some(error);
+    record.previous_state = previous_state;
+    record.error_count = record.error_count.saturating_add(1);
+    record.retry_count = retry_count;
+    record.uptime_s = embassy_time::Instant::now().as_secs() as u32;
+    record.store_rtc();
+
+    // Flash only on changes, as a recovery loop re-enters the same error
+    if changed {
+        record.store_flash();
+    }
+}
+
+/// Encode the boot report: `[0x1C][reset reason][last reset reason][last error]`
+/// `[previous state][error count u32][retry count u32][uptime s u32]`
+/// `[failed boots][safe mode][boot count u32][uptime s u32]`, with `0xFF` for
+/// no error, the error fields as found at boot, and this boot's count and
+/// current uptime, followed by the last panic: `[previous boot panicked]`
+/// `[boot count u32][uptime s u32][message length][message...]` (all zero
+/// with no panic recorded)
+pub fn to_response() -> heapless::Vec<u8, RESPONSE_SIZE> {
+    let (reason, record) = critical_section::with(|cs| *BOOT.borrow_ref(cs));
+    let mut response = [0u8; REPORT_FIXED_SIZE];
+    response[0] = config::BOOT_REPORT_HEADER;
+    response[1] = reason;
+    response[2] = record.last_reset_reason;
+    response[3] = record.last_error.map_or(NO_STATE, |s| s as u8);
+    response[4] = record.previous_state.map_or(NO_STATE, |s| s as u8);
+    response[5..9].copy_from_slice(&record.error_count.to_be_bytes());
+    response[9..13].copy_from_slice(&record.retry_count.to_be_bytes());
+    response[13..17].copy_from_slice(&record.uptime_s.to_be_bytes());
+    response[17] = record.failed_boots;
+    response[18] = is_safe_mode() as u8;
+    response[19..23].copy_from_slice(&boot_count().to_be_bytes());
+    let uptime_s = embassy_time::Instant::now().as_secs() as u32;
+    response[23..27].copy_from_slice(&uptime_s.to_be_bytes());
+
+    let mut report = heapless::Vec::new();
+    let _ = report.extend_from_slice(&response);
+    let (panic, panicked) = last_panic();
+    let _ = report.push(panicked as u8);
+    match panic {
+        Some(panic) => {
+            let _ = report.extend_from_slice(&panic.boot_count.to_be_bytes());
+            let _ = report.extend_from_slice(&panic.uptime_s.to_be_bytes());
+            let _ = report.push(panic.message.len() as u8);
+            let _ = report.extend_from_slice(panic.message.as_bytes());
+        }
+        None => {
+            let _ = report.extend_from_slice(&[0; 9]);
+        }
+    }
+    report
+}
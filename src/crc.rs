@@ -0,0 +1,34 @@
+//! CRC checksum helpers
+//!
+//! Bitwise implementations - small code size matters more than throughput here.
+
+/// CRC-32 (IEEE 802.3, reflected, init/xorout 0xFFFFFFFF)
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0xFFFF_FFFF, data) ^ 0xFFFF_FFFF
+}
+
+/// Feed more bytes into a running CRC-32 (start with 0xFFFFFFFF, finish with `^ 0xFFFFFFFF`)
+pub fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(
+            crc32_update(crc32_update(0xFFFF_FFFF, b"1234"), b"56789") ^ 0xFFFF_FFFF,
+            0xCBF4_3926
+        );
+    }
+}
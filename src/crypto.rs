@@ -0,0 +1,629 @@
+//! Encrypted transport module
+//!
+//! Optional ChaCha20-Poly1305 envelope around any protocol packet, keyed with
+//! a pre-shared key, so the LED stream (which mirrors on-screen colours) can't
+//! be read by others on the network.
+//!
+//! Envelope: `[0x20][nonce:12][ciphertext][tag:16]`, with the header byte as
+//! associated data. The plaintext is an ordinary protocol packet.
+//!
+//! The nonce is `[sender id:4][counter:8]` (big-endian). Each sender's counter
+//! must increase monotonically; packets whose counter was already seen, or
+//! fell out of the replay window, are rejected so captured packets can't be
+//! replayed later.
+//!
+//! The newest counter of each sender survives restarts, so packets captured
+//! before one stay rejected after it. A copy in RTC RAM keeps the exact
+//! counters across resets; the flash copy (`config::REPLAY_MARKS_FLASH_OFFSET`)
+//! only moves every `REPLAY_MARK_MARGIN` counters, and reserves the counters up
+//! to its next move, so after a power cycle a sender has to get past that mark
+//! first. Senders dropped from the table to make room for a new one leave
+//! their mark behind as a floor every new sender has to get past.
+
+use crate::{BoardError, config};
+use chacha20poly1305::aead::AeadInPlace;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce, Tag};
+use core::ops::Range;
+use heapless::Vec;
+
+/// Nonce length in bytes
+pub const NONCE_LEN: usize = 12;
+
+/// Authentication tag length in bytes
+pub const TAG_LEN: usize = 16;
+
+/// Envelope bytes around the plaintext
+pub const ENVELOPE_OVERHEAD: usize = 1 + NONCE_LEN + TAG_LEN;
+
+/// Pre-shared key cipher for packet envelopes
+#[derive(Clone)]
+pub struct PacketCipher {
+    cipher: ChaCha20Poly1305,
+}
+
+impl PacketCipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+        }
+    }
+
+    /// Decrypt and authenticate an envelope in place
+    ///
+    /// Returns the nonce and the range of `packet` now holding the plaintext.
+    pub fn open(&self, packet: &mut [u8]) -> Result<(PacketNonce, Range<usize>), BoardError> {
+        if packet.len() < ENVELOPE_OVERHEAD || packet[0] != config::ENCRYPTED_HEADER {
+            return Err(BoardError::ProtocolError);
+        }
+
+        let (aad, rest) = packet.split_at_mut(1);
+        let (nonce, rest) = rest.split_at_mut(NONCE_LEN);
+        let (ciphertext, tag) = rest.split_at_mut(rest.len() - TAG_LEN);
+
+        self.cipher
+            .decrypt_in_place_detached(
+                Nonce::from_slice(nonce),
+                aad,
+                ciphertext,
+                Tag::from_slice(tag),
+            )
+            .map_err(|_| BoardError::ProtocolError)?;
+
+        let nonce = PacketNonce {
+            sender_id: u32::from_be_bytes([nonce[0], nonce[1], nonce[2], nonce[3]]),
+            counter: u64::from_be_bytes([
+                nonce[4], nonce[5], nonce[6], nonce[7], nonce[8], nonce[9], nonce[10], nonce[11],
+            ]),
+        };
+        Ok((nonce, 1 + NONCE_LEN..packet.len() - TAG_LEN))
+    }
+}
+
+/// Decoded envelope nonce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketNonce {
+    pub sender_id: u32,
+    pub counter: u64,
+}
+
+/// Counters older than the newest one by this much are always rejected
+const REPLAY_WINDOW: u64 = 64;
+
+/// Senders tracked by the replay guard at once
+pub const MAX_REPLAY_SENDERS: usize = 8;
+
+/// Counters reserved past a sender's newest one each time its mark is written
+/// to flash, so the mark is written once per this many packets
+const REPLAY_MARK_MARGIN: u64 = 1 << 14;
+
+/// Sliding replay window of one sender
+#[derive(Debug, Clone, Copy)]
+struct SenderWindow {
+    sender_id: u32,
+    /// Highest counter accepted
+    highest: u64,
+    /// Bit `n` set: counter `highest - n` was accepted
+    seen: u64,
+    /// Counters up to this one are covered by the sender's flash mark
+    reserved: u64,
+    /// Acceptance order, for evicting the least recently active sender
+    last_used: u32,
+}
+
+/// Counter high-water mark kept across restarts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayMark {
+    /// Counters up to `counter` may have been used by the sender
+    Sender { sender_id: u32, counter: u64 },
+    /// Counters up to this one are rejected from senders without a mark
+    Forgotten(u64),
+}
+
+impl ReplayMark {
+    /// Encoded size: [magic:2][kind][0][sender id:4][counter:8][crc32:4]
+    pub const SIZE: usize = 20;
+
+    const MAGIC: [u8; 2] = *b"RM";
+    const KIND_SENDER: u8 = 1;
+    const KIND_FORGOTTEN: u8 = 2;
+
+    pub fn encode(&self) -> [u8; Self::SIZE] {
+        let (kind, sender_id, counter) = match *self {
+            ReplayMark::Sender { sender_id, counter } => (Self::KIND_SENDER, sender_id, counter),
+            ReplayMark::Forgotten(counter) => (Self::KIND_FORGOTTEN, 0, counter),
+        };
+        let mut entry = [0u8; Self::SIZE];
+        entry[..2].copy_from_slice(&Self::MAGIC);
+        entry[2] = kind;
+        entry[4..8].copy_from_slice(&sender_id.to_be_bytes());
+        entry[8..16].copy_from_slice(&counter.to_be_bytes());
+        let crc = crate::crc::crc32(&entry[..16]);
+        entry[16..].copy_from_slice(&crc.to_be_bytes());
+        entry
+    }
+
+    /// Decode an entry (`None` for erased or damaged ones)
+    pub fn decode(entry: &[u8; Self::SIZE]) -> Option<Self> {
+        let (body, crc) = entry.split_at(16);
+        if body[..2] != Self::MAGIC
+            || crate::crc::crc32(body) != u32::from_be_bytes(crc.try_into().ok()?)
+        {
+            return None;
+        }
+        let sender_id = u32::from_be_bytes(body[4..8].try_into().ok()?);
+        let counter = u64::from_be_bytes(body[8..16].try_into().ok()?);
+        match body[2] {
+            Self::KIND_SENDER => Some(ReplayMark::Sender { sender_id, counter }),
+            Self::KIND_FORGOTTEN => Some(ReplayMark::Forgotten(counter)),
+            _ => None,
+        }
+    }
+}
+
+/// Rejects envelopes whose nonce counter was already used
+#[derive(Debug, Default)]
+pub struct ReplayGuard {
+    senders: Vec<SenderWindow, MAX_REPLAY_SENDERS>,
+    /// Counters up to this one are rejected from senders not in the table;
+    /// raised to the mark of every sender dropped from it
+    forgotten: u64,
+    /// `forgotten` as last written to flash
+    forgotten_reserved: u64,
+    clock: u32,
+    /// Envelopes rejected as replays
+    pub rejected: u32,
+}
+
+impl ReplayGuard {
+    /// Rebuild the guard from the marks kept across a restart
+    ///
+    /// Every counter up to a sender's mark counts as seen. Senders that don't
+    /// fit the table raise the floor for unknown senders instead.
+    pub fn restore(marks: impl IntoIterator<Item = ReplayMark>) -> Self {
+        let mut guard = Self::default();
+        for mark in marks {
+            match mark {
+                ReplayMark::Sender { sender_id, counter } => {
+                    if let Some(window) =
+                        guard.senders.iter_mut().find(|w| w.sender_id == sender_id)
+                    {
+                        window.highest = window.highest.max(counter);
+                        window.reserved = window.highest;
+                    } else if let Err(window) = guard.senders.push(SenderWindow {
+                        sender_id,
+                        highest: counter,
+                        seen: u64::MAX,
+                        reserved: counter,
+                        last_used: 0,
+                    }) {
+                        guard.forgotten = guard.forgotten.max(window.highest);
+                    }
+                }
+                ReplayMark::Forgotten(counter) => guard.forgotten = guard.forgotten.max(counter),
+            }
+        }
+        guard.forgotten_reserved = guard.forgotten;
+        guard
+    }
+
+    /// Check an authenticated nonce, recording it if it is new
+    pub fn accept(&mut self, nonce: PacketNonce) -> bool {
+        self.clock = self.clock.wrapping_add(1);
+
+        let Some(window) = self
+            .senders
+            .iter_mut()
+            .find(|w| w.sender_id == nonce.sender_id)
+        else {
+            // Packets from a dropped sender can't be told from its replays
+            if nonce.counter <= self.forgotten {
+                self.rejected = self.rejected.saturating_add(1);
+                return false;
+            }
+            // First packet from this sender - evict the least recently active one if full
+            if self.senders.is_full()
+                && let Some(oldest) = (0..self.senders.len())
+                    .max_by_key(|&i| self.clock.wrapping_sub(self.senders[i].last_used))
+            {
+                let evicted = self.senders.swap_remove(oldest);
+                self.forgotten = self.forgotten.max(evicted.highest.max(evicted.reserved));
+            }
+            let _ = self.senders.push(SenderWindow {
+                sender_id: nonce.sender_id,
+                highest: nonce.counter,
+                seen: 1,
+                reserved: 0,
+                last_used: self.clock,
+            });
+            return true;
+        };
+
+        if nonce.counter > window.highest {
+            let shift = nonce.counter - window.highest;
+            window.seen = if shift >= REPLAY_WINDOW {
+                1
+            } else {
+                (window.seen << shift) | 1
+            };
+            window.highest = nonce.counter;
+        } else {
+            let age = window.highest - nonce.counter;
+            if age >= REPLAY_WINDOW || window.seen & (1 << age) != 0 {
+                self.rejected = self.rejected.saturating_add(1);
+                return false;
+            }
+            window.seen |= 1 << age;
+        }
+
+        window.last_used = self.clock;
+        true
+    }
+
+    /// Next mark to write to flash, if any (call after `accept` until `None`)
+    ///
+    /// A sender's mark is due once its counter passes the last one written,
+    /// and then reserves `REPLAY_MARK_MARGIN` counters ahead.
+    pub fn take_reservation(&mut self) -> Option<ReplayMark> {
+        if let Some(window) = self.senders.iter_mut().find(|w| w.highest > w.reserved) {
+            window.reserved = window.highest.saturating_add(REPLAY_MARK_MARGIN);
+            return Some(ReplayMark::Sender {
+                sender_id: window.sender_id,
+                counter: window.reserved,
+            });
+        }
+        (self.forgotten > self.forgotten_reserved).then(|| {
+            self.forgotten_reserved = self.forgotten;
+            ReplayMark::Forgotten(self.forgotten)
+        })
+    }
+
+    /// Newest counter of every sender, and the floor for the others
+    pub fn marks(&self) -> impl Iterator<Item = ReplayMark> + '_ {
+        self.senders
+            .iter()
+            .map(|w| ReplayMark::Sender {
+                sender_id: w.sender_id,
+                counter: w.highest,
+            })
+            .chain(core::iter::once(ReplayMark::Forgotten(self.forgotten)))
+    }
+
+    /// Marks as reserved in flash, for rewriting the flash copy
+    pub fn reservations(&self) -> impl Iterator<Item = ReplayMark> + '_ {
+        self.senders
+            .iter()
+            .map(|w| ReplayMark::Sender {
+                sender_id: w.sender_id,
+                counter: w.reserved.max(w.highest),
+            })
+            .chain(core::iter::once(ReplayMark::Forgotten(
+                self.forgotten_reserved.max(self.forgotten),
+            )))
+    }
+}
+
+/// Entries of the flash log
+#[cfg(target_os = "none")]
+const MARK_LOG_ENTRIES: usize = 4096 / ReplayMark::SIZE;
+
+/// Slots of the RTC copy: one per sender and the floor for the others
+#[cfg(target_os = "none")]
+const RTC_MARK_SLOTS: usize = MAX_REPLAY_SENDERS + 1;
+
+/// Exact marks, kept across resets other than power loss
+#[cfg(target_os = "none")]
+#[esp_hal::ram(rtc_fast, persistent)]
+static mut RTC_MARKS: [[u8; ReplayMark::SIZE]; RTC_MARK_SLOTS] =
+    [[0; ReplayMark::SIZE]; RTC_MARK_SLOTS];
+
+/// Word-aligned flash entry, so it is written without a bounce buffer
+#[cfg(target_os = "none")]
+#[repr(align(4))]
+struct FlashEntry([u8; ReplayMark::SIZE]);
+
+/// RTC RAM and flash copies of the replay guard's marks
+///
+/// The flash copy is a log of entries in its own sector, appended to as marks
+/// are reserved; the sector is only erased, and rewritten with the current
+/// marks, once the log is full.
+#[cfg(target_os = "none")]
+pub struct MarkStore {
+    /// Next free entry of the flash log
+    next_entry: usize,
+}
+
+#[cfg(target_os = "none")]
+impl MarkStore {
+    /// Restore the guard left by the previous boot: from RTC RAM after a
+    /// reset, from flash after a power cycle
+    pub fn load() -> (Self, ReplayGuard) {
+        use embedded_storage::nor_flash::ReadNorFlash;
+
+        let mut flash = esp_storage::FlashStorage::new();
+        let mut next_entry = 0;
+        let log = core::iter::from_fn(|| {
+            while next_entry < MARK_LOG_ENTRIES {
+                let mut entry = FlashEntry([0; ReplayMark::SIZE]);
+                if flash.read(Self::offset(next_entry), &mut entry.0).is_err() {
+                    // Unreadable - start a fresh log on the next write
+                    next_entry = MARK_LOG_ENTRIES;
+                    return None;
+                }
+                if entry.0 == [0xFF; ReplayMark::SIZE] {
+                    return None;
+                }
+                next_entry += 1;
+                if let Some(mark) = ReplayMark::decode(&entry.0) {
+                    return Some(mark);
+                }
+            }
+            None
+        });
+        let stored = ReplayGuard::restore(log);
+
+        let rtc = unsafe { core::ptr::read_volatile(&raw const RTC_MARKS) };
+        let exact: Vec<ReplayMark, RTC_MARK_SLOTS> =
+            rtc.iter().filter_map(ReplayMark::decode).collect();
+        // A valid RTC copy always holds the floor
+        let guard = if exact
+            .iter()
+            .any(|mark| matches!(mark, ReplayMark::Forgotten(_)))
+        {
+            crate::println!("[CRYPTO] Replay marks restored from RTC RAM");
+            ReplayGuard::restore(exact)
+        } else {
+            crate::println!("[CRYPTO] Replay marks restored from flash");
+            stored
+        };
+        (Self { next_entry }, guard)
+    }
+
+    /// Save the guard's marks after it accepted a packet
+    pub fn save(&mut self, guard: &mut ReplayGuard) {
+        let mut rtc = [[0; ReplayMark::SIZE]; RTC_MARK_SLOTS];
+        for (slot, mark) in rtc.iter_mut().zip(guard.marks()) {
+            *slot = mark.encode();
+        }
+        unsafe { core::ptr::write_volatile(&raw mut RTC_MARKS, rtc) };
+
+        while let Some(mark) = guard.take_reservation() {
+            if let Err(e) = self.append(mark, guard) {
+                crate::println!("[CRYPTO] Saving replay mark failed: {:?}", e);
+                return;
+            }
+        }
+    }
+
+    /// Append a mark to the flash log, compacting it when full
+    fn append(&mut self, mark: ReplayMark, guard: &ReplayGuard) -> Result<(), BoardError> {
+        use embedded_storage::nor_flash::NorFlash;
+
+        let mut flash = esp_storage::FlashStorage::new();
+        if self.next_entry >= MARK_LOG_ENTRIES {
+            // The guard's reservations already include this mark
+            let start = config::REPLAY_MARKS_FLASH_OFFSET;
+            flash
+                .erase(start, start + 4096)
+                .map_err(|_| BoardError::StorageError)?;
+            self.next_entry = 0;
+            for mark in guard.reservations() {
+                self.write(&mut flash, mark)?;
+            }
+            return Ok(());
+        }
+        self.write(&mut flash, mark)
+    }
+
+    fn write(
+        &mut self,
+        flash: &mut esp_storage::FlashStorage,
+        mark: ReplayMark,
+    ) -> Result<(), BoardError> {
+        use embedded_storage::nor_flash::NorFlash;
+
+        let entry = FlashEntry(mark.encode());
+        flash
+            .write(Self::offset(self.next_entry), &entry.0)
+            .map_err(|_| BoardError::StorageError)?;
+        self.next_entry += 1;
+        Ok(())
+    }
+
+    fn offset(index: usize) -> u32 {
+        config::REPLAY_MARKS_FLASH_OFFSET + (index * ReplayMark::SIZE) as u32
+    }
+}
+
+/// Parse a 64-digit hex pre-shared key (empty or malformed disables encryption)
+pub fn parse_key(hex: &str) -> Option<[u8; 32]> {
+    let hex = hex.as_bytes();
+    if hex.len() != 64 {
+        return None;
+    }
+
+    let mut key = [0u8; 32];
+    for (byte, pair) in key.iter_mut().zip(hex.chunks_exact(2)) {
+        let high = (pair[0] as char).to_digit(16)?;
+        let low = (pair[1] as char).to_digit(16)?;
+        *byte = (high << 4 | low) as u8;
+    }
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    fn nonce(sender_id: u32, counter: u64) -> PacketNonce {
+        PacketNonce { sender_id, counter }
+    }
+
+    /// Wrap a plaintext the way a sender would
+    fn seal(key: &[u8; 32], nonce: PacketNonce, plaintext: &[u8]) -> std::vec::Vec<u8> {
+        let mut packet = std::vec![config::ENCRYPTED_HEADER];
+        packet.extend_from_slice(&nonce.sender_id.to_be_bytes());
+        packet.extend_from_slice(&nonce.counter.to_be_bytes());
+        let mut body = plaintext.to_vec();
+        let tag = ChaCha20Poly1305::new(Key::from_slice(key))
+            .encrypt_in_place_detached(Nonce::from_slice(&packet[1..]), &packet[..1], &mut body)
+            .unwrap();
+        packet.extend_from_slice(&body);
+        packet.extend_from_slice(&tag);
+        packet
+    }
+
+    #[test]
+    fn open_round_trips() {
+        let plaintext = [0x02, 0x00, 0x00, 0xFF, 0x80, 0x00];
+        let mut packet = seal(&KEY, nonce(3, 42), &plaintext);
+
+        let (opened, range) = PacketCipher::new(&KEY).open(&mut packet).unwrap();
+        assert_eq!(opened, nonce(3, 42));
+        assert_eq!(&packet[range], &plaintext);
+    }
+
+    #[test]
+    fn open_rejects_tampered_packets() {
+        let cipher = PacketCipher::new(&KEY);
+        let mut packet = seal(&KEY, nonce(3, 42), &[1, 2, 3]);
+        let last = packet.len() - 1;
+        packet[last] ^= 1;
+        assert!(cipher.open(&mut packet).is_err());
+
+        let mut packet = seal(&KEY, nonce(3, 42), &[1, 2, 3]);
+        packet[1 + NONCE_LEN] ^= 1;
+        assert!(cipher.open(&mut packet).is_err());
+
+        let mut packet = seal(&[8; 32], nonce(3, 42), &[1, 2, 3]);
+        assert!(cipher.open(&mut packet).is_err());
+    }
+
+    #[test]
+    fn open_rejects_wrong_headers() {
+        let cipher = PacketCipher::new(&KEY);
+        let mut packet = seal(&KEY, nonce(3, 42), &[1, 2, 3]);
+        packet[0] = 0x02;
+        assert!(cipher.open(&mut packet).is_err());
+
+        let mut short = [config::ENCRYPTED_HEADER; ENVELOPE_OVERHEAD - 1];
+        assert!(cipher.open(&mut short).is_err());
+    }
+
+    #[test]
+    fn keys_parse_from_hex() {
+        let hex = "000102030405060708090a0b0c0d0e0f101112131415161718191A1B1C1D1E1F";
+        let key = parse_key(hex).unwrap();
+        assert_eq!(key[0], 0x00);
+        assert_eq!(key[10], 0x0A);
+        assert_eq!(key[31], 0x1F);
+
+        assert_eq!(parse_key(""), None);
+        assert_eq!(parse_key(&hex[..62]), None);
+        assert_eq!(parse_key(&std::format!("{hex}00")), None);
+        assert_eq!(parse_key(&hex.replace("0a", "0g")), None);
+    }
+
+    #[test]
+    fn duplicates_are_rejected() {
+        let mut guard = ReplayGuard::default();
+        assert!(guard.accept(nonce(1, 100)));
+        assert!(!guard.accept(nonce(1, 100)));
+        assert!(guard.accept(nonce(1, 101)));
+        assert!(!guard.accept(nonce(1, 101)));
+        assert_eq!(guard.rejected, 2);
+    }
+
+    #[test]
+    fn window_edge() {
+        let mut guard = ReplayGuard::default();
+        assert!(guard.accept(nonce(1, 1000)));
+        assert!(guard.accept(nonce(1, 1000 - (REPLAY_WINDOW - 1))));
+        assert!(!guard.accept(nonce(1, 1000 - REPLAY_WINDOW)));
+    }
+
+    #[test]
+    fn out_of_order_packets_are_accepted_once() {
+        let mut guard = ReplayGuard::default();
+        for counter in [10, 12, 11, 15, 13] {
+            assert!(guard.accept(nonce(1, counter)));
+        }
+        for counter in [10, 11, 12, 13, 15] {
+            assert!(!guard.accept(nonce(1, counter)));
+        }
+        assert!(guard.accept(nonce(1, 14)));
+    }
+
+    #[test]
+    fn evicted_senders_raise_the_floor() {
+        let mut guard = ReplayGuard::default();
+        for sender in 0..MAX_REPLAY_SENDERS as u32 {
+            assert!(guard.accept(nonce(sender, 500 + sender as u64)));
+        }
+        // Sender 0 is the least recently active and makes room for sender 100
+        assert!(guard.accept(nonce(100, 1000)));
+
+        assert!(!guard.accept(nonce(0, 500)));
+        assert!(!guard.accept(nonce(200, 400)));
+        assert!(guard.accept(nonce(0, 501)));
+    }
+
+    #[test]
+    fn restored_marks_reject_older_counters() {
+        let mut guard = ReplayGuard::default();
+        assert!(guard.accept(nonce(1, 50)));
+        assert!(guard.accept(nonce(2, 70)));
+        let marks: std::vec::Vec<_> = guard.marks().collect();
+
+        let mut restored = ReplayGuard::restore(marks);
+        assert!(!restored.accept(nonce(1, 50)));
+        assert!(!restored.accept(nonce(1, 20)));
+        assert!(!restored.accept(nonce(2, 70)));
+        assert!(restored.accept(nonce(1, 51)));
+        assert!(restored.accept(nonce(2, 71)));
+    }
+
+    #[test]
+    fn reservations_run_ahead_of_the_counter() {
+        let mut guard = ReplayGuard::default();
+        assert!(guard.accept(nonce(1, 50)));
+        let mark = guard.take_reservation();
+        assert_eq!(
+            mark,
+            Some(ReplayMark::Sender {
+                sender_id: 1,
+                counter: 50 + REPLAY_MARK_MARGIN
+            })
+        );
+        assert_eq!(guard.take_reservation(), None);
+
+        // Nothing to write until the counter passes the reservation
+        assert!(guard.accept(nonce(1, 50 + REPLAY_MARK_MARGIN)));
+        assert_eq!(guard.take_reservation(), None);
+        assert!(guard.accept(nonce(1, 51 + REPLAY_MARK_MARGIN)));
+        assert!(guard.take_reservation().is_some());
+
+        // After a power cycle the reserved counters stay rejected
+        let mut restored = ReplayGuard::restore(mark);
+        assert!(!restored.accept(nonce(1, 50 + REPLAY_MARK_MARGIN)));
+        assert!(restored.accept(nonce(1, 51 + REPLAY_MARK_MARGIN)));
+    }
+
+    #[test]
+    fn marks_round_trip() {
+        for mark in [
+            ReplayMark::Sender {
+                sender_id: 0xDEAD_BEEF,
+                counter: 1 << 40,
+            },
+            ReplayMark::Forgotten(12345),
+        ] {
+            let mut entry = mark.encode();
+            assert_eq!(ReplayMark::decode(&entry), Some(mark));
+            entry[10] ^= 1;
+            assert_eq!(ReplayMark::decode(&entry), None);
+        }
+        assert_eq!(ReplayMark::decode(&[0xFF; ReplayMark::SIZE]), None);
+    }
+}
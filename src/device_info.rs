@@ -0,0 +1,102 @@
+//! Device information module
+//!
+//! Facts about this board that senders use to configure themselves: firmware
+//! version, MAC address, LED count, pixel format and packet limits. The reply
+//! also carries the uptime and boot count, so an app can tell a board that
+//! rebooted behind its back.
+//!
+//! The eFuse base MAC doubles as the device ID: it never changes, unlike the
+//! board's address or name, so apps key their records of a board on it. The
+//! 6-byte MAC field of the `0x10` and `0x1A` replies carries it, and mDNS
+//! advertises it as the `id=` TXT key (`device_id`).
+
+use crate::config;
+use crate::led_control::PixelFormat;
+use heapless::{String, Vec};
+
+/// Maximum encoded size of a device info response
+pub const MAX_DEVICE_INFO_SIZE: usize = 64;
+
+/// Board identity and capabilities
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// Base MAC address from eFuse
+    pub mac: [u8; 6],
+    /// Configured number of LEDs on the strip
+    pub led_count: u16,
+    /// Pixel format expected in LED data
+    pub pixel_format: PixelFormat,
+    /// Largest UDP packet accepted
+    pub max_packet_size: u16,
+    /// Seconds since boot
+    pub uptime_s: u32,
+    /// Boots since the crash log was created, including this one
+    pub boot_count: u32,
+}
+
+impl DeviceInfo {
+    /// Read device information from eFuse and the stored configuration
+    pub fn read() -> Self {
+        Self {
+            mac: esp_hal::efuse::Efuse::read_base_mac_address(),
+            led_count: crate::config_store::led_count() as u16,
+            pixel_format: crate::config_store::pixel_format(),
+            max_packet_size: crate::udp_server::MAX_PACKET_SIZE as u16,
+            uptime_s: embassy_time::Instant::now().as_secs() as u32,
+            boot_count: crate::crash_log::boot_count(),
+        }
+    }
+
+    /// Stable device ID: the base MAC as 12 lowercase hex digits
+    pub fn device_id(&self) -> String<12> {
+        let mut id = String::new();
+        for byte in self.mac {
+            let _ = core::fmt::write(&mut id, format_args!("{:02x}", byte));
+        }
+        id
+    }
+
+    /// Encode as a device info response
+    ///
+    /// Format: [0x10][protocol version][mac (device ID):6][led_count:2][pixel format]
+    /// [bytes per LED][max_packet_size:2][version length][version...][uptime s:4]
+    /// [boot count:4]
+    pub fn to_response(&self) -> Vec<u8, MAX_DEVICE_INFO_SIZE> {
+        let mut response = Vec::new();
+        let version = crate::VERSION.as_bytes();
+        let version = &version[..version.len().min(32)];
+
+        let _ = response.push(config::DEVICE_INFO_HEADER);
+        let _ = response.push(config::PROTOCOL_VERSION);
+        let _ = response.extend_from_slice(&self.mac);
+        let _ = response.extend_from_slice(&self.led_count.to_be_bytes());
+        let _ = response.push(self.pixel_format as u8);
+        let _ = response.push(self.pixel_format.bytes_per_led() as u8);
+        let _ = response.extend_from_slice(&self.max_packet_size.to_be_bytes());
+        let _ = response.push(version.len() as u8);
+        let _ = response.extend_from_slice(version);
+        let _ = response.extend_from_slice(&self.uptime_s.to_be_bytes());
+        let _ = response.extend_from_slice(&self.boot_count.to_be_bytes());
+
+        response
+    }
+
+    /// Encode as a broadcast discovery reply
+    ///
+    /// Format: [0x1A][magic:4][mac (device ID):6][port:2][led_count:2][name length][name...]
+    pub fn to_discovery_response(&self, port: u16, name: &str) -> Vec<u8, MAX_DEVICE_INFO_SIZE> {
+        let mut response = Vec::new();
+        let name = name.as_bytes();
+        let name = &name[..name.len().min(MAX_DEVICE_INFO_SIZE - 16)];
+
+        let _ = response.push(config::DISCOVERY_HEADER);
+        let _ = response.extend_from_slice(config::DISCOVERY_MAGIC);
+        let _ = response.extend_from_slice(&self.mac);
+        let _ = response.extend_from_slice(&port.to_be_bytes());
+        let _ = response.extend_from_slice(&self.led_count.to_be_bytes());
+        let _ = response.push(name.len() as u8);
+        let _ = response.extend_from_slice(name);
+
+        response
+    }
+}
@@ -0,0 +1,106 @@
+//! Bring-up diagnostics module
+//!
+//! On-demand GPIO toggling for scope/logic-analyzer verification. Only pins
+//! not claimed by any driver may be toggled, so a diagnostic can run while the
+//! system is otherwise operational.
+
+use crate::config;
+use crate::println;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Timer};
+use esp_hal::gpio::{AnyPin, Level, Output, OutputConfig};
+
+/// Shortest accepted toggle half-period in milliseconds
+const MIN_HALF_PERIOD_MS: u16 = 1;
+
+/// GPIO toggle request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpioToggleRequest {
+    /// GPIO number
+    pub pin: u8,
+    /// Time the pin spends in each level
+    pub half_period_ms: u16,
+    /// Number of high/low cycles
+    pub cycles: u16,
+}
+
+/// Result of submitting a diagnostics request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DiagnosticsStatus {
+    Accepted = 0,
+    /// Pin is in use by a driver, reserved for flash/USB, or doesn't exist
+    PinNotAllowed = 1,
+    /// A diagnostic is already running
+    Busy = 2,
+    /// Malformed request
+    Invalid = 3,
+}
+
+/// Pending GPIO toggle request
+static GPIO_TOGGLE_REQUESTS: Channel<CriticalSectionRawMutex, GpioToggleRequest, 1> =
+    Channel::new();
+
+/// Check whether `pin` may be toggled without disturbing the running system
+pub fn is_pin_allowed(pin: u8) -> bool {
+    if !config::DIAG_GPIO_PINS.contains(&pin) {
+        return false;
+    }
+    !crate::board_profile::PROFILE.uses_pin(pin, config::ADALIGHT_ENABLED)
+}
+
+/// Parse and queue a GPIO toggle request
+///
+/// Format: [0x11][pin][half_period_ms:2][cycles:2]
+pub fn submit_gpio_toggle(data: &[u8]) -> DiagnosticsStatus {
+    if data.len() != 6 || data[0] != config::DIAGNOSTICS_GPIO_HEADER {
+        return DiagnosticsStatus::Invalid;
+    }
+
+    let request = GpioToggleRequest {
+        pin: data[1],
+        half_period_ms: u16::from_be_bytes([data[2], data[3]]),
+        cycles: u16::from_be_bytes([data[4], data[5]]),
+    };
+
+    if request.half_period_ms < MIN_HALF_PERIOD_MS || request.cycles == 0 {
+        return DiagnosticsStatus::Invalid;
+    }
+    if !is_pin_allowed(request.pin) {
+        return DiagnosticsStatus::PinNotAllowed;
+    }
+
+    match GPIO_TOGGLE_REQUESTS.try_send(request) {
+        Ok(_) => DiagnosticsStatus::Accepted,
+        Err(_) => DiagnosticsStatus::Busy,
+    }
+}
+
+/// Diagnostics task: runs queued GPIO toggle requests one at a time
+#[embassy_executor::task]
+pub async fn diagnostics_task() {
+    loop {
+        let request = GPIO_TOGGLE_REQUESTS.receive().await;
+        crate::memory::checkpoint(crate::memory::Subsystem::Diagnostics);
+        println!(
+            "[DIAG] Toggling GPIO{} every {}ms for {} cycles",
+            request.pin, request.half_period_ms, request.cycles
+        );
+
+        // SAFETY: `submit_gpio_toggle` only accepts pins from the diagnostics
+        // allowlist, none of which are owned by a driver
+        let pin = unsafe { AnyPin::steal(request.pin) };
+        let mut output = Output::new(pin, Level::Low, OutputConfig::default());
+        let half_period = Duration::from_millis(request.half_period_ms as u64);
+
+        for _ in 0..request.cycles {
+            output.set_high();
+            Timer::after(half_period).await;
+            output.set_low();
+            Timer::after(half_period).await;
+        }
+
+        println!("[DIAG] GPIO{} toggle finished", request.pin);
+    }
+}
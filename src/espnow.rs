@@ -0,0 +1,41 @@
+//! ESP-NOW receive module
+//!
+//! Lets a sender ESP32 (or the desktop through a dongle board) push LED data
+//! peer-to-peer, without any access point involved. That cuts latency and keeps
+//! working when the Wi-Fi infrastructure is down.
+//!
+//! Each ESP-NOW frame carries one ordinary 0x02/0x05 data packet (at most 250
+//! bytes, so larger strips are sent as several zones) and is rendered as soon
+//! as it arrives.
+
+use crate::config;
+use crate::led_control::LedDataSignal;
+use crate::println;
+use crate::udp_server::UdpServer;
+use alloc::vec::Vec;
+use esp_wifi::esp_now::EspNow;
+
+/// ESP-NOW receiver task
+#[embassy_executor::task]
+pub async fn espnow_task(mut esp_now: EspNow<'static>, led_data_sender: &'static LedDataSignal) {
+    match config::ESPNOW_PEER {
+        Some(peer) => println!(
+            "[ESPNOW] Accepting LED data from {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            peer[0], peer[1], peer[2], peer[3], peer[4], peer[5]
+        ),
+        None => println!("[ESPNOW] Accepting LED data from any peer"),
+    }
+
+    let mut frame: Vec<u8> = Vec::new();
+
+    loop {
+        crate::memory::checkpoint(crate::memory::Subsystem::EspNow);
+
+        let received = esp_now.receive_async().await;
+        if config::ESPNOW_PEER.is_some_and(|peer| peer != received.info.src_address) {
+            continue;
+        }
+
+        let _ = UdpServer::render_packet(&mut frame, received.data(), led_data_sender);
+    }
+}
@@ -0,0 +1,368 @@
+//! ESP-Touch (SmartConfig v1) provisioning module
+//!
+//! The Espressif ESP-Touch phone apps onboard a board without it joining
+//! anything: while the phone is on the target network it broadcasts UDP
+//! packets whose *lengths* encode the SSID and password. The board sniffs the
+//! air in promiscuous mode, so only the 802.11 frame lengths are needed, even
+//! on encrypted networks.
+//!
+//! The stream starts with a guide code (lengths 515, 514, 513, 512), which also
+//! tells the fixed encryption/header overhead. Each data byte then follows as
+//! three lengths (minus 40): `[crc high | data high]`, `256 + index`,
+//! `[crc low | data low]`, with the CRC-8 over the byte and its index. The
+//! decoded payload is:
+//!
+//! `[total len][password len][ssid crc][bssid crc][xor][phone ip:4][password][ssid]`
+//!
+//! Once the board joins the network it acknowledges with
+//! `[total len][mac:6][ip:4]` to the phone on UDP port 18266.
+
+use crate::BoardError;
+use crate::println;
+use crate::wifi::WiFiManager;
+use embassy_net::{
+    IpAddress, IpEndpoint, Stack,
+    udp::{PacketMetadata, UdpSocket},
+};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Instant, Timer};
+use esp_wifi::wifi::{PromiscuousPkt, Sniffer};
+use heapless::{String, Vec};
+
+/// Lengths of the guide code, offset by the frame overhead
+const GUIDE_CODE: [u16; 4] = [515, 514, 513, 512];
+
+/// Constant added to every datum length by the sender
+const DATUM_EXTRA_LEN: u16 = 40;
+
+/// Bytes before the password: total len, password len, ssid crc, bssid crc, xor, ip
+const HEADER_LEN: usize = 9;
+
+/// Largest payload: header, 64 byte password, 32 byte SSID
+const MAX_PAYLOAD: usize = HEADER_LEN + 64 + 32;
+
+/// UDP port the phone app listens on for the acknowledgement
+const ACK_PORT: u16 = 18266;
+
+/// Acknowledgements sent, as the phone may miss some
+const ACK_COUNT: usize = 30;
+
+/// Dwell time per channel while looking for a guide code
+const CHANNEL_DWELL: Duration = Duration::from_millis(200);
+
+/// A locked sender is dropped after this long without a new byte
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Senders tracked at once while searching for a guide code
+const MAX_CANDIDATES: usize = 8;
+
+/// A broadcast data frame seen in promiscuous mode
+#[derive(Debug, Clone, Copy)]
+struct SniffedFrame {
+    /// Original sender and whether the frame was on its way to the AP
+    source: ([u8; 6], bool),
+    len: u16,
+}
+
+/// Frames handed from the WiFi driver callback to the listener
+static FRAMES: Channel<CriticalSectionRawMutex, SniffedFrame, 32> = Channel::new();
+
+/// Promiscuous receive callback, runs in the WiFi driver's context
+fn on_frame(packet: PromiscuousPkt<'_>) {
+    let data = packet.data;
+    // Data frames only, with a 24 byte header
+    if data.len() < 24 || data[0] & 0x0C != 0x08 {
+        return;
+    }
+
+    // Destination and source address positions depend on the DS bits
+    let (destination, source, to_ds) = match data[1] & 0x03 {
+        0x01 => (&data[16..22], &data[10..16], true),
+        0x02 => (&data[4..10], &data[16..22], false),
+        _ => return,
+    };
+    if destination != [0xFF; 6] {
+        return;
+    }
+
+    let mut sender = [0u8; 6];
+    sender.copy_from_slice(source);
+    FRAMES
+        .try_send(SniffedFrame {
+            source: (sender, to_ds),
+            len: packet.len as u16,
+        })
+        .ok();
+}
+
+/// CRC-8/MAXIM as used by ESP-Touch
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x01 != 0 {
+                (crc >> 1) ^ 0x8C
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// Credentials decoded from an ESP-Touch stream
+#[derive(Debug, Clone)]
+pub struct EspTouchResult {
+    pub ssid: String<32>,
+    pub password: String<64>,
+    /// Phone address awaiting the acknowledgement
+    pub phone: [u8; 4],
+    /// Payload length, echoed in the acknowledgement
+    pub total_len: u8,
+}
+
+/// Decoder for the datum lengths of one locked sender
+#[derive(Debug)]
+pub struct EspTouchDecoder {
+    /// Frame overhead found from the guide code
+    offset: u16,
+    /// Last three datum values
+    window: Vec<u16, 3>,
+    payload: [u8; MAX_PAYLOAD],
+    received: u128,
+}
+
+impl EspTouchDecoder {
+    /// Start decoding with the frame overhead found from the guide code
+    pub fn new(offset: u16) -> Self {
+        Self {
+            offset,
+            window: Vec::new(),
+            payload: [0; MAX_PAYLOAD],
+            received: 0,
+        }
+    }
+
+    /// Feed one frame length, returning true when it completed a byte
+    pub fn push(&mut self, len: u16) -> bool {
+        let Some(value) = len
+            .checked_sub(self.offset + DATUM_EXTRA_LEN)
+            .filter(|&v| v < 512)
+        else {
+            return false;
+        };
+
+        if self.window.is_full() {
+            self.window.remove(0);
+        }
+        self.window.push(value).ok();
+
+        let &[high, sequence, low] = self.window.as_slice() else {
+            return false;
+        };
+        if high >= 256 || sequence < 256 || low >= 256 {
+            return false;
+        }
+        self.window.clear();
+
+        let index = (sequence - 256) as u8;
+        let byte = ((high & 0x0F) << 4 | (low & 0x0F)) as u8;
+        let crc = ((high & 0xF0) | (low >> 4)) as u8;
+        if crc8(&[byte, index]) != crc || index as usize >= MAX_PAYLOAD {
+            return false;
+        }
+
+        self.payload[index as usize] = byte;
+        self.received |= 1 << index;
+        true
+    }
+
+    /// The decoded credentials, once every byte arrived and checks out
+    pub fn result(&self) -> Option<EspTouchResult> {
+        if self.received & 0x03 != 0x03 {
+            return None;
+        }
+        let total_len = self.payload[0] as usize;
+        let password_len = self.payload[1] as usize;
+        if !(HEADER_LEN + password_len..=MAX_PAYLOAD).contains(&total_len) {
+            return None;
+        }
+        let complete = (1u128 << total_len) - 1;
+        if self.received & complete != complete {
+            return None;
+        }
+
+        let payload = &self.payload[..total_len];
+        let ssid = &payload[HEADER_LEN + password_len..];
+        if payload.iter().fold(0, |xor, b| xor ^ b) != 0 || crc8(ssid) != payload[2] {
+            return None;
+        }
+
+        Some(EspTouchResult {
+            ssid: String::try_from(core::str::from_utf8(ssid).ok()?).ok()?,
+            password: String::try_from(
+                core::str::from_utf8(&payload[HEADER_LEN..HEADER_LEN + password_len]).ok()?,
+            )
+            .ok()?,
+            phone: [payload[5], payload[6], payload[7], payload[8]],
+            total_len: total_len as u8,
+        })
+    }
+}
+
+/// Recent frame lengths of a sender not yet locked on
+struct Candidate {
+    source: ([u8; 6], bool),
+    recent: [u16; 4],
+}
+
+impl Candidate {
+    /// Frame overhead, if the last four lengths were a guide code
+    fn guide_offset(&self) -> Option<u16> {
+        let offset = self.recent[0].checked_sub(GUIDE_CODE[0])?;
+        GUIDE_CODE
+            .iter()
+            .zip(self.recent)
+            .all(|(guide, len)| guide + offset == len)
+            .then_some(offset)
+    }
+}
+
+/// Listen for an ESP-Touch broadcast for up to `window`
+///
+/// Hops channels until a guide code shows up, then stays on that channel with
+/// the sender locked. The station must not be associated meanwhile.
+pub async fn listen(
+    wifi_manager: &mut WiFiManager<'_>,
+    sniffer: &mut Sniffer,
+    window: Duration,
+) -> Result<Option<EspTouchResult>, BoardError> {
+    wifi_manager.start_station().await?;
+    sniffer.set_receive_cb(on_frame);
+    sniffer
+        .set_promiscuous_mode(true)
+        .map_err(|_| BoardError::WiFiError)?;
+    FRAMES.clear();
+    println!("[TOUCH] Listening for ESP-Touch for {}s", window.as_secs());
+
+    let started = Instant::now();
+    let mut channel = 1u8;
+    let mut hopped = Instant::now();
+    let mut candidates: Vec<Candidate, MAX_CANDIDATES> = Vec::new();
+    let mut locked: Option<(([u8; 6], bool), EspTouchDecoder, Instant)> = None;
+    wifi_manager.set_channel(channel)?;
+
+    let result = loop {
+        if started.elapsed() >= window {
+            break None;
+        }
+
+        // Drop a sender that stopped making progress
+        if locked
+            .as_ref()
+            .is_some_and(|(_, _, progress)| progress.elapsed() >= LOCK_TIMEOUT)
+        {
+            println!("[TOUCH] Lost sender on channel {}", channel);
+            locked = None;
+        }
+
+        if locked.is_none() && hopped.elapsed() >= CHANNEL_DWELL {
+            channel = channel % 13 + 1;
+            wifi_manager.set_channel(channel)?;
+            hopped = Instant::now();
+            candidates.clear();
+        }
+
+        let Ok(frame) = embassy_time::with_timeout(CHANNEL_DWELL, FRAMES.receive()).await else {
+            continue;
+        };
+
+        if let Some((source, decoder, progress)) = locked.as_mut() {
+            if frame.source == *source && decoder.push(frame.len) {
+                *progress = Instant::now();
+                if let Some(result) = decoder.result() {
+                    break Some(result);
+                }
+            }
+            continue;
+        }
+
+        let position = candidates.iter().position(|c| c.source == frame.source);
+        let candidate = match position {
+            Some(i) => &mut candidates[i],
+            None => {
+                if candidates.is_full() {
+                    candidates.remove(0);
+                }
+                candidates
+                    .push(Candidate {
+                        source: frame.source,
+                        recent: [0; 4],
+                    })
+                    .ok();
+                candidates.last_mut().unwrap()
+            }
+        };
+        candidate.recent.rotate_left(1);
+        candidate.recent[3] = frame.len;
+
+        if let Some(offset) = candidate.guide_offset() {
+            println!("[TOUCH] Guide code found on channel {}", channel);
+            locked = Some((
+                candidate.source,
+                EspTouchDecoder::new(offset),
+                Instant::now(),
+            ));
+        }
+    };
+
+    sniffer
+        .set_promiscuous_mode(false)
+        .map_err(|_| BoardError::WiFiError)?;
+    if let Some(result) = &result {
+        println!(
+            "[TOUCH] Received credentials for '{}'",
+            result.ssid.as_str()
+        );
+    }
+    Ok(result)
+}
+
+/// Acknowledge a completed ESP-Touch session once the network is up
+#[embassy_executor::task]
+pub async fn esptouch_ack_task(stack: &'static Stack<'static>, phone: [u8; 4], total_len: u8) {
+    stack.wait_config_up().await;
+    let Some(address) = stack.config_v4().map(|config| config.address.address()) else {
+        return;
+    };
+
+    let mut rx_meta = [PacketMetadata::EMPTY; 1];
+    let mut rx_buffer = [0; 16];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_buffer = [0; 64];
+    let mut socket = UdpSocket::new(
+        *stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    if socket.bind(0).is_err() {
+        return;
+    }
+
+    let mut ack = [0u8; 11];
+    ack[0] = total_len;
+    ack[1..7].copy_from_slice(&esp_hal::efuse::Efuse::read_base_mac_address());
+    ack[7..].copy_from_slice(&address.octets());
+
+    let [a, b, c, d] = phone;
+    let endpoint = IpEndpoint::new(IpAddress::v4(a, b, c, d), ACK_PORT);
+    for _ in 0..ACK_COUNT {
+        socket.send_to(&ack, endpoint).await.ok();
+        Timer::after(Duration::from_millis(100)).await;
+    }
+    println!("[TOUCH] Acknowledged to {}", endpoint);
+}
@@ -0,0 +1,95 @@
+//! Factory reset button
+//!
+//! Holding the board profile's button (active low) for
+//! `config::FACTORY_RESET_HOLD_MS` wipes the stored settings, including the
+//! provisioned networks and the device name, and restarts the board into the
+//! setup portal. The button is polled from boot on, so it can be held while
+//! the board powers up or at any time later. While it is held the strip
+//! fills up red; releasing it early cancels the reset.
+
+use crate::println;
+use crate::{BoardError, config};
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::gpio::{AnyPin, Input, InputConfig, Pull};
+
+/// Button sampling interval
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Marker left in RTC RAM for the next boot to open the setup portal
+const PROVISIONING_REQUEST_MAGIC: u32 = u32::from_be_bytes(*b"PROV");
+
+/// Provisioning request, kept across the software reset
+#[esp_hal::ram(rtc_fast, persistent)]
+static mut PROVISIONING_REQUEST: u32 = 0;
+
+/// Whether the previous boot asked for the setup portal (cleared when read)
+pub fn take_provisioning_request() -> bool {
+    let requested = unsafe { core::ptr::read_volatile(&raw const PROVISIONING_REQUEST) }
+        == PROVISIONING_REQUEST_MAGIC;
+    unsafe { core::ptr::write_volatile(&raw mut PROVISIONING_REQUEST, 0) };
+    requested
+}
+
+/// Forget every stored setting and the networks kept by older firmware
+pub fn wipe() -> Result<(), BoardError> {
+    crate::config_store::reset()?;
+    crate::provisioning::clear_legacy_records()?;
+    crate::crash_log::clear_failed_boots();
+    Ok(())
+}
+
+/// Factory reset button task
+#[embassy_executor::task]
+pub async fn factory_reset_task(pin: u8) {
+    // SAFETY: the profile's button pin is given to no other driver
+    let button = Input::new(
+        unsafe { AnyPin::steal(pin) },
+        InputConfig::default().with_pull(Pull::Up),
+    );
+    let hold = Duration::from_millis(config::FACTORY_RESET_HOLD_MS as u64);
+    println!("[RESET] Factory reset button on GPIO{}", pin);
+
+    loop {
+        Timer::after(POLL_INTERVAL).await;
+        if button.is_high() {
+            continue;
+        }
+
+        println!("[RESET] Button held - factory reset in {}s", hold.as_secs());
+        let pressed = Instant::now();
+        while button.is_low() {
+            let held = pressed.elapsed();
+            if held >= hold {
+                break;
+            }
+            let percent = held.as_millis() * 100 / hold.as_millis();
+            crate::led_control::set_reset_progress(percent.max(1) as u8);
+            Timer::after(POLL_INTERVAL).await;
+        }
+
+        if pressed.elapsed() < hold {
+            crate::led_control::set_reset_progress(0);
+            println!("[RESET] Button released - factory reset cancelled");
+            continue;
+        }
+
+        crate::led_control::set_reset_progress(100);
+        match wipe() {
+            Ok(()) => {
+                println!("[RESET] Settings wiped - restarting into setup");
+                unsafe {
+                    core::ptr::write_volatile(
+                        &raw mut PROVISIONING_REQUEST,
+                        PROVISIONING_REQUEST_MAGIC,
+                    )
+                };
+                Timer::after(Duration::from_secs(1)).await;
+                esp_hal::system::software_reset();
+            }
+            Err(e) => {
+                println!("[RESET] Factory reset failed: {:?}", e);
+                crate::led_control::set_reset_progress(0);
+            }
+        }
+    }
+}
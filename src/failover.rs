@@ -0,0 +1,152 @@
+//! Board-to-board failover module
+//!
+//! A primary board multicasts a heartbeat; a standby board listening on the
+//! same group stays dark until the heartbeat disappears, then takes over
+//! rendering. Both boards must receive the same LED stream (the sender
+//! addresses both, or uses multicast LED data) so the standby's segment map
+//! is already current when it takes over.
+
+use crate::config;
+use crate::println;
+use core::sync::atomic::{AtomicBool, Ordering};
+use embassy_net::{
+    IpAddress, IpEndpoint, Stack,
+    udp::{PacketMetadata, UdpSocket},
+};
+use embassy_time::{Duration, Instant, Timer};
+
+/// Heartbeat magic bytes
+const HEARTBEAT_MAGIC: &[u8; 4] = b"ALHB";
+
+/// Failover role of this board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailoverRole {
+    /// No failover - always render
+    Disabled,
+    /// Render and multicast a heartbeat
+    Primary,
+    /// Render only while the primary's heartbeat is missing
+    Standby,
+}
+
+/// Failover settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FailoverConfig {
+    pub role: FailoverRole,
+    /// Pairs a primary with its standby on a shared multicast group
+    pub group_id: u8,
+    pub heartbeat_interval_ms: u32,
+    /// Heartbeat silence after which the standby takes over
+    pub takeover_timeout_ms: u32,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            role: config::FAILOVER_ROLE,
+            group_id: config::FAILOVER_GROUP_ID,
+            heartbeat_interval_ms: config::FAILOVER_HEARTBEAT_INTERVAL_MS,
+            takeover_timeout_ms: config::FAILOVER_TAKEOVER_TIMEOUT_MS,
+        }
+    }
+}
+
+/// Whether this board currently drives its strip
+static RENDERING_ACTIVE: AtomicBool = AtomicBool::new(true);
+
+/// Check whether LED frames should be rendered on this board
+pub fn is_rendering_active() -> bool {
+    RENDERING_ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Failover heartbeat task (primary sends, standby monitors)
+#[embassy_executor::task]
+pub async fn failover_task(stack: &'static Stack<'static>, failover: FailoverConfig) {
+    if failover.role == FailoverRole::Disabled {
+        return;
+    }
+
+    // A standby starts dark until it has confirmed the primary is missing
+    RENDERING_ACTIVE.store(failover.role != FailoverRole::Standby, Ordering::Relaxed);
+
+    stack.wait_config_up().await;
+
+    let [a, b, c, d] = config::FAILOVER_MULTICAST_ADDR;
+    let group_addr = IpAddress::v4(a, b, c, d);
+    if let Err(e) = stack.join_multicast_group(group_addr) {
+        println!("[FAILOVER] Failed to join multicast group: {:?}", e);
+        // Never leave a standby permanently dark because of a local error
+        RENDERING_ACTIVE.store(true, Ordering::Relaxed);
+        return;
+    }
+
+    let mut rx_buffer = [0; 256];
+    let mut tx_buffer = [0; 256];
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut socket = UdpSocket::new(
+        *stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+
+    if let Err(e) = socket.bind(config::FAILOVER_PORT) {
+        println!("[FAILOVER] Bind failed: {:?}", e);
+        RENDERING_ACTIVE.store(true, Ordering::Relaxed);
+        return;
+    }
+
+    let group = IpEndpoint::new(group_addr, config::FAILOVER_PORT);
+    let heartbeat_interval = Duration::from_millis(failover.heartbeat_interval_ms as u64);
+    let takeover_timeout = Duration::from_millis(failover.takeover_timeout_ms as u64);
+
+    println!(
+        "[FAILOVER] Running as {:?} (group {})",
+        failover.role, failover.group_id
+    );
+
+    match failover.role {
+        FailoverRole::Primary => {
+            let mut heartbeat = [0u8; 5];
+            heartbeat[..4].copy_from_slice(HEARTBEAT_MAGIC);
+            heartbeat[4] = failover.group_id;
+
+            loop {
+                crate::memory::checkpoint(crate::memory::Subsystem::Failover);
+                socket.send_to(&heartbeat, group).await.ok();
+                Timer::after(heartbeat_interval).await;
+            }
+        }
+        FailoverRole::Standby => {
+            let mut buffer = [0u8; 16];
+            let mut last_heartbeat = Instant::now();
+
+            loop {
+                crate::memory::checkpoint(crate::memory::Subsystem::Failover);
+                if let Ok(Ok((len, _))) =
+                    embassy_time::with_timeout(heartbeat_interval, socket.recv_from(&mut buffer))
+                        .await
+                    && len == 5
+                    && &buffer[..4] == HEARTBEAT_MAGIC
+                    && buffer[4] == failover.group_id
+                {
+                    last_heartbeat = Instant::now();
+                    if is_rendering_active() {
+                        println!("[FAILOVER] Primary is back - handing over rendering");
+                        RENDERING_ACTIVE.store(false, Ordering::Relaxed);
+                    }
+                }
+
+                if !is_rendering_active()
+                    && Instant::now().duration_since(last_heartbeat) > takeover_timeout
+                {
+                    println!("[FAILOVER] ⚠️ Primary heartbeat lost - taking over rendering");
+                    RENDERING_ACTIVE.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+        FailoverRole::Disabled => {}
+    }
+}
@@ -0,0 +1,222 @@
+//! Lifecycle hooks module
+//!
+//! Maps lifecycle events (boot, LED data start/stop) to a short list of
+//! declarative actions, covering simple automations without a hub.
+//!
+//! The table is kept in the config store (key `0x0C`), so it can be changed
+//! at runtime; the `config::HOOKS_ON_*` constants are its defaults.
+//!
+//! Wire and record format: for each event in the order boot, data start,
+//! data stop, `MAX_HOOK_ACTIONS` slots of [kind][value]. Kinds: 0 empty,
+//! 1 set mode (value: `0x15` mode code), 2 set brightness, 3 set power
+//! (value 0 or 1).
+
+use crate::protocol::LedMode;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+
+/// Maximum actions per hook
+pub const MAX_HOOK_ACTIONS: usize = 4;
+
+/// Encoded size of a hook table
+pub const HOOK_TABLE_SIZE: usize = 3 * MAX_HOOK_ACTIONS * 2;
+
+/// Action kinds in the encoded table
+const KIND_EMPTY: u8 = 0;
+const KIND_SET_MODE: u8 = 1;
+const KIND_SET_BRIGHTNESS: u8 = 2;
+const KIND_SET_POWER: u8 = 3;
+
+/// Lifecycle events that can trigger hooks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    /// System finished booting
+    Boot,
+    /// LED data started arriving
+    DataStart,
+    /// LED data stopped arriving (ambient timeout)
+    DataStop,
+}
+
+/// Actions a hook can perform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookAction {
+    /// Switch the LED task to a preset mode (e.g. idle animation or off)
+    SetMode(LedMode),
+    /// Set the global brightness (0-255)
+    SetBrightness(u8),
+    /// Drive the PSU enable pin
+    SetPower(bool),
+}
+
+impl HookAction {
+    /// Encode as [kind][value]
+    fn encode(action: Option<Self>) -> [u8; 2] {
+        match action {
+            None => [KIND_EMPTY, 0],
+            Some(HookAction::SetMode(mode)) => [KIND_SET_MODE, mode as u8],
+            Some(HookAction::SetBrightness(brightness)) => [KIND_SET_BRIGHTNESS, brightness],
+            Some(HookAction::SetPower(on)) => [KIND_SET_POWER, on as u8],
+        }
+    }
+
+    /// Decode [kind][value]; the outer None marks an invalid slot
+    fn decode(kind: u8, value: u8) -> Option<Option<Self>> {
+        Some(match (kind, value) {
+            (KIND_EMPTY, _) => None,
+            (KIND_SET_MODE, code) => Some(HookAction::SetMode(LedMode::from_code(code)?)),
+            (KIND_SET_BRIGHTNESS, brightness) => Some(HookAction::SetBrightness(brightness)),
+            (KIND_SET_POWER, on @ (0 | 1)) => Some(HookAction::SetPower(on == 1)),
+            _ => return None,
+        })
+    }
+}
+
+/// Hook table: actions run for each lifecycle event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HookTable {
+    pub on_boot: [Option<HookAction>; MAX_HOOK_ACTIONS],
+    pub on_data_start: [Option<HookAction>; MAX_HOOK_ACTIONS],
+    pub on_data_stop: [Option<HookAction>; MAX_HOOK_ACTIONS],
+}
+
+impl Default for HookTable {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl HookTable {
+    /// Build-time defaults
+    pub const DEFAULT: Self = Self {
+        on_boot: crate::config::HOOKS_ON_BOOT,
+        on_data_start: crate::config::HOOKS_ON_DATA_START,
+        on_data_stop: crate::config::HOOKS_ON_DATA_STOP,
+    };
+
+    /// Actions bound to `event`
+    pub fn actions(&self, event: HookEvent) -> &[Option<HookAction>; MAX_HOOK_ACTIONS] {
+        match event {
+            HookEvent::Boot => &self.on_boot,
+            HookEvent::DataStart => &self.on_data_start,
+            HookEvent::DataStop => &self.on_data_stop,
+        }
+    }
+
+    /// Encode for the config store
+    pub fn encode(&self) -> [u8; HOOK_TABLE_SIZE] {
+        let mut out = [0; HOOK_TABLE_SIZE];
+        let slots = self
+            .on_boot
+            .iter()
+            .chain(&self.on_data_start)
+            .chain(&self.on_data_stop);
+        for (chunk, action) in out.chunks_exact_mut(2).zip(slots) {
+            chunk.copy_from_slice(&HookAction::encode(*action));
+        }
+        out
+    }
+
+    /// Decode a table, None if it's the wrong size or holds an unknown action
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != HOOK_TABLE_SIZE {
+            return None;
+        }
+        let mut table = Self {
+            on_boot: [None; MAX_HOOK_ACTIONS],
+            on_data_start: [None; MAX_HOOK_ACTIONS],
+            on_data_stop: [None; MAX_HOOK_ACTIONS],
+        };
+        let slots = table
+            .on_boot
+            .iter_mut()
+            .chain(&mut table.on_data_start)
+            .chain(&mut table.on_data_stop);
+        for (chunk, action) in data.chunks_exact(2).zip(slots) {
+            *action = HookAction::decode(chunk[0], chunk[1])?;
+        }
+        Some(table)
+    }
+}
+
+/// Pending lifecycle events
+static HOOK_EVENTS: Channel<CriticalSectionRawMutex, HookEvent, 4> = Channel::new();
+
+/// Report a lifecycle event (non-blocking, dropped if the queue is full)
+pub fn trigger(event: HookEvent) {
+    let _ = HOOK_EVENTS.try_send(event);
+}
+
+/// Hook runner task: runs the stored table's actions for each event
+#[cfg(target_os = "none")]
+#[embassy_executor::task]
+pub async fn hooks_task(
+    led_mode_sender: &'static embassy_sync::channel::Sender<
+        'static,
+        CriticalSectionRawMutex,
+        LedMode,
+        2,
+    >,
+    mut power_pin: Option<esp_hal::gpio::Output<'static>>,
+) {
+    use crate::println;
+
+    loop {
+        let event = HOOK_EVENTS.receive().await;
+        crate::memory::checkpoint(crate::memory::Subsystem::Hooks);
+
+        let hooks = crate::config_store::hooks();
+        for action in hooks.actions(event).iter().flatten() {
+            println!("[HOOK] {:?} -> {:?}", event, action);
+            match *action {
+                HookAction::SetMode(mode) => {
+                    let _ = led_mode_sender.try_send(mode);
+                }
+                HookAction::SetBrightness(brightness) => {
+                    crate::led_control::set_global_brightness(brightness);
+                }
+                HookAction::SetPower(on) => {
+                    if let Some(ref mut pin) = power_pin {
+                        if on {
+                            pin.set_high();
+                        } else {
+                            pin.set_low();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_round_trips() {
+        let mut table = HookTable::DEFAULT;
+        table.on_boot[0] = Some(HookAction::SetPower(true));
+        table.on_data_start[1] = Some(HookAction::SetBrightness(200));
+        table.on_data_stop[3] = Some(HookAction::SetMode(LedMode::Off));
+
+        let encoded = table.encode();
+        assert_eq!(&encoded[..2], &[KIND_SET_POWER, 1]);
+        assert_eq!(HookTable::decode(&encoded), Some(table));
+    }
+
+    #[test]
+    fn invalid_tables_are_rejected() {
+        let mut encoded = HookTable::DEFAULT.encode();
+        assert!(HookTable::decode(&encoded[1..]).is_none());
+
+        encoded[0] = 9;
+        assert!(HookTable::decode(&encoded).is_none());
+
+        encoded[..2].copy_from_slice(&[KIND_SET_MODE, 7]);
+        assert!(HookTable::decode(&encoded).is_none());
+
+        encoded[..2].copy_from_slice(&[KIND_SET_POWER, 2]);
+        assert!(HookTable::decode(&encoded).is_none());
+    }
+}
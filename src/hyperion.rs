@@ -0,0 +1,69 @@
+//! Hyperion "udpraw" compatibility module
+//!
+//! Hyperion's UDP raw output device sends one headerless datagram per frame:
+//! RGB triples for every LED, starting at the first LED. This task listens on
+//! a dedicated port so Hyperion can drive the board without the ambient light
+//! protocol, converting each frame to the strip's pixel format.
+
+use crate::config;
+use crate::led_control::{LedData, LedDataSignal};
+use crate::println;
+use embassy_net::{
+    Stack,
+    udp::{PacketMetadata, UdpSocket},
+};
+use embassy_time::Instant;
+
+/// Hyperion udpraw receiver task
+#[embassy_executor::task]
+pub async fn hyperion_task(
+    stack: &'static Stack<'static>,
+    led_data_sender: &'static LedDataSignal,
+    port: u16,
+) {
+    stack.wait_config_up().await;
+
+    let mut rx_buffer = [0; 4096];
+    let mut tx_buffer = [0; 64];
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut socket = UdpSocket::new(
+        *stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+
+    if let Err(e) = socket.bind(port) {
+        println!("[HYPERION] Bind failed: {:?}", e);
+        return;
+    }
+    println!("[HYPERION] Listening for udpraw frames on port {}", port);
+
+    let mut buffer = [0u8; config::MAX_LEDS * 3];
+    loop {
+        crate::memory::checkpoint(crate::memory::Subsystem::Hyperion);
+
+        let Ok((len, _)) = socket.recv_from(&mut buffer).await else {
+            continue;
+        };
+
+        // Whole RGB triples only, for at most the configured LED count
+        let rgb_len = (len - len % 3).min(crate::config_store::led_count() * 3);
+        if rgb_len == 0 {
+            continue;
+        }
+
+        let mut data = crate::led_control::take_recycled_frame().unwrap_or_default();
+        crate::led_control::rgb_to_pixel_format(
+            &buffer[..rgb_len],
+            crate::config_store::pixel_format(),
+            &mut data,
+        );
+        led_data_sender.signal(LedData {
+            data,
+            timestamp: Instant::now(),
+        });
+    }
+}
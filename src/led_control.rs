@@ -1,77 +1,126 @@
 use crate::BoardError;
+use crate::color_correction::ColorCorrection;
+use crate::println;
 use alloc::vec;
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicU32, Ordering};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::channel::{Channel, Receiver, Sender};
+use embassy_sync::signal::Signal;
 use embassy_time::{Duration, Instant};
 use esp_hal::gpio::Level;
-use esp_hal::rmt::{PulseCode, TxChannel};
-use esp_println::println;
+use esp_hal::rmt::{PulseCode, Rmt, TxChannelAsync, TxChannelConfig, TxChannelCreatorAsync};
 use static_cell::StaticCell;
 
-/// LED status states for visual feedback
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum LedStatus {
-    // System initialization states
-    Starting,
-    HardwareInit,
-    WiFiDriverInit,
-
-    // Network connection states
-    WiFiConnecting,
-    WiFiConnected,
-    DHCPRequesting,
-    NetworkReady,
-
-    // Service states
-    ServicesStarting,
-    UDPServerBinding,
-    UDPServerListening,
-    MDNSAdvertising,
-
-    // Operational states
-    Operational,
-    DataReceiving,
-    LEDRendering,
-    ConnectionMonitoring,
-
-    // Error states
-    WiFiError,
-    NetworkError,
-    ServiceError,
-    HardwareError,
-    CriticalError,
-
-    // Recovery states
-    Reconnecting,
-    ServiceRestarting,
-    SystemRecovering,
-
-    // Legacy states (for backward compatibility)
-    Error, // Maps to CriticalError
+pub use crate::led_status::LedStatus;
+pub use crate::protocol::{LedMode, PixelFormat};
+
+/// RMT channel driving the strip (channel 0 is a TX channel on every supported chip)
+///
+/// In async mode, so the LED task yields while a transmission drains instead
+/// of busy-waiting at interrupt priority.
+pub type LedChannel = esp_hal::rmt::Channel<esp_hal::Async, 0>;
+
+/// Global brightness multiplier applied to everything sent to the strip
+static GLOBAL_BRIGHTNESS: AtomicU8 = AtomicU8::new(crate::config::DEFAULT_BRIGHTNESS);
+
+/// Set the global brightness (255 = full, 0 = dark), capped at the stored limit
+pub fn set_global_brightness(brightness: u8) {
+    let brightness = brightness.min(crate::config_store::max_brightness());
+    GLOBAL_BRIGHTNESS.store(brightness, Ordering::Relaxed);
+}
+
+/// Set the global brightness and keep it for the next boot
+///
+/// For brightness changes made by the user at runtime; the flash write waits
+/// until the brightness has settled (`config_store::update_deferred`).
+pub fn set_saved_brightness(brightness: u8) {
+    set_global_brightness(brightness);
+    let applied = global_brightness();
+    let saved = crate::config_store::update_deferred(|stored| {
+        stored.brightness = applied;
+        Ok(())
+    });
+    if let Err(e) = saved {
+        println!("[LED] Brightness not saved: {:?}", e);
+    }
+}
+
+/// Get the global brightness
+pub fn global_brightness() -> u8 {
+    GLOBAL_BRIGHTNESS.load(Ordering::Relaxed)
+}
+
+/// Factory reset countdown in percent of the hold time (0 = button not held)
+static RESET_PROGRESS: AtomicU8 = AtomicU8::new(0);
+
+/// Show the factory reset countdown over everything else (0 ends it)
+pub fn set_reset_progress(percent: u8) {
+    RESET_PROGRESS.store(percent.min(100), Ordering::Relaxed);
+}
+
+/// Strip power: when disabled the strip stays blank regardless of mode or data
+static STRIP_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Blank the strip (`false`) or resume normal output (`true`)
+pub fn set_strip_enabled(enabled: bool) {
+    STRIP_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Check whether the strip is enabled
+pub fn is_strip_enabled() -> bool {
+    STRIP_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Scale a channel value by a brightness (255 leaves the value unchanged)
+fn scale_brightness(value: u8, brightness: u8) -> u8 {
+    ((value as u16 * (brightness as u16 + 1)) >> 8) as u8
 }
 
+/// RMT codes sent per transmission: one channel RAM block (48 codes on the
+/// ESP32-C3, C6 and S3, 64 on the ESP32)
+const CHUNK_CODES: usize = 48;
+
+/// Frame bytes encoded per transmission, leaving room for the end marker
+const CHUNK_BYTES: usize = (CHUNK_CODES - 1) / 8;
+
 /// LED controller for RGBW LED strips using RMT peripheral
 pub struct LedController<TX>
 where
-    TX: TxChannel,
+    TX: TxChannelAsync,
 {
-    channel: Option<TX>,
+    channel: TX,
     status: LedStatus,
     status_counter: u32,
     breathing_counter: u32,
+    /// Gamma and white balance tables, rebuilt when the stored settings change
+    correction: ColorCorrection,
+    correction_generation: Option<u32>,
 }
 
 impl<TX> LedController<TX>
 where
-    TX: TxChannel,
+    TX: TxChannelAsync,
 {
     /// Create a new LED controller
     pub fn new(channel: TX) -> Self {
         Self {
-            channel: Some(channel),
+            channel,
             status: LedStatus::Starting,
             status_counter: 0,
             breathing_counter: 30, // Start at minimum brightness
+            correction: ColorCorrection::IDENTITY,
+            correction_generation: None,
+        }
+    }
+
+    /// Pick up changed gamma, white balance or pixel format settings
+    fn refresh_correction(&mut self) {
+        let generation = crate::config_store::generation();
+        if self.correction_generation != Some(generation) {
+            let stored = crate::config_store::get();
+            self.correction =
+                ColorCorrection::new(stored.gamma, stored.white_balance, stored.pixel_format);
+            self.correction_generation = Some(generation);
         }
     }
 
@@ -89,7 +138,7 @@ where
     }
 
     /// Update LED display with status indication and breathing effect
-    pub fn update_display(&mut self) {
+    pub async fn update_display(&mut self) {
         const LED_COUNT: usize = 500;
         const STATUS_LEDS: usize = 3; // First 3 LEDs for status
 
@@ -116,49 +165,7 @@ where
             };
 
         // Status indication timing (faster blinking)
-        let status_on = match self.status {
-            // System initialization states - very fast blink
-            LedStatus::Starting | LedStatus::HardwareInit | LedStatus::WiFiDriverInit => {
-                (self.status_counter / 8) % 2 == 0
-            }
-
-            // Network connection states - fast blink
-            LedStatus::WiFiConnecting
-            | LedStatus::WiFiConnected
-            | LedStatus::DHCPRequesting
-            | LedStatus::Reconnecting => (self.status_counter / 12) % 2 == 0,
-
-            // Service states - medium blink
-            LedStatus::ServicesStarting
-            | LedStatus::UDPServerBinding
-            | LedStatus::UDPServerListening
-            | LedStatus::MDNSAdvertising => (self.status_counter / 16) % 2 == 0,
-
-            // Operational states - slow pulse
-            LedStatus::NetworkReady | LedStatus::Operational | LedStatus::ConnectionMonitoring => {
-                (self.status_counter / 20) % 3 == 0
-            }
-
-            // Data processing states - very fast pulse
-            LedStatus::DataReceiving | LedStatus::LEDRendering => {
-                (self.status_counter / 6) % 2 == 0
-            }
-
-            // Error states - medium blink
-            LedStatus::WiFiError
-            | LedStatus::NetworkError
-            | LedStatus::ServiceError
-            | LedStatus::HardwareError
-            | LedStatus::Error => (self.status_counter / 20) % 2 == 0,
-
-            // Critical error - fast blink
-            LedStatus::CriticalError => (self.status_counter / 10) % 2 == 0,
-
-            // Recovery states - slow blink
-            LedStatus::ServiceRestarting | LedStatus::SystemRecovering => {
-                (self.status_counter / 25) % 2 == 0
-            }
-        };
+        let status_on = self.status.blink_on(self.status_counter);
 
         // Create LED data buffer (4 bytes per LED: G, R, B, W)
         let mut led_data = vec![0u8; LED_COUNT * 4];
@@ -189,70 +196,117 @@ where
         // Silent LED status update
 
         // Forward the data to LED hardware
-        self.forward_raw_stream(&led_data).ok(); // Silent error handling
+        self.forward_raw_stream(&led_data).await.ok(); // Silent error handling
     }
 
     /// Forward raw LED data stream to hardware
-    pub fn forward_raw_stream(&mut self, data: &[u8]) -> Result<(), BoardError> {
-        // For large data, truncate to safe size for stability
-        const MAX_SAFE_PULSES: usize = 4000; // Conservative limit for stable operation
-        let total_pulses_needed = data.len() * 8 + 1; // 8 pulses per byte + reset
-
-        let actual_data = if total_pulses_needed > MAX_SAFE_PULSES {
-            let max_safe_bytes = (MAX_SAFE_PULSES - 1) / 8; // Reserve 1 pulse for reset
-            let safe_bytes = max_safe_bytes & !3; // Round down to multiple of 4 (complete LEDs)
-            &data[..safe_bytes]
+    ///
+    /// A frame longer than `MAX_LEDS` LEDs is cut to its first `MAX_LEDS` LEDs,
+    /// which are still shown, and reported with `BoardError::LedError`.
+    pub async fn forward_raw_stream(&mut self, data: &[u8]) -> Result<(), BoardError> {
+        let bytes_per_led = crate::config_store::pixel_format().bytes_per_led();
+        let max_len = crate::config::MAX_FRAME_BYTES;
+        let truncated = data.len() > max_len;
+        let frame = if truncated {
+            // Whole LEDs only, so the channel order doesn't shift
+            &data[..max_len - max_len % bytes_per_led]
         } else {
             data
         };
 
-        // Convert each byte to RMT pulses
-        let mut pulses = vec::Vec::with_capacity(actual_data.len() * 8 + 1);
-        for &byte in actual_data {
-            let byte_pulses = byte_to_pulses(byte);
-            pulses.extend_from_slice(&byte_pulses);
-        }
+        // The frame goes out a chunk at a time, so the waveform never has to
+        // be held in RAM as a whole: while one chunk drains from the RMT, the
+        // next is encoded into the other half of a ping-pong buffer
+        self.refresh_correction();
+        let brightness = global_brightness();
+        let correction = &self.correction;
+        let encode = |chunk: usize, codes: &mut [u32; CHUNK_CODES]| {
+            let encode_start = Instant::now();
+            let len = encode_chunk(frame, chunk, correction, bytes_per_led, brightness, codes);
+            (len, encode_start.elapsed().as_micros() as u32)
+        };
 
-        // Add reset pulse
-        pulses.push(PulseCode::new(Level::Low, 800, Level::Low, 0));
-
-        // Transmit data
-        if let Some(channel) = self.channel.take() {
-            match channel.transmit(&pulses) {
-                Ok(transaction) => {
-                    // Use non-blocking approach to avoid infinite wait
-                    match transaction.wait() {
-                        Ok(channel) => {
-                            self.channel = Some(channel);
-                            Ok(())
-                        }
-                        Err((_, channel)) => {
-                            self.channel = Some(channel);
-                            // Don't treat warnings as errors - LED transmission often succeeds despite warnings
-                            Ok(())
-                        }
-                    }
-                }
-                Err(_) => Err(BoardError::LedError),
+        let chunk_count = frame.len().div_ceil(CHUNK_BYTES).max(1);
+        let mut buffers = [[0u32; CHUNK_CODES]; 2];
+        let start = Instant::now();
+        let (mut len, mut encode_us) = encode(0, &mut buffers[0]);
+        let mut result = Ok(());
+        for chunk in 0..chunk_count {
+            let [even, odd] = &mut buffers;
+            let (sending, next) = if chunk % 2 == 0 {
+                (even, odd)
+            } else {
+                (odd, even)
+            };
+            let (sent, encoded) =
+                embassy_futures::join::join(self.channel.transmit(&sending[..len]), async {
+                    (chunk + 1 < chunk_count).then(|| encode(chunk + 1, next))
+                })
+                .await;
+            if sent.is_err() {
+                result = Err(BoardError::LedError);
+                break;
             }
-        } else {
-            Err(BoardError::LedError)
+            if let Some((next_len, us)) = encoded {
+                len = next_len;
+                encode_us += us;
+            }
+        }
+
+        if result.is_ok() {
+            let total_us = start.elapsed().as_micros() as u32;
+            record_refresh(encode_us, total_us.saturating_sub(encode_us));
         }
+        if truncated {
+            return Err(BoardError::LedError);
+        }
+        result
     }
 }
 
+/// Encode chunk `index` of a frame into RMT codes, applying the color
+/// correction and then the global brightness
+///
+/// The last chunk ends with the reset pulse, the others with an end marker.
+/// Returns the number of codes written.
+fn encode_chunk(
+    frame: &[u8],
+    index: usize,
+    correction: &ColorCorrection,
+    bytes_per_led: usize,
+    brightness: u8,
+    codes: &mut [u32; CHUNK_CODES],
+) -> usize {
+    let start = index * CHUNK_BYTES;
+    let end = (start + CHUNK_BYTES).min(frame.len());
+    for (n, &byte) in frame[start..end].iter().enumerate() {
+        let corrected = correction.apply((start + n) % bytes_per_led, byte);
+        codes[n * 8..n * 8 + 8]
+            .copy_from_slice(&byte_to_pulses(scale_brightness(corrected, brightness)));
+    }
+
+    let len = (end - start) * 8;
+    codes[len] = if end == frame.len() {
+        // Reset pulse
+        PulseCode::new(Level::Low, 800, Level::Low, 0)
+    } else {
+        PulseCode::empty()
+    };
+    len + 1
+}
+
 /// Convert a single byte to RMT pulses for RGBW LEDs
-/// Uses SK6812 timing: 1-bit = 6 high + 6 low cycles, 0-bit = 3 high + 9 low cycles at 10MHz
+/// Uses SK6812 timing: 1-bit = 6 high + 6 low cycles, 0-bit = 3 high + 9 low cycles of 100ns
 fn byte_to_pulses(byte: u8) -> [u32; 8] {
     let mut pulses = [0u32; 8];
 
-    for i in 0..8 {
+    for (i, pulse) in pulses.iter_mut().enumerate() {
         let bit = (byte >> (7 - i)) & 1;
-        pulses[i] = if bit == 1 {
-            // 1-bit: 6 high cycles + 6 low cycles at 10MHz = 600ns high + 600ns low
+        *pulse = if bit == 1 {
+            // 1-bit: 6 high cycles + 6 low cycles of 100ns = 600ns high + 600ns low
             PulseCode::new(Level::High, 6, Level::Low, 6)
         } else {
-            // 0-bit: 3 high cycles + 9 low cycles at 10MHz = 300ns high + 900ns low
+            // 0-bit: 3 high cycles + 9 low cycles of 100ns = 300ns high + 900ns low
             PulseCode::new(Level::High, 3, Level::Low, 9)
         };
     }
@@ -263,14 +317,14 @@ fn byte_to_pulses(byte: u8) -> [u32; 8] {
 /// Universal driver board controller for raw LED data streams
 pub struct UniversalDriverBoard<TX>
 where
-    TX: TxChannel,
+    TX: TxChannelAsync,
 {
     led_controller: LedController<TX>,
 }
 
 impl<TX> UniversalDriverBoard<TX>
 where
-    TX: TxChannel,
+    TX: TxChannelAsync,
 {
     /// Create a new universal driver board
     pub fn new(channel: TX) -> Self {
@@ -285,20 +339,23 @@ where
     }
 
     /// Update the display
-    pub fn update_display(&mut self) {
-        self.led_controller.update_display();
+    pub async fn update_display(&mut self) {
+        self.led_controller.update_display().await;
     }
 
     /// Forward raw LED data stream (main function for desktop communication)
-    pub fn forward_raw_stream(&mut self, data: &[u8]) -> Result<(), BoardError> {
-        self.led_controller.forward_raw_stream(data)
+    pub async fn forward_raw_stream(&mut self, data: &[u8]) -> Result<(), BoardError> {
+        self.led_controller.forward_raw_stream(data).await
     }
 
     /// Update LEDs with packet data (for UDP server compatibility)
-    pub fn update_leds(&mut self, packet: &crate::udp_server::LedPacket) -> Result<(), BoardError> {
+    pub async fn update_leds(
+        &mut self,
+        packet: &crate::udp_server::LedPacket<'_>,
+    ) -> Result<(), BoardError> {
         // For now, just forward the raw data directly
         // In a more sophisticated implementation, we could handle offset-based updates
-        self.forward_raw_stream(&packet.data)
+        self.forward_raw_stream(packet.data).await
     }
 }
 
@@ -306,43 +363,246 @@ where
 #[derive(Debug, Clone)]
 pub struct LedData {
     pub data: alloc::vec::Vec<u8>,
+    /// When the frame's data was received
     pub timestamp: Instant,
 }
 
-/// LED operation modes
+/// Smoothed receive-to-render latency of ambient frames (microseconds)
+static RENDER_LATENCY_US: AtomicU32 = AtomicU32::new(0);
+
+/// Get the smoothed receive-to-render latency of ambient frames in microseconds
+pub fn render_latency_us() -> u32 {
+    RENDER_LATENCY_US.load(Ordering::Relaxed)
+}
+
+/// LED pipeline figures over the last `config::PIPELINE_STATS_WINDOW_MS`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+    /// Strip refreshes per second, times 100
+    pub render_fps_x100: u32,
+    /// New LED data frames shown per second, times 100
+    pub frame_fps_x100: u32,
+    /// Time to turn a frame into RMT pulses (correction, brightness, encoding)
+    pub encode_avg_us: u32,
+    pub encode_max_us: u32,
+    /// Time to clock a frame out to the strip over RMT
+    pub transmit_avg_us: u32,
+    pub transmit_max_us: u32,
+}
+
+struct PipelineMeter {
+    window_start: Option<Instant>,
+    frames: u32,
+    encode: crate::benchmark::StageTiming,
+    transmit: crate::benchmark::StageTiming,
+    last: PipelineStats,
+}
+
+impl PipelineMeter {
+    /// Close the window once it is long enough
+    fn roll(&mut self) {
+        let now = Instant::now();
+        let start = *self.window_start.get_or_insert(now);
+        let elapsed_ms = now.duration_since(start).as_millis();
+        if elapsed_ms < crate::config::PIPELINE_STATS_WINDOW_MS {
+            return;
+        }
+        let per_second_x100 = |count: u32| (count as u64 * 100_000 / elapsed_ms) as u32;
+        self.last = PipelineStats {
+            render_fps_x100: per_second_x100(self.transmit.count),
+            frame_fps_x100: per_second_x100(self.frames),
+            encode_avg_us: self.encode.average_us(),
+            encode_max_us: self.encode.max_us,
+            transmit_avg_us: self.transmit.average_us(),
+            transmit_max_us: self.transmit.max_us,
+        };
+        self.window_start = Some(now);
+        self.frames = 0;
+        self.encode = crate::benchmark::StageTiming::EMPTY;
+        self.transmit = crate::benchmark::StageTiming::EMPTY;
+    }
+}
+
+static PIPELINE: critical_section::Mutex<core::cell::RefCell<PipelineMeter>> =
+    critical_section::Mutex::new(core::cell::RefCell::new(PipelineMeter {
+        window_start: None,
+        frames: 0,
+        encode: crate::benchmark::StageTiming::EMPTY,
+        transmit: crate::benchmark::StageTiming::EMPTY,
+        last: PipelineStats {
+            render_fps_x100: 0,
+            frame_fps_x100: 0,
+            encode_avg_us: 0,
+            encode_max_us: 0,
+            transmit_avg_us: 0,
+            transmit_max_us: 0,
+        },
+    }));
+
+/// Record the encode and transmit time of one strip refresh
+fn record_refresh(encode_us: u32, transmit_us: u32) {
+    critical_section::with(|cs| {
+        let mut meter = PIPELINE.borrow_ref_mut(cs);
+        meter.encode.record(encode_us);
+        meter.transmit.record(transmit_us);
+        meter.roll();
+    });
+}
+
+/// Record that a new LED data frame reached the strip
+fn record_frame_shown() {
+    critical_section::with(|cs| PIPELINE.borrow_ref_mut(cs).frames += 1);
+}
+
+/// LED pipeline figures of the last complete window (zero while the strip
+/// isn't being refreshed, e.g. latched dark)
+pub fn pipeline_stats() -> PipelineStats {
+    critical_section::with(|cs| {
+        let meter = PIPELINE.borrow_ref(cs);
+        match meter.window_start {
+            Some(start)
+                if start.elapsed().as_millis() < 2 * crate::config::PIPELINE_STATS_WINDOW_MS =>
+            {
+                meter.last
+            }
+            _ => PipelineStats::default(),
+        }
+    })
+}
+
+/// Pixel group binning: each logical pixel drives `group_size` physical LEDs
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum LedMode {
-    /// Non-ambient mode: breathing + status indication
-    NonAmbient,
-    /// Ambient mode: display UDP data
-    Ambient,
+pub struct PixelGrouping {
+    /// Physical LEDs per logical pixel (1 disables grouping)
+    pub group_size: usize,
+    /// Blend towards the next logical pixel across the group instead of repeating
+    pub smoothing: bool,
+}
+
+impl Default for PixelGrouping {
+    fn default() -> Self {
+        Self {
+            group_size: crate::config::PIXEL_GROUP_SIZE,
+            smoothing: crate::config::PIXEL_GROUP_SMOOTHING,
+        }
+    }
+}
+
+/// Convert RGB triples into `format`, replacing the contents of `out`
+///
+/// The white channel of RGBW formats is left off.
+pub fn rgb_to_pixel_format(rgb: &[u8], format: PixelFormat, out: &mut alloc::vec::Vec<u8>) {
+    out.clear();
+    for pixel in rgb.chunks_exact(3) {
+        let (r, g, b) = (pixel[0], pixel[1], pixel[2]);
+        match format {
+            PixelFormat::Rgb => out.extend_from_slice(&[r, g, b]),
+            PixelFormat::Grb => out.extend_from_slice(&[g, r, b]),
+            PixelFormat::Rgbw => out.extend_from_slice(&[r, g, b, 0]),
+            PixelFormat::Grbw => out.extend_from_slice(&[g, r, b, 0]),
+        }
+    }
+}
+
+/// Expand logical pixels into physical LEDs according to `grouping`
+///
+/// The output is capped at `config::MAX_LEDS` physical LEDs.
+pub fn expand_pixel_groups(data: &[u8], grouping: PixelGrouping) -> alloc::vec::Vec<u8> {
+    const BPL: usize = crate::config::BYTES_PER_LED;
+
+    let group_size = grouping.group_size.max(1);
+    let logical_count = data.len() / BPL;
+    let physical_count = (logical_count * group_size).min(crate::config::MAX_LEDS);
+    let mut expanded = vec![0u8; physical_count * BPL];
+
+    for led in 0..physical_count {
+        let pixel = led / group_size;
+        let step = led % group_size;
+        let current = &data[pixel * BPL..(pixel + 1) * BPL];
+        let target = &mut expanded[led * BPL..(led + 1) * BPL];
+
+        if grouping.smoothing && pixel + 1 < logical_count {
+            // Linear blend from this pixel towards the next one
+            let next = &data[(pixel + 1) * BPL..(pixel + 2) * BPL];
+            for channel in 0..BPL {
+                let from = current[channel] as i32;
+                let to = next[channel] as i32;
+                target[channel] = (from + (to - from) * step as i32 / group_size as i32) as u8;
+            }
+        } else {
+            target.copy_from_slice(current);
+        }
+    }
+
+    expanded
+}
+
+/// Latest-value slot for ambient frames
+///
+/// A newer frame overwrites one the LED task hasn't picked up yet, so the task
+/// always renders the freshest frame instead of working through a backlog.
+pub type LedDataSignal = Signal<CriticalSectionRawMutex, LedData>;
+
+/// Mode the LED task rendered in its last frame
+static ACTIVE_MODE: AtomicU8 = AtomicU8::new(LedMode::NonAmbient as u8);
+
+/// Mode currently rendered (Off while blanked or on failover standby)
+pub fn active_mode() -> LedMode {
+    LedMode::from_code(ACTIVE_MODE.load(Ordering::Relaxed)).unwrap_or(LedMode::NonAmbient)
+}
+
+/// Whether the LED task switches between Ambient and NonAmbient on its own
+/// as data starts and stops; an explicit mode command turns this off
+static AUTO_MODE_SWITCH: AtomicBool = AtomicBool::new(true);
+
+/// Enable or disable data-driven mode auto-switching
+pub fn set_auto_mode_switch(enabled: bool) {
+    AUTO_MODE_SWITCH.store(enabled, Ordering::Relaxed);
+}
+
+/// Check whether data-driven mode auto-switching is enabled
+pub fn is_auto_mode_switch() -> bool {
+    AUTO_MODE_SWITCH.load(Ordering::Relaxed)
+}
+
+/// Frame buffer the LED task has finished with, handed back to the UDP
+/// server so committing a frame reuses its allocation
+static FRAME_RECYCLE: Signal<CriticalSectionRawMutex, alloc::vec::Vec<u8>> = Signal::new();
+
+/// Take a spare frame buffer for the next commit, if the LED task returned one
+pub fn take_recycled_frame() -> Option<alloc::vec::Vec<u8>> {
+    FRAME_RECYCLE.try_take()
 }
 
 /// Static channels for LED task communication
 static LED_STATUS_CHANNEL: StaticCell<Channel<CriticalSectionRawMutex, LedStatus, 8>> =
     StaticCell::new();
-static LED_DATA_CHANNEL: StaticCell<Channel<CriticalSectionRawMutex, LedData, 4>> =
-    StaticCell::new();
+static LED_DATA_SIGNAL: LedDataSignal = Signal::new();
 static LED_MODE_CHANNEL: StaticCell<Channel<CriticalSectionRawMutex, LedMode, 2>> =
     StaticCell::new();
 
+/// Ends of the LED status and mode channels
+pub type LedStatusSender = Sender<'static, CriticalSectionRawMutex, LedStatus, 8>;
+pub type LedStatusReceiver = Receiver<'static, CriticalSectionRawMutex, LedStatus, 8>;
+pub type LedModeSender = Sender<'static, CriticalSectionRawMutex, LedMode, 2>;
+pub type LedModeReceiver = Receiver<'static, CriticalSectionRawMutex, LedMode, 2>;
+
 /// Initialize LED communication channels
 pub fn init_led_channels() -> (
-    Sender<'static, CriticalSectionRawMutex, LedStatus, 8>,
-    Sender<'static, CriticalSectionRawMutex, LedData, 4>,
-    Sender<'static, CriticalSectionRawMutex, LedMode, 2>,
-    Receiver<'static, CriticalSectionRawMutex, LedStatus, 8>,
-    Receiver<'static, CriticalSectionRawMutex, LedData, 4>,
-    Receiver<'static, CriticalSectionRawMutex, LedMode, 2>,
+    LedStatusSender,
+    &'static LedDataSignal,
+    LedModeSender,
+    LedStatusReceiver,
+    &'static LedDataSignal,
+    LedModeReceiver,
 ) {
     let status_channel = LED_STATUS_CHANNEL.init(Channel::new());
-    let data_channel = LED_DATA_CHANNEL.init(Channel::new());
     let mode_channel = LED_MODE_CHANNEL.init(Channel::new());
 
     let status_sender = status_channel.sender();
     let status_receiver = status_channel.receiver();
-    let data_sender = data_channel.sender();
-    let data_receiver = data_channel.receiver();
+    let data_sender = &LED_DATA_SIGNAL;
+    let data_receiver = &LED_DATA_SIGNAL;
     let mode_sender = mode_channel.sender();
     let mode_receiver = mode_channel.receiver();
 
@@ -364,6 +624,10 @@ struct LedTaskState {
     breathing_counter: u32,
     last_ambient_data: Option<LedData>,
     ambient_timeout: Duration,
+    /// LED data is currently arriving (drives the DataStart/DataStop hooks)
+    data_active: bool,
+    strip_blanked: bool,
+    pixel_grouping: PixelGrouping,
 }
 
 impl LedTaskState {
@@ -375,6 +639,9 @@ impl LedTaskState {
             breathing_counter: 30, // Start at minimum brightness
             last_ambient_data: None,
             ambient_timeout: Duration::from_secs(5), // Switch back to non-ambient after 5s
+            data_active: false,
+            strip_blanked: false,
+            pixel_grouping: PixelGrouping::default(),
         }
     }
 
@@ -395,20 +662,51 @@ impl LedTaskState {
 /// Main LED task running at 30fps
 #[embassy_executor::task]
 pub async fn led_task(
-    led_controller: &'static embassy_sync::mutex::Mutex<
-        CriticalSectionRawMutex,
-        crate::led_control::UniversalDriverBoard<esp_hal::rmt::Channel<esp_hal::Blocking, 0>>,
-    >,
-    status_receiver: Receiver<'static, CriticalSectionRawMutex, LedStatus, 8>,
-    data_receiver: Receiver<'static, CriticalSectionRawMutex, LedData, 4>,
-    mode_receiver: Receiver<'static, CriticalSectionRawMutex, LedMode, 2>,
+    rmt: Rmt<'static, esp_hal::Blocking>,
+    data_pin: esp_hal::gpio::AnyPin<'static>,
+    status_receiver: LedStatusReceiver,
+    data_receiver: &'static LedDataSignal,
+    mode_receiver: LedModeReceiver,
+    mut status_led: Option<esp_hal::gpio::Output<'static>>,
 ) -> ! {
-    let mut ticker = embassy_time::Ticker::every(Duration::from_millis(33)); // 30fps ≈ 33.33ms
+    // 30fps ≈ 33.33ms
+    let mut ticker =
+        embassy_time::Ticker::every(Duration::from_millis(crate::config::LED_FRAME_INTERVAL_MS));
     let mut state = LedTaskState::new();
 
+    // Async drivers can't be sent to another executor, so the channel is set
+    // up here, on the one that drives it
+    let tx_config = TxChannelConfig::default()
+        .with_clk_divider(crate::config::RMT_CLK_DIVIDER)
+        .with_idle_output_level(Level::Low)
+        .with_idle_output(false)
+        .with_carrier_modulation(false);
+    let channel = rmt
+        .into_async()
+        .channel0
+        .configure(data_pin, tx_config)
+        .inspect_err(|e| println!("[LED] RMT channel init failed: {:?}", e));
+    let Ok(channel) = channel else {
+        crate::self_test::record_failure(crate::self_test::Failure::Rmt);
+        crate::self_test::finish_strip_check();
+        loop {
+            core::future::pending::<()>().await;
+        }
+    };
+    let mut controller = UniversalDriverBoard::new(channel);
+    if crate::config::SELF_TEST_LED_SWEEP
+        && crate::self_test::led_sweep(&mut controller).await.is_err()
+    {
+        crate::self_test::record_failure(crate::self_test::Failure::Strip);
+    }
+    crate::self_test::finish_strip_check();
+
     println!("[LED] LED task started at 30fps");
 
     loop {
+        crate::memory::checkpoint(crate::memory::Subsystem::Led);
+        crate::watchdog::heartbeat(crate::watchdog::Task::Led);
+
         // Check for new messages (non-blocking)
         while let Ok(status) = status_receiver.try_receive() {
             state.current_status = status;
@@ -420,43 +718,106 @@ pub async fn led_task(
             println!("[LED] Mode switched: {:?}", mode);
         }
 
-        while let Ok(data) = data_receiver.try_receive() {
-            state.last_ambient_data = Some(data);
+        // Only the newest frame is kept - older ones were overwritten
+        let mut new_frame = false;
+        if let Some(mut data) = data_receiver.try_take() {
+            new_frame = true;
+            // Expand once on arrival rather than on every re-render
+            if state.pixel_grouping.group_size > 1 {
+                data.data = expand_pixel_groups(&data.data, state.pixel_grouping);
+            }
+            if let Some(old) = state.last_ambient_data.replace(data) {
+                FRAME_RECYCLE.signal(old.data);
+            }
+            if !state.data_active {
+                state.data_active = true;
+                crate::hooks::trigger(crate::hooks::HookEvent::DataStart);
+            }
             // Automatically switch to ambient mode when data is received
-            if state.current_mode != LedMode::Ambient {
+            if is_auto_mode_switch() && state.current_mode != LedMode::Ambient {
                 state.current_mode = LedMode::Ambient;
                 println!("[LED] Auto-switched to Ambient mode");
             }
         }
 
         // Auto-switch back to non-ambient mode if no recent data
-        if state.current_mode == LedMode::Ambient && state.should_switch_to_non_ambient() {
-            state.current_mode = LedMode::NonAmbient;
-            println!("[LED] Auto-switched to NonAmbient mode (timeout)");
+        if state.data_active && state.should_switch_to_non_ambient() {
+            state.data_active = false;
+            crate::hooks::trigger(crate::hooks::HookEvent::DataStop);
+            if is_auto_mode_switch() && state.current_mode == LedMode::Ambient {
+                state.current_mode = LedMode::NonAmbient;
+                println!("[LED] Auto-switched to NonAmbient mode (timeout)");
+            }
         }
 
+        // A blanked strip, or a failover standby whose primary is alive, stays dark.
+        // During a firmware update LED data is ignored: the strip shows the
+        // progress, and is latched dark once the image is written
+        let render_mode = match state.current_status {
+            LedStatus::OtaApplying => LedMode::Off,
+            LedStatus::OtaDownloading if is_strip_enabled() => LedMode::NonAmbient,
+            _ if is_strip_enabled() && crate::failover::is_rendering_active() => state.current_mode,
+            _ => LedMode::Off,
+        };
+        ACTIVE_MODE.store(render_mode as u8, Ordering::Relaxed);
+
+        // A held factory reset button takes over the strip, even when blanked
+        let reset_progress = RESET_PROGRESS.load(Ordering::Relaxed);
+
         // Update LED display based on current mode
-        {
-            let mut controller = led_controller.lock().await;
-            match state.current_mode {
+        if reset_progress > 0 {
+            update_reset_countdown_display(&mut controller, reset_progress).await;
+        } else if render_mode != LedMode::Off && state.current_status == LedStatus::OtaDownloading {
+            update_ota_progress_display(&mut controller, state.status_counter).await;
+        } else {
+            match render_mode {
                 LedMode::NonAmbient => {
                     // Skip status indication when operational - but still do breathing
                     if !matches!(state.current_status, LedStatus::Operational) {
                         controller.set_status(state.current_status);
                     }
-                    update_non_ambient_display(&mut controller, &mut state);
+                    update_non_ambient_display(&mut controller, &mut state).await;
                 }
                 LedMode::Ambient => {
                     if let Some(ref data) = state.last_ambient_data {
                         // Display ambient data
-                        let _ = controller.forward_raw_stream(&data.data);
+                        let render_start = Instant::now();
+                        let _ = controller.forward_raw_stream(&data.data).await;
+                        if new_frame {
+                            crate::benchmark::record_render(render_start);
+                            record_frame_shown();
+
+                            // Exponential moving average over roughly 8 frames
+                            let latency = data.timestamp.elapsed().as_micros() as u32;
+                            let average = RENDER_LATENCY_US.load(Ordering::Relaxed);
+                            RENDER_LATENCY_US
+                                .store(average - average / 8 + latency / 8, Ordering::Relaxed);
+                        }
                     } else {
                         // Fallback to non-ambient display
-                        update_non_ambient_display(&mut controller, &mut state);
+                        update_non_ambient_display(&mut controller, &mut state).await;
+                    }
+                }
+                LedMode::Effect => {
+                    update_effect_display(&mut controller, &state).await;
+                }
+                LedMode::Off => {
+                    // Strip only needs to be latched dark once
+                    if !state.strip_blanked {
+                        blank_display(&mut controller).await;
+                        state.strip_blanked = true;
                     }
                 }
             }
         }
+        if reset_progress > 0 || render_mode != LedMode::Off {
+            state.strip_blanked = false;
+        }
+
+        // The board's indicator LED blinks like the status LEDs, in every mode
+        if let Some(ref mut led) = status_led {
+            led.set_level(state.current_status.blink_on(state.status_counter).into());
+        }
 
         // Update counters for next frame
         state.update_counters();
@@ -467,12 +828,13 @@ pub async fn led_task(
 }
 
 /// Update LED display for non-ambient mode (breathing + status indication)
-fn update_non_ambient_display(
-    controller: &mut UniversalDriverBoard<esp_hal::rmt::Channel<esp_hal::Blocking, 0>>,
+async fn update_non_ambient_display(
+    controller: &mut UniversalDriverBoard<LedChannel>,
     state: &mut LedTaskState,
 ) {
-    const LED_COUNT: usize = 60; // Only update first 60 LEDs to reduce transmission time
     const STATUS_LEDS: usize = 3; // First 3 LEDs for status
+    // Only update configured LEDs to reduce transmission time
+    let led_count = crate::config_store::led_count().max(STATUS_LEDS);
 
     // Breathing effect parameters (5 second cycle)
     const BREATHING_MIN: u32 = 30;
@@ -492,74 +854,108 @@ fn update_non_ambient_display(
     };
 
     // Status indication timing (faster blinking)
-    let status_on = match state.current_status {
-        // System initialization states - very fast blink
-        LedStatus::Starting | LedStatus::HardwareInit | LedStatus::WiFiDriverInit => {
-            (state.status_counter / 8) % 2 == 0
-        }
+    let status_on = state.current_status.blink_on(state.status_counter);
+
+    // Create LED data buffer in the strip's pixel format; a frame is
+    // skipped if the heap can't fit it
+    let format = crate::config_store::pixel_format();
+    let bytes_per_led = format.bytes_per_led();
+    let Some(mut led_data) = crate::memory::try_zeroed(led_count * bytes_per_led) else {
+        return;
+    };
 
-        // Network connection states - fast blink
-        LedStatus::WiFiConnecting
-        | LedStatus::WiFiConnected
-        | LedStatus::DHCPRequesting
-        | LedStatus::Reconnecting => (state.status_counter / 12) % 2 == 0,
-
-        // Service states - medium blink
-        LedStatus::ServicesStarting
-        | LedStatus::UDPServerBinding
-        | LedStatus::UDPServerListening
-        | LedStatus::MDNSAdvertising => (state.status_counter / 16) % 2 == 0,
-
-        // Operational states - slow pulse
-        LedStatus::NetworkReady | LedStatus::Operational | LedStatus::ConnectionMonitoring => {
-            (state.status_counter / 20) % 3 == 0
-        }
+    for (i, pixel) in led_data.chunks_exact_mut(bytes_per_led).enumerate() {
+        let rgbw = if i < STATUS_LEDS {
+            // Status LEDs (first 3 LEDs) - white, or off between blinks
+            if status_on { [255; 4] } else { [0; 4] }
+        } else {
+            // Breathing effect for remaining LEDs - white channel only
+            [0, 0, 0, breathing_brightness as u8]
+        };
+        format.write_pixel(rgbw, pixel);
+    }
 
-        // Data processing states - very fast pulse
-        LedStatus::DataReceiving | LedStatus::LEDRendering => (state.status_counter / 6) % 2 == 0,
+    // Forward the data to LED hardware
+    let _ = controller.forward_raw_stream(&led_data).await; // Silent error handling
+}
 
-        // Error states - medium blink
-        LedStatus::WiFiError
-        | LedStatus::NetworkError
-        | LedStatus::ServiceError
-        | LedStatus::HardwareError
-        | LedStatus::Error => (state.status_counter / 20) % 2 == 0,
+/// Update LED display for effect mode (rainbow cycling along the strip)
+async fn update_effect_display(
+    controller: &mut UniversalDriverBoard<LedChannel>,
+    state: &LedTaskState,
+) {
+    let led_count = crate::config_store::led_count();
+    // Hue steps per frame (~8.5s per full cycle at 30fps)
+    const HUE_SPEED: u32 = 1;
 
-        // Critical error - fast blink
-        LedStatus::CriticalError => (state.status_counter / 10) % 2 == 0,
+    let phase = (state.breathing_counter * HUE_SPEED) as usize;
 
-        // Recovery states - slow blink
-        LedStatus::ServiceRestarting | LedStatus::SystemRecovering => {
-            (state.status_counter / 25) % 2 == 0
-        }
+    let format = crate::config_store::pixel_format();
+    let bytes_per_led = format.bytes_per_led();
+    let Some(mut led_data) = crate::memory::try_zeroed(led_count * bytes_per_led) else {
+        return;
     };
+    for (i, pixel) in led_data.chunks_exact_mut(bytes_per_led).enumerate() {
+        let hue = ((i * 256 / led_count + phase) % 256) as u8;
+        let (r, g, b) = color_wheel(hue);
+        format.write_pixel([r, g, b, 0], pixel);
+    }
 
-    // Create LED data buffer (4 bytes per LED: G, R, B, W)
-    let mut led_data = vec![0u8; LED_COUNT * 4];
-
-    // Set status LEDs (first 3 LEDs) - white color only
-    for i in 0..STATUS_LEDS {
-        let offset = i * 4;
-        if status_on {
-            // White color (equal values for G, R, B, W)
-            led_data[offset] = 255; // G
-            led_data[offset + 1] = 255; // R
-            led_data[offset + 2] = 255; // B
-            led_data[offset + 3] = 255; // W
-        }
-        // else: LEDs remain off (0, 0, 0, 0)
+    let _ = controller.forward_raw_stream(&led_data).await; // Silent error handling
+}
+
+/// Map a hue (0-255) onto a fully saturated RGB colour
+fn color_wheel(hue: u8) -> (u8, u8, u8) {
+    let segment = hue / 85;
+    let step = (hue % 85) * 3;
+    match segment {
+        0 => (255 - step, step, 0),
+        1 => (0, 255 - step, step),
+        _ => (step, 0, 255 - step),
+    }
+}
+
+/// Fill the strip red in proportion to how long the reset button has been held
+async fn update_reset_countdown_display(
+    controller: &mut UniversalDriverBoard<LedChannel>,
+    percent: u8,
+) {
+    let led_count = crate::config_store::led_count();
+    let lit = (led_count * percent as usize).div_ceil(100);
+
+    let mut rgb = vec![0u8; led_count * 3];
+    for pixel in rgb.chunks_exact_mut(3).take(lit) {
+        pixel[0] = 255;
     }
+    let mut led_data = alloc::vec::Vec::new();
+    rgb_to_pixel_format(&rgb, crate::config_store::pixel_format(), &mut led_data);
+    let _ = controller.forward_raw_stream(&led_data).await; // Silent error handling
+}
 
-    // Set breathing effect for remaining LEDs - white color only
-    for i in STATUS_LEDS..LED_COUNT {
-        let offset = i * 4;
-        let brightness = breathing_brightness as u8;
-        led_data[offset] = 0; // G
-        led_data[offset + 1] = 0; // R
-        led_data[offset + 2] = 0; // B
-        led_data[offset + 3] = brightness; // W
+/// Firmware update progress: the written share of the strip in blue, with
+/// the LED past it flashing so the strip keeps moving between updates
+async fn update_ota_progress_display(
+    controller: &mut UniversalDriverBoard<LedChannel>,
+    counter: u32,
+) {
+    let led_count = crate::config_store::led_count();
+    let percent = crate::ota::OtaProgress::read().percent() as usize;
+    let lit = (led_count * percent) / 100;
+
+    let mut rgb = vec![0u8; led_count * 3];
+    for pixel in rgb.chunks_exact_mut(3).take(lit) {
+        pixel[2] = 160;
+    }
+    if lit < led_count && (counter / 8).is_multiple_of(2) {
+        rgb[lit * 3 + 2] = 255;
     }
+    let mut led_data = alloc::vec::Vec::new();
+    rgb_to_pixel_format(&rgb, crate::config_store::pixel_format(), &mut led_data);
+    let _ = controller.forward_raw_stream(&led_data).await; // Silent error handling
+}
 
-    // Forward the data to LED hardware
-    let _ = controller.forward_raw_stream(&led_data); // Silent error handling
+/// Turn every LED of the strip off
+async fn blank_display(controller: &mut UniversalDriverBoard<LedChannel>) {
+    let led_data = vec![0u8; crate::config::MAX_FRAME_BYTES];
+    let _ = controller.forward_raw_stream(&led_data).await; // Silent error handling
 }
@@ -0,0 +1,119 @@
+//! LED status codes
+//!
+//! Kept apart from the LED driver so the state machine builds without the
+//! chip support crates.
+
+/// LED status states for visual feedback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedStatus {
+    // System initialization states
+    Starting,
+    HardwareInit,
+    WiFiDriverInit,
+
+    // Network connection states
+    WiFiConnecting,
+    WiFiConnected,
+    DHCPRequesting,
+    NetworkReady,
+
+    // Service states
+    ServicesStarting,
+    UDPServerBinding,
+    UDPServerListening,
+    MDNSAdvertising,
+
+    // Operational states
+    Operational,
+    DataReceiving,
+    LEDRendering,
+    ConnectionMonitoring,
+
+    // Error states
+    WiFiError,
+    NetworkError,
+    ServiceError,
+    HardwareError,
+    CriticalError,
+
+    // Recovery states
+    Reconnecting,
+    ServiceRestarting,
+    SystemRecovering,
+
+    // Setup access point open, waiting for credentials
+    Provisioning,
+
+    // mDNS responder failed to start (retrying with backoff)
+    DiscoveryError,
+
+    // Firmware update downloading / being written (LED data suspended)
+    OtaDownloading,
+    OtaApplying,
+
+    // Legacy states (for backward compatibility)
+    Error, // Maps to CriticalError
+}
+
+impl LedStatus {
+    /// Whether the status LEDs are lit on frame `counter` of this status
+    ///
+    /// Each status has its own blink pattern at the 30fps frame rate.
+    pub fn blink_on(self, counter: u32) -> bool {
+        match self {
+            // System initialization states - very fast blink
+            LedStatus::Starting | LedStatus::HardwareInit | LedStatus::WiFiDriverInit => {
+                (counter / 8).is_multiple_of(2)
+            }
+
+            // Network connection states - fast blink
+            LedStatus::WiFiConnecting
+            | LedStatus::WiFiConnected
+            | LedStatus::DHCPRequesting
+            | LedStatus::Reconnecting => (counter / 12).is_multiple_of(2),
+
+            // Service states - medium blink
+            LedStatus::ServicesStarting
+            | LedStatus::UDPServerBinding
+            | LedStatus::UDPServerListening
+            | LedStatus::MDNSAdvertising => (counter / 16).is_multiple_of(2),
+
+            // Operational states - slow pulse
+            LedStatus::NetworkReady | LedStatus::Operational | LedStatus::ConnectionMonitoring => {
+                (counter / 20).is_multiple_of(3)
+            }
+
+            // Data processing states - very fast pulse
+            LedStatus::DataReceiving | LedStatus::LEDRendering => (counter / 6).is_multiple_of(2),
+
+            // Error states - medium blink
+            LedStatus::WiFiError
+            | LedStatus::NetworkError
+            | LedStatus::ServiceError
+            | LedStatus::Error => (counter / 20).is_multiple_of(2),
+
+            // Hardware self-test failed - long-short blink
+            LedStatus::HardwareError => matches!((counter / 6) % 10, 0..=3 | 6),
+
+            // Critical error - fast blink
+            LedStatus::CriticalError => (counter / 10).is_multiple_of(2),
+
+            // Recovery states - slow blink
+            LedStatus::ServiceRestarting | LedStatus::SystemRecovering => {
+                (counter / 25).is_multiple_of(2)
+            }
+
+            // Provisioning - double blink
+            LedStatus::Provisioning => matches!((counter / 6) % 8, 0 | 2),
+
+            // Discovery failed - triple blink
+            LedStatus::DiscoveryError => matches!((counter / 6) % 10, 0 | 2 | 4),
+
+            // Firmware update arriving - quick flashes, so it doesn't look hung
+            LedStatus::OtaDownloading => (counter / 3).is_multiple_of(4),
+
+            // Firmware update being applied - steady on
+            LedStatus::OtaApplying => true,
+        }
+    }
+}
@@ -1,40 +1,460 @@
-#![no_std]
+#![cfg_attr(target_os = "none", no_std)]
 
-//! ESP32-C3 Ambient Light Hardware Board Library
+//! ESP32 Ambient Light Hardware Board Library
 //!
 //! This library provides modules for implementing a WiFi-enabled LED hardware
 //! communication bridge that receives UDP packets and forwards them to WS2812 LED strips.
+//!
+//! The target chip is picked with a cargo feature: `esp32c3` (the default),
+//! `esp32c6`, `esp32s3` or `esp32`. The chip-specific constants live in
+//! [`config`]; everything else is shared.
 
 extern crate alloc;
 
-pub mod led_control;
+// Portable modules, also built for the host so `cargo test` covers them
+pub mod board_profile;
+pub mod color_correction;
+pub mod config_store;
+pub mod crc;
+pub mod crypto;
+pub mod hooks;
+pub mod led_status;
+pub mod protocol;
 pub mod state_machine;
+
+#[cfg(all(
+    target_os = "none",
+    not(any(
+        feature = "esp32c3",
+        feature = "esp32c6",
+        feature = "esp32s3",
+        feature = "esp32"
+    ))
+))]
+compile_error!("enable one chip feature: esp32c3, esp32c6, esp32s3 or esp32");
+
+#[cfg(all(feature = "driver-board", feature = "esp32"))]
+compile_error!("the driver board profile uses GPIO9/10, flash pins on the ESP32");
+
+// Board modules, built for the chip only
+#[cfg(target_os = "none")]
+pub mod adalight;
+#[cfg(target_os = "none")]
+pub mod benchmark;
+#[cfg(target_os = "none")]
+pub mod ble_provisioning;
+#[cfg(target_os = "none")]
+pub mod crash_log;
+#[cfg(target_os = "none")]
+pub mod device_info;
+#[cfg(target_os = "none")]
+pub mod diagnostics;
+#[cfg(target_os = "none")]
+pub mod espnow;
+#[cfg(target_os = "none")]
+pub mod esptouch;
+#[cfg(target_os = "none")]
+pub mod factory_reset;
+#[cfg(target_os = "none")]
+pub mod failover;
+#[cfg(target_os = "none")]
+pub mod hyperion;
+#[cfg(target_os = "none")]
+pub mod led_control;
+#[cfg(target_os = "none")]
+pub mod log_sink;
+#[cfg(target_os = "none")]
+pub mod mdns;
+#[cfg(target_os = "none")]
+pub mod memory;
+#[cfg(target_os = "none")]
+pub mod metrics;
+#[cfg(target_os = "none")]
+pub mod mqtt;
+#[cfg(target_os = "none")]
+pub mod ota;
+#[cfg(target_os = "none")]
+pub mod ota_push;
+#[cfg(target_os = "none")]
+pub mod provisioning;
+#[cfg(target_os = "none")]
+pub mod self_test;
+#[cfg(target_os = "none")]
+pub mod temperature;
+#[cfg(target_os = "none")]
 pub mod udp_server;
+#[cfg(all(target_os = "none", not(feature = "esp32")))]
+pub mod usb_serial;
+#[cfg(target_os = "none")]
+pub mod watchdog;
+#[cfg(target_os = "none")]
+pub mod web_config;
+#[cfg(target_os = "none")]
+pub mod websocket;
+#[cfg(target_os = "none")]
 pub mod wifi;
 
+/// Console output: the serial console (and the remote log sink, see
+/// [`log_sink`]) on the board, stdout in host tests
+#[cfg(target_os = "none")]
+#[macro_export]
+macro_rules! println {
+    () => {
+        $crate::log_sink::print(format_args!(""))
+    };
+    ($($arg:tt)*) => {
+        $crate::log_sink::print(format_args!($($arg)*))
+    };
+}
+#[cfg(not(target_os = "none"))]
+pub use std::println;
+
+/// Build-time board settings from the optional `board.toml` (see build.rs)
+mod board_config {
+    include!(concat!(env!("OUT_DIR"), "/board_config.rs"));
+}
+
 /// Project version information
 pub const VERSION: &str = "0.1.0-dev";
 
+/// Git commit the firmware was built from (`unknown` outside a checkout),
+/// with `-dirty` for uncommitted changes
+pub const BUILD_HASH: &str = env!("BUILD_HASH");
+
 /// Default configuration constants
 pub mod config {
-    /// Default UDP port for LED data communication
-    pub const UDP_PORT: u16 = 23042;
+    /// Default UDP port for LED data communication (`board.toml` can override it)
+    pub const UDP_PORT: u16 = match crate::board_config::UDP_PORT {
+        Some(port) => port,
+        None => 23042,
+    };
+
+    /// Accept Adalight frames over UART1 as a wired fallback
+    pub const ADALIGHT_ENABLED: bool = false;
+
+    /// Adalight UART baud rate
+    pub const ADALIGHT_BAUD_RATE: u32 = 115200;
+
+    /// Accept framed LED data over the built-in USB-Serial-JTAG port (not on the ESP32,
+    /// which has none)
+    pub const USB_SERIAL_INPUT_ENABLED: bool = false;
+
+    /// TCP port for the WebSocket streaming endpoint (None disables)
+    pub const WEBSOCKET_PORT: Option<u16> = None;
+
+    /// TCP port of the web settings page (None disables it)
+    /// Also co-advertised over mDNS as `_http._tcp`
+    pub const HTTP_PORT: Option<u16> = Some(80);
+
+    /// First LED (absolute offset in the sender's stream) driven by this board
+    pub const CHAIN_LED_OFFSET: u16 = 0;
+
+    /// Next board in a chain, as IPv4 address and UDP port (None disables forwarding)
+    /// LEDs beyond `CHAIN_LED_OFFSET + LED_COUNT` are forwarded there
+    pub const CHAIN_NEXT_HOP: Option<([u8; 4], u16)> = None;
 
-    /// Default LED data GPIO pin
-    pub const LED_DATA_PIN: u8 = 4;
+    /// Accept LED data over ESP-NOW, without an access point
+    pub const ESPNOW_ENABLED: bool = false;
+
+    /// Only accept ESP-NOW frames from this sender MAC (None accepts any peer)
+    pub const ESPNOW_PEER: Option<[u8; 6]> = None;
+
+    /// MQTT broker address and port (None disables the MQTT client)
+    pub const MQTT_BROKER: Option<([u8; 4], u16)> = None;
+
+    /// Syslog collector to mirror console lines to, as IPv4 address and UDP
+    /// port (usually 514; None disables the remote log sink)
+    pub const LOG_SINK: Option<([u8; 4], u16)> = None;
+
+    /// Topic prefix for MQTT state and command topics
+    pub const MQTT_TOPIC_PREFIX: &str = "board-rs";
+
+    /// Interval between MQTT state publications
+    pub const MQTT_PUBLISH_INTERVAL_MS: u32 = 10000;
+
+    /// MQTT credentials, read from the `MQTT_USERNAME` / `MQTT_PASSWORD`
+    /// environment variables at compile time (empty connects anonymously)
+    pub const MQTT_USERNAME: &str = env!("MQTT_USERNAME");
+    pub const MQTT_PASSWORD: &str = env!("MQTT_PASSWORD");
+
+    /// UDP port for Hyperion "udpraw" frames (headerless RGB, None disables)
+    pub const HYPERION_UDP_PORT: Option<u16> = None;
+
+    /// Multicast group to accept LED data on, in addition to unicast (None disables)
+    pub const LED_MULTICAST_GROUP: Option<[u8; 4]> = None;
+
+    /// Heap size, sized to the chip's RAM (Wi-Fi and BLE buffers come from the heap)
+    #[cfg(feature = "esp32c3")]
+    pub const HEAP_SIZE: usize = 72 * 1024;
+    #[cfg(feature = "esp32c6")]
+    pub const HEAP_SIZE: usize = 96 * 1024;
+    #[cfg(feature = "esp32s3")]
+    pub const HEAP_SIZE: usize = 128 * 1024;
+    #[cfg(feature = "esp32")]
+    pub const HEAP_SIZE: usize = 96 * 1024;
+
+    /// RMT source clock in MHz and channel divider, giving the 100ns tick the
+    /// LED pulse lengths are counted in (the ESP32's RMT only runs from the
+    /// 80MHz APB clock)
+    #[cfg(not(feature = "esp32"))]
+    pub const RMT_FREQUENCY_MHZ: u32 = 10;
+    #[cfg(not(feature = "esp32"))]
+    pub const RMT_CLK_DIVIDER: u8 = 1;
+    #[cfg(feature = "esp32")]
+    pub const RMT_FREQUENCY_MHZ: u32 = 80;
+    #[cfg(feature = "esp32")]
+    pub const RMT_CLK_DIVIDER: u8 = 8;
 
     /// Maximum supported LEDs per strip
     pub const MAX_LEDS: usize = 1000;
 
+    /// Configured number of LEDs on the strip (`board.toml` can override it)
+    pub const LED_COUNT: usize = match crate::board_config::LED_COUNT {
+        Some(count) => count,
+        None => 60,
+    };
+    const _: () = assert!(
+        LED_COUNT >= 1 && LED_COUNT <= MAX_LEDS,
+        "LED_COUNT out of range"
+    );
+
+    /// Pixel format (channel order) of the strip (`board.toml` can override it)
+    pub const PIXEL_FORMAT: crate::protocol::PixelFormat = match crate::board_config::PIXEL_FORMAT {
+        Some(format) => format,
+        None => crate::protocol::PixelFormat::Grbw,
+    };
+
+    /// Bytes per LED in the data stream (G, R, B, W)
+    pub const BYTES_PER_LED: usize = PIXEL_FORMAT.bytes_per_led();
+
+    /// Largest LED frame the RMT output can send: `MAX_LEDS` LEDs of the
+    /// configured format
+    pub const MAX_FRAME_BYTES: usize = MAX_LEDS * BYTES_PER_LED;
+
+    /// Physical LEDs driven by each logical pixel of the data stream
+    pub const PIXEL_GROUP_SIZE: usize = 1;
+
+    /// Interpolate between neighbouring logical pixels inside a group
+    pub const PIXEL_GROUP_SMOOTHING: bool = false;
+
+    /// LED frame interval in milliseconds (30fps)
+    pub const LED_FRAME_INTERVAL_MS: u64 = 33;
+
     /// mDNS service name
     pub const MDNS_SERVICE_NAME: &str = "_ambient_light._udp.local.";
 
+    /// Default mDNS service instance name (empty derives `ambient-<last 3 MAC bytes>`;
+    /// `board.toml` can override it)
+    pub const MDNS_INSTANCE_NAME: &str = match crate::board_config::DEVICE_NAME {
+        Some(name) => name,
+        None => "",
+    };
+
+    /// Default mDNS host name (`<hostname>.local.`)
+    pub const MDNS_HOSTNAME: &str = "board-rs";
+
+    /// Host name sent to the DHCP server (option 12), shown in router client lists
+    /// (empty derives it from the device name, e.g. `ambient-a1b2c3`)
+    pub const DHCP_HOSTNAME: &str = "";
+
+    /// Default location advertised as the `room=` TXT key (empty to omit)
+    pub const MDNS_LOCATION: &str = "";
+
+    /// Delay before the mDNS responder is restarted after a failure
+    pub const MDNS_RETRY_INTERVAL_MS: u32 = 5000;
+
+    /// Longest delay between mDNS restarts (the delay doubles per failure)
+    pub const MDNS_RETRY_MAX_INTERVAL_MS: u32 = 300_000;
+
     /// Protocol header byte for LED data packets
     pub const PROTOCOL_HEADER: u8 = 0x02;
 
     /// Protocol header byte for connection check packets
     pub const CONNECTION_CHECK_HEADER: u8 = 0x01;
 
+    /// Protocol header byte for sequenced LED data packets
+    /// (flags + 16-bit sequence number ahead of the offset)
+    pub const SEQUENCED_DATA_HEADER: u8 = 0x05;
+
+    /// Sequenced packet flag: a CRC-32 of all preceding bytes is appended (big-endian)
+    pub const DATA_FLAG_CRC32: u8 = 0x01;
+
+    /// Sequenced packet flag: the board replies with a data acknowledgement
+    pub const DATA_FLAG_ACK: u8 = 0x02;
+
+    /// Sequenced packet flag: a priority byte follows the flags (0 = ambient stream)
+    pub const DATA_FLAG_PRIORITY: u8 = 0x04;
+
+    /// Silence in milliseconds after which a raised stream priority expires
+    pub const PRIORITY_TIMEOUT_MS: u32 = 1000;
+
+    /// Protocol header byte for data acknowledgements (`[0x08][sequence:2][status]`)
+    pub const DATA_ACK_HEADER: u8 = 0x08;
+
+    /// Protocol header byte for the sequence statistics query/response
+    pub const SEQUENCE_STATS_HEADER: u8 = 0x06;
+
+    /// Protocol header byte for the device info query/response
+    pub const DEVICE_INFO_HEADER: u8 = 0x10;
+
+    /// Protocol version reported in the device info response
+    pub const PROTOCOL_VERSION: u8 = 1;
+
+    /// Protocol header byte for the GPIO toggle diagnostics command
+    pub const DIAGNOSTICS_GPIO_HEADER: u8 = 0x11;
+
+    /// GPIOs the diagnostics command may toggle (flash, USB and UART pins excluded)
+    #[cfg(not(feature = "esp32"))]
+    pub const DIAG_GPIO_PINS: &[u8] = &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+    #[cfg(feature = "esp32")]
+    pub const DIAG_GPIO_PINS: &[u8] = &[2, 4, 5, 12, 13, 14, 15, 18, 19, 21, 22, 23];
+
+    /// Protocol header byte for the global brightness command
+    pub const BRIGHTNESS_HEADER: u8 = 0x12;
+
+    /// Global brightness at boot (255 = full; `board.toml` can override it)
+    pub const DEFAULT_BRIGHTNESS: u8 = match crate::board_config::BRIGHTNESS {
+        Some(brightness) => brightness,
+        None => 255,
+    };
+
+    /// Protocol header byte for the strip power (blank) command
+    pub const POWER_HEADER: u8 = 0x13;
+
+    /// Protocol header byte for the memory usage query/response
+    pub const MEMORY_STATS_HEADER: u8 = 0x14;
+
+    /// Protocol header byte for the explicit LED mode command
+    pub const MODE_HEADER: u8 = 0x15;
+
+    /// Mode command code returning to data-driven auto-switching
+    pub const MODE_AUTO: u8 = 0xFF;
+
+    /// Protocol header byte for the throughput self-benchmark command
+    pub const BENCHMARK_HEADER: u8 = 0x16;
+
+    /// Protocol header byte for the source takeover command
+    pub const SOURCE_TAKEOVER_HEADER: u8 = 0x17;
+
+    /// Idle time in milliseconds after which the data source lock is released
+    pub const SOURCE_LOCK_TIMEOUT_MS: u32 = 5000;
+
+    /// Protocol header byte for the UDP link statistics query/response
+    pub const UDP_STATS_HEADER: u8 = 0x18;
+
+    /// Protocol header byte for the ping/latency echo
+    pub const PING_HEADER: u8 = 0x19;
+
+    /// Protocol header byte for the set-instance-name command
+    pub const INSTANCE_NAME_HEADER: u8 = 0x1B;
+
+    /// Protocol header byte for the broadcast discovery probe/reply
+    pub const DISCOVERY_HEADER: u8 = 0x1A;
+
+    /// Protocol header byte for the boot report (crash log) query/response
+    pub const BOOT_REPORT_HEADER: u8 = 0x1C;
+
+    /// Protocol header byte for the reboot command
+    pub const REBOOT_HEADER: u8 = 0x1D;
+
+    /// Protocol header byte for the config get/set command
+    pub const CONFIG_HEADER: u8 = 0x1E;
+
+    /// Protocol header byte for the firmware update command
+    pub const OTA_HEADER: u8 = 0x1F;
+
+    /// Roll back to the previous firmware when a new image resets before its
+    /// first boot reaches `Operational`
+    pub const OTA_ROLLBACK_ENABLED: bool = true;
+
+    /// Default firmware image URL for updates, read from the `OTA_URL` build
+    /// variable (empty: the update command must carry a URL)
+    pub const OTA_URL: &str = env!("OTA_URL");
+
+    /// Protocol header byte for the firmware info query/response
+    pub const FIRMWARE_INFO_HEADER: u8 = 0x21;
+
+    /// Protocol header byte for the firmware update progress query/report
+    pub const OTA_PROGRESS_HEADER: u8 = 0x22;
+
+    /// Interval of the progress reports sent during a firmware update
+    pub const OTA_PROGRESS_INTERVAL_MS: u64 = 1000;
+
+    /// TCP port accepting firmware images pushed from the desktop app (None
+    /// disables; not started with `LED_PSK` set, as pushes aren't authenticated)
+    pub const OTA_PUSH_PORT: Option<u16> = Some(23044);
+
+    /// Largest data chunk of a pushed firmware image
+    pub const OTA_PUSH_MAX_CHUNK: usize = 1024;
+
+    /// Magic following the reboot header, so a stray byte can't restart the board
+    pub const REBOOT_MAGIC: &[u8; 4] = b"BOOT";
+
+    /// Magic following the discovery header, so stray broadcasts are ignored
+    pub const DISCOVERY_MAGIC: &[u8; 4] = b"ALBD";
+
+    /// Protocol header byte for ChaCha20-Poly1305 encrypted packets
+    pub const ENCRYPTED_HEADER: u8 = 0x20;
+
+    /// Pre-shared key for encrypted packets, 64 hex digits (empty disables encryption)
+    /// Read from the `LED_PSK` environment variable at compile time
+    pub const ENCRYPTION_KEY_HEX: &str = env!("LED_PSK");
+
+    /// Protocol header byte for fragments of a multi-packet LED frame
+    pub const FRAGMENT_HEADER: u8 = 0x07;
+
+    /// Maximum number of fragments per LED frame
+    pub const MAX_FRAGMENTS: u8 = 32;
+
+    /// Backwards sequence jump treated as a sender restart rather than a stale packet
+    pub const SEQUENCE_RESYNC_WINDOW: u16 = 256;
+
+    /// Pause between two consecutive sequenced packets of a sender that counts
+    /// as a stall in the per-sender statistics
+    pub const SENDER_STALL_MS: u32 = 250;
+
+    /// Failover role of this board
+    #[cfg(target_os = "none")]
+    pub const FAILOVER_ROLE: crate::failover::FailoverRole =
+        crate::failover::FailoverRole::Disabled;
+
+    /// Failover group pairing a primary with its standby
+    pub const FAILOVER_GROUP_ID: u8 = 0;
+
+    /// Multicast group carrying failover heartbeats
+    pub const FAILOVER_MULTICAST_ADDR: [u8; 4] = [239, 255, 90, 42];
+
+    /// UDP port for failover heartbeats
+    pub const FAILOVER_PORT: u16 = 23043;
+
+    /// Primary heartbeat interval in milliseconds
+    pub const FAILOVER_HEARTBEAT_INTERVAL_MS: u32 = 500;
+
+    /// Heartbeat silence in milliseconds after which the standby takes over
+    pub const FAILOVER_TAKEOVER_TIMEOUT_MS: u32 = 2000;
+
+    /// How long the factory reset button (`board_profile::PROFILE.button_pin`)
+    /// must be held
+    pub const FACTORY_RESET_HOLD_MS: u32 = 5000;
+
+    /// Hook actions run once the system has booted
+    pub const HOOKS_ON_BOOT: [Option<crate::hooks::HookAction>; crate::hooks::MAX_HOOK_ACTIONS] =
+        [None; crate::hooks::MAX_HOOK_ACTIONS];
+
+    /// Hook actions run when LED data starts arriving
+    pub const HOOKS_ON_DATA_START: [Option<crate::hooks::HookAction>;
+        crate::hooks::MAX_HOOK_ACTIONS] = [None; crate::hooks::MAX_HOOK_ACTIONS];
+
+    /// Hook actions run when LED data stops arriving
+    pub const HOOKS_ON_DATA_STOP: [Option<crate::hooks::HookAction>;
+        crate::hooks::MAX_HOOK_ACTIONS] = [None; crate::hooks::MAX_HOOK_ACTIONS];
+
+    /// Configure an IPv6 link-local address (derived from the MAC) alongside DHCPv4
+    pub const IPV6_ENABLED: bool = true;
+
+    /// Time in milliseconds to wait for a DHCPv4 lease before continuing on IPv6 only
+    pub const DHCP_IPV6_FALLBACK_MS: u32 = 15000;
+
     /// WiFi configuration
     /// Read from environment variables at compile time
     pub const WIFI_SSID: &str = env!("WIFI_SSID");
@@ -43,8 +463,170 @@ pub mod config {
     /// WiFi connection timeout in milliseconds
     pub const WIFI_CONNECT_TIMEOUT_MS: u32 = 10000;
 
-    /// WiFi reconnection interval in milliseconds
+    /// Longest time in the WiFi connecting state before it counts as a WiFi
+    /// error (0 disables; connection rounds are already paced by the backoff)
+    pub const WIFI_CONNECTING_STATE_TIMEOUT_MS: u32 = 0;
+
+    /// Longest wait for an address before the DHCP state times out (0 disables)
+    pub const DHCP_STATE_TIMEOUT_MS: u32 = 30_000;
+
+    /// Longest time to bring up the network services before it counts as a
+    /// service error (0 disables)
+    pub const SERVICE_START_STATE_TIMEOUT_MS: u32 = 10_000;
+
+    /// Longest time a firmware update may take before it counts as failed and
+    /// LED output resumes (0 disables)
+    pub const OTA_STATE_TIMEOUT_MS: u32 = 300_000;
+
+    /// Authentication method of the WiFi network: `auto` detects it from a scan,
+    /// falling back to WPA2 (open without a password); or one of `open`, `wpa2`,
+    /// `wpa/wpa2`, `wpa3`, `wpa2/wpa3`
+    pub const WIFI_AUTH_METHOD: &str = "auto";
+
+    /// The WiFi network is hidden (doesn't broadcast its SSID): find it with a
+    /// probe naming it and join that access point by BSSID and channel
+    pub const WIFI_HIDDEN_SSID: bool = false;
+
+    /// WiFi modem sleep: `off` (lowest latency), `min` (wake every DTIM) or `max`
+    /// (wake on the listen interval, most jitter)
+    pub const WIFI_POWER_SAVE: &str = "off";
+
+    /// Only join this access point of the network (None lets the board pick and roam)
+    pub const WIFI_BSSID: Option<[u8; 6]> = None;
+
+    /// While the signal is weak, look for a stronger access point of the same
+    /// network (mesh nodes) at most this often; 0 disables roaming
+    pub const ROAM_INTERVAL_MS: u32 = 60_000;
+
+    /// Signal gain (dB) another access point must offer before roaming to it
+    pub const ROAM_MIN_IMPROVEMENT_DB: u8 = 8;
+
+    /// Wait after the first failed connection round, doubled after each further
+    /// failure (with ±20% jitter)
     pub const WIFI_RECONNECT_INTERVAL_MS: u32 = 5000;
+
+    /// Longest wait between connection rounds
+    pub const WIFI_RECONNECT_MAX_INTERVAL_MS: u32 = 300_000;
+
+    /// Window over which the LED pipeline FPS and stage timings are measured
+    pub const PIPELINE_STATS_WINDOW_MS: u64 = 1000;
+
+    /// Interval of the heap usage line on the console (0 disables)
+    pub const HEAP_REPORT_INTERVAL_MS: u32 = 60_000;
+
+    /// Interval between signal strength samples while connected
+    pub const RSSI_SAMPLE_INTERVAL_MS: u32 = 5000;
+
+    /// Signal strength (dBm) below which the network counts as degraded
+    pub const RSSI_DEGRADED_DBM: i8 = -75;
+
+    /// Signal strength (dBm) a degraded network must climb back to
+    pub const RSSI_RECOVERED_DBM: i8 = -70;
+
+    /// Interval between chip temperature samples
+    pub const TEMPERATURE_SAMPLE_INTERVAL_MS: u32 = 10_000;
+
+    /// Chip temperature (°C) at which the overheating flag is raised; the die
+    /// runs 10-20 °C above the surrounding air
+    pub const TEMPERATURE_WARNING_C: i8 = 80;
+
+    /// Chip temperature (°C) an overheating chip must cool back to
+    pub const TEMPERATURE_RECOVERED_C: i8 = 75;
+
+    /// Consecutive failed connection attempts before the setup access point
+    /// opens (0 opens it only when no credentials are configured)
+    pub const PROVISIONING_AFTER_FAILURES: u32 = 5;
+
+    /// With credentials configured, an idle setup access point gives the
+    /// network another try after this long (milliseconds)
+    pub const PROVISIONING_RETRY_MS: u32 = 300_000;
+
+    /// Listen for ESP-Touch (SmartConfig) before opening the setup access point
+    pub const ESPTOUCH_ENABLED: bool = true;
+
+    /// How long to listen for ESP-Touch each time provisioning starts
+    pub const ESPTOUCH_WINDOW_MS: u32 = 60_000;
+
+    /// Also offer provisioning over BLE (GATT) while the setup access point is up
+    pub const BLE_PROVISIONING_ENABLED: bool = true;
+
+    /// WPA2 password of the configuration access point kept up alongside the
+    /// station connection, read from the `CONFIG_AP_PASSWORD` build variable
+    /// (8-63 characters; empty disables the access point)
+    pub const CONFIG_AP_PASSWORD: &str = env!("CONFIG_AP_PASSWORD");
+
+    /// Leave the configuration access point's SSID out of its beacons
+    pub const CONFIG_AP_HIDDEN: bool = true;
+
+    /// Provisioned WiFi networks kept in flash; a newly provisioned one takes the
+    /// highest priority and the lowest priority one is dropped when full
+    pub const MAX_STORED_NETWORKS: usize = 4;
+
+    /// Flash offset of the provisioned WiFi networks (the default `nvs` partition)
+    pub const CREDENTIALS_FLASH_OFFSET: u32 = 0x9000;
+
+    /// Flash offset of the crash log (next sector of the `nvs` partition)
+    pub const CRASH_LOG_FLASH_OFFSET: u32 = 0xA000;
+
+    /// Flash offset of the persistent configuration store (third `nvs` sector)
+    pub const CONFIG_STORE_FLASH_OFFSET: u32 = 0xB000;
+
+    /// Flash offset of the encrypted transport's replay marks (fourth `nvs` sector)
+    pub const REPLAY_MARKS_FLASH_OFFSET: u32 = 0xC000;
+
+    /// Quiet time after a runtime brightness change before it is written to flash
+    pub const CONFIG_SAVE_DELAY_MS: u32 = 2000;
+
+    /// Time between a panic and the reset that follows it, long enough to
+    /// read the message on the console
+    pub const PANIC_REBOOT_DELAY_MS: u32 = 3000;
+
+    /// Blink the indicator LED (if the profile has one) in three quick flashes
+    /// and a pause until the reset after a panic
+    pub const PANIC_LED_CODE: bool = true;
+
+    /// Consecutive boots that reset before the system came up, after which the
+    /// board starts in safe mode (0 disables safe mode)
+    pub const SAFE_MODE_BOOT_FAILURES: u8 = 5;
+
+    /// A boot only counts as successful if the system comes up within this long
+    pub const SAFE_MODE_BOOT_WINDOW_MS: u32 = 120_000;
+
+    /// Packets per second accepted from each sender (0 disables rate limiting)
+    pub const RATE_LIMIT_PACKETS_PER_SEC: u32 = 1000;
+
+    /// Packets a sender may burst above its rate before packets are dropped
+    pub const RATE_LIMIT_BURST: u32 = 100;
+
+    /// Time without a 0x01 keepalive that counts as one missed keepalive
+    /// (default for the stored setting, like the two below)
+    pub const KEEPALIVE_TIMEOUT_MS: u32 = 30000;
+
+    /// Consecutive missed keepalives tolerated before the timeout action fires
+    pub const KEEPALIVE_MAX_MISSED: u8 = 0;
+
+    /// Blank the strip (instead of only changing state) on keepalive timeout
+    pub const KEEPALIVE_BLANK_ON_TIMEOUT: bool = false;
+
+    /// Heap allocation the boot self-test must be able to make
+    pub const SELF_TEST_HEAP_PROBE_BYTES: usize = 16 * 1024;
+
+    /// Briefly light each color channel of the strip at boot, so a dead strip
+    /// or wrong wiring shows before the network comes up
+    pub const SELF_TEST_LED_SWEEP: bool = false;
+
+    /// Reset the board through the hardware watchdog when a core task hangs
+    pub const WATCHDOG_ENABLED: bool = true;
+
+    /// Hardware watchdog timeout; the supervisor feeds it every second
+    pub const WATCHDOG_TIMEOUT_MS: u32 = 5000;
+
+    /// Longest gap between LED task or UDP server loop iterations before a reset
+    pub const WATCHDOG_TASK_STALL_MS: u32 = 10_000;
+
+    /// Longest gap between state machine loop iterations before a reset; long
+    /// enough for an ESP-Touch window or a connection round over every network
+    pub const WATCHDOG_STATE_MACHINE_STALL_MS: u32 = 120_000;
 }
 
 /// Error types for the atmosphere light board
@@ -62,4 +644,12 @@ pub enum BoardError {
     SystemError,
     /// mDNS service error
     MdnsError,
+    /// MQTT broker connection error
+    MqttError,
+    /// WebSocket connection error
+    WebSocketError,
+    /// Flash storage error
+    StorageError,
+    /// Firmware update error
+    OtaError,
 }
@@ -0,0 +1,151 @@
+//! Remote log sink
+//!
+//! Boards behind a TV are rarely within reach of a serial cable. With
+//! `config::LOG_SINK` set, every console line is also sent as a syslog
+//! message (RFC 5424, facility user, one line per UDP datagram) to a
+//! collector on the network, e.g. rsyslog, syslog-ng or simply
+//! `nc -ulk 514`.
+//!
+//! `crate::println!` routes through [`print`]: the line always goes to the
+//! serial console and, while the sink runs, is queued for the sink task.
+//! Queueing never blocks, so logging from the LED path stays cheap; lines
+//! that don't fit the queue are dropped and counted, and lines longer than
+//! `LINE_CAPACITY` are cut off. Once the sink runs, lines printed while the
+//! network is down wait in the queue.
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use embassy_net::{
+    IpAddress, IpEndpoint, Stack,
+    udp::{PacketMetadata, UdpSocket},
+};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use heapless::String;
+
+/// Longest line mirrored
+const LINE_CAPACITY: usize = 160;
+
+/// Lines waiting for the sink task
+const QUEUE_DEPTH: usize = 16;
+
+/// Syslog facility: user-level messages
+const FACILITY_USER: u8 = 1;
+
+/// Syslog severities
+const SEVERITY_ERROR: u8 = 3;
+const SEVERITY_WARNING: u8 = 4;
+const SEVERITY_INFO: u8 = 6;
+
+/// Lines queued for the collector
+static LINES: Channel<CriticalSectionRawMutex, String<LINE_CAPACITY>, QUEUE_DEPTH> = Channel::new();
+
+/// Set once the sink task runs; nothing is queued before
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Lines dropped because the queue was full
+static DROPPED: AtomicU32 = AtomicU32::new(0);
+
+/// Print a console line and mirror it to the collector (used by `println!`)
+#[doc(hidden)]
+pub fn print(args: fmt::Arguments<'_>) {
+    esp_println::println!("{}", args);
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut line = String::new();
+    // A line too long for the buffer is cut off
+    let _ = line.write_fmt(args);
+    if LINES.try_send(line).is_err() {
+        critical_section::with(|_| {
+            DROPPED.store(
+                DROPPED.load(Ordering::Relaxed).saturating_add(1),
+                Ordering::Relaxed,
+            )
+        });
+    }
+}
+
+/// Take the number of lines dropped since the last call
+fn take_dropped() -> u32 {
+    critical_section::with(|_| {
+        let dropped = DROPPED.load(Ordering::Relaxed);
+        DROPPED.store(0, Ordering::Relaxed);
+        dropped
+    })
+}
+
+/// Severity of a line, from the markers the firmware logs with
+fn severity(line: &str) -> u8 {
+    if line.contains('❌') {
+        SEVERITY_ERROR
+    } else if line.contains('⚠') {
+        SEVERITY_WARNING
+    } else {
+        SEVERITY_INFO
+    }
+}
+
+/// Encode one syslog message: `<PRI>1 - HOSTNAME board-rs - - - MSG`
+///
+/// The timestamp is left to the collector, as the board has no wall clock.
+fn encode(hostname: &str, line: &str) -> String<{ LINE_CAPACITY + 64 }> {
+    let mut message = String::new();
+    let _ = write!(
+        message,
+        "<{}>1 - {} board-rs - - - {}",
+        FACILITY_USER * 8 + severity(line),
+        hostname,
+        line
+    );
+    message
+}
+
+/// Log sink task: sends queued lines to `collector`
+#[embassy_executor::task]
+pub async fn log_sink_task(stack: &'static Stack<'static>, collector: ([u8; 4], u16)) {
+    let mac = esp_hal::efuse::Efuse::read_base_mac_address();
+    let hostname = crate::wifi::dhcp_hostname(&mac);
+    let [a, b, c, d] = collector.0;
+    let endpoint = IpEndpoint::new(IpAddress::v4(a, b, c, d), collector.1);
+
+    let mut rx_buffer = [0; 16];
+    let mut tx_buffer = [0; 1024];
+    let mut rx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut socket = UdpSocket::new(
+        *stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    // Port 0 picks an ephemeral source port
+    if let Err(e) = socket.bind(0) {
+        crate::println!("[LOG] Bind failed: {:?}", e);
+        return;
+    }
+
+    ENABLED.store(true, Ordering::Relaxed);
+    crate::println!("[LOG] Mirroring log lines to {}", endpoint);
+
+    loop {
+        let line = LINES.receive().await;
+        stack.wait_config_up().await;
+
+        let dropped = take_dropped();
+        if dropped > 0 {
+            let mut note = String::<LINE_CAPACITY>::new();
+            let _ = write!(note, "[LOG] ⚠️ {} lines dropped", dropped);
+            socket
+                .send_to(encode(&hostname, &note).as_bytes(), endpoint)
+                .await
+                .ok();
+        }
+        // Errors aren't logged: the message would come straight back here
+        socket
+            .send_to(encode(&hostname, &line).as_bytes(), endpoint)
+            .await
+            .ok();
+    }
+}
@@ -1,31 +1,36 @@
 #![no_std]
 #![no_main]
 
+use board_rs::println;
 use esp_hal::clock::CpuClock;
-use esp_hal::rmt::{Rmt, TxChannelCreator};
+use esp_hal::rmt::Rmt;
 use esp_hal::rng::Rng;
 use esp_hal::time::Rate;
 use esp_hal::timer::timg::TimerGroup;
-use esp_println::println;
 
 // Standard library imports
 extern crate alloc;
-use alloc::vec::Vec;
 
 // WiFi imports
 use esp_wifi::wifi;
 
 // Embassy-net imports
-use embassy_net::{Config, Stack, StackResources};
+use embassy_net::{
+    Config, ConfigV6, DhcpConfig, Ipv4Cidr, Ipv6Cidr, Stack, StackResources, StaticConfigV4,
+    StaticConfigV6,
+};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
-use embassy_sync::mutex::Mutex;
-use esp_hal_embassy::Executor;
-use static_cell::StaticCell;
+use esp_hal::interrupt::Priority;
+use esp_hal::interrupt::software::SoftwareInterruptControl;
+use esp_hal_embassy::{Executor, InterruptExecutor};
+use static_cell::{ConstStaticCell, StaticCell};
 
 // LED control imports - using direct RMT for RGBW
 
 // Import our library modules
+use board_rs::board_profile::PROFILE;
 use board_rs::config;
+use board_rs::memory;
 use board_rs::state_machine::{Action, SystemEvent, SystemStateMachine};
 
 // Add app descriptor for espflash compatibility
@@ -34,28 +39,17 @@ esp_bootloader_esp_idf::esp_app_desc!();
 // Static cells for embassy components
 static WIFI_INIT_CELL: StaticCell<esp_wifi::EspWifiController<'static>> = StaticCell::new();
 static STACK_CELL: StaticCell<Stack<'static>> = StaticCell::new();
+static AP_STACK_CELL: StaticCell<Stack<'static>> = StaticCell::new();
 static WIFI_MANAGER_CELL: StaticCell<board_rs::wifi::WiFiManager<'static>> = StaticCell::new();
-// Use the concrete channel type
-type ConcreteChannel = esp_hal::rmt::Channel<esp_hal::Blocking, 0>;
-type LedControllerType = board_rs::led_control::UniversalDriverBoard<ConcreteChannel>;
-static LED_CONTROLLER_CELL: StaticCell<
-    embassy_sync::mutex::Mutex<
-        embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
-        LedControllerType,
-    >,
-> = StaticCell::new();
-
-// Static cell for system state machine
-static STATE_MACHINE_CELL: StaticCell<
-    embassy_sync::mutex::Mutex<
-        embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
-        SystemStateMachine,
-    >,
-> = StaticCell::new();
 
 // Static executor for embassy tasks
 static EXECUTOR: StaticCell<Executor> = StaticCell::new();
 
+// High-priority interrupt executor for the LED task, so network work on the
+// thread-mode executor can't delay frame ticks or RMT transmissions. Software
+// interrupt 2 is taken by the esp-wifi scheduler
+static LED_EXECUTOR: StaticCell<InterruptExecutor<1>> = StaticCell::new();
+
 // Static cells for LED communication channels
 static LED_STATUS_SENDER_CELL: StaticCell<
     embassy_sync::channel::Sender<
@@ -65,14 +59,6 @@ static LED_STATUS_SENDER_CELL: StaticCell<
         8,
     >,
 > = StaticCell::new();
-static LED_DATA_SENDER_CELL: StaticCell<
-    embassy_sync::channel::Sender<
-        'static,
-        CriticalSectionRawMutex,
-        board_rs::led_control::LedData,
-        4,
-    >,
-> = StaticCell::new();
 static LED_MODE_SENDER_CELL: StaticCell<
     embassy_sync::channel::Sender<
         'static,
@@ -82,13 +68,52 @@ static LED_MODE_SENDER_CELL: StaticCell<
     >,
 > = StaticCell::new();
 
+/// Report the panic, keep it for the next boot and restart
 #[panic_handler]
-fn panic(_: &core::panic::PanicInfo) -> ! {
-    loop {}
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use esp_hal::delay::Delay;
+    use esp_hal::gpio::{AnyPin, Level, Output, OutputConfig};
+
+    // A panic while handling one goes straight to the reset
+    static PANICKING: AtomicBool = AtomicBool::new(false);
+    if PANICKING.load(Ordering::Relaxed) {
+        esp_hal::system::software_reset();
+    }
+    PANICKING.store(true, Ordering::Relaxed);
+
+    // Straight to the console: the remote log sink can't run anymore
+    esp_println::println!("[PANIC] {}", info);
+    board_rs::crash_log::record_panic(info);
+    esp_println::println!("[PANIC] Restarting in {} ms", config::PANIC_REBOOT_DELAY_MS);
+
+    let delay = Delay::new();
+    match PROFILE.status_led_pin.filter(|_| config::PANIC_LED_CODE) {
+        Some(pin) => {
+            // The LED task may have owned the pin; it runs no more
+            let mut led = Output::new(
+                unsafe { AnyPin::steal(pin) },
+                Level::Low,
+                OutputConfig::default(),
+            );
+            // Three quick flashes and a pause, one second per round
+            for _ in 0..config::PANIC_REBOOT_DELAY_MS.div_ceil(1000) {
+                for _ in 0..3 {
+                    led.set_high();
+                    delay.delay_millis(100);
+                    led.set_low();
+                    delay.delay_millis(100);
+                }
+                delay.delay_millis(400);
+            }
+        }
+        None => delay.delay_millis(config::PANIC_REBOOT_DELAY_MS),
+    }
+    esp_hal::system::software_reset()
 }
 
-// Embassy task to run the network stack
-#[embassy_executor::task]
+// Embassy task to run a network stack (station and setup access point)
+#[embassy_executor::task(pool_size = 2)]
 async fn net_task(
     mut runner: embassy_net::Runner<'static, esp_wifi::wifi::WifiDevice<'static>>,
 ) -> ! {
@@ -99,40 +124,76 @@ async fn net_task(
 #[embassy_executor::task]
 async fn state_machine_task(
     wifi_manager: &'static mut board_rs::wifi::WiFiManager<'static>,
-    _stack: &'static Stack<'static>,
+    stack: &'static Stack<'static>,
+    ap_stack: &'static Stack<'static>,
+    mut sniffer: esp_wifi::wifi::Sniffer,
+    rng: Rng,
     led_status_sender: &'static embassy_sync::channel::Sender<
         'static,
         CriticalSectionRawMutex,
         board_rs::led_control::LedStatus,
         8,
     >,
-    state_machine: &'static Mutex<CriticalSectionRawMutex, SystemStateMachine>,
+    mut state_machine: SystemStateMachine,
 ) -> ! {
+    use board_rs::esptouch;
+    use board_rs::provisioning::{self, WifiCredentials};
     use board_rs::state_machine::SystemState;
-    use embassy_time::{Duration, Timer};
+    use embassy_time::{Duration, Instant, Timer};
+
+    let spawner = embassy_executor::Spawner::for_current_executor().await;
 
     // Initialize state machine
-    {
-        let mut sm = state_machine.lock().await;
-        sm.handle_event(SystemEvent::SystemStarted);
-    }
+    state_machine.handle_event(SystemEvent::SystemStarted);
 
     // Track last logged error to avoid repetition
     let mut last_logged_error: Option<SystemState> = None;
+    // Last state that wasn't an error, for the crash log
+    let mut last_good_state: Option<SystemState> = None;
+    // Whether this boot has been judged for the crash loop count
+    let mut boot_judged = false;
+    // When the current DHCP wait began, for the IPv6-only fallback
+    let mut dhcp_wait_started: Option<Instant> = None;
     // Track last sent LED status to avoid repetition
     let mut last_led_status: Option<board_rs::led_control::LedStatus> = None;
+    // Consecutive failed connection attempts, for the provisioning fallback
+    let mut wifi_failures: u32 = 0;
+    // Spaces out connection rounds while the network is unreachable
+    let mut backoff = board_rs::wifi::ReconnectBackoff::new(rng);
+    // When the setup access point was opened
+    let mut provisioning_since: Option<Instant> = None;
+    // When the signal strength was last sampled
+    let mut last_rssi_sample = Instant::now();
+    // When a stronger access point was last looked for
+    let mut last_roam: Option<Instant> = None;
+    // When heap usage was last logged
+    let mut last_heap_report = Instant::now();
 
     // Main state machine loop
     loop {
+        memory::checkpoint(memory::Subsystem::StateMachine);
+        board_rs::watchdog::heartbeat(board_rs::watchdog::Task::StateMachine);
+
         // Get current state and actions
-        let (_current_state, actions) = {
-            let mut sm = state_machine.lock().await;
-            let actions = sm.update();
-            (sm.get_current_state(), actions)
-        };
+        let actions = state_machine.update();
+        let current_state = state_machine.get_current_state();
+        if !state_machine.is_error_state() {
+            // A recurring error is logged again once the system recovered
+            last_logged_error = None;
+            last_good_state = Some(current_state);
+        }
 
-        // Collect events to send to state machine to reduce lock contention
-        let mut events_to_send = Vec::new();
+        // Coming up in time ends a crash loop; safe mode lasts until a power
+        // cycle or new credentials
+        if !boot_judged && state_machine.is_operational() {
+            boot_judged = true;
+            board_rs::ota::confirm_image();
+            if !board_rs::crash_log::is_safe_mode()
+                && Instant::now().as_millis() < config::SAFE_MODE_BOOT_WINDOW_MS as u64
+            {
+                board_rs::crash_log::clear_failed_boots();
+            }
+        }
 
         // Execute actions based on state machine output
         for action in actions {
@@ -145,33 +206,133 @@ async fn state_machine_task(
                     }
                 }
                 Action::StartWiFiConnection => {
-                    match wifi_manager
-                        .connect(config::WIFI_SSID, config::WIFI_PASSWORD)
-                        .await
-                    {
+                    if !backoff.ready() {
+                        continue;
+                    }
+                    let networks = WifiCredentials::configured();
+                    if board_rs::factory_reset::take_provisioning_request() {
+                        println!("[WIFI] Factory reset - opening the setup portal");
+                        state_machine.handle_event(SystemEvent::ProvisioningRequested);
+                        continue;
+                    }
+                    if networks.is_empty() {
+                        println!("[WIFI] No credentials configured");
+                        state_machine.handle_event(SystemEvent::ProvisioningRequested);
+                        continue;
+                    }
+
+                    // Fall back through the networks in priority order
+                    let mut result = Err(board_rs::BoardError::WiFiError);
+                    for credentials in &networks {
+                        result = wifi_manager
+                            .connect(&credentials.ssid, &credentials.password)
+                            .await;
+                        if result.is_ok() {
+                            break;
+                        }
+                    }
+                    match result {
                         Ok(_) => {
                             println!("[WIFI] Connected");
-                            events_to_send.push(SystemEvent::WiFiConnected);
+                            wifi_failures = 0;
+                            backoff.reset();
+                            state_machine.handle_event(SystemEvent::WiFiConnected);
                         }
                         Err(_) => {
-                            events_to_send.push(SystemEvent::WiFiConnectionFailed);
+                            wifi_failures += 1;
+                            let wait = backoff.record_failure();
+                            println!("[WIFI] Next connection attempt in {}s", wait.as_secs());
+                            if config::PROVISIONING_AFTER_FAILURES > 0
+                                && wifi_failures >= config::PROVISIONING_AFTER_FAILURES
+                            {
+                                wifi_failures = 0;
+                                state_machine.handle_event(SystemEvent::ProvisioningRequested);
+                            } else {
+                                state_machine.handle_event(SystemEvent::WiFiConnectionFailed);
+                            }
+                        }
+                    }
+                }
+                Action::StartProvisioning => {
+                    provisioning::set_active(true);
+
+                    // ESP-Touch needs the radio hopping channels, so it gets a
+                    // window before the access point pins one
+                    if config::ESPTOUCH_ENABLED {
+                        let window = Duration::from_millis(config::ESPTOUCH_WINDOW_MS as u64);
+                        match esptouch::listen(wifi_manager, &mut sniffer, window).await {
+                            Ok(Some(result)) => {
+                                let stored = WifiCredentials::new(&result.ssid, &result.password)
+                                    .ok_or(board_rs::BoardError::ProtocolError)
+                                    .and_then(|credentials| credentials.store());
+                                match stored {
+                                    Ok(()) => {
+                                        spawner
+                                            .spawn(esptouch::esptouch_ack_task(
+                                                stack,
+                                                result.phone,
+                                                result.total_len,
+                                            ))
+                                            .ok();
+                                        provisioning::set_active(false);
+                                        backoff.reset();
+                                        state_machine.handle_event(SystemEvent::RecoveryRequested);
+                                        continue;
+                                    }
+                                    Err(e) => {
+                                        println!("[TOUCH] Failed to save credentials: {:?}", e)
+                                    }
+                                }
+                            }
+                            Ok(None) => println!("[TOUCH] No ESP-Touch sender found"),
+                            Err(e) => println!("[TOUCH] Listening failed: {:?}", e),
                         }
                     }
+
+                    // Scanning needs station mode, so it happens before the AP opens
+                    match wifi_manager.scan().await {
+                        Ok(results) => provisioning::set_scan_results(results),
+                        Err(e) => println!("[PROV] Network scan failed: {:?}", e),
+                    }
+
+                    match wifi_manager
+                        .start_access_point(&provisioning::ap_ssid())
+                        .await
+                    {
+                        Ok(()) => {
+                            provisioning_since = Some(Instant::now());
+                            // Spawned on first use only; later spawns find them running
+                            spawner.spawn(provisioning::dhcp_server_task(ap_stack)).ok();
+                            spawner.spawn(provisioning::captive_dns_task(ap_stack)).ok();
+                            spawner.spawn(provisioning::portal_http_task(ap_stack)).ok();
+                        }
+                        Err(e) => println!("[PROV] Failed to start access point: {:?}", e),
+                    }
                 }
                 Action::StartDHCPRequest => {
+                    let dhcp_started = *dhcp_wait_started.get_or_insert_with(Instant::now);
                     if let Some(ip) = wifi_manager.get_ip_address() {
                         println!("[DHCP] IP: {}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3]);
-                        events_to_send.push(SystemEvent::DHCPSuccess);
+                        dhcp_wait_started = None;
+                        state_machine.handle_event(SystemEvent::DHCPSuccess);
+                    } else if let Some(ipv6) = wifi_manager.get_ipv6_address().filter(|_| {
+                        dhcp_started.elapsed()
+                            > Duration::from_millis(config::DHCP_IPV6_FALLBACK_MS as u64)
+                    }) {
+                        // No DHCPv4 lease - carry on IPv6 only
+                        println!("[DHCP] No IPv4 lease - continuing on IPv6 ({})", ipv6);
+                        dhcp_wait_started = None;
+                        state_machine.handle_event(SystemEvent::DHCPSuccess);
                     } else {
                         // Continue waiting for DHCP
                         Timer::after(Duration::from_millis(1000)).await;
                     }
                 }
                 Action::StartNetworkServices => {
-                    events_to_send.push(SystemEvent::UDPServerStarted);
+                    state_machine.handle_event(SystemEvent::UDPServerStarted);
                 }
                 Action::StartUDPServer => {
-                    events_to_send.push(SystemEvent::UDPServerStarted);
+                    state_machine.handle_event(SystemEvent::UDPServerStarted);
                 }
                 Action::StartMDNSService => {
                     // mDNS service is handled by the dedicated mdns_server_task
@@ -179,20 +340,39 @@ async fn state_machine_task(
                     println!("[MDNS] Service start requested - handled by mdns_server_task");
                 }
                 Action::MonitorConnection => {
-                    // Monitor WiFi connection without triggering state machine events
-                    // to avoid deadlock. Events will be handled in the next loop iteration.
-                    let _ = wifi_manager.monitor_connection();
+                    // Backstop for the event-driven link monitoring below
+                    if let Some(event) = wifi_manager.monitor_connection() {
+                        state_machine.handle_event(event);
+                    }
                 }
+                Action::RunSelfTest => match board_rs::self_test::run().await {
+                    Ok(()) => {
+                        state_machine.handle_event(SystemEvent::SelfTestPassed);
+                    }
+                    Err(failure) => {
+                        println!("[SELFTEST] Self-test failed: {:?}", failure);
+                        state_machine.handle_event(SystemEvent::SelfTestFailed);
+                    }
+                },
                 Action::SystemRecover => {
                     println!("[STATE] Initiating system recovery...");
-                    events_to_send.push(SystemEvent::RecoveryRequested);
+                    state_machine.handle_event(SystemEvent::RecoveryRequested);
                 }
-                Action::LogError(error_state) => {
-                    // Only log if this is a new error state
-                    if last_logged_error != Some(error_state) {
-                        println!("[STATE] Error logged: {:?}", error_state);
-                        last_logged_error = Some(error_state);
-                    }
+                Action::Reboot => {
+                    println!("[STATE] Rebooting...");
+                    // Let the acknowledgement leave before the reset
+                    Timer::after(Duration::from_millis(500)).await;
+                    esp_hal::system::software_reset();
+                }
+                // Only log if this is a new error state
+                Action::LogError(error_state) if last_logged_error != Some(error_state) => {
+                    println!("[STATE] Error logged: {:?}", error_state);
+                    last_logged_error = Some(error_state);
+                    board_rs::crash_log::record_error(
+                        error_state,
+                        last_good_state,
+                        state_machine.get_retry_count(),
+                    );
                 }
                 _ => {
                     // Handle other actions as needed
@@ -200,19 +380,85 @@ async fn state_machine_task(
             }
         }
 
-        // Send all collected events in a single lock acquisition
-        if !events_to_send.is_empty() {
-            let mut sm = state_machine.lock().await;
-            for event in events_to_send {
-                sm.handle_event(event);
+        // With credentials to retry, give the network another chance once the
+        // portal has sat unused for a while
+        if current_state == SystemState::Provisioning {
+            let retry_after = Duration::from_millis(config::PROVISIONING_RETRY_MS as u64);
+            let idle = provisioning::portal_idle_time().is_none_or(|idle| idle >= retry_after);
+            if provisioning_since.is_some_and(|since| since.elapsed() >= retry_after)
+                && idle
+                && !WifiCredentials::configured().is_empty()
+            {
+                println!("[PROV] Setup portal idle - retrying the network");
+                provisioning_since = None;
+                provisioning::set_active(false);
+                backoff.reset();
+                wifi_manager.stop_access_point().await.ok();
+                state_machine.handle_event(SystemEvent::RecoveryRequested);
+            }
+        }
+
+        // Watch the signal strength for weak-signal diagnostics
+        if last_rssi_sample.elapsed()
+            >= Duration::from_millis(config::RSSI_SAMPLE_INTERVAL_MS as u64)
+        {
+            last_rssi_sample = Instant::now();
+            if let Some(event) = wifi_manager.monitor_signal() {
+                state_machine.handle_event(event);
+            }
+
+            // On a weak signal, move to a stronger node of the same network
+            let roam_interval = Duration::from_millis(config::ROAM_INTERVAL_MS as u64);
+            if config::ROAM_INTERVAL_MS > 0
+                && wifi_manager.is_signal_degraded()
+                && last_roam.is_none_or(|at| at.elapsed() >= roam_interval)
+            {
+                last_roam = Some(Instant::now());
+                match wifi_manager.roam().await {
+                    Ok(_) => {}
+                    // A failed scan leaves the association in place
+                    Err(_) if wifi_manager.is_connected() => {}
+                    Err(e) => {
+                        println!("[WIFI] Roaming failed: {:?}", e);
+                        state_machine.handle_event(SystemEvent::WiFiDisconnected);
+                    }
+                }
             }
         }
 
+        if config::HEAP_REPORT_INTERVAL_MS > 0
+            && last_heap_report.elapsed()
+                >= Duration::from_millis(config::HEAP_REPORT_INTERVAL_MS as u64)
+        {
+            last_heap_report = Instant::now();
+            let usage = memory::stats();
+            println!(
+                "[MEM] Heap {} used, {} free, largest block {}, peak {}, {} allocation failures",
+                usage.heap_used,
+                usage.heap_free,
+                usage.heap_largest_block,
+                usage.heap_peak,
+                usage.alloc_failures
+            );
+        }
+
         // LED display is now handled by the dedicated LED task at 30fps
         // No need to update LED display here anymore
 
-        // Increase delay to reduce CPU usage and lock contention
-        Timer::after(Duration::from_millis(200)).await;
+        // Pace the loop to reduce CPU usage, waking early when the WiFi link drops
+        if let Ok(event) = embassy_time::with_timeout(
+            Duration::from_millis(200),
+            wifi_manager.wait_for_link_change(),
+        )
+        .await
+        {
+            state_machine.handle_event(event);
+        }
+
+        // Events queued by the other tasks meanwhile
+        while let Some(event) = board_rs::state_machine::try_receive_event() {
+            state_machine.handle_event(event);
+        }
     }
 }
 
@@ -220,28 +466,45 @@ async fn state_machine_task(
 #[embassy_executor::task]
 async fn udp_server_task(
     stack: &'static Stack<'static>,
-    led_data_sender: &'static embassy_sync::channel::Sender<
+    led_data_sender: &'static board_rs::led_control::LedDataSignal,
+    led_mode_sender: &'static embassy_sync::channel::Sender<
         'static,
         CriticalSectionRawMutex,
-        board_rs::led_control::LedData,
-        4,
+        board_rs::led_control::LedMode,
+        2,
     >,
-    state_machine: &'static Mutex<CriticalSectionRawMutex, SystemStateMachine>,
 ) {
-    use board_rs::udp_server::UdpServer;
+    use board_rs::udp_server::{KeepaliveConfig, UdpBuffers, UdpServer};
+
+    // Socket buffers stay off the task stack
+    static UDP_BUFFERS: ConstStaticCell<UdpBuffers> = ConstStaticCell::new(UdpBuffers::new());
 
     // Create UDP server
     let mut udp_server = UdpServer::new();
     udp_server.set_stack(stack);
+    udp_server.set_buffers(UDP_BUFFERS.take());
+    udp_server.set_keepalive_config(KeepaliveConfig::stored());
+    udp_server.set_multicast_group(
+        config::LED_MULTICAST_GROUP.map(|[a, b, c, d]| embassy_net::IpAddress::v4(a, b, c, d)),
+    );
+    udp_server.set_chain_next_hop(config::CHAIN_NEXT_HOP.map(|([a, b, c, d], port)| {
+        embassy_net::IpEndpoint::new(embassy_net::IpAddress::v4(a, b, c, d), port)
+    }));
+    udp_server.set_device_info(board_rs::device_info::DeviceInfo::read());
+    if let Some(key) = board_rs::crypto::parse_key(config::ENCRYPTION_KEY_HEX) {
+        udp_server.set_encryption_key(&key);
+        println!("[UDP] Encrypted transport enabled");
+    }
 
     // Bind to the configured port
-    match udp_server.bind(config::UDP_PORT) {
+    let port = board_rs::config_store::udp_port();
+    match udp_server.bind(port) {
         Ok(_) => {
-            println!("[UDP] Listening on port {}", config::UDP_PORT);
+            println!("[UDP] Listening on port {}", port);
 
             // Start listening for packets
             match udp_server
-                .start_listening(led_data_sender, state_machine)
+                .start_listening(led_data_sender, led_mode_sender)
                 .await
             {
                 Ok(_) => {
@@ -259,258 +522,262 @@ async fn udp_server_task(
 }
 
 /// mDNS server background task
+///
+/// Restarts the responder with a doubling delay whenever it can't start, and
+/// reports the failure to the state machine so it shows on the status LEDs.
 #[embassy_executor::task]
 async fn mdns_server_task(stack: &'static Stack<'static>) {
-    use embassy_net::udp::UdpSocket;
-    use embassy_net::{IpAddress, IpEndpoint};
+    use board_rs::state_machine::send_event;
     use embassy_time::{Duration, Timer};
 
     // Wait for network to be ready
     stack.wait_config_up().await;
     Timer::after(Duration::from_secs(2)).await;
 
-    // Get our IP address
-    let config = stack.config_v4();
-    if let Some(config) = config {
-        let our_ip = config.address.address();
+    // A name pushed during provisioning overrides the default instance name
+    if let Some(name) = board_rs::provisioning::load_device_name() {
+        board_rs::mdns::set_instance_name(&name).ok();
+    }
 
-        // Join mDNS multicast group (224.0.0.251)
-        let mdns_multicast_addr = IpAddress::v4(224, 0, 0, 251);
-        match stack.join_multicast_group(mdns_multicast_addr) {
-            Ok(_) => println!("[MDNS] Joined multicast group 224.0.0.251"),
-            Err(e) => {
-                println!("[MDNS] Failed to join multicast group: {:?}", e);
-                return;
-            }
-        }
+    let max_interval = Duration::from_millis(config::MDNS_RETRY_MAX_INTERVAL_MS as u64);
+    let mut retry_interval = Duration::from_millis(config::MDNS_RETRY_INTERVAL_MS as u64);
+    loop {
+        mdns_responder(stack).await;
 
-        // Create UDP socket for mDNS
-        let mut rx_buffer = [0; 1500];
-        let mut tx_buffer = [0; 1500];
-        let mut rx_meta = [embassy_net::udp::PacketMetadata::EMPTY; 8];
-        let mut tx_meta = [embassy_net::udp::PacketMetadata::EMPTY; 8];
-        let mut socket = UdpSocket::new(
-            *stack,
-            &mut rx_meta,
-            &mut rx_buffer,
-            &mut tx_meta,
-            &mut tx_buffer,
+        send_event(SystemEvent::MdnsFailed);
+        println!(
+            "[MDNS] Responder failed - retrying in {}s",
+            retry_interval.as_secs()
         );
+        Timer::after(retry_interval).await;
+        retry_interval = (retry_interval * 2).min(max_interval);
+        send_event(SystemEvent::MdnsRetrying);
+    }
+}
 
-        // Bind to mDNS port (5353)
-        match socket.bind(5353) {
-            Ok(_) => {
-                println!("[MDNS] Bound to port 5353");
+/// Run the mDNS responder; only returns if it couldn't start
+async fn mdns_responder(stack: &'static Stack<'static>) {
+    use board_rs::mdns;
+    use embassy_net::udp::UdpSocket;
+    use embassy_net::{IpAddress, IpEndpoint, Ipv6Address};
+    use embassy_time::Duration;
+
+    /// Join the mDNS multicast group of each address family in use
+    /// (224.0.0.251 / ff02::fb) that isn't joined yet
+    fn join_groups(
+        stack: &Stack<'_>,
+        ipv4: bool,
+        ipv6: bool,
+        groups: &mut heapless::Vec<IpEndpoint, 2>,
+    ) {
+        let wanted = [
+            (ipv4, IpAddress::v4(224, 0, 0, 251)),
+            (
+                ipv6,
+                IpAddress::Ipv6(Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb)),
+            ),
+        ];
+        for (in_use, group) in wanted {
+            if !in_use || groups.iter().any(|joined| joined.addr == group) {
+                continue;
+            }
+            match stack.join_multicast_group(group) {
+                Ok(_) => {
+                    println!("[MDNS] Joined multicast group {}", group);
+                    let _ = groups.push(IpEndpoint::new(group, 5353));
+                }
+                Err(e) => println!("[MDNS] Failed to join multicast group {}: {:?}", group, e),
+            }
+        }
+    }
 
-                // Create mDNS response packet
-                let response = create_mdns_response(our_ip, board_rs::config::UDP_PORT);
-                let mdns_multicast = IpEndpoint::new(mdns_multicast_addr, 5353);
+    // Get our IP addresses (either may be missing on v4-only or v6-only networks)
+    let mut our_ipv4 = stack.config_v4().map(|config| config.address.address());
+    let mut our_ipv6 = stack.config_v6().map(|config| config.address.address());
+    if our_ipv4.is_none() && our_ipv6.is_none() {
+        println!("[MDNS] No IP address configured - not advertising");
+        return;
+    }
 
-                // Send initial mDNS announcement
-                match socket.send_to(&response, mdns_multicast).await {
-                    Ok(_) => println!("[MDNS] Initial announcement sent"),
-                    Err(e) => println!("[MDNS] Failed to send initial announcement: {:?}", e),
-                }
+    let mut mdns_groups = heapless::Vec::<IpEndpoint, 2>::new();
+    join_groups(
+        stack,
+        our_ipv4.is_some(),
+        our_ipv6.is_some(),
+        &mut mdns_groups,
+    );
+    if mdns_groups.is_empty() {
+        println!("[MDNS] No multicast group joined - not advertising");
+        return;
+    }
 
-                let mut last_announcement = embassy_time::Instant::now();
+    // Create UDP socket for mDNS
+    let mut rx_buffer = [0; 1500];
+    let mut tx_buffer = [0; 1500];
+    let mut rx_meta = [embassy_net::udp::PacketMetadata::EMPTY; 8];
+    let mut tx_meta = [embassy_net::udp::PacketMetadata::EMPTY; 8];
+    let mut socket = UdpSocket::new(
+        *stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+
+    // Bind to mDNS port (5353)
+    match socket.bind(mdns::MDNS_PORT) {
+        Ok(_) => {
+            println!("[MDNS] Bound to port 5353");
+            board_rs::state_machine::send_event(SystemEvent::MdnsStarted);
+
+            // Create mDNS response packet
+            let mut mdns_config = mdns::current_config();
+            let mut response = mdns::build_response(
+                &mdns_config,
+                our_ipv4,
+                our_ipv6,
+                board_rs::config_store::udp_port(),
+            );
+
+            let mut announcements = mdns::AnnouncementSchedule::default();
+
+            // Start mDNS responder loop
+            loop {
+                memory::checkpoint(memory::Subsystem::Mdns);
+                let mut buffer = [0u8; 1500];
+
+                // Rebuild and re-announce when the instance name or location changes
+                if let Some(new_config) = mdns::take_config_update()
+                    && new_config != mdns_config
+                {
+                    mdns_config = new_config;
+                    response = mdns::build_response(
+                        &mdns_config,
+                        our_ipv4,
+                        our_ipv6,
+                        board_rs::config_store::udp_port(),
+                    );
+                    println!(
+                        "[MDNS] Advertising as '{}'",
+                        mdns_config.instance_name.as_str()
+                    );
+                    announcements.restart();
+                }
 
-                // Start mDNS responder loop
-                loop {
-                    let mut buffer = [0u8; 1500];
+                // Follow address changes (a lease renewed with a new address, another
+                // AP); while the link is down the last addresses stay advertised
+                let ipv4 = stack.config_v4().map(|config| config.address.address());
+                let ipv6 = stack.config_v6().map(|config| config.address.address());
+                if (ipv4.is_some() || ipv6.is_some()) && (ipv4, ipv6) != (our_ipv4, our_ipv6) {
+                    println!(
+                        "[MDNS] Address changed from {:?} to {:?} - re-announcing",
+                        our_ipv4, ipv4
+                    );
+                    our_ipv4 = ipv4;
+                    our_ipv6 = ipv6;
+                    join_groups(stack, ipv4.is_some(), ipv6.is_some(), &mut mdns_groups);
+                    response = mdns::build_response(
+                        &mdns_config,
+                        our_ipv4,
+                        our_ipv6,
+                        board_rs::config_store::udp_port(),
+                    );
+                    announcements.restart();
+                }
 
-                    // Send periodic announcements every 30 seconds
-                    let now = embassy_time::Instant::now();
-                    if now.duration_since(last_announcement) > Duration::from_secs(30) {
-                        // Silent periodic announcement
-                        match socket.send_to(&response, mdns_multicast).await {
-                            Ok(_) => {}  // Silent success
-                            Err(_) => {} // Silent error - mDNS is not critical
+                // Announce on the startup / record-change schedule
+                if announcements.poll() {
+                    for group in mdns_groups.iter() {
+                        match socket.send_to(&response, *group).await {
+                            Ok(_) => println!("[MDNS] Announcement sent to {}", group.addr),
+                            Err(e) => println!("[MDNS] Failed to send announcement: {:?}", e),
                         }
-                        last_announcement = now;
                     }
+                }
 
-                    // Listen for mDNS queries with timeout
-                    match embassy_time::with_timeout(
-                        Duration::from_millis(1000),
-                        socket.recv_from(&mut buffer),
-                    )
-                    .await
-                    {
-                        Ok(Ok((len, endpoint))) => {
-                            println!("[MDNS] Received query from {:?} ({} bytes)", endpoint, len);
-
-                            // Simple mDNS query detection and response
-                            if len > 12 {
-                                // Check if this is a query (QR bit = 0)
-                                if (buffer[2] & 0x80) == 0 {
-                                    println!("[MDNS] Processing mDNS query");
-
-                                    // Create response with matching transaction ID
-                                    let mut query_response = response.clone();
-                                    query_response[0] = buffer[0]; // Copy transaction ID
-                                    query_response[1] = buffer[1];
-
-                                    // Send mDNS response to multicast address
-                                    match socket.send_to(&query_response, mdns_multicast).await {
-                                        Ok(_) => println!("[MDNS] Sent multicast response"),
-                                        Err(e) => println!(
-                                            "[MDNS] Failed to send multicast response: {:?}",
-                                            e
-                                        ),
-                                    }
-
-                                    // Also send unicast response for compatibility
-                                    match socket.send_to(&query_response, endpoint).await {
-                                        Ok(_) => println!(
-                                            "[MDNS] Sent unicast response to {:?}",
-                                            endpoint
-                                        ),
-                                        Err(e) => println!(
-                                            "[MDNS] Failed to send unicast response: {:?}",
-                                            e
-                                        ),
-                                    }
-                                }
+                // Listen for mDNS queries until the next announcement is due
+                let timeout = announcements
+                    .time_until_next()
+                    .map_or(Duration::from_millis(1000), |due| {
+                        due.min(Duration::from_millis(1000))
+                    });
+                match embassy_time::with_timeout(timeout, socket.recv_from(&mut buffer)).await {
+                    Ok(Ok((len, endpoint))) => {
+                        // Stay quiet unless the query asks about one of our names
+                        let Some(answer) = mdns::answer_query(
+                            &mdns_config,
+                            &buffer[..len],
+                            endpoint.endpoint.port,
+                            our_ipv4,
+                            our_ipv6,
+                            board_rs::config_store::udp_port(),
+                        ) else {
+                            continue;
+                        };
+
+                        let destination = match answer.reply_to {
+                            mdns::ReplyTo::Unicast => Some(endpoint.endpoint),
+                            // The multicast group of the query's family
+                            mdns::ReplyTo::Multicast => mdns_groups.iter().copied().find(|group| {
+                                matches!(
+                                    (group.addr, endpoint.endpoint.addr),
+                                    (IpAddress::Ipv4(_), IpAddress::Ipv4(_))
+                                        | (IpAddress::Ipv6(_), IpAddress::Ipv6(_))
+                                )
+                            }),
+                        };
+                        if let Some(destination) = destination {
+                            println!(
+                                "[MDNS] Answering query from {} ({:?})",
+                                endpoint.endpoint, answer.reply_to
+                            );
+                            if let Err(e) = socket.send_to(&answer.packet, destination).await {
+                                println!("[MDNS] Failed to send response: {:?}", e);
                             }
                         }
-                        Ok(Err(_)) => {
-                            // Silent socket error - mDNS is not critical
-                        }
-                        Err(_) => {
-                            // Timeout - normal, continue loop
-                        }
+                    }
+                    Ok(Err(_)) => {
+                        // Silent socket error - mDNS is not critical
+                    }
+                    Err(_) => {
+                        // Timeout - normal, continue loop
                     }
                 }
             }
-            Err(e) => {
-                println!("[MDNS] Failed to bind to port 5353: {:?}", e);
-            }
+        }
+        Err(e) => {
+            println!("[MDNS] Failed to bind to port 5353: {:?}", e);
         }
     }
 }
 
-/// Create a proper mDNS response packet for service discovery
-fn create_mdns_response(ip: embassy_net::Ipv4Address, port: u16) -> [u8; 512] {
-    let mut response = [0u8; 512];
-
-    // DNS Header (12 bytes) - Standard mDNS response format
-    response[0] = 0x00;
-    response[1] = 0x00; // Transaction ID: 0
-    response[2] = 0x84;
-    response[3] = 0x00; // Flags: Response (1), Authoritative (1), no recursion
-    response[4] = 0x00;
-    response[5] = 0x00; // Questions: 0
-    response[6] = 0x00;
-    response[7] = 0x03; // Answer RRs: 3 (PTR, SRV, A)
-    response[8] = 0x00;
-    response[9] = 0x00; // Authority RRs: 0
-    response[10] = 0x00;
-    response[11] = 0x00; // Additional RRs: 0
-
-    let mut offset = 12;
-
-    // Record 1: PTR Record "_ambient_light._udp.local." -> "board-rs._ambient_light._udp.local."
-    let service_type_encoded = b"\x0e_ambient_light\x04_udp\x05local\x00";
-    response[offset..offset + service_type_encoded.len()].copy_from_slice(service_type_encoded);
-    offset += service_type_encoded.len();
-
-    // PTR record header
-    response[offset] = 0x00;
-    response[offset + 1] = 0x0C; // Type: PTR (12)
-    response[offset + 2] = 0x80;
-    response[offset + 3] = 0x01; // Class: IN (1) with cache flush bit
-    response[offset + 4] = 0x00;
-    response[offset + 5] = 0x00; // TTL high
-    response[offset + 6] = 0x00;
-    response[offset + 7] = 0x78; // TTL low (120 seconds)
-    offset += 8;
-
-    // PTR data: "board-rs._ambient_light._udp.local."
-    let instance_full = b"\x08board-rs\x0e_ambient_light\x04_udp\x05local\x00";
-    response[offset] = 0x00;
-    response[offset + 1] = instance_full.len() as u8; // Data length
-    offset += 2;
-
-    let instance_name_offset = offset;
-    response[offset..offset + instance_full.len()].copy_from_slice(instance_full);
-    offset += instance_full.len();
-
-    // Record 2: SRV Record "board-rs._ambient_light._udp.local."
-    // Use compression pointer to instance name
-    response[offset] = 0xC0;
-    response[offset + 1] = instance_name_offset as u8;
-    offset += 2;
-
-    // SRV record header
-    response[offset] = 0x00;
-    response[offset + 1] = 0x21; // Type: SRV (33)
-    response[offset + 2] = 0x80;
-    response[offset + 3] = 0x01; // Class: IN with cache flush bit
-    response[offset + 4] = 0x00;
-    response[offset + 5] = 0x00; // TTL high
-    response[offset + 6] = 0x00;
-    response[offset + 7] = 0x78; // TTL low
-    offset += 8;
-
-    // SRV data
-    let hostname_encoded = b"\x08board-rs\x05local\x00";
-    let srv_data_len = 6 + hostname_encoded.len(); // priority + weight + port + hostname
-    response[offset] = 0x00;
-    response[offset + 1] = srv_data_len as u8;
-    offset += 2;
-
-    response[offset] = 0x00;
-    response[offset + 1] = 0x00; // Priority: 0
-    response[offset + 2] = 0x00;
-    response[offset + 3] = 0x00; // Weight: 0
-    response[offset + 4] = (port >> 8) as u8;
-    response[offset + 5] = (port & 0xFF) as u8; // Port
-    offset += 6;
-
-    // Target hostname "board-rs.local."
-    let hostname_offset = offset;
-    response[offset..offset + hostname_encoded.len()].copy_from_slice(hostname_encoded);
-    offset += hostname_encoded.len();
-
-    // Record 3: A Record "board-rs.local."
-    // Use compression pointer to hostname
-    response[offset] = 0xC0;
-    response[offset + 1] = hostname_offset as u8;
-    offset += 2;
-
-    response[offset] = 0x00;
-    response[offset + 1] = 0x01; // Type: A (1)
-    response[offset + 2] = 0x80;
-    response[offset + 3] = 0x01; // Class: IN with cache flush bit
-    response[offset + 4] = 0x00;
-    response[offset + 5] = 0x00; // TTL high
-    response[offset + 6] = 0x00;
-    response[offset + 7] = 0x78; // TTL low
-    response[offset + 8] = 0x00;
-    response[offset + 9] = 0x04; // Data length: 4
-    offset += 10;
-
-    // IP address
-    let ip_octets = ip.octets();
-    response[offset] = ip_octets[0];
-    response[offset + 1] = ip_octets[1];
-    response[offset + 2] = ip_octets[2];
-    response[offset + 3] = ip_octets[3];
-
-    response
-}
-
 #[esp_hal::main]
 fn main() -> ! {
+    // Paint the unused stack first so the high-water mark covers everything after
+    memory::paint_stack();
+
     let config = esp_hal::Config::default().with_cpu_clock(CpuClock::max());
     let peripherals = esp_hal::init(config);
 
-    // Initialize heap allocator for WiFi (72KB)
-    esp_alloc::heap_allocator!(size: 72 * 1024);
+    // Report why the previous run ended before anything else can fail
+    board_rs::crash_log::init();
+
+    // A new image that failed its first boot goes back to the previous one
+    if board_rs::ota::check_pending_image() {
+        esp_hal::system::software_reset();
+    }
+
+    // Settings changed since the firmware was flashed
+    board_rs::config_store::init();
+    board_rs::led_control::set_global_brightness(board_rs::config_store::get().brightness);
+
+    // Initialize heap allocator for WiFi (sized per chip)
+    esp_alloc::heap_allocator!(size: config::HEAP_SIZE);
 
     // Initialize embassy time system
     let timer_group0 = TimerGroup::new(peripherals.TIMG0);
     esp_hal_embassy::init(timer_group0.timer0);
+    let watchdog = timer_group0.wdt;
 
     // Initialize WiFi driver
     let timer_group1 = TimerGroup::new(peripherals.TIMG1);
@@ -523,14 +790,51 @@ fn main() -> ! {
     // Create WiFi controller and device using esp-wifi 0.14.1 API with embassy-net support
     let (wifi_controller, wifi_interfaces) = wifi::new(wifi_init_ref, peripherals.WIFI).unwrap();
     let wifi_device = wifi_interfaces.sta;
+    let ap_device = wifi_interfaces.ap;
+    let esp_now = wifi_interfaces.esp_now;
+    let sniffer = wifi_interfaces.sniffer;
+    let ble_connector = esp_wifi::ble::controller::BleConnector::new(wifi_init_ref, peripherals.BT);
 
     // Create embassy-net stack with DHCP configuration
-    static STACK_RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
+    // Sockets: DHCP, UDP server, mDNS, failover, Hyperion, WebSocket, MQTT, ESP-Touch ack,
+    // web settings, DNS, OTA download, OTA push, log sink
+    static STACK_RESOURCES: StaticCell<StackResources<13>> = StaticCell::new();
     let stack_resources = STACK_RESOURCES.init(StackResources::new());
 
-    let config = Config::dhcpv4(Default::default());
+    let mut dhcp_config = DhcpConfig::default();
+    let hostname = board_rs::wifi::dhcp_hostname(&esp_hal::efuse::Efuse::read_base_mac_address());
+    println!("[NET] DHCP host name {}", hostname);
+    dhcp_config.hostname = Some(hostname);
+    let mut net_config = Config::dhcpv4(dhcp_config);
+    if config::IPV6_ENABLED {
+        let mac = esp_hal::efuse::Efuse::read_base_mac_address();
+        let link_local = board_rs::wifi::link_local_ipv6(mac);
+        net_config.ipv6 = ConfigV6::Static(StaticConfigV6 {
+            address: Ipv6Cidr::new(link_local, 64),
+            gateway: None,
+            dns_servers: heapless::Vec::new(),
+        });
+        println!("[NET] IPv6 link-local address {}", link_local);
+    }
 
-    let (stack, runner) = embassy_net::new(wifi_device, config, stack_resources, 1234);
+    let (stack, runner) = embassy_net::new(wifi_device, net_config, stack_resources, 1234);
+
+    // Setup access point network, used while provisioning and by the
+    // configuration access point
+    // Sockets: DHCP server, captive DNS, portal HTTP (+1 spare)
+    static AP_STACK_RESOURCES: StaticCell<StackResources<4>> = StaticCell::new();
+    let ap_config = Config::ipv4_static(StaticConfigV4 {
+        address: Ipv4Cidr::new(board_rs::provisioning::AP_ADDRESS, 24),
+        gateway: None,
+        dns_servers: heapless::Vec::new(),
+    });
+    let (ap_stack, ap_runner) = embassy_net::new(
+        ap_device,
+        ap_config,
+        AP_STACK_RESOURCES.init(StackResources::new()),
+        5678,
+    );
+    let ap_stack_ref = AP_STACK_CELL.init(ap_stack);
 
     // Create WiFi manager with controller
     use board_rs::wifi::WiFiManager;
@@ -541,52 +845,87 @@ fn main() -> ! {
     wifi_manager.set_stack(*stack_ref);
 
     // Initialize LED controller with WS2812 hardware driver
-    use esp_hal::gpio::{Level, Output, OutputConfig};
-    let mut test_pin = Output::new(peripherals.GPIO4, Level::Low, OutputConfig::default());
-
-    // Quick GPIO test
-    for _ in 0..3 {
-        test_pin.set_high();
-        for _ in 0..500000 {
-            unsafe {
-                core::ptr::read_volatile(&0u32);
-            }
-        }
-        test_pin.set_low();
-        for _ in 0..500000 {
-            unsafe {
-                core::ptr::read_volatile(&0u32);
+    use esp_hal::gpio::{AnyPin, Level, Output, OutputConfig};
+
+    // Board pins come from the board profile by number. SAFETY: each profile
+    // pin is taken once, here, and no other driver is given it
+    println!("[BOARD] Board profile: {}", PROFILE.name);
+
+    // PSU enable pin for power hooks, on by default
+    let power_pin = PROFILE.power_enable_pin.map(|pin| {
+        Output::new(
+            unsafe { AnyPin::steal(pin) },
+            Level::High,
+            OutputConfig::default(),
+        )
+    });
+
+    // Indicator LED mirroring the status LEDs, driven by the LED task
+    let status_led = PROFILE.status_led_pin.map(|pin| {
+        Output::new(
+            unsafe { AnyPin::steal(pin) },
+            Level::Low,
+            OutputConfig::default(),
+        )
+    });
+
+    // Adalight wired input on UART1
+    let adalight_uart = if config::ADALIGHT_ENABLED {
+        let uart_config =
+            esp_hal::uart::Config::default().with_baudrate(config::ADALIGHT_BAUD_RATE);
+        match esp_hal::uart::Uart::new(peripherals.UART1, uart_config) {
+            Ok(uart) => Some(
+                uart.with_rx(unsafe { AnyPin::steal(PROFILE.adalight_rx_pin) })
+                    .into_async(),
+            ),
+            Err(e) => {
+                println!("[ADALIGHT] UART init failed: {:?}", e);
+                None
             }
         }
+    } else {
+        None
+    };
+
+    // Framed LED data input on the built-in USB serial port (logging keeps the TX side)
+    #[cfg(not(feature = "esp32"))]
+    let usb_serial_rx = if config::USB_SERIAL_INPUT_ENABLED {
+        let (rx, _tx) = esp_hal::usb_serial_jtag::UsbSerialJtag::new(peripherals.USB_DEVICE)
+            .into_async()
+            .split();
+        Some(rx)
+    } else {
+        None
+    };
+
+    // Chip temperature sensor (not on every chip)
+    #[cfg(any(feature = "esp32c3", feature = "esp32c6"))]
+    let temperature_sensor = esp_hal::tsens::TemperatureSensor::new(
+        peripherals.TSENS,
+        esp_hal::tsens::Config::default(),
+    )
+    .ok();
+
+    // The profile's data pin drives the strip; bring-up toggling is available on
+    // demand through the diagnostics command instead of at every boot
+    let led_pin = unsafe { AnyPin::steal(PROFILE.led_data_pin) };
+
+    // Initialize RMT peripheral; with the channel divider set by the LED task
+    // it ticks every 100ns. A strip driver that can't start is reported by the
+    // boot self-test instead of panicking here
+    let frequency = Rate::from_mhz(config::RMT_FREQUENCY_MHZ);
+    let rmt = Rmt::new(peripherals.RMT, frequency)
+        .inspect_err(|e| println!("[LED] RMT init failed: {:?}", e))
+        .ok();
+    if rmt.is_none() {
+        board_rs::self_test::record_failure(board_rs::self_test::Failure::Rmt);
     }
 
-    // Now reconfigure for RMT use
-    let led_pin = test_pin.into_peripheral_output(); // Convert back to peripheral for RMT use
-
-    // Initialize RMT peripheral with 10MHz frequency for better WS2812 timing
-    let frequency = Rate::from_mhz(10);
-    let rmt = Rmt::new(peripherals.RMT, frequency).unwrap();
-
-    // Configure RMT channel for RGBW control
-    let tx_config = esp_hal::rmt::TxChannelConfig::default()
-        .with_clk_divider(1)
-        .with_idle_output_level(esp_hal::gpio::Level::Low)
-        .with_idle_output(false)
-        .with_carrier_modulation(false);
-
-    let rmt_channel = rmt.channel0.configure(led_pin, tx_config).unwrap();
-
-    // Create LED controller with RMT channel
-    use board_rs::led_control::UniversalDriverBoard;
-    let led_controller = UniversalDriverBoard::new(rmt_channel);
-
     // Create static references for embassy tasks
     let _wifi_manager = WIFI_MANAGER_CELL.init(wifi_manager);
-    let led_controller = LED_CONTROLLER_CELL.init(Mutex::new(led_controller));
 
-    // Initialize system state machine
+    // Initialize system state machine, owned by the state machine task
     let state_machine = SystemStateMachine::new();
-    let _state_machine = STATE_MACHINE_CELL.init(Mutex::new(state_machine));
 
     // Initialize LED communication channels
     let (
@@ -598,34 +937,173 @@ fn main() -> ! {
         led_mode_receiver,
     ) = board_rs::led_control::init_led_channels();
 
-    // Store senders in static cells for task access
+    // Store senders in static cells for task access (the data signal is already static)
     let _led_status_sender = LED_STATUS_SENDER_CELL.init(led_status_sender);
-    let _led_data_sender = LED_DATA_SENDER_CELL.init(led_data_sender);
     let _led_mode_sender = LED_MODE_SENDER_CELL.init(led_mode_sender);
 
+    // Start the LED task at 30fps on the high-priority interrupt executor
+    let sw_ints = SoftwareInterruptControl::new(peripherals.SW_INTERRUPT);
+    let led_executor = LED_EXECUTOR.init(InterruptExecutor::new(sw_ints.software_interrupt1));
+    let led_spawner = led_executor.start(Priority::Priority2);
+    // Safe mode leaves the strip dark in case LED output caused the crash loop
+    let safe_mode = board_rs::crash_log::is_safe_mode();
+    let led_started = rmt.filter(|_| !safe_mode).is_some_and(|rmt| {
+        led_spawner
+            .spawn(board_rs::led_control::led_task(
+                rmt,
+                led_pin,
+                led_status_receiver,
+                led_data_receiver,
+                led_mode_receiver,
+                status_led,
+            ))
+            .is_ok()
+    });
+    // Without the LED task there is no strip check for the self-test to wait for
+    if !led_started {
+        board_rs::self_test::finish_strip_check();
+    }
+
     // Initialize embassy executor and run tasks
     let executor = EXECUTOR.init(Executor::new());
     executor.run(|spawner| {
         spawner.spawn(net_task(runner)).ok();
+        spawner.spawn(net_task(ap_runner)).ok();
+        spawner.spawn(board_rs::config_store::persist_task()).ok();
+        if let Some(pin) = PROFILE.button_pin {
+            spawner
+                .spawn(board_rs::factory_reset::factory_reset_task(pin))
+                .ok();
+        }
         spawner
             .spawn(state_machine_task(
                 _wifi_manager,
                 stack_ref,
+                ap_stack_ref,
+                sniffer,
+                rng,
                 _led_status_sender,
-                _state_machine,
+                state_machine,
             ))
             .ok();
         spawner
-            .spawn(udp_server_task(stack_ref, _led_data_sender, _state_machine))
+            .spawn(udp_server_task(
+                stack_ref,
+                led_data_sender,
+                _led_mode_sender,
+            ))
             .ok();
         spawner.spawn(mdns_server_task(stack_ref)).ok();
-        // Start the LED task at 30fps
+        if board_rs::wifi::config_ap_enabled() || safe_mode {
+            // Served for the whole uptime; the configuration (or, in safe mode,
+            // setup) access point comes up with the first station connection
+            spawner
+                .spawn(board_rs::provisioning::dhcp_server_task(ap_stack_ref))
+                .ok();
+            spawner
+                .spawn(board_rs::provisioning::captive_dns_task(ap_stack_ref))
+                .ok();
+            spawner
+                .spawn(board_rs::provisioning::portal_http_task(ap_stack_ref))
+                .ok();
+        }
+        if let Some(port) = config::HTTP_PORT {
+            spawner
+                .spawn(board_rs::web_config::web_config_task(stack_ref, port))
+                .ok();
+        }
+        if config::BLE_PROVISIONING_ENABLED {
+            spawner
+                .spawn(board_rs::ble_provisioning::ble_provisioning_task(
+                    ble_connector,
+                ))
+                .ok();
+        }
         spawner
-            .spawn(board_rs::led_control::led_task(
-                led_controller,
-                led_status_receiver,
-                led_data_receiver,
-                led_mode_receiver,
+            .spawn(board_rs::diagnostics::diagnostics_task())
+            .ok();
+        if let Some(collector) = config::LOG_SINK {
+            spawner
+                .spawn(board_rs::log_sink::log_sink_task(stack_ref, collector))
+                .ok();
+        }
+        #[cfg(any(feature = "esp32c3", feature = "esp32c6"))]
+        if let Some(sensor) = temperature_sensor {
+            spawner
+                .spawn(board_rs::temperature::temperature_task(sensor))
+                .ok();
+        }
+        // Also in safe mode, so a broken build can be replaced over the network
+        spawner.spawn(board_rs::ota::ota_task(stack_ref)).ok();
+        if let Some(port) = config::OTA_PUSH_PORT {
+            if config::ENCRYPTION_KEY_HEX.is_empty() {
+                spawner
+                    .spawn(board_rs::ota_push::ota_push_task(stack_ref, port))
+                    .ok();
+            } else {
+                println!("[OTA] Pushed updates off - LED_PSK is set");
+            }
+        }
+        if config::WATCHDOG_ENABLED {
+            spawner
+                .spawn(board_rs::watchdog::watchdog_task(watchdog))
+                .ok();
+        }
+        if safe_mode {
+            println!("[SAFE] Safe mode - LED inputs and hooks stay off");
+            return;
+        }
+        if let Some(uart) = adalight_uart {
+            spawner
+                .spawn(board_rs::adalight::adalight_task(uart, led_data_sender))
+                .ok();
+        }
+        #[cfg(not(feature = "esp32"))]
+        if let Some(rx) = usb_serial_rx {
+            spawner
+                .spawn(board_rs::usb_serial::usb_serial_task(rx, led_data_sender))
+                .ok();
+        }
+        if let Some(port) = config::HYPERION_UDP_PORT {
+            spawner
+                .spawn(board_rs::hyperion::hyperion_task(
+                    stack_ref,
+                    led_data_sender,
+                    port,
+                ))
+                .ok();
+        }
+        if config::ESPNOW_ENABLED {
+            spawner
+                .spawn(board_rs::espnow::espnow_task(esp_now, led_data_sender))
+                .ok();
+        }
+        if let Some(broker) = config::MQTT_BROKER {
+            spawner
+                .spawn(board_rs::mqtt::mqtt_task(
+                    stack_ref,
+                    _led_mode_sender,
+                    broker,
+                ))
+                .ok();
+        }
+        if let Some(port) = config::WEBSOCKET_PORT {
+            spawner
+                .spawn(board_rs::websocket::websocket_task(
+                    stack_ref,
+                    led_data_sender,
+                    port,
+                ))
+                .ok();
+        }
+        spawner
+            .spawn(board_rs::hooks::hooks_task(_led_mode_sender, power_pin))
+            .ok();
+        board_rs::hooks::trigger(board_rs::hooks::HookEvent::Boot);
+        spawner
+            .spawn(board_rs::failover::failover_task(
+                stack_ref,
+                board_rs::failover::FailoverConfig::default(),
             ))
             .ok();
     });
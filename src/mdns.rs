@@ -0,0 +1,984 @@
+//! mDNS service advertisement module
+//!
+//! Builds the DNS-SD record set (PTR, SRV, TXT, A/AAAA) advertising the
+//! `_ambient_light._udp` service, plus capability subtypes (RFC 6763 §7.1)
+//! such as `_rgbw._sub._ambient_light._udp` that clients can browse for
+//! instead. With the HTTP API enabled, the web UI is co-advertised as
+//! `_http._tcp`. Answers only the questions about our own names (RFC 6762 §6), and
+//! holds the runtime-selectable instance name and
+//! location so multi-board installs can be told apart.
+
+use crate::device_info::DeviceInfo;
+use crate::{BoardError, config};
+use core::cell::RefCell;
+use embassy_net::{Ipv4Address, Ipv6Address};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant};
+use heapless::{String, Vec};
+
+/// Maximum size of an mDNS packet built by this module
+pub const MAX_MDNS_PACKET_SIZE: usize = 512;
+
+/// mDNS packet buffer
+pub type MdnsPacket = Vec<u8, MAX_MDNS_PACKET_SIZE>;
+
+/// DNS record types
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+
+/// Longest DNS name accepted in a question
+const MAX_NAME_LEN: usize = 255;
+
+/// Compression pointers followed per name before giving up
+const MAX_NAME_POINTERS: usize = 8;
+
+/// mDNS UDP port
+pub const MDNS_PORT: u16 = 5353;
+
+/// Class IN
+const CLASS_IN: u16 = 0x0001;
+
+/// Class IN with the cache-flush bit set
+const CLASS_IN_FLUSH: u16 = 0x8001;
+
+/// Unicast-response (QU) bit in a question's class
+const UNICAST_RESPONSE_BIT: u16 = 0x8000;
+
+/// Record TTL in seconds
+const RECORD_TTL: u32 = 120;
+
+/// Record TTL in legacy unicast responses (RFC 6762 §6.7)
+const LEGACY_RECORD_TTL: u32 = 10;
+
+/// DNS-SD service type of the HTTP API
+const HTTP_SERVICE_NAME: &str = "_http._tcp.local.";
+
+/// Web UI path advertised in the `_http._tcp` TXT record
+const HTTP_PATH: &str = "/";
+
+/// Most subtypes advertised at once
+const MAX_SUBTYPES: usize = 3;
+
+/// Unsolicited announcements sent at startup and after a record change
+const ANNOUNCEMENT_COUNT: u8 = 3;
+
+/// Delay before the second announcement; doubles after each one
+const ANNOUNCEMENT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runtime mDNS advertisement settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MdnsConfig {
+    /// Service instance name (`<instance>._ambient_light._udp.local.`)
+    pub instance_name: String<63>,
+    /// Host name (`<hostname>.local.`)
+    pub hostname: String<63>,
+    /// Location advertised as the `room=` TXT key (empty to omit)
+    pub location: String<32>,
+    /// Board identity advertised in the TXT record
+    pub device_info: DeviceInfo,
+}
+
+impl Default for MdnsConfig {
+    fn default() -> Self {
+        let device_info = DeviceInfo::read();
+        let instance_name = default_instance_name(&device_info.mac);
+        let mut hostname = String::new();
+        let _ = hostname.push_str(config::MDNS_HOSTNAME);
+        let mut location = String::new();
+        let _ = location.push_str(config::MDNS_LOCATION);
+
+        Self {
+            instance_name,
+            hostname,
+            location,
+            device_info,
+        }
+    }
+}
+
+/// Instance name used unless overridden: `config::MDNS_INSTANCE_NAME`, or
+/// `ambient-<last 3 MAC bytes>` so boards on one network are distinguishable
+pub fn default_instance_name(mac: &[u8; 6]) -> String<63> {
+    let mut name = String::new();
+    if config::MDNS_INSTANCE_NAME.is_empty() {
+        let _ = core::fmt::write(
+            &mut name,
+            format_args!("ambient-{:02x}{:02x}{:02x}", mac[3], mac[4], mac[5]),
+        );
+    } else {
+        let _ = name.push_str(config::MDNS_INSTANCE_NAME);
+    }
+    name
+}
+
+/// Pending runtime update of the advertisement settings
+static CONFIG_UPDATE: Signal<CriticalSectionRawMutex, MdnsConfig> = Signal::new();
+
+/// Settings most recently passed to `update_config`
+static CURRENT_CONFIG: critical_section::Mutex<RefCell<Option<MdnsConfig>>> =
+    critical_section::Mutex::new(RefCell::new(None));
+
+/// Change the advertised instance name/location at runtime
+///
+/// The responder rebuilds its records and re-announces on the next loop.
+pub fn update_config(config: MdnsConfig) {
+    critical_section::with(|cs| {
+        CURRENT_CONFIG.borrow_ref_mut(cs).replace(config.clone());
+    });
+    CONFIG_UPDATE.signal(config);
+}
+
+/// The advertisement settings currently in effect
+pub fn current_config() -> MdnsConfig {
+    critical_section::with(|cs| CURRENT_CONFIG.borrow_ref(cs).clone()).unwrap_or_default()
+}
+
+/// Override the advertised instance name at runtime (empty restores the default)
+///
+/// Names must be 1-63 bytes without dots.
+pub fn set_instance_name(name: &str) -> Result<(), BoardError> {
+    if name.len() > 63 || name.contains('.') {
+        return Err(BoardError::MdnsError);
+    }
+
+    let mut config = current_config();
+    config.instance_name = if name.is_empty() {
+        default_instance_name(&config.device_info.mac)
+    } else {
+        let mut instance_name = String::new();
+        let _ = instance_name.push_str(name);
+        instance_name
+    };
+    update_config(config);
+    Ok(())
+}
+
+/// Take a pending configuration update, if any
+pub fn take_config_update() -> Option<MdnsConfig> {
+    CONFIG_UPDATE.try_take()
+}
+
+/// Service subtypes reflecting the board's capabilities
+///
+/// - `_rgb` / `_rgbw`: colour channels per LED
+/// - `_encrypted`: a pre-shared key is configured (0x20 envelopes required)
+/// - `_ws`: the WebSocket streaming endpoint is enabled
+pub fn subtypes(config: &MdnsConfig) -> Vec<&'static str, MAX_SUBTYPES> {
+    let mut subtypes = Vec::new();
+    let _ = subtypes.push(if config.device_info.pixel_format.bytes_per_led() == 4 {
+        "_rgbw"
+    } else {
+        "_rgb"
+    });
+    if crate::crypto::parse_key(config::ENCRYPTION_KEY_HEX).is_some() {
+        let _ = subtypes.push("_encrypted");
+    }
+    if config::WEBSOCKET_PORT.is_some() {
+        let _ = subtypes.push("_ws");
+    }
+    subtypes
+}
+
+/// Unsolicited announcement schedule (RFC 6762 §8.3)
+///
+/// Announces `ANNOUNCEMENT_COUNT` times, one second apart with the interval
+/// doubling, then stays quiet: queriers refresh their caches themselves before
+/// the TTL runs out. `restart` repeats the pattern when our records change.
+#[derive(Debug, Clone, Copy)]
+pub struct AnnouncementSchedule {
+    remaining: u8,
+    interval: Duration,
+    next: Instant,
+}
+
+impl Default for AnnouncementSchedule {
+    fn default() -> Self {
+        Self {
+            remaining: ANNOUNCEMENT_COUNT,
+            interval: ANNOUNCEMENT_INTERVAL,
+            next: Instant::now(),
+        }
+    }
+}
+
+impl AnnouncementSchedule {
+    /// Start the announcement pattern over, e.g. after our records changed
+    pub fn restart(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Time until the next announcement is due, if any remain
+    pub fn time_until_next(&self) -> Option<Duration> {
+        (self.remaining > 0).then(|| self.next.saturating_duration_since(Instant::now()))
+    }
+
+    /// Check whether an announcement is due now, advancing the schedule if so
+    pub fn poll(&mut self) -> bool {
+        let now = Instant::now();
+        if self.remaining == 0 || now < self.next {
+            return false;
+        }
+        self.remaining -= 1;
+        self.next = now + self.interval;
+        self.interval *= 2;
+        true
+    }
+}
+
+/// A set of the records we advertise
+///
+/// Bits 5-7 are the subtype PTRs, indexed as in `subtypes`; the `HTTP_`
+/// records advertise the HTTP API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Records(u16);
+
+impl Records {
+    pub const NONE: Self = Self(0);
+    pub const PTR: Self = Self(1 << 0);
+    pub const SRV: Self = Self(1 << 1);
+    pub const TXT: Self = Self(1 << 2);
+    pub const A: Self = Self(1 << 3);
+    pub const AAAA: Self = Self(1 << 4);
+    pub const SUBTYPES: Self = Self(0xE0);
+    pub const HTTP_PTR: Self = Self(1 << 8);
+    pub const HTTP_SRV: Self = Self(1 << 9);
+    pub const HTTP_TXT: Self = Self(1 << 10);
+    pub const ALL: Self = Self(0x7FF);
+
+    /// PTR record of the subtype at `index`
+    pub const fn subtype(index: usize) -> Self {
+        Self(1 << (5 + index))
+    }
+
+    /// Records we can advertise given the configured subtypes and addresses
+    fn available(config: &MdnsConfig, ipv4: bool, ipv6: bool) -> Self {
+        let mut available = Self::PTR | Self::SRV | Self::TXT;
+        for index in 0..subtypes(config).len() {
+            available |= Self::subtype(index);
+        }
+        if config::HTTP_PORT.is_some() {
+            available |= Self::HTTP_PTR | Self::HTTP_SRV | Self::HTTP_TXT;
+        }
+        if ipv4 {
+            available |= Self::A;
+        }
+        if ipv6 {
+            available |= Self::AAAA;
+        }
+        available
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn without(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    pub fn intersect(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    fn count(self) -> u16 {
+        self.0.count_ones() as u16
+    }
+}
+
+impl core::ops::BitOr for Records {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl core::ops::BitOrAssign for Records {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+/// Read a possibly compressed name starting at `pos` into `name` as
+/// dot-separated labels with a trailing dot
+///
+/// Returns the position just past the name in the original message.
+fn read_name(packet: &[u8], mut pos: usize, name: &mut String<MAX_NAME_LEN>) -> Option<usize> {
+    let mut end = None;
+    let mut pointers = 0;
+
+    loop {
+        let len = *packet.get(pos)? as usize;
+        match len {
+            0 => return Some(end.unwrap_or(pos + 1)),
+            0xC0.. => {
+                pointers += 1;
+                if pointers > MAX_NAME_POINTERS {
+                    return None;
+                }
+                let target = (len & 0x3F) << 8 | *packet.get(pos + 1)? as usize;
+                end.get_or_insert(pos + 2);
+                pos = target;
+            }
+            1..=63 => {
+                let label = core::str::from_utf8(packet.get(pos + 1..pos + 1 + len)?).ok()?;
+                name.push_str(label).ok()?;
+                name.push('.').ok()?;
+                pos += 1 + len;
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Check whether `name` is `<label>.<domain>` (case-insensitive)
+fn name_is(name: &str, label: &str, domain: &str) -> bool {
+    let name = name.as_bytes();
+    let split = label.len();
+    name.len() == split + 1 + domain.len()
+        && name[..split].eq_ignore_ascii_case(label.as_bytes())
+        && name[split] == b'.'
+        && name[split + 1..].eq_ignore_ascii_case(domain.as_bytes())
+}
+
+/// Check whether `name` is `<subtype>._sub._ambient_light._udp.local.`
+fn subtype_is(name: &str, subtype: &str) -> bool {
+    let split = subtype.len();
+    name.len() > split + 1
+        && name.as_bytes()[..split].eq_ignore_ascii_case(subtype.as_bytes())
+        && name.as_bytes()[split] == b'.'
+        && name
+            .get(split + 1..)
+            .is_some_and(|rest| name_is(rest, "_sub", config::MDNS_SERVICE_NAME))
+}
+
+/// Records answering one question, or none if it isn't about our names
+fn answers_for(config: &MdnsConfig, name: &str, qtype: u16) -> Records {
+    let wants = |rtype: u16, records: Records| {
+        if qtype == rtype || qtype == TYPE_ANY {
+            records
+        } else {
+            Records::NONE
+        }
+    };
+
+    if name.eq_ignore_ascii_case(config::MDNS_SERVICE_NAME) {
+        wants(TYPE_PTR, Records::PTR)
+    } else if name_is(name, &config.instance_name, config::MDNS_SERVICE_NAME) {
+        wants(TYPE_SRV, Records::SRV) | wants(TYPE_TXT, Records::TXT)
+    } else if name_is(name, &config.hostname, "local.") {
+        wants(TYPE_A, Records::A) | wants(TYPE_AAAA, Records::AAAA)
+    } else if let Some(index) = subtypes(config)
+        .iter()
+        .position(|subtype| subtype_is(name, subtype))
+    {
+        wants(TYPE_PTR, Records::subtype(index))
+    } else if name.eq_ignore_ascii_case(HTTP_SERVICE_NAME) {
+        wants(TYPE_PTR, Records::HTTP_PTR)
+    } else if name_is(name, &config.instance_name, HTTP_SERVICE_NAME) {
+        wants(TYPE_SRV, Records::HTTP_SRV) | wants(TYPE_TXT, Records::HTTP_TXT)
+    } else {
+        Records::NONE
+    }
+}
+
+/// Where a query's answer should be sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyTo {
+    /// The mDNS multicast group of the query's address family
+    Multicast,
+    /// Directly to the querier's address and port
+    Unicast,
+}
+
+/// Response to an mDNS query
+#[derive(Debug, Clone)]
+pub struct Answer {
+    pub packet: MdnsPacket,
+    pub reply_to: ReplyTo,
+}
+
+/// Answer an mDNS query, if it asks about any of our records
+///
+/// Returns `None` for responses, malformed packets, and queries about other
+/// names. Records implied by the answers (SRV/TXT/address for a PTR answer,
+/// addresses for an SRV answer, RFC 6763 §12) go into the additional section.
+///
+/// Queries from a port other than 5353 are legacy unicast queries (RFC 6762
+/// §6.7): they are answered directly, echoing the ID and questions, with short
+/// TTLs and no cache-flush bit. Queries whose matching questions all have the
+/// QU bit set (§5.4) are also answered directly; all others via multicast.
+pub fn answer_query(
+    config: &MdnsConfig,
+    query: &[u8],
+    source_port: u16,
+    ipv4: Option<Ipv4Address>,
+    ipv6: Option<Ipv6Address>,
+    port: u16,
+) -> Option<Answer> {
+    if query.len() < 12 {
+        return None;
+    }
+    // QR = 0 (query) and opcode 0 (standard query) only
+    if query[2] & 0xF8 != 0 {
+        return None;
+    }
+
+    let questions = u16::from_be_bytes([query[4], query[5]]);
+    let mut answers = Records::NONE;
+    let mut unicast_requested = true;
+    let mut pos = 12;
+    for _ in 0..questions {
+        let mut name = String::new();
+        pos = read_name(query, pos, &mut name)?;
+        let qtype = u16::from_be_bytes([*query.get(pos)?, *query.get(pos + 1)?]);
+        let qclass = u16::from_be_bytes([*query.get(pos + 2)?, *query.get(pos + 3)?]);
+        pos += 4;
+
+        let matched = answers_for(config, &name, qtype);
+        if !matched.is_empty() {
+            answers |= matched;
+            unicast_requested &= qclass & UNICAST_RESPONSE_BIT != 0;
+        }
+    }
+
+    // Only advertise addresses we actually have
+    let available = Records::available(config, ipv4.is_some(), ipv6.is_some());
+
+    let legacy = source_port != MDNS_PORT;
+    let source = RecordSource {
+        config,
+        ipv4,
+        ipv6,
+        port,
+        cache_flush: !legacy,
+        ttl: if legacy {
+            LEGACY_RECORD_TTL
+        } else {
+            RECORD_TTL
+        },
+    };
+
+    // Leave out what the querier already has
+    let known_count = u16::from_be_bytes([query[6], query[7]]);
+    let known = known_answers(&source, query, pos, known_count);
+    let answers = answers.intersect(available).without(known);
+    if answers.is_empty() {
+        return None;
+    }
+
+    let browsed = !answers
+        .intersect(Records::PTR | Records::SUBTYPES)
+        .is_empty();
+    let mut additional = Records::NONE;
+    if browsed {
+        additional |= Records::SRV | Records::TXT;
+    }
+    if answers.contains(Records::HTTP_PTR) {
+        additional |= Records::HTTP_SRV | Records::HTTP_TXT;
+    }
+    let targets_host =
+        Records::PTR | Records::SUBTYPES | Records::SRV | Records::HTTP_PTR | Records::HTTP_SRV;
+    if !answers.intersect(targets_host).is_empty() {
+        additional |= Records::A | Records::AAAA;
+    }
+    let additional = additional
+        .intersect(available)
+        .without(answers)
+        .without(known);
+
+    let packet = if legacy {
+        let mut packet = build_records(
+            &source,
+            answers,
+            additional,
+            Some((questions, &query[12..pos])),
+        );
+        // Echo the query ID
+        packet[0] = query[0];
+        packet[1] = query[1];
+        packet
+    } else {
+        build_records(&source, answers, additional, None)
+    };
+
+    Some(Answer {
+        packet,
+        reply_to: if legacy || unicast_requested {
+            ReplyTo::Unicast
+        } else {
+            ReplyTo::Multicast
+        },
+    })
+}
+
+/// Our records listed in a query's known-answer section with at least half
+/// their TTL remaining (RFC 6762 §7.1); these are left out of the response
+fn known_answers(source: &RecordSource, query: &[u8], mut pos: usize, count: u16) -> Records {
+    let config = source.config;
+    let mut known = Records::NONE;
+
+    for _ in 0..count {
+        let mut name = String::new();
+        let Some(next) = read_name(query, pos, &mut name) else {
+            break;
+        };
+        let Some(header) = query.get(next..next + 10) else {
+            break;
+        };
+        let rtype = u16::from_be_bytes([header[0], header[1]]);
+        let ttl = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let rdata_at = next + 10;
+        let rdata_len = u16::from_be_bytes([header[8], header[9]]) as usize;
+        let Some(rdata) = query.get(rdata_at..rdata_at + rdata_len) else {
+            break;
+        };
+        pos = rdata_at + rdata_len;
+
+        if ttl < RECORD_TTL / 2 {
+            continue;
+        }
+
+        // Which of our records this would be, going by owner name and type
+        let owner = answers_for(config, &name, rtype);
+        if owner.is_empty() {
+            continue;
+        }
+        let http = !owner
+            .intersect(Records::HTTP_PTR | Records::HTTP_SRV | Records::HTTP_TXT)
+            .is_empty();
+        let mut target = String::new();
+        let matches = match rtype {
+            // Service, subtype or HTTP PTR pointing at our instance
+            TYPE_PTR => {
+                let service = if http {
+                    HTTP_SERVICE_NAME
+                } else {
+                    config::MDNS_SERVICE_NAME
+                };
+                read_name(query, rdata_at, &mut target).is_some()
+                    && name_is(&target, &config.instance_name, service)
+            }
+            TYPE_SRV => {
+                let port = if http {
+                    config::HTTP_PORT
+                } else {
+                    Some(source.port)
+                };
+                rdata.len() > 6
+                    && Some(u16::from_be_bytes([rdata[4], rdata[5]])) == port
+                    && read_name(query, rdata_at + 6, &mut target).is_some()
+                    && name_is(&target, &config.hostname, "local.")
+            }
+            TYPE_TXT => {
+                let mut txt = DnsWriter::new();
+                if http {
+                    write_http_txt_entries(&mut txt);
+                } else {
+                    write_txt_entries(&mut txt, config);
+                }
+                rdata == txt.buf.as_slice()
+            }
+            TYPE_A => source.ipv4.is_some_and(|ip| rdata == &ip.octets()[..]),
+            TYPE_AAAA => source.ipv6.is_some_and(|ip| rdata == &ip.octets()[..]),
+            _ => false,
+        };
+        if matches {
+            known |= owner;
+        }
+    }
+
+    known
+}
+
+/// Minimal DNS message writer over a fixed-size buffer
+struct DnsWriter {
+    buf: MdnsPacket,
+}
+
+impl DnsWriter {
+    fn new() -> Self {
+        Self { buf: Vec::new() }
+    }
+
+    fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn u8(&mut self, value: u8) {
+        let _ = self.buf.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        let _ = self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        let _ = self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn bytes(&mut self, data: &[u8]) {
+        let _ = self.buf.extend_from_slice(data);
+    }
+
+    /// Write a single length-prefixed label
+    fn label(&mut self, label: &str) {
+        let len = label.len().min(63);
+        self.u8(len as u8);
+        self.bytes(&label.as_bytes()[..len]);
+    }
+
+    /// Write a dot-separated name, terminated with the root label
+    fn name(&mut self, name: &str) {
+        for label in name.split('.').filter(|l| !l.is_empty()) {
+            self.label(label);
+        }
+        self.u8(0);
+    }
+
+    /// Write a compression pointer to an earlier name
+    fn pointer(&mut self, offset: usize) {
+        self.u16(0xC000 | offset as u16);
+    }
+
+    /// Write type, class and TTL of a resource record
+    fn record_header(&mut self, rtype: u16, class: u16, ttl: u32) {
+        self.u16(rtype);
+        self.u16(class);
+        self.u32(ttl);
+    }
+
+    /// Reserve the RDLENGTH field, returning its position
+    fn begin_rdata(&mut self) -> usize {
+        let pos = self.len();
+        self.u16(0);
+        pos
+    }
+
+    /// Patch the RDLENGTH field reserved by `begin_rdata`
+    fn end_rdata(&mut self, pos: usize) {
+        let rdlen = (self.len() - pos - 2) as u16;
+        if let Some(field) = self.buf.get_mut(pos..pos + 2) {
+            field.copy_from_slice(&rdlen.to_be_bytes());
+        }
+    }
+
+    fn finish(self) -> MdnsPacket {
+        self.buf
+    }
+}
+
+/// Build the mDNS response advertising our service
+///
+/// Records: PTR (service and each subtype → instance), SRV (instance → host:port),
+/// TXT (instance metadata), and A / AAAA (host → address) for each
+/// configured address family.
+pub fn build_response(
+    config: &MdnsConfig,
+    ipv4: Option<Ipv4Address>,
+    ipv6: Option<Ipv6Address>,
+    port: u16,
+) -> MdnsPacket {
+    let answers = Records::available(config, ipv4.is_some(), ipv6.is_some());
+    let source = RecordSource {
+        config,
+        ipv4,
+        ipv6,
+        port,
+        cache_flush: true,
+        ttl: RECORD_TTL,
+    };
+    build_records(&source, answers, Records::NONE, None)
+}
+
+/// Everything needed to write our records
+struct RecordSource<'c> {
+    config: &'c MdnsConfig,
+    ipv4: Option<Ipv4Address>,
+    ipv6: Option<Ipv6Address>,
+    port: u16,
+    /// Set the cache-flush bit on the records we own
+    cache_flush: bool,
+    ttl: u32,
+}
+
+impl RecordSource<'_> {
+    /// Class of a record; shared records (the PTRs, which other boards also
+    /// answer) never carry the cache-flush bit (RFC 6762 §10.2)
+    fn class(&self, shared: bool) -> u16 {
+        if self.cache_flush && !shared {
+            CLASS_IN_FLUSH
+        } else {
+            CLASS_IN
+        }
+    }
+}
+
+/// Offsets of names already written, for compression pointers
+#[derive(Default)]
+struct NameOffsets {
+    service: Option<usize>,
+    instance: Option<usize>,
+    hostname: Option<usize>,
+    http_service: Option<usize>,
+    http_instance: Option<usize>,
+}
+
+impl NameOffsets {
+    /// Write `_ambient_light._udp.local.`
+    fn service(&mut self, w: &mut DnsWriter) {
+        match self.service {
+            Some(offset) => w.pointer(offset),
+            None => {
+                self.service = Some(w.len());
+                w.name(config::MDNS_SERVICE_NAME);
+            }
+        }
+    }
+
+    /// Write `<instance>._ambient_light._udp.local.`
+    fn instance(&mut self, w: &mut DnsWriter, config: &MdnsConfig) {
+        match self.instance {
+            Some(offset) => w.pointer(offset),
+            None => {
+                self.instance = Some(w.len());
+                w.label(&config.instance_name);
+                self.service(w);
+            }
+        }
+    }
+
+    /// Write `_http._tcp.local.`
+    fn http_service(&mut self, w: &mut DnsWriter) {
+        match self.http_service {
+            Some(offset) => w.pointer(offset),
+            None => {
+                self.http_service = Some(w.len());
+                w.name(HTTP_SERVICE_NAME);
+            }
+        }
+    }
+
+    /// Write `<instance>._http._tcp.local.`
+    fn http_instance(&mut self, w: &mut DnsWriter, config: &MdnsConfig) {
+        match self.http_instance {
+            Some(offset) => w.pointer(offset),
+            None => {
+                self.http_instance = Some(w.len());
+                w.label(&config.instance_name);
+                self.http_service(w);
+            }
+        }
+    }
+
+    /// Write `<hostname>.local.`
+    fn hostname(&mut self, w: &mut DnsWriter, config: &MdnsConfig) {
+        match self.hostname {
+            Some(offset) => w.pointer(offset),
+            None => {
+                self.hostname = Some(w.len());
+                w.label(&config.hostname);
+                w.name("local");
+            }
+        }
+    }
+}
+
+/// Write one of our records
+fn write_record(
+    w: &mut DnsWriter,
+    names: &mut NameOffsets,
+    record: Records,
+    source: &RecordSource,
+) {
+    let config = source.config;
+    match record {
+        // PTR "_ambient_light._udp.local." -> "<instance>._ambient_light._udp.local."
+        Records::PTR => {
+            names.service(w);
+            w.record_header(TYPE_PTR, source.class(true), source.ttl);
+            let rdata = w.begin_rdata();
+            names.instance(w, config);
+            w.end_rdata(rdata);
+        }
+        // SRV "<instance>._ambient_light._udp.local." -> "<hostname>.local.":port
+        Records::SRV => {
+            names.instance(w, config);
+            w.record_header(TYPE_SRV, source.class(false), source.ttl);
+            let rdata = w.begin_rdata();
+            w.u16(0); // Priority: 0
+            w.u16(0); // Weight: 0
+            w.u16(source.port);
+            names.hostname(w, config);
+            w.end_rdata(rdata);
+        }
+        // TXT "<instance>._ambient_light._udp.local."
+        Records::TXT => {
+            names.instance(w, config);
+            w.record_header(TYPE_TXT, source.class(false), source.ttl);
+            let rdata = w.begin_rdata();
+            write_txt_entries(w, config);
+            w.end_rdata(rdata);
+        }
+        // A "<hostname>.local."
+        Records::A => {
+            if let Some(ip) = source.ipv4 {
+                names.hostname(w, config);
+                w.record_header(TYPE_A, source.class(false), source.ttl);
+                let rdata = w.begin_rdata();
+                w.bytes(&ip.octets());
+                w.end_rdata(rdata);
+            }
+        }
+        // AAAA "<hostname>.local."
+        Records::AAAA => {
+            if let Some(ip) = source.ipv6 {
+                names.hostname(w, config);
+                w.record_header(TYPE_AAAA, source.class(false), source.ttl);
+                let rdata = w.begin_rdata();
+                w.bytes(&ip.octets());
+                w.end_rdata(rdata);
+            }
+        }
+        // PTR "_http._tcp.local." -> "<instance>._http._tcp.local."
+        Records::HTTP_PTR => {
+            names.http_service(w);
+            w.record_header(TYPE_PTR, source.class(true), source.ttl);
+            let rdata = w.begin_rdata();
+            names.http_instance(w, config);
+            w.end_rdata(rdata);
+        }
+        // SRV "<instance>._http._tcp.local." -> "<hostname>.local.":HTTP port
+        Records::HTTP_SRV => {
+            if let Some(port) = config::HTTP_PORT {
+                names.http_instance(w, config);
+                w.record_header(TYPE_SRV, source.class(false), source.ttl);
+                let rdata = w.begin_rdata();
+                w.u16(0); // Priority: 0
+                w.u16(0); // Weight: 0
+                w.u16(port);
+                names.hostname(w, config);
+                w.end_rdata(rdata);
+            }
+        }
+        // TXT "<instance>._http._tcp.local."
+        Records::HTTP_TXT => {
+            names.http_instance(w, config);
+            w.record_header(TYPE_TXT, source.class(false), source.ttl);
+            let rdata = w.begin_rdata();
+            write_http_txt_entries(w);
+            w.end_rdata(rdata);
+        }
+        // PTR "<subtype>._sub._ambient_light._udp.local." -> "<instance>._ambient_light._udp.local."
+        subtype => {
+            let Some(name) = (0..MAX_SUBTYPES)
+                .position(|index| subtype == Records::subtype(index))
+                .and_then(|index| subtypes(config).get(index).copied())
+            else {
+                return;
+            };
+            w.label(name);
+            w.label("_sub");
+            names.service(w);
+            w.record_header(TYPE_PTR, source.class(true), source.ttl);
+            let rdata = w.begin_rdata();
+            names.instance(w, config);
+            w.end_rdata(rdata);
+        }
+    }
+}
+
+/// Build a response with the given answer and additional records
+///
+/// `questions` (count and raw section) is echoed back for legacy unicast
+/// queries; it is copied to the same offset, so compression pointers inside
+/// it stay valid.
+fn build_records(
+    source: &RecordSource,
+    answers: Records,
+    additional: Records,
+    questions: Option<(u16, &[u8])>,
+) -> MdnsPacket {
+    let mut w = DnsWriter::new();
+    let (question_count, question_bytes) = questions.unwrap_or((0, &[]));
+
+    // DNS Header (12 bytes) - Standard mDNS response format
+    w.u16(0x0000); // Transaction ID: 0
+    w.u16(0x8400); // Flags: Response (1), Authoritative (1), no recursion
+    w.u16(question_count); // Questions
+    w.u16(answers.count()); // Answer RRs
+    w.u16(0); // Authority RRs: 0
+    w.u16(additional.count()); // Additional RRs
+    w.bytes(question_bytes);
+
+    let mut names = NameOffsets::default();
+    for section in [answers, additional] {
+        for record in [
+            Records::PTR,
+            Records::subtype(0),
+            Records::subtype(1),
+            Records::subtype(2),
+            Records::SRV,
+            Records::TXT,
+            Records::HTTP_PTR,
+            Records::HTTP_SRV,
+            Records::HTTP_TXT,
+            Records::A,
+            Records::AAAA,
+        ] {
+            if section.contains(record) {
+                write_record(&mut w, &mut names, record, source);
+            }
+        }
+    }
+
+    w.finish()
+}
+
+/// Write the TXT record strings
+///
+/// Device info lets the desktop configure itself at discovery time, without a
+/// follow-up 0x10 query.
+fn write_txt_entries(w: &mut DnsWriter, config: &MdnsConfig) {
+    let info = &config.device_info;
+    let mac = info.mac;
+
+    txt_entry(w, format_args!("id={}", info.device_id()));
+    txt_entry(w, format_args!("ver={}", crate::VERSION));
+    txt_entry(w, format_args!("proto={}", config::PROTOCOL_VERSION));
+    txt_entry(
+        w,
+        format_args!(
+            "mac={:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+            mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+        ),
+    );
+    txt_entry(w, format_args!("leds={}", info.led_count));
+    txt_entry(w, format_args!("fmt={}", info.pixel_format.name()));
+    if !config.location.is_empty() {
+        txt_entry(w, format_args!("room={}", config.location));
+    }
+}
+
+/// Write the `_http._tcp` TXT record strings (DNS-SD `path=` key)
+fn write_http_txt_entries(w: &mut DnsWriter) {
+    txt_entry(w, format_args!("path={}", HTTP_PATH));
+}
+
+/// Write one length-prefixed `key=value` TXT string
+fn txt_entry(w: &mut DnsWriter, entry: core::fmt::Arguments) {
+    let mut text = String::<64>::new();
+    if core::fmt::write(&mut text, entry).is_ok() {
+        w.u8(text.len() as u8);
+        w.bytes(text.as_bytes());
+    }
+}
@@ -0,0 +1,307 @@
+//! Memory usage instrumentation
+//!
+//! Tracks heap and stack high-water marks so new features can be sized
+//! against the chip's limited RAM. All embassy tasks (including the LED task
+//! on the interrupt executor) run on the single main stack, so per-subsystem
+//! figures are estimates: the deepest stack pointer and the largest heap usage
+//! seen at that subsystem's checkpoints. The painted stack watermark gives
+//! the true overall stack peak.
+//!
+//! The heap is shared by Wi-Fi and the per-frame buffers, so fragmentation
+//! matters as much as the free total: the largest block that can still be
+//! allocated is probed on demand, and the frame paths allocate fallibly,
+//! counting failures instead of panicking.
+
+use crate::println;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// Pattern written over unused stack at boot
+const STACK_PAINT: u32 = 0xA5A5_A5A5;
+
+/// Stack kept unpainted below the current stack pointer while painting
+const PAINT_MARGIN: usize = 256;
+
+unsafe extern "C" {
+    /// Lowest address of the main stack (from the esp-hal linker script)
+    static _stack_end_cpu0: u32;
+    /// Highest address of the main stack (from the esp-hal linker script)
+    static _stack_start_cpu0: u32;
+}
+
+/// Instrumented subsystems
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Subsystem {
+    Led = 0,
+    Udp = 1,
+    Mdns = 2,
+    StateMachine = 3,
+    Failover = 4,
+    Hooks = 5,
+    Diagnostics = 6,
+    Hyperion = 7,
+    Adalight = 8,
+    UsbSerial = 9,
+    WebSocket = 10,
+    Mqtt = 11,
+    EspNow = 12,
+    Provisioning = 13,
+    WebConfig = 14,
+    Ota = 15,
+}
+
+/// Number of instrumented subsystems
+pub const SUBSYSTEM_COUNT: usize = 16;
+
+impl Subsystem {
+    /// All subsystems in id order
+    pub const ALL: [Subsystem; SUBSYSTEM_COUNT] = [
+        Subsystem::Led,
+        Subsystem::Udp,
+        Subsystem::Mdns,
+        Subsystem::StateMachine,
+        Subsystem::Failover,
+        Subsystem::Hooks,
+        Subsystem::Diagnostics,
+        Subsystem::Hyperion,
+        Subsystem::Adalight,
+        Subsystem::UsbSerial,
+        Subsystem::WebSocket,
+        Subsystem::Mqtt,
+        Subsystem::EspNow,
+        Subsystem::Provisioning,
+        Subsystem::WebConfig,
+        Subsystem::Ota,
+    ];
+}
+
+/// Lowest stack pointer seen per subsystem (u32::MAX = never sampled)
+static STACK_LOW: [AtomicU32; SUBSYSTEM_COUNT] =
+    [const { AtomicU32::new(u32::MAX) }; SUBSYSTEM_COUNT];
+
+/// Largest heap usage seen per subsystem
+static HEAP_PEAK: [AtomicU32; SUBSYSTEM_COUNT] = [const { AtomicU32::new(0) }; SUBSYSTEM_COUNT];
+
+/// Largest heap usage seen at any checkpoint
+static HEAP_PEAK_TOTAL: AtomicU32 = AtomicU32::new(0);
+
+/// Fallible allocations that failed
+static ALLOC_FAILURES: AtomicU32 = AtomicU32::new(0);
+
+/// Per-subsystem high-water marks
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SubsystemUsage {
+    /// Deepest stack usage seen at the subsystem's checkpoints (bytes)
+    pub stack_peak: u32,
+    /// Largest heap usage seen at the subsystem's checkpoints (bytes)
+    pub heap_peak: u32,
+}
+
+/// Snapshot of memory usage
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryStats {
+    pub heap_used: u32,
+    pub heap_free: u32,
+    pub heap_peak: u32,
+    pub stack_size: u32,
+    /// Overall stack high-water mark from the painted region (bytes)
+    pub stack_peak: u32,
+    pub subsystems: [SubsystemUsage; SUBSYSTEM_COUNT],
+    /// Largest block the heap could hand out right now (bytes)
+    pub heap_largest_block: u32,
+    /// Fallible allocations that failed since boot
+    pub alloc_failures: u32,
+}
+
+/// Read the current stack pointer
+#[inline(always)]
+fn stack_pointer() -> usize {
+    let sp: usize;
+    #[cfg(target_arch = "riscv32")]
+    unsafe {
+        core::arch::asm!("mv {}, sp", out(reg) sp)
+    };
+    // a1 is the stack pointer on Xtensa (ESP32, ESP32-S3)
+    #[cfg(target_arch = "xtensa")]
+    unsafe {
+        core::arch::asm!("mov {}, a1", out(reg) sp)
+    };
+    sp
+}
+
+fn stack_bounds() -> (usize, usize) {
+    (
+        core::ptr::addr_of!(_stack_end_cpu0) as usize,
+        core::ptr::addr_of!(_stack_start_cpu0) as usize,
+    )
+}
+
+/// Fill the unused part of the stack with the paint pattern
+///
+/// Call once, early in `main`, before spawning any tasks.
+pub fn paint_stack() {
+    let (bottom, _) = stack_bounds();
+    let limit = stack_pointer().saturating_sub(PAINT_MARGIN);
+
+    let mut addr = bottom;
+    while addr + 4 <= limit {
+        unsafe { core::ptr::write_volatile(addr as *mut u32, STACK_PAINT) };
+        addr += 4;
+    }
+}
+
+/// Overall stack high-water mark: scan up from the bottom for the first
+/// overwritten word
+fn painted_stack_peak() -> u32 {
+    let (bottom, top) = stack_bounds();
+
+    let mut addr = bottom;
+    while addr < top {
+        if unsafe { core::ptr::read_volatile(addr as *const u32) } != STACK_PAINT {
+            break;
+        }
+        addr += 4;
+    }
+    (top - addr) as u32
+}
+
+/// Record the current stack depth and heap usage for `subsystem`
+///
+/// Cheap enough to call once per task loop iteration.
+pub fn checkpoint(subsystem: Subsystem) {
+    let sp = stack_pointer() as u32;
+    let heap_used = esp_alloc::HEAP.used() as u32;
+    let index = subsystem as usize;
+
+    // No compare-and-swap on the C3, so update the marks in a critical section
+    critical_section::with(|_| {
+        if sp < STACK_LOW[index].load(Ordering::Relaxed) {
+            STACK_LOW[index].store(sp, Ordering::Relaxed);
+        }
+        if heap_used > HEAP_PEAK[index].load(Ordering::Relaxed) {
+            HEAP_PEAK[index].store(heap_used, Ordering::Relaxed);
+        }
+        if heap_used > HEAP_PEAK_TOTAL.load(Ordering::Relaxed) {
+            HEAP_PEAK_TOTAL.store(heap_used, Ordering::Relaxed);
+        }
+    });
+}
+
+/// Count an allocation that failed; the first one is logged
+pub fn note_alloc_failure(size: usize) {
+    let failures = critical_section::with(|_| {
+        let failures = ALLOC_FAILURES.load(Ordering::Relaxed);
+        ALLOC_FAILURES.store(failures.saturating_add(1), Ordering::Relaxed);
+        failures
+    });
+    if failures == 0 {
+        println!(
+            "[MEM] Allocation of {} bytes failed ({} bytes free)",
+            size,
+            esp_alloc::HEAP.free()
+        );
+    }
+}
+
+/// A zeroed buffer of `len` bytes, or None (counted) if the heap can't fit it
+pub fn try_zeroed(len: usize) -> Option<Vec<u8>> {
+    let mut buffer = Vec::new();
+    if buffer.try_reserve_exact(len).is_err() {
+        note_alloc_failure(len);
+        return None;
+    }
+    buffer.resize(len, 0);
+    Some(buffer)
+}
+
+/// Largest block the heap can allocate right now
+///
+/// Found by bisection with trial allocations, each freed at once; other
+/// tasks may allocate in between, so it is an estimate.
+pub fn largest_free_block() -> u32 {
+    let fits = |size: usize| {
+        let Ok(layout) = Layout::from_size_align(size, 4) else {
+            return false;
+        };
+        // SAFETY: `layout` has a non-zero size; the block is freed right away
+        let block = unsafe { alloc::alloc::alloc(layout) };
+        if block.is_null() {
+            return false;
+        }
+        unsafe { alloc::alloc::dealloc(block, layout) };
+        true
+    };
+
+    let (mut low, mut high) = (0usize, esp_alloc::HEAP.free());
+    while low < high {
+        let mid = low + (high - low).div_ceil(2);
+        if fits(mid) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low as u32
+}
+
+/// Take a snapshot of current usage and high-water marks
+pub fn stats() -> MemoryStats {
+    let (bottom, top) = stack_bounds();
+    let heap_used = esp_alloc::HEAP.used() as u32;
+
+    let mut subsystems = [SubsystemUsage::default(); SUBSYSTEM_COUNT];
+    for (usage, subsystem) in subsystems.iter_mut().zip(Subsystem::ALL) {
+        let index = subsystem as usize;
+        let low = STACK_LOW[index].load(Ordering::Relaxed);
+        usage.stack_peak = if low == u32::MAX {
+            0
+        } else {
+            (top as u32).saturating_sub(low)
+        };
+        usage.heap_peak = HEAP_PEAK[index].load(Ordering::Relaxed);
+    }
+
+    MemoryStats {
+        heap_used,
+        heap_free: esp_alloc::HEAP.free() as u32,
+        heap_peak: HEAP_PEAK_TOTAL.load(Ordering::Relaxed).max(heap_used),
+        stack_size: (top - bottom) as u32,
+        stack_peak: painted_stack_peak(),
+        subsystems,
+        heap_largest_block: largest_free_block(),
+        alloc_failures: ALLOC_FAILURES.load(Ordering::Relaxed),
+    }
+}
+
+/// Memory stats response size
+pub const RESPONSE_SIZE: usize = 1 + 5 * 4 + 1 + SUBSYSTEM_COUNT * 9 + 2 * 4;
+
+impl MemoryStats {
+    /// Encode as a memory stats response:
+    /// `[header][heap used u32][heap free u32][heap peak u32][stack size u32]`
+    /// `[stack peak u32][count][per subsystem: id, stack peak u32, heap peak u32]`
+    /// `[largest free block u32][allocation failures u32]`
+    pub fn to_response(&self) -> [u8; RESPONSE_SIZE] {
+        let mut response = [0u8; RESPONSE_SIZE];
+        response[0] = crate::config::MEMORY_STATS_HEADER;
+        response[1..5].copy_from_slice(&self.heap_used.to_be_bytes());
+        response[5..9].copy_from_slice(&self.heap_free.to_be_bytes());
+        response[9..13].copy_from_slice(&self.heap_peak.to_be_bytes());
+        response[13..17].copy_from_slice(&self.stack_size.to_be_bytes());
+        response[17..21].copy_from_slice(&self.stack_peak.to_be_bytes());
+        response[21] = SUBSYSTEM_COUNT as u8;
+
+        let mut pos = 22;
+        for (usage, subsystem) in self.subsystems.iter().zip(Subsystem::ALL) {
+            response[pos] = subsystem as u8;
+            response[pos + 1..pos + 5].copy_from_slice(&usage.stack_peak.to_be_bytes());
+            response[pos + 5..pos + 9].copy_from_slice(&usage.heap_peak.to_be_bytes());
+            pos += 9;
+        }
+        response[pos..pos + 4].copy_from_slice(&self.heap_largest_block.to_be_bytes());
+        response[pos + 4..pos + 8].copy_from_slice(&self.alloc_failures.to_be_bytes());
+        response
+    }
+}
@@ -0,0 +1,203 @@
+//! Prometheus metrics
+//!
+//! `GET /metrics` on the settings page server returns the board's health in
+//! the Prometheus text format, so it can be scraped and graphed next to the
+//! rest of a home lab: frame rates, UDP traffic and drops, signal strength,
+//! heap and chip temperature. Counters count from boot; `board_rs_boots_total`
+//! and `board_rs_uptime_seconds` tell a restart from a counter wrap. Gauges
+//! without a reading (RSSI while disconnected, temperature on chips without a
+//! sensor) are left out rather than reported as 0.
+
+use alloc::string::String;
+use core::fmt::{Display, Write};
+
+/// Content type of the exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Append one sample with its help and type lines
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl Display) {
+    let _ = write!(
+        out,
+        "# HELP board_rs_{name} {help}\n# TYPE board_rs_{name} {kind}\nboard_rs_{name} {value}\n"
+    );
+}
+
+/// Format a value kept in hundredths, e.g. 5994 as `59.94`
+fn hundredths(value: u32) -> impl Display {
+    struct Hundredths(u32);
+    impl Display for Hundredths {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            write!(f, "{}.{:02}", self.0 / 100, self.0 % 100)
+        }
+    }
+    Hundredths(value)
+}
+
+/// Render all metrics
+pub fn render() -> String {
+    let mut out = String::new();
+    let device = crate::device_info::DeviceInfo::read();
+
+    let _ = write!(
+        out,
+        "# HELP board_rs_info Firmware and device identity\n# TYPE board_rs_info gauge\n\
+         board_rs_info{{version=\"{}\",build=\"{}\",device_id=\"{}\"}} 1\n",
+        crate::VERSION,
+        crate::BUILD_HASH,
+        device.device_id()
+    );
+    metric(
+        &mut out,
+        "uptime_seconds",
+        "gauge",
+        "Seconds since boot",
+        device.uptime_s,
+    );
+    metric(
+        &mut out,
+        "boots_total",
+        "counter",
+        "Boots since the crash log was created",
+        device.boot_count,
+    );
+    metric(
+        &mut out,
+        "system_state",
+        "gauge",
+        "System state machine state code",
+        crate::state_machine::current_state() as u8,
+    );
+
+    let pipeline = crate::led_control::pipeline_stats();
+    metric(
+        &mut out,
+        "led_render_fps",
+        "gauge",
+        "Strip refreshes per second",
+        hundredths(pipeline.render_fps_x100),
+    );
+    metric(
+        &mut out,
+        "led_frame_fps",
+        "gauge",
+        "New LED data frames shown per second",
+        hundredths(pipeline.frame_fps_x100),
+    );
+    metric(
+        &mut out,
+        "led_brightness",
+        "gauge",
+        "Global brightness (0-255)",
+        crate::led_control::global_brightness(),
+    );
+
+    let udp = crate::udp_server::counters();
+    metric(
+        &mut out,
+        "udp_packets_received_total",
+        "counter",
+        "UDP packets received",
+        udp.packets_received,
+    );
+    metric(
+        &mut out,
+        "udp_bytes_received_total",
+        "counter",
+        "UDP bytes received",
+        udp.bytes_received,
+    );
+    metric(
+        &mut out,
+        "udp_parse_errors_total",
+        "counter",
+        "UDP packets that failed to decode",
+        udp.parse_errors,
+    );
+    metric(
+        &mut out,
+        "udp_packets_dropped_total",
+        "counter",
+        "UDP packets discarded before reaching the frame buffer",
+        udp.dropped,
+    );
+    metric(
+        &mut out,
+        "udp_sequence_lost_total",
+        "counter",
+        "Sequence numbers missing from the LED data stream",
+        udp.sequence_lost,
+    );
+    metric(
+        &mut out,
+        "frames_committed_total",
+        "counter",
+        "Frames handed to the LED task",
+        udp.frames_committed,
+    );
+
+    if let Some(rssi) = crate::wifi::last_rssi() {
+        metric(
+            &mut out,
+            "wifi_rssi_dbm",
+            "gauge",
+            "Signal strength of the access point",
+            rssi,
+        );
+    }
+    metric(
+        &mut out,
+        "network_degraded",
+        "gauge",
+        "1 while the signal is weak",
+        crate::state_machine::is_network_degraded() as u8,
+    );
+
+    let memory = crate::memory::stats();
+    metric(
+        &mut out,
+        "heap_used_bytes",
+        "gauge",
+        "Heap in use",
+        memory.heap_used,
+    );
+    metric(
+        &mut out,
+        "heap_free_bytes",
+        "gauge",
+        "Heap free",
+        memory.heap_free,
+    );
+    metric(
+        &mut out,
+        "heap_largest_block_bytes",
+        "gauge",
+        "Largest block the heap could hand out",
+        memory.heap_largest_block,
+    );
+    metric(
+        &mut out,
+        "heap_alloc_failures_total",
+        "counter",
+        "Fallible allocations that failed",
+        memory.alloc_failures,
+    );
+
+    if let Some(celsius) = crate::temperature::last_celsius() {
+        metric(
+            &mut out,
+            "chip_temperature_celsius",
+            "gauge",
+            "Chip die temperature",
+            celsius,
+        );
+    }
+    metric(
+        &mut out,
+        "chip_overheating",
+        "gauge",
+        "1 while the chip is above the warning temperature",
+        crate::temperature::is_overheating() as u8,
+    );
+
+    out
+}
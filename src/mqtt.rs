@@ -0,0 +1,358 @@
+//! MQTT client module
+//!
+//! Minimal MQTT 3.1.1 client (QoS 0 only) so the board integrates with home
+//! automation brokers without the desktop app running. It publishes state and
+//! telemetry under `<prefix>/state`, periodically and on every state machine
+//! transition, and accepts commands on:
+//!
+//! - `<prefix>/brightness/set`: `0`-`255`
+//! - `<prefix>/power/set`: `ON` / `OFF`
+//! - `<prefix>/mode/set`: `ambient`, `idle`, `effect`, `off` or `auto`
+//!
+//! `<prefix>/availability` is retained as `online`, with `offline` as the
+//! last will.
+
+use crate::BoardError;
+use crate::config;
+use crate::led_control::LedMode;
+use crate::println;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use embassy_net::{IpAddress, IpEndpoint, Stack, tcp::TcpSocket};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Sender;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use embedded_io_async::Write;
+
+/// Keepalive interval announced to the broker
+const KEEPALIVE_SECS: u16 = 60;
+
+/// Delay before reconnecting after the broker connection drops
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Largest packet accepted from the broker
+const MAX_INCOMING_PACKET: usize = 512;
+
+const PACKET_CONNECT: u8 = 0x10;
+const PACKET_CONNACK: u8 = 0x20;
+const PACKET_PUBLISH: u8 = 0x30;
+const PACKET_SUBSCRIBE: u8 = 0x82;
+const PACKET_PINGREQ: u8 = 0xC0;
+
+/// Append a length-prefixed string
+fn put_str(packet: &mut Vec<u8>, value: &[u8]) {
+    packet.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    packet.extend_from_slice(value);
+}
+
+/// Prefix a packet body with its fixed header
+fn finish(packet_type: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(body.len() + 5);
+    packet.push(packet_type);
+
+    // Remaining length as a base-128 varint
+    let mut remaining = body.len();
+    loop {
+        let mut byte = (remaining % 128) as u8;
+        remaining /= 128;
+        if remaining > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if remaining == 0 {
+            break;
+        }
+    }
+
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn connect_packet(client_id: &str, will_topic: &str) -> Vec<u8> {
+    let username = config::MQTT_USERNAME.as_bytes();
+    let password = config::MQTT_PASSWORD.as_bytes();
+
+    // Clean session, retained last will
+    let mut flags = 0x02 | 0x04 | 0x20;
+    if !username.is_empty() {
+        flags |= 0x80;
+        if !password.is_empty() {
+            flags |= 0x40;
+        }
+    }
+
+    let mut body = Vec::new();
+    put_str(&mut body, b"MQTT");
+    body.push(4);
+    body.push(flags);
+    body.extend_from_slice(&KEEPALIVE_SECS.to_be_bytes());
+    put_str(&mut body, client_id.as_bytes());
+    put_str(&mut body, will_topic.as_bytes());
+    put_str(&mut body, b"offline");
+    if flags & 0x80 != 0 {
+        put_str(&mut body, username);
+    }
+    if flags & 0x40 != 0 {
+        put_str(&mut body, password);
+    }
+    finish(PACKET_CONNECT, &body)
+}
+
+fn subscribe_packet(packet_id: u16, topic: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&packet_id.to_be_bytes());
+    put_str(&mut body, topic.as_bytes());
+    body.push(0);
+    finish(PACKET_SUBSCRIBE, &body)
+}
+
+fn publish_packet(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_str(&mut body, topic.as_bytes());
+    body.extend_from_slice(payload);
+    finish(PACKET_PUBLISH | retain as u8, &body)
+}
+
+/// Split the first complete packet off `buffer`: (first byte, body, total length)
+fn next_packet(buffer: &[u8]) -> Option<(u8, &[u8], usize)> {
+    let mut remaining = 0usize;
+    for (i, &byte) in buffer.iter().enumerate().skip(1).take(4) {
+        remaining |= ((byte & 0x7F) as usize) << (7 * (i - 1));
+        if byte & 0x80 == 0 {
+            let total = i + 1 + remaining;
+            return (buffer.len() >= total).then(|| (buffer[0], &buffer[i + 1..total], total));
+        }
+    }
+    None
+}
+
+/// Topic and payload of an incoming QoS 0 PUBLISH
+fn parse_publish(body: &[u8]) -> Option<(&[u8], &[u8])> {
+    let topic_len = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+    let topic = body.get(2..2 + topic_len)?;
+    Some((topic, &body[2 + topic_len..]))
+}
+
+/// Apply a command published to one of the `/set` topics
+fn handle_command(
+    prefix: &str,
+    topic: &[u8],
+    payload: &[u8],
+    led_mode_sender: &Sender<'static, CriticalSectionRawMutex, LedMode, 2>,
+) {
+    let Some(command) = topic
+        .strip_prefix(prefix.as_bytes())
+        .and_then(|rest| rest.strip_suffix(b"/set"))
+    else {
+        return;
+    };
+    let payload = payload.trim_ascii();
+
+    match command {
+        b"/brightness" => {
+            let Some(brightness) = core::str::from_utf8(payload)
+                .ok()
+                .and_then(|s| s.parse::<u8>().ok())
+            else {
+                return;
+            };
+            crate::led_control::set_saved_brightness(brightness);
+            println!("[MQTT] Brightness set to {}", brightness);
+        }
+        b"/power" => {
+            let enabled = payload.eq_ignore_ascii_case(b"ON") || payload == b"1";
+            crate::led_control::set_strip_enabled(enabled);
+            println!("[MQTT] Strip {}", if enabled { "on" } else { "off" });
+        }
+        b"/mode" => {
+            let code = match payload.to_ascii_lowercase().as_slice() {
+                b"ambient" => LedMode::Ambient as u8,
+                b"idle" => LedMode::NonAmbient as u8,
+                b"effect" => LedMode::Effect as u8,
+                b"off" => LedMode::Off as u8,
+                b"auto" => config::MODE_AUTO,
+                _ => return,
+            };
+            crate::udp_server::UdpServer::handle_mode_command(code, led_mode_sender);
+        }
+        _ => {}
+    }
+}
+
+/// JSON state and telemetry document
+fn state_payload() -> String {
+    let memory = crate::memory::stats();
+    format!(
+        "{{\"state\":\"{:?}\",\"mode\":\"{:?}\",\"auto_mode\":{},\"brightness\":{},\"power\":\"{}\",\"ssid\":\"{}\",\"rssi\":{},\"network_degraded\":{},\"temperature_c\":{},\"overheating\":{},\"uptime_s\":{},\"boot_count\":{},\"heap_free\":{},\"heap_largest_block\":{},\"alloc_failures\":{}}}",
+        crate::state_machine::current_state(),
+        crate::led_control::active_mode(),
+        crate::led_control::is_auto_mode_switch(),
+        crate::led_control::global_brightness(),
+        if crate::led_control::is_strip_enabled() {
+            "ON"
+        } else {
+            "OFF"
+        },
+        crate::wifi::active_ssid()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\""),
+        crate::wifi::last_rssi().unwrap_or(0),
+        crate::state_machine::is_network_degraded(),
+        crate::temperature::last_celsius()
+            .map_or(String::from("null"), |celsius| format!("{}", celsius)),
+        crate::temperature::is_overheating(),
+        Instant::now().as_secs(),
+        crate::crash_log::boot_count(),
+        memory.heap_free,
+        memory.heap_largest_block,
+        memory.alloc_failures,
+    )
+}
+
+/// Set on every state machine transition, so state changes are published at once
+static STATE_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// State machine observer: request an immediate state publish
+fn on_state_change(_change: &crate::state_machine::StateChange) {
+    STATE_CHANGED.signal(());
+}
+
+/// Run one broker session until the connection drops
+async fn run_session(
+    socket: &mut TcpSocket<'_>,
+    prefix: &str,
+    client_id: &str,
+    led_mode_sender: &Sender<'static, CriticalSectionRawMutex, LedMode, 2>,
+) -> Result<(), BoardError> {
+    let availability_topic = format!("{}/availability", prefix);
+    let state_topic = format!("{}/state", prefix);
+
+    socket
+        .write_all(&connect_packet(client_id, &availability_topic))
+        .await
+        .map_err(|_| BoardError::MqttError)?;
+
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut chunk = [0u8; 128];
+    let mut connected = false;
+    let publish_interval = Duration::from_millis(config::MQTT_PUBLISH_INTERVAL_MS as u64);
+    let mut last_publish: Option<Instant> = None;
+    let mut last_send = Instant::now();
+
+    loop {
+        crate::memory::checkpoint(crate::memory::Subsystem::Mqtt);
+
+        // Drain complete packets from the broker
+        while let Some((first, body, total)) = next_packet(&buffer) {
+            match first & 0xF0 {
+                PACKET_CONNACK => {
+                    if body.get(1) != Some(&0) {
+                        println!("[MQTT] Broker refused connection: {:?}", body.get(1));
+                        return Err(BoardError::MqttError);
+                    }
+                    println!("[MQTT] Connected to broker");
+                    connected = true;
+
+                    let filter = format!("{}/+/set", prefix);
+                    socket
+                        .write_all(&subscribe_packet(1, &filter))
+                        .await
+                        .map_err(|_| BoardError::MqttError)?;
+                    socket
+                        .write_all(&publish_packet(&availability_topic, b"online", true))
+                        .await
+                        .map_err(|_| BoardError::MqttError)?;
+                    // Publish the initial state right away
+                    last_publish = None;
+                }
+                PACKET_PUBLISH => {
+                    if let Some((topic, payload)) = parse_publish(body) {
+                        handle_command(prefix, topic, payload, led_mode_sender);
+                    }
+                }
+                // SUBACK and PINGRESP need no handling
+                _ => {}
+            }
+            buffer.drain(..total);
+        }
+        if buffer.len() > MAX_INCOMING_PACKET {
+            return Err(BoardError::MqttError);
+        }
+
+        if STATE_CHANGED.try_take().is_some() {
+            last_publish = None;
+        }
+        if connected && last_publish.is_none_or(|t| t.elapsed() >= publish_interval) {
+            let payload = state_payload();
+            socket
+                .write_all(&publish_packet(&state_topic, payload.as_bytes(), false))
+                .await
+                .map_err(|_| BoardError::MqttError)?;
+            last_send = Instant::now();
+            last_publish = Some(last_send);
+        }
+
+        // Keep the session alive when nothing else was sent
+        if last_send.elapsed() >= Duration::from_secs(KEEPALIVE_SECS as u64 / 2) {
+            socket
+                .write_all(&[PACKET_PINGREQ, 0])
+                .await
+                .map_err(|_| BoardError::MqttError)?;
+            last_send = Instant::now();
+        }
+
+        match embassy_time::with_timeout(Duration::from_millis(500), socket.read(&mut chunk)).await
+        {
+            Ok(Ok(0)) | Ok(Err(_)) => return Err(BoardError::MqttError),
+            Ok(Ok(n)) => buffer.extend_from_slice(&chunk[..n]),
+            Err(_) => {}
+        }
+    }
+}
+
+/// MQTT client task
+#[embassy_executor::task]
+pub async fn mqtt_task(
+    stack: &'static Stack<'static>,
+    led_mode_sender: &'static Sender<'static, CriticalSectionRawMutex, LedMode, 2>,
+    broker: ([u8; 4], u16),
+) {
+    let mac = esp_hal::efuse::Efuse::read_base_mac_address();
+    let client_id = format!(
+        "{}-{:02x}{:02x}{:02x}",
+        config::MDNS_HOSTNAME,
+        mac[3],
+        mac[4],
+        mac[5]
+    );
+    let prefix = config::MQTT_TOPIC_PREFIX;
+    let [a, b, c, d] = broker.0;
+    crate::state_machine::register_observer(on_state_change).ok();
+    let endpoint = IpEndpoint::new(IpAddress::v4(a, b, c, d), broker.1);
+
+    let mut rx_buffer = [0; 1024];
+    let mut tx_buffer = [0; 1024];
+
+    loop {
+        stack.wait_config_up().await;
+
+        let mut socket = TcpSocket::new(*stack, &mut rx_buffer, &mut tx_buffer);
+        socket.set_timeout(Some(Duration::from_secs(KEEPALIVE_SECS as u64 * 2)));
+
+        match socket.connect(endpoint).await {
+            Ok(()) => {
+                if let Err(e) = run_session(&mut socket, prefix, &client_id, led_mode_sender).await
+                {
+                    println!("[MQTT] Session ended: {:?}", e);
+                }
+            }
+            Err(e) => println!("[MQTT] Connect to {} failed: {:?}", endpoint, e),
+        }
+
+        socket.abort();
+        socket.flush().await.ok();
+        Timer::after(RECONNECT_DELAY).await;
+    }
+}
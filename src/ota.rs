@@ -0,0 +1,717 @@
+//! Firmware updates (OTA)
+//!
+//! A new image is written to the app partition the board is not running
+//! from (`ota_0` / `ota_1` in `partitions.csv`) while the current firmware
+//! keeps going. Once the whole image is in flash, the boot slot in `otadata`
+//! is switched and the board restarts into it; until then nothing changes,
+//! so a failed or interrupted update leaves the running firmware in place.
+//!
+//! A new image has to prove itself: its first boot marks it pending, and
+//! only reaching `Operational` confirms it. If that boot ends in a reset
+//! (crash, watchdog) first, the next boot switches back to the previous
+//! image. A power cycle gives the pending image another try.
+//!
+//! Updates are pulled over plain HTTP from `config::OTA_URL`, or from a URL
+//! given with the `0x1F` command. The state machine follows along through
+//! the `OtaStarted` / `OtaDownloaded` / `OtaFailed` events.
+
+use crate::println;
+use crate::{BoardError, config};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicU32, Ordering};
+use embassy_net::dns::DnsQueryType;
+use embassy_net::{IpAddress, Stack, tcp::TcpSocket};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::Duration;
+use embedded_io_async::Write;
+use embedded_storage::{ReadStorage, Storage};
+use esp_bootloader_esp_idf::ota::{Ota, OtaImageState, Slot};
+use esp_bootloader_esp_idf::partitions::{
+    self, AppPartitionSubType, DataPartitionSubType, PartitionType,
+};
+use esp_storage::FlashStorage;
+use heapless::String;
+
+/// First byte of every ESP app image
+const IMAGE_MAGIC: u8 = 0xE9;
+
+/// Flash sector size; the image is written a sector at a time
+const SECTOR_SIZE: usize = 4096;
+
+/// Longest accepted update URL
+pub const MAX_URL_LEN: usize = 128;
+
+/// Longest accepted HTTP response header
+const MAX_RESPONSE_HEADER: usize = 1024;
+
+/// Bytes between download progress log lines
+const PROGRESS_LOG_INTERVAL: u32 = 64 * 1024;
+
+/// OTA command reply status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OtaStatus {
+    /// The update was queued
+    Started = 0,
+    /// An update is already running
+    Busy = 1,
+    /// No URL given and none configured, or the URL isn't `http://`
+    InvalidUrl = 2,
+}
+
+/// URL of the requested update
+static UPDATE_REQUEST: Signal<CriticalSectionRawMutex, String<MAX_URL_LEN>> = Signal::new();
+
+/// An update is queued or running
+static IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// The running image is on trial and not confirmed yet
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Stage of the current or last update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OtaPhase {
+    /// No update since boot
+    Idle = 0,
+    /// Image arriving and being written to flash
+    Receiving = 1,
+    /// Image complete, being checked and switched to
+    Verifying = 2,
+    /// New image in place; the board is restarting
+    Restarting = 3,
+    /// The update failed, or a push was interrupted
+    Stopped = 4,
+}
+
+static PHASE: AtomicU8 = AtomicU8::new(OtaPhase::Idle as u8);
+static PROGRESS_WRITTEN: AtomicU32 = AtomicU32::new(0);
+static PROGRESS_TOTAL: AtomicU32 = AtomicU32::new(0);
+
+/// Size of a progress report
+pub const PROGRESS_RESPONSE_SIZE: usize = 10;
+
+/// Progress of the current or last update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OtaProgress {
+    pub phase: OtaPhase,
+    /// Image bytes written so far
+    pub written: u32,
+    /// Image size (0 before the first update)
+    pub total: u32,
+}
+
+impl OtaProgress {
+    /// Read the current progress
+    pub fn read() -> Self {
+        let phase = match PHASE.load(Ordering::Relaxed) {
+            1 => OtaPhase::Receiving,
+            2 => OtaPhase::Verifying,
+            3 => OtaPhase::Restarting,
+            4 => OtaPhase::Stopped,
+            _ => OtaPhase::Idle,
+        };
+        Self {
+            phase,
+            written: PROGRESS_WRITTEN.load(Ordering::Relaxed),
+            total: PROGRESS_TOTAL.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Whether the update is still going
+    pub fn is_active(&self) -> bool {
+        matches!(self.phase, OtaPhase::Receiving | OtaPhase::Verifying)
+    }
+
+    /// Share of the image written, 0-100
+    pub fn percent(&self) -> u8 {
+        if self.total == 0 {
+            return 0;
+        }
+        (self.written as u64 * 100 / self.total as u64).min(100) as u8
+    }
+
+    /// Encode as a progress report
+    ///
+    /// Format: [0x22][phase][written:4][total:4]
+    pub fn to_response(&self) -> [u8; PROGRESS_RESPONSE_SIZE] {
+        let mut response = [0u8; PROGRESS_RESPONSE_SIZE];
+        response[0] = config::OTA_PROGRESS_HEADER;
+        response[1] = self.phase as u8;
+        response[2..6].copy_from_slice(&self.written.to_be_bytes());
+        response[6..10].copy_from_slice(&self.total.to_be_bytes());
+        response
+    }
+}
+
+/// Move the update to another stage
+pub fn set_phase(phase: OtaPhase) {
+    PHASE.store(phase as u8, Ordering::Relaxed);
+}
+
+/// App partition the board booted from: 0 `ota_0`, 1 `ota_1`, `u8::MAX` not
+/// read yet. Kept from boot on, as `otadata` names the next boot's slot once
+/// an update is written.
+static RUNNING_SLOT: AtomicU8 = AtomicU8::new(u8::MAX);
+
+/// Images started so far, so a paused push can tell whether another update
+/// has written over its partition since
+static IMAGES_STARTED: AtomicU32 = AtomicU32::new(0);
+
+/// Whether a firmware update is queued or running
+pub fn is_in_progress() -> bool {
+    IN_PROGRESS.load(Ordering::Relaxed)
+}
+
+/// Mark an update as running; false if one already is
+pub fn try_claim() -> bool {
+    // No atomic swap on the C3
+    critical_section::with(|_| {
+        let busy = IN_PROGRESS.load(Ordering::Relaxed);
+        IN_PROGRESS.store(true, Ordering::Relaxed);
+        !busy
+    })
+}
+
+/// Mark the running update as over (it failed or was paused)
+pub fn release() {
+    IN_PROGRESS.store(false, Ordering::Relaxed);
+}
+
+/// Queue an update from `url` (empty uses `config::OTA_URL`)
+pub fn request_update(url: &[u8]) -> OtaStatus {
+    let url = if url.is_empty() {
+        config::OTA_URL
+    } else {
+        match core::str::from_utf8(url) {
+            Ok(url) => url,
+            Err(_) => return OtaStatus::InvalidUrl,
+        }
+    };
+    if parse_url(url).is_none() {
+        return OtaStatus::InvalidUrl;
+    }
+    let Ok(url) = String::try_from(url) else {
+        return OtaStatus::InvalidUrl;
+    };
+
+    if !try_claim() {
+        return OtaStatus::Busy;
+    }
+    UPDATE_REQUEST.signal(url);
+    OtaStatus::Started
+}
+
+/// Split an `http://host[:port]/path` URL into host, port and path
+fn parse_url(url: &str) -> Option<(&str, u16, &str)> {
+    let rest = url.strip_prefix("http://")?;
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => rest.split_at(slash),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().ok()?),
+        None => (authority, 80),
+    };
+    (!host.is_empty()).then_some((host, port, path))
+}
+
+/// Run `f` on the boot slot bookkeeping in the `otadata` partition
+fn with_ota<R>(
+    f: impl FnOnce(&mut Ota<'_, FlashStorage>) -> Result<R, BoardError>,
+) -> Result<R, BoardError> {
+    let mut flash = FlashStorage::new();
+    let mut table = [0u8; partitions::PARTITION_TABLE_MAX_LEN];
+    let table = partitions::read_partition_table(&mut flash, &mut table)
+        .map_err(|_| BoardError::StorageError)?;
+    let entry = table
+        .find_partition(PartitionType::Data(DataPartitionSubType::Ota))
+        .ok()
+        .flatten()
+        .ok_or(BoardError::OtaError)?;
+    let mut region = entry.as_embedded_storage(&mut flash);
+    let mut ota = Ota::new(&mut region).map_err(|_| BoardError::StorageError)?;
+    f(&mut ota)
+}
+
+/// App partition of a boot slot
+fn slot_partition(slot: Slot) -> AppPartitionSubType {
+    match slot {
+        // Without a selected slot the bootloader runs `ota_0`
+        Slot::None | Slot::Slot0 => AppPartitionSubType::Ota0,
+        Slot::Slot1 => AppPartitionSubType::Ota1,
+    }
+}
+
+/// Boot slot the board is running from
+pub fn running_slot() -> Result<Slot, BoardError> {
+    match RUNNING_SLOT.load(Ordering::Relaxed) {
+        0 => Ok(Slot::Slot0),
+        1 => Ok(Slot::Slot1),
+        _ => {
+            let slot = with_ota(|ota| ota.current_slot().map_err(|_| BoardError::StorageError))?;
+            remember_running_slot(slot);
+            Ok(slot)
+        }
+    }
+}
+
+fn remember_running_slot(slot: Slot) {
+    let index = match slot_partition(slot) {
+        AppPartitionSubType::Ota1 => 1,
+        _ => 0,
+    };
+    RUNNING_SLOT.store(index, Ordering::Relaxed);
+}
+
+/// The slot an update goes to, or a rollback returns to
+fn other_slot(slot: Slot) -> Slot {
+    match slot {
+        Slot::None | Slot::Slot0 => Slot::Slot1,
+        Slot::Slot1 => Slot::Slot0,
+    }
+}
+
+/// Put a new image on trial, or roll back one whose trial boot failed
+///
+/// Call once at boot, after `crash_log::init`. Returns true when the board
+/// has to restart into the previous image.
+pub fn check_pending_image() -> bool {
+    let result = with_ota(|ota| {
+        let slot = ota.current_slot().map_err(|_| BoardError::StorageError)?;
+        remember_running_slot(slot);
+        if !config::OTA_ROLLBACK_ENABLED || matches!(slot, Slot::None) {
+            return Ok(false);
+        }
+        let state = ota
+            .current_ota_state()
+            .map_err(|_| BoardError::StorageError)?;
+        match state {
+            OtaImageState::New => {
+                println!(
+                    "[OTA] First boot of {:?} - kept once operational",
+                    slot_partition(slot)
+                );
+                ota.set_current_ota_state(OtaImageState::PendingVerify)
+                    .map_err(|_| BoardError::StorageError)?;
+                PENDING.store(true, Ordering::Relaxed);
+                Ok(false)
+            }
+            OtaImageState::PendingVerify if crate::crash_log::is_power_on_boot() => {
+                println!("[OTA] Power cycled during the trial - trying the new image again");
+                PENDING.store(true, Ordering::Relaxed);
+                Ok(false)
+            }
+            OtaImageState::PendingVerify => {
+                let previous = other_slot(slot);
+                println!(
+                    "[OTA] New image reset before confirming - rolling back to {:?}",
+                    slot_partition(previous)
+                );
+                ota.set_current_ota_state(OtaImageState::Invalid)
+                    .and_then(|_| ota.set_current_slot(previous))
+                    .and_then(|_| ota.set_current_ota_state(OtaImageState::Valid))
+                    .map_err(|_| BoardError::StorageError)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    });
+    result.unwrap_or_else(|e| {
+        println!("[OTA] Couldn't read the boot slot: {:?}", e);
+        false
+    })
+}
+
+/// Confirm an image on trial; call once the system is operational
+pub fn confirm_image() {
+    // No atomic swap on the C3, so read and clear in one critical section
+    let pending = critical_section::with(|_| {
+        let pending = PENDING.load(Ordering::Relaxed);
+        PENDING.store(false, Ordering::Relaxed);
+        pending
+    });
+    if !pending {
+        return;
+    }
+    let result = with_ota(|ota| {
+        ota.set_current_ota_state(OtaImageState::Valid)
+            .map_err(|_| BoardError::StorageError)
+    });
+    match result {
+        Ok(()) => println!("[OTA] New firmware confirmed"),
+        Err(e) => println!("[OTA] Failed to confirm the new firmware: {:?}", e),
+    }
+}
+
+/// Offset and size of an app partition
+fn app_partition(subtype: AppPartitionSubType) -> Result<(u32, u32), BoardError> {
+    let mut flash = FlashStorage::new();
+    let mut table = [0u8; partitions::PARTITION_TABLE_MAX_LEN];
+    let table = partitions::read_partition_table(&mut flash, &mut table)
+        .map_err(|_| BoardError::StorageError)?;
+    let entry = table
+        .find_partition(PartitionType::App(subtype))
+        .ok()
+        .flatten()
+        .ok_or(BoardError::OtaError)?;
+    Ok((entry.offset(), entry.len()))
+}
+
+/// Maximum encoded size of a firmware info response
+pub const MAX_FIRMWARE_INFO_SIZE: usize = 80;
+
+/// What the board runs, for deciding whether it needs an update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirmwareInfo {
+    /// App partition running: 0 `ota_0`, 1 `ota_1`
+    pub partition: u8,
+    /// Start of the running partition in flash (0 if unknown)
+    pub offset: u32,
+    /// The running image is on trial, not confirmed yet
+    pub pending: bool,
+    /// An update is being downloaded or pushed
+    pub updating: bool,
+}
+
+impl FirmwareInfo {
+    /// Read the running partition and update state
+    pub fn read() -> Self {
+        let subtype = slot_partition(running_slot().unwrap_or(Slot::None));
+        Self {
+            partition: match subtype {
+                AppPartitionSubType::Ota1 => 1,
+                _ => 0,
+            },
+            offset: app_partition(subtype).map_or(0, |(offset, _)| offset),
+            pending: PENDING.load(Ordering::Relaxed),
+            updating: is_in_progress(),
+        }
+    }
+
+    /// Encode as a firmware info response
+    ///
+    /// Format: [0x21][partition][flags: bit 0 pending, bit 1 updating]
+    /// [partition offset:4][version length][version...][build length][build hash...]
+    pub fn to_response(&self) -> heapless::Vec<u8, MAX_FIRMWARE_INFO_SIZE> {
+        let mut response = heapless::Vec::new();
+        let version = crate::VERSION.as_bytes();
+        let version = &version[..version.len().min(32)];
+        let build = crate::BUILD_HASH.as_bytes();
+        let build = &build[..build.len().min(32)];
+
+        let _ = response.push(config::FIRMWARE_INFO_HEADER);
+        let _ = response.push(self.partition);
+        let _ = response.push(self.pending as u8 | (self.updating as u8) << 1);
+        let _ = response.extend_from_slice(&self.offset.to_be_bytes());
+        let _ = response.push(version.len() as u8);
+        let _ = response.extend_from_slice(version);
+        let _ = response.push(build.len() as u8);
+        let _ = response.extend_from_slice(build);
+        response
+    }
+
+    /// Encode for the `/firmware` HTTP endpoint
+    pub fn to_json(&self) -> alloc::string::String {
+        let mut json = alloc::string::String::new();
+        let _ = core::fmt::write(
+            &mut json,
+            format_args!(
+                "{{\"version\":\"{}\",\"build\":\"{}\",\"partition\":\"ota_{}\",\
+                 \"offset\":{},\"pending\":{},\"updating\":{}}}",
+                crate::VERSION,
+                crate::BUILD_HASH,
+                self.partition,
+                self.offset,
+                self.pending,
+                self.updating
+            ),
+        );
+        json
+    }
+}
+
+/// Writes a new image into the inactive app partition
+pub struct OtaWriter {
+    flash: FlashStorage,
+    /// Slot the image will boot from
+    slot: Slot,
+    /// Partition start in flash
+    offset: u32,
+    /// Announced image size
+    size: u32,
+    /// Bytes already in flash
+    flushed: u32,
+    /// Sector being filled
+    buffer: Vec<u8>,
+    /// Number of this image in `IMAGES_STARTED`
+    image: u32,
+}
+
+impl OtaWriter {
+    /// Start writing an image of `size` bytes
+    ///
+    /// Fails when there is no inactive app partition or the image doesn't fit it.
+    pub fn begin(size: u32) -> Result<Self, BoardError> {
+        let slot = other_slot(running_slot()?);
+        let (offset, capacity) = app_partition(slot_partition(slot))?;
+        if size == 0 || size > capacity {
+            println!(
+                "[OTA] Image of {} bytes doesn't fit the {} byte partition",
+                size, capacity
+            );
+            return Err(BoardError::OtaError);
+        }
+        println!(
+            "[OTA] Writing {} bytes to {:?} at {:#x}",
+            size,
+            slot_partition(slot),
+            offset
+        );
+        PROGRESS_WRITTEN.store(0, Ordering::Relaxed);
+        PROGRESS_TOTAL.store(size, Ordering::Relaxed);
+        set_phase(OtaPhase::Receiving);
+        Ok(Self {
+            flash: FlashStorage::new(),
+            slot,
+            offset,
+            size,
+            flushed: 0,
+            buffer: Vec::with_capacity(SECTOR_SIZE),
+            image: critical_section::with(|_| {
+                let image = IMAGES_STARTED.load(Ordering::Relaxed) + 1;
+                IMAGES_STARTED.store(image, Ordering::Relaxed);
+                image
+            }),
+        })
+    }
+
+    /// Bytes accepted so far
+    pub fn written(&self) -> u32 {
+        self.flushed + self.buffer.len() as u32
+    }
+
+    /// Announced image size
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Whether no other image was started after this one
+    pub fn is_latest(&self) -> bool {
+        IMAGES_STARTED.load(Ordering::Relaxed) == self.image
+    }
+
+    /// Drop the data not yet in flash and return where writing continues
+    ///
+    /// Used when a push is interrupted: everything up to the returned offset
+    /// is kept and need not be sent again.
+    pub fn rewind(&mut self) -> u32 {
+        self.buffer.clear();
+        PROGRESS_WRITTEN.store(self.flushed, Ordering::Relaxed);
+        self.flushed
+    }
+
+    /// CRC-32 of the complete image, read back from flash
+    pub fn image_crc(&mut self) -> Result<u32, BoardError> {
+        set_phase(OtaPhase::Verifying);
+        if !self.buffer.is_empty() {
+            self.flush()?;
+        }
+        if self.flushed != self.size {
+            return Err(BoardError::OtaError);
+        }
+        let mut chunk = [0u8; 256];
+        let mut crc = 0xFFFF_FFFF;
+        let mut position = 0;
+        while position < self.size {
+            let len = (self.size - position).min(chunk.len() as u32) as usize;
+            self.flash
+                .read(self.offset + position, &mut chunk[..len])
+                .map_err(|_| BoardError::StorageError)?;
+            crc = crate::crc::crc32_update(crc, &chunk[..len]);
+            position += len as u32;
+        }
+        Ok(crc ^ 0xFFFF_FFFF)
+    }
+
+    /// Append image data
+    pub fn write(&mut self, mut data: &[u8]) -> Result<(), BoardError> {
+        if self.written() == 0 && data.first().is_some_and(|&b| b != IMAGE_MAGIC) {
+            println!("[OTA] Not a firmware image");
+            return Err(BoardError::OtaError);
+        }
+        if self.written() + data.len() as u32 > self.size {
+            return Err(BoardError::OtaError);
+        }
+        while !data.is_empty() {
+            let take = data.len().min(SECTOR_SIZE - self.buffer.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.buffer.len() == SECTOR_SIZE {
+                self.flush()?;
+            }
+        }
+        PROGRESS_WRITTEN.store(self.written(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), BoardError> {
+        self.flash
+            .write(self.offset + self.flushed, &self.buffer)
+            .map_err(|_| BoardError::StorageError)?;
+        self.flushed += self.buffer.len() as u32;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Write the rest of the image and boot from it on the next restart
+    pub fn finish(mut self) -> Result<(), BoardError> {
+        set_phase(OtaPhase::Verifying);
+        if !self.buffer.is_empty() {
+            self.flush()?;
+        }
+        if self.flushed != self.size {
+            println!(
+                "[OTA] Image incomplete ({} of {} bytes)",
+                self.flushed, self.size
+            );
+            return Err(BoardError::OtaError);
+        }
+        let mut magic = [0u8; 1];
+        self.flash
+            .read(self.offset, &mut magic)
+            .map_err(|_| BoardError::StorageError)?;
+        if magic[0] != IMAGE_MAGIC {
+            return Err(BoardError::OtaError);
+        }
+
+        let slot = self.slot;
+        with_ota(|ota| {
+            ota.set_current_slot(slot)
+                .and_then(|_| ota.set_current_ota_state(OtaImageState::New))
+                .map_err(|_| BoardError::StorageError)
+        })?;
+        println!("[OTA] Next boot runs {:?}", slot_partition(slot));
+        set_phase(OtaPhase::Restarting);
+        Ok(())
+    }
+}
+
+/// Resolve a host name or IPv4 literal
+async fn resolve(stack: &Stack<'static>, host: &str) -> Result<IpAddress, BoardError> {
+    if let Ok(address) = host.parse() {
+        return Ok(IpAddress::Ipv4(address));
+    }
+    stack
+        .dns_query(host, DnsQueryType::A)
+        .await
+        .ok()
+        .and_then(|addresses| addresses.first().copied())
+        .ok_or(BoardError::OtaError)
+}
+
+/// Download an image over HTTP into the inactive partition
+async fn download(stack: &Stack<'static>, url: &str) -> Result<OtaWriter, BoardError> {
+    let (host, port, path) = parse_url(url).ok_or(BoardError::OtaError)?;
+    let address = resolve(stack, host).await?;
+
+    let mut rx_buffer = [0; 2048];
+    let mut tx_buffer = [0; 256];
+    let mut socket = TcpSocket::new(*stack, &mut rx_buffer, &mut tx_buffer);
+    socket.set_timeout(Some(Duration::from_secs(15)));
+    socket
+        .connect((address, port))
+        .await
+        .map_err(|_| BoardError::OtaError)?;
+
+    let mut request = String::<{ MAX_URL_LEN + 64 }>::new();
+    let _ = core::fmt::write(
+        &mut request,
+        format_args!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            path, host
+        ),
+    );
+    socket
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|_| BoardError::OtaError)?;
+
+    // Status line and headers; whatever follows them is the start of the image
+    let mut header = [0u8; MAX_RESPONSE_HEADER];
+    let mut len = 0;
+    let header_end = loop {
+        if let Some(end) = header[..len].windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+        if len == header.len() {
+            return Err(BoardError::OtaError);
+        }
+        match socket.read(&mut header[len..]).await {
+            Ok(0) | Err(_) => return Err(BoardError::OtaError),
+            Ok(n) => len += n,
+        }
+    };
+    let status_ok = header
+        .split(|&b| b == b' ')
+        .nth(1)
+        .is_some_and(|code| code == b"200");
+    let size = crate::websocket::header_value(&header[..header_end], "Content-Length")
+        .and_then(|value| core::str::from_utf8(value).ok()?.parse::<u32>().ok());
+    let (true, Some(size)) = (status_ok, size) else {
+        println!("[OTA] Server refused the download or sent no length");
+        return Err(BoardError::OtaError);
+    };
+
+    let mut writer = OtaWriter::begin(size)?;
+    writer.write(&header[header_end..len])?;
+    let mut chunk = [0u8; 1024];
+    let mut next_log = PROGRESS_LOG_INTERVAL;
+    while writer.written() < size {
+        let n = match socket.read(&mut chunk).await {
+            Ok(0) | Err(_) => return Err(BoardError::OtaError),
+            Ok(n) => n,
+        };
+        writer.write(&chunk[..n])?;
+        if writer.written() >= next_log {
+            println!("[OTA] {} / {} bytes", writer.written(), size);
+            next_log += PROGRESS_LOG_INTERVAL;
+        }
+    }
+    socket.close();
+    Ok(writer)
+}
+
+/// Runs requested updates, one at a time
+#[embassy_executor::task]
+pub async fn ota_task(stack: &'static Stack<'static>) {
+    use crate::state_machine::{SystemEvent, send_event};
+
+    loop {
+        let url = UPDATE_REQUEST.wait().await;
+        crate::memory::checkpoint(crate::memory::Subsystem::Ota);
+        println!("[OTA] Updating from {}", url);
+        send_event(SystemEvent::OtaStarted);
+
+        let result = match download(stack, &url).await {
+            Ok(writer) => {
+                send_event(SystemEvent::OtaDownloaded);
+                writer.finish()
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => {
+                println!("[OTA] Update written - restarting");
+                send_event(SystemEvent::RebootRequested);
+            }
+            Err(e) => {
+                println!("[OTA] Update failed: {:?}", e);
+                set_phase(OtaPhase::Stopped);
+                send_event(SystemEvent::OtaFailed);
+                release();
+            }
+        }
+    }
+}
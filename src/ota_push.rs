@@ -0,0 +1,241 @@
+//! Firmware updates pushed over TCP
+//!
+//! For installs without an update server: the desktop app connects to
+//! `config::OTA_PUSH_PORT` and streams the image itself. Multi-byte fields
+//! are big-endian.
+//!
+//! - Begin: `"OTAP"[size:4][image crc32:4]`
+//! - Chunk: `[offset:4][length:2][data...][data crc32:4]`, at most
+//!   `config::OTA_PUSH_MAX_CHUNK` bytes of data
+//!
+//! Every message is answered with `[status][next offset:4]` (see
+//! [`PushStatus`]); the app sends the next chunk from the offset the board
+//! asks for. A dropped connection pauses the push: beginning again with the
+//! same size and CRC continues from the last flash sector written. Once the
+//! last byte is in, the image is read back and checked against its CRC
+//! before the board switches to it and restarts.
+
+use crate::ota::{self, OtaWriter};
+use crate::println;
+use crate::state_machine::{SystemEvent, send_event};
+use crate::{BoardError, config};
+use embassy_net::{Stack, tcp::TcpSocket};
+use embassy_time::Duration;
+use embedded_io_async::{Read, Write};
+
+/// Magic opening a push
+const BEGIN_MAGIC: &[u8; 4] = b"OTAP";
+
+/// Reply status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PushStatus {
+    /// Accepted; send from the next offset
+    Ok = 0,
+    /// Chunk failed its CRC or didn't start at the next offset; send again
+    /// from the next offset
+    Resend = 1,
+    /// Another update is running
+    Busy = 2,
+    /// Malformed message, no push begun, or an image that doesn't fit or
+    /// isn't a firmware image
+    Invalid = 3,
+    /// Writing to flash failed
+    FlashError = 4,
+    /// The complete image doesn't match its CRC; the push starts over
+    ImageCrcMismatch = 5,
+    /// Image verified; the board restarts into it
+    Complete = 6,
+}
+
+/// An image being received, kept across connections so a push can resume
+struct Session {
+    writer: OtaWriter,
+    crc: u32,
+}
+
+async fn reply(
+    socket: &mut TcpSocket<'_>,
+    status: PushStatus,
+    next: u32,
+) -> Result<(), BoardError> {
+    let mut message = [0u8; 5];
+    message[0] = status as u8;
+    message[1..].copy_from_slice(&next.to_be_bytes());
+    socket
+        .write_all(&message)
+        .await
+        .map_err(|_| BoardError::OtaError)?;
+    socket.flush().await.map_err(|_| BoardError::OtaError)
+}
+
+/// Handle a begin message; returns the offset to continue from
+fn begin(session: &mut Option<Session>, size: u32, crc: u32) -> Result<u32, PushStatus> {
+    if !ota::try_claim() {
+        return Err(PushStatus::Busy);
+    }
+    if let Some(paused) = session
+        .as_mut()
+        .filter(|s| s.writer.size() == size && s.crc == crc && s.writer.is_latest())
+    {
+        let offset = paused.writer.rewind();
+        println!("[OTA] Resuming push at {} of {} bytes", offset, size);
+        return Ok(offset);
+    }
+    match OtaWriter::begin(size) {
+        Ok(writer) => {
+            *session = Some(Session { writer, crc });
+            Ok(0)
+        }
+        Err(e) => {
+            *session = None;
+            ota::release();
+            Err(match e {
+                BoardError::StorageError => PushStatus::FlashError,
+                _ => PushStatus::Invalid,
+            })
+        }
+    }
+}
+
+/// Serve one connection; returns true once an image is complete
+///
+/// `claimed` is set once the push holds the update lock.
+async fn serve(
+    socket: &mut TcpSocket<'_>,
+    session: &mut Option<Session>,
+    claimed: &mut bool,
+) -> Result<bool, BoardError> {
+    let mut header = [0u8; 12];
+    socket
+        .read_exact(&mut header)
+        .await
+        .map_err(|_| BoardError::OtaError)?;
+    if &header[..4] != BEGIN_MAGIC {
+        reply(socket, PushStatus::Invalid, 0).await?;
+        return Ok(false);
+    }
+    let size = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    let crc = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+    match begin(session, size, crc) {
+        Ok(offset) => {
+            *claimed = true;
+            reply(socket, PushStatus::Ok, offset).await?
+        }
+        Err(status) => {
+            reply(socket, status, 0).await?;
+            return Ok(false);
+        }
+    }
+    send_event(SystemEvent::OtaStarted);
+    let Some(active) = session.as_mut() else {
+        return Ok(false);
+    };
+
+    let mut chunk = [0u8; config::OTA_PUSH_MAX_CHUNK + 4];
+    let mut next_log = 0;
+    loop {
+        let mut head = [0u8; 6];
+        socket
+            .read_exact(&mut head)
+            .await
+            .map_err(|_| BoardError::OtaError)?;
+        let offset = u32::from_be_bytes([head[0], head[1], head[2], head[3]]);
+        let length = u16::from_be_bytes([head[4], head[5]]) as usize;
+        if length == 0 || length > config::OTA_PUSH_MAX_CHUNK {
+            reply(socket, PushStatus::Invalid, active.writer.written()).await?;
+            return Err(BoardError::OtaError);
+        }
+        let message = &mut chunk[..length + 4];
+        socket
+            .read_exact(message)
+            .await
+            .map_err(|_| BoardError::OtaError)?;
+        let (data, tail) = message.split_at(length);
+        let data_crc = u32::from_be_bytes([tail[0], tail[1], tail[2], tail[3]]);
+
+        if offset != active.writer.written() || crate::crc::crc32(data) != data_crc {
+            reply(socket, PushStatus::Resend, active.writer.written()).await?;
+            continue;
+        }
+        if let Err(e) = active.writer.write(data) {
+            let status = match e {
+                BoardError::StorageError => PushStatus::FlashError,
+                _ => PushStatus::Invalid,
+            };
+            reply(socket, status, active.writer.rewind()).await?;
+            return Err(e);
+        }
+
+        let written = active.writer.written();
+        if written >= next_log {
+            println!("[OTA] Pushed {} / {} bytes", written, active.writer.size());
+            next_log = written + 64 * 1024;
+        }
+        if written < active.writer.size() {
+            reply(socket, PushStatus::Ok, written).await?;
+            continue;
+        }
+
+        // Last chunk: check what actually landed in flash
+        let expected = active.crc;
+        if !matches!(active.writer.image_crc(), Ok(crc) if crc == expected) {
+            println!("[OTA] Pushed image failed its CRC check");
+            *session = None;
+            reply(socket, PushStatus::ImageCrcMismatch, 0).await?;
+            return Err(BoardError::OtaError);
+        }
+        send_event(SystemEvent::OtaDownloaded);
+        let Some(done) = session.take() else {
+            return Err(BoardError::OtaError);
+        };
+        if let Err(e) = done.writer.finish() {
+            reply(socket, PushStatus::FlashError, 0).await?;
+            return Err(e);
+        }
+        reply(socket, PushStatus::Complete, written).await?;
+        return Ok(true);
+    }
+}
+
+/// TCP push update task
+#[embassy_executor::task]
+pub async fn ota_push_task(stack: &'static Stack<'static>, port: u16) {
+    let mut rx_buffer = [0; 2048];
+    let mut tx_buffer = [0; 64];
+    let mut session: Option<Session> = None;
+    println!("[OTA] Accepting pushed updates on port {}", port);
+
+    loop {
+        let mut socket = TcpSocket::new(*stack, &mut rx_buffer, &mut tx_buffer);
+        socket.set_timeout(Some(Duration::from_secs(10)));
+
+        if let Err(e) = socket.accept(port).await {
+            println!("[OTA] Accept failed: {:?}", e);
+            continue;
+        }
+        crate::memory::checkpoint(crate::memory::Subsystem::Ota);
+
+        let mut claimed = false;
+        match serve(&mut socket, &mut session, &mut claimed).await {
+            Ok(true) => {
+                socket.close();
+                socket.flush().await.ok();
+                println!("[OTA] Pushed update written - restarting");
+                send_event(SystemEvent::RebootRequested);
+                continue;
+            }
+            Ok(false) => {}
+            Err(e) => println!("[OTA] Push interrupted: {:?}", e),
+        }
+        // The push paused or failed; LED output resumes until it continues
+        if claimed {
+            ota::set_phase(ota::OtaPhase::Stopped);
+            ota::release();
+            send_event(SystemEvent::OtaFailed);
+        }
+        socket.close();
+        socket.flush().await.ok();
+        socket.abort();
+    }
+}
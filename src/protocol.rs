@@ -0,0 +1,601 @@
+//! LED data protocol parsing
+//!
+//! Decodes the packet formats shared by every transport (UDP, USB serial,
+//! WebSocket, ESP-NOW). Kept free of networking and chip support code so it
+//! builds for the host and is covered by unit tests.
+
+use crate::{BoardError, config};
+
+/// Pixel format (channel order) of the LED strip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PixelFormat {
+    Rgb = 0,
+    Grb = 1,
+    Rgbw = 2,
+    Grbw = 3,
+}
+
+impl PixelFormat {
+    /// Pixel format from its wire code
+    pub const fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(PixelFormat::Rgb),
+            1 => Some(PixelFormat::Grb),
+            2 => Some(PixelFormat::Rgbw),
+            3 => Some(PixelFormat::Grbw),
+            _ => None,
+        }
+    }
+
+    /// Bytes per LED in the data stream
+    pub const fn bytes_per_led(self) -> usize {
+        match self {
+            PixelFormat::Rgb | PixelFormat::Grb => 3,
+            PixelFormat::Rgbw | PixelFormat::Grbw => 4,
+        }
+    }
+
+    /// Write one LED in this format
+    ///
+    /// Formats without a white channel mix the white value into red, green
+    /// and blue. `out` holds `bytes_per_led()` bytes.
+    pub fn write_pixel(self, [r, g, b, w]: [u8; 4], out: &mut [u8]) {
+        let mix = |c: u8| c.saturating_add(w);
+        match self {
+            PixelFormat::Rgb => out.copy_from_slice(&[mix(r), mix(g), mix(b)]),
+            PixelFormat::Grb => out.copy_from_slice(&[mix(g), mix(r), mix(b)]),
+            PixelFormat::Rgbw => out.copy_from_slice(&[r, g, b, w]),
+            PixelFormat::Grbw => out.copy_from_slice(&[g, r, b, w]),
+        }
+    }
+
+    /// Lower-case name, e.g. for discovery records
+    pub const fn name(self) -> &'static str {
+        match self {
+            PixelFormat::Rgb => "rgb",
+            PixelFormat::Grb => "grb",
+            PixelFormat::Rgbw => "rgbw",
+            PixelFormat::Grbw => "grbw",
+        }
+    }
+}
+
+/// LED operation modes (discriminants are the mode command wire codes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum LedMode {
+    /// Ambient mode: display UDP data
+    Ambient = 0,
+    /// Non-ambient mode: breathing + status indication
+    NonAmbient = 1,
+    /// Effect mode: built-in rainbow animation
+    Effect = 2,
+    /// Off mode: strip blanked, no idle animation
+    Off = 3,
+}
+
+impl LedMode {
+    /// Mode for a wire code of the mode command
+    pub const fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(LedMode::Ambient),
+            1 => Some(LedMode::NonAmbient),
+            2 => Some(LedMode::Effect),
+            3 => Some(LedMode::Off),
+            _ => None,
+        }
+    }
+}
+
+/// Largest data packet header (0x05 with priority) plus CRC trailer
+const DATA_PACKET_OVERHEAD: usize = 7 + 4;
+
+/// Maximum UDP packet size for LED data: a whole `config::MAX_LEDS` frame in
+/// one packet, encrypted or not
+pub const MAX_PACKET_SIZE: usize = config::MAX_LEDS * config::BYTES_PER_LED
+    + DATA_PACKET_OVERHEAD
+    + crate::crypto::ENVELOPE_OVERHEAD;
+
+/// UDP packet structure for LED data
+///
+/// The color data borrows the receive buffer, so it is copied only once:
+/// straight into the frame buffer region selected by the offset.
+#[derive(Debug)]
+pub struct LedPacket<'d> {
+    /// Sequence number (sequenced packets only)
+    pub sequence: Option<u16>,
+    /// Packet flags (sequenced packets only, 0 otherwise)
+    pub flags: u8,
+    /// LED start offset (16-bit big-endian)
+    pub offset: u16,
+    /// LED color data (RGB or RGBW)
+    pub data: &'d [u8],
+}
+
+/// One fragment of a multi-packet LED frame
+#[derive(Debug)]
+pub struct FrameFragment<'d> {
+    /// Frame this fragment belongs to
+    pub frame_id: u16,
+    /// Fragment index within the frame
+    pub index: u8,
+    /// Total number of fragments in the frame
+    pub count: u8,
+    /// Byte offset of this fragment's data within the frame
+    pub byte_offset: u16,
+    /// LED color data
+    pub data: &'d [u8],
+}
+
+/// Setting addressed by a config packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ConfigKey {
+    /// Whole configuration as a versioned, CRC-checked blob (export/import)
+    All = 0x00,
+    /// LEDs on the strip (2 bytes)
+    LedCount = 0x01,
+    /// Pixel format code (1 byte, same bytes per LED as the build)
+    PixelFormat = 0x02,
+    /// Global brightness (1 byte)
+    Brightness = 0x03,
+    /// Device name (UTF-8, at most 63 bytes; empty restores the default)
+    DeviceName = 0x04,
+    /// UDP port for LED data (2 bytes)
+    UdpPort = 0x05,
+    /// Highest global brightness accepted from any source (1 byte)
+    MaxBrightness = 0x06,
+    /// Gamma in tenths (1 byte, 10-30; 10 is linear)
+    Gamma = 0x07,
+    /// White balance: red, green, blue and white scales (4 bytes, 255 unchanged)
+    WhiteBalance = 0x08,
+    /// Time without a keepalive that counts as one miss (4 bytes, ms, non-zero)
+    KeepaliveTimeout = 0x09,
+    /// Missed keepalives tolerated before the timeout action (1 byte)
+    KeepaliveMaxMissed = 0x0A,
+    /// Keepalive timeout action (1 byte: 0 state only, 1 blank the strip)
+    KeepaliveAction = 0x0B,
+    /// Hook table (24 bytes, see `hooks`)
+    Hooks = 0x0C,
+}
+
+impl ConfigKey {
+    /// Key from its wire code
+    pub const fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0x00 => Some(ConfigKey::All),
+            0x01 => Some(ConfigKey::LedCount),
+            0x02 => Some(ConfigKey::PixelFormat),
+            0x03 => Some(ConfigKey::Brightness),
+            0x04 => Some(ConfigKey::DeviceName),
+            0x05 => Some(ConfigKey::UdpPort),
+            0x06 => Some(ConfigKey::MaxBrightness),
+            0x07 => Some(ConfigKey::Gamma),
+            0x08 => Some(ConfigKey::WhiteBalance),
+            0x09 => Some(ConfigKey::KeepaliveTimeout),
+            0x0A => Some(ConfigKey::KeepaliveMaxMissed),
+            0x0B => Some(ConfigKey::KeepaliveAction),
+            0x0C => Some(ConfigKey::Hooks),
+            _ => None,
+        }
+    }
+}
+
+/// Config packet reply status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ConfigStatus {
+    Ok = 0,
+    UnknownKey = 1,
+    InvalidValue = 2,
+    StorageFailed = 3,
+}
+
+/// Get or set request for a stored setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigRequest<'d> {
+    Get(ConfigKey),
+    Set(ConfigKey, &'d [u8]),
+}
+
+/// Config packet operation codes
+const CONFIG_OP_GET: u8 = 0x00;
+const CONFIG_OP_SET: u8 = 0x01;
+
+/// Check if packet is a connection check packet
+pub fn is_connection_check(data: &[u8]) -> bool {
+    data.len() == 1 && data[0] == config::CONNECTION_CHECK_HEADER
+}
+
+/// Check if packet is a broadcast discovery probe
+pub fn is_discovery_probe(data: &[u8]) -> bool {
+    data.len() == 1 + config::DISCOVERY_MAGIC.len()
+        && data[0] == config::DISCOVERY_HEADER
+        && &data[1..] == config::DISCOVERY_MAGIC
+}
+
+/// Parse a fragment of a multi-packet LED frame
+///
+/// Format: [0x07][frame_id:2][index][count][byte_offset:2][data...]
+pub fn parse_fragment(data: &[u8]) -> Result<FrameFragment<'_>, BoardError> {
+    if data.len() < 8 || data[0] != config::FRAGMENT_HEADER {
+        return Err(BoardError::ProtocolError);
+    }
+
+    Ok(FrameFragment {
+        frame_id: u16::from_be_bytes([data[1], data[2]]),
+        index: data[3],
+        count: data[4],
+        byte_offset: u16::from_be_bytes([data[5], data[6]]),
+        data: &data[7..],
+    })
+}
+
+/// Reassembles fragmented LED frames
+///
+/// Only one frame is assembled at a time; a fragment of a newer frame
+/// abandons the incomplete one.
+#[derive(Debug, Default)]
+pub struct FrameAssembler {
+    frame_id: Option<u16>,
+    /// Set once `frame_id` has been handed out whole
+    completed: bool,
+    count: u8,
+    received_mask: u32,
+    buffer: alloc::vec::Vec<u8>,
+    /// Frames abandoned before all fragments arrived
+    pub incomplete_frames: u32,
+}
+
+impl FrameAssembler {
+    /// Abandon the frame in progress (e.g. when the data source changes)
+    pub fn reset(&mut self) {
+        self.frame_id = None;
+        self.completed = false;
+        self.count = 0;
+        self.received_mask = 0;
+        self.buffer.clear();
+    }
+
+    /// Add a fragment, returning the whole frame once every fragment has arrived
+    pub fn push(&mut self, fragment: &FrameFragment<'_>) -> Option<&[u8]> {
+        if fragment.count == 0
+            || fragment.count > config::MAX_FRAGMENTS
+            || fragment.index >= fragment.count
+        {
+            return None;
+        }
+
+        if self.frame_id == Some(fragment.frame_id) {
+            // Late duplicate of the frame just handed out
+            if self.completed {
+                return None;
+            }
+        } else {
+            // Out-of-date fragment of an older frame
+            if let Some(current) = self.frame_id {
+                if (fragment.frame_id.wrapping_sub(current) as i16) < 0 {
+                    return None;
+                }
+                if self.received_mask != 0 {
+                    self.incomplete_frames = self.incomplete_frames.saturating_add(1);
+                }
+            }
+            self.frame_id = Some(fragment.frame_id);
+            self.completed = false;
+            self.count = fragment.count;
+            self.received_mask = 0;
+            self.buffer.clear();
+        }
+
+        let max_len = config::MAX_FRAME_BYTES;
+        let start = fragment.byte_offset as usize;
+        let end = start + fragment.data.len();
+        if end > max_len {
+            return None;
+        }
+        if self.buffer.len() < end {
+            self.buffer.resize(end, 0);
+        }
+        self.buffer[start..end].copy_from_slice(fragment.data);
+        self.received_mask |= 1 << fragment.index;
+
+        let complete_mask = if self.count == 32 {
+            u32::MAX
+        } else {
+            (1u32 << self.count) - 1
+        };
+        if self.received_mask == complete_mask {
+            // Keep the frame id so late duplicates are ignored
+            self.completed = true;
+            self.received_mask = 0;
+            self.count = 0;
+            Some(&self.buffer)
+        } else {
+            None
+        }
+    }
+}
+
+/// Parse a config packet
+///
+/// Format: [0x1E][op: 0 get, 1 set][key][value...]
+pub fn parse_config_request(data: &[u8]) -> Result<ConfigRequest<'_>, ConfigStatus> {
+    let [config::CONFIG_HEADER, op, key, value @ ..] = data else {
+        return Err(ConfigStatus::InvalidValue);
+    };
+    let key = ConfigKey::from_code(*key).ok_or(ConfigStatus::UnknownKey)?;
+    match *op {
+        CONFIG_OP_GET if value.is_empty() => Ok(ConfigRequest::Get(key)),
+        CONFIG_OP_SET => Ok(ConfigRequest::Set(key, value)),
+        _ => Err(ConfigStatus::InvalidValue),
+    }
+}
+
+/// Stream priority of a data packet (0 unless a sequenced packet carries one)
+pub fn packet_priority(data: &[u8]) -> u8 {
+    match data {
+        [config::SEQUENCED_DATA_HEADER, flags, priority, ..]
+            if flags & config::DATA_FLAG_PRIORITY != 0 =>
+        {
+            *priority
+        }
+        _ => 0,
+    }
+}
+
+/// Parse raw packet data according to protocol specification
+pub fn parse_packet(data: &[u8]) -> Result<LedPacket<'_>, BoardError> {
+    // 解析数据包，不打印详细的数据内容
+
+    // Connection check packets should be handled before calling this function
+    if is_connection_check(data) {
+        return Err(BoardError::ProtocolError);
+    }
+
+    if data.len() < 3 {
+        return Err(BoardError::ProtocolError);
+    }
+
+    // Check protocol header
+    // 0x02: [header][offset:2][data...]
+    // 0x05: [header][flags][priority if flagged][sequence:2][offset:2][data...][crc32:4 if flagged]
+    let (flags, sequence, body) = match data[0] {
+        config::PROTOCOL_HEADER => (0, None, &data[1..]),
+        config::SEQUENCED_DATA_HEADER => {
+            let flags = data[1];
+            let seq_at = if flags & config::DATA_FLAG_PRIORITY != 0 {
+                3
+            } else {
+                2
+            };
+            if data.len() < seq_at + 4 {
+                return Err(BoardError::ProtocolError);
+            }
+            let sequence = u16::from_be_bytes([data[seq_at], data[seq_at + 1]]);
+            let mut end = data.len();
+
+            // Reject corrupted frames instead of rendering garbage colors
+            if flags & config::DATA_FLAG_CRC32 != 0 {
+                if data.len() < seq_at + 8 {
+                    return Err(BoardError::ProtocolError);
+                }
+                end -= 4;
+                let expected =
+                    u32::from_be_bytes([data[end], data[end + 1], data[end + 2], data[end + 3]]);
+                if crate::crc::crc32(&data[..end]) != expected {
+                    return Err(BoardError::ProtocolError);
+                }
+            }
+
+            (flags, Some(sequence), &data[seq_at + 2..end])
+        }
+        _ => return Err(BoardError::ProtocolError),
+    };
+
+    // Parse offset (16-bit big-endian)
+    let offset = u16::from_be_bytes([body[0], body[1]]);
+
+    // LED data stays in the receive buffer until it is merged into the frame
+    let led_data = &body[2..];
+    if led_data.len() > MAX_PACKET_SIZE {
+        return Err(BoardError::ProtocolError);
+    }
+
+    // LED数据解析完成，不打印数据长度
+
+    Ok(LedPacket {
+        sequence,
+        flags,
+        offset,
+        data: led_data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connection_check_is_a_single_header_byte() {
+        assert!(is_connection_check(&[config::CONNECTION_CHECK_HEADER]));
+        assert!(!is_connection_check(&[config::CONNECTION_CHECK_HEADER, 0]));
+        assert!(parse_packet(&[config::CONNECTION_CHECK_HEADER]).is_err());
+    }
+
+    #[test]
+    fn discovery_probe_needs_the_magic() {
+        let mut probe = [config::DISCOVERY_HEADER; 5];
+        probe[1..].copy_from_slice(config::DISCOVERY_MAGIC);
+        assert!(is_discovery_probe(&probe));
+        probe[4] ^= 0xFF;
+        assert!(!is_discovery_probe(&probe));
+    }
+
+    #[test]
+    fn parses_config_requests() {
+        assert_eq!(
+            parse_config_request(&[config::CONFIG_HEADER, 0x00, 0x03]),
+            Ok(ConfigRequest::Get(ConfigKey::Brightness))
+        );
+        assert_eq!(
+            parse_config_request(&[config::CONFIG_HEADER, 0x01, 0x01, 0x00, 0x90]),
+            Ok(ConfigRequest::Set(ConfigKey::LedCount, &[0x00, 0x90]))
+        );
+        assert_eq!(
+            parse_config_request(&[config::CONFIG_HEADER, 0x00, 0x7F]),
+            Err(ConfigStatus::UnknownKey)
+        );
+        assert_eq!(
+            parse_config_request(&[config::CONFIG_HEADER, 0x00, 0x03, 0x00]),
+            Err(ConfigStatus::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn parses_plain_data_packet() {
+        let packet = parse_packet(&[config::PROTOCOL_HEADER, 0x01, 0x02, 10, 20, 30]).unwrap();
+        assert_eq!(packet.sequence, None);
+        assert_eq!(packet.flags, 0);
+        assert_eq!(packet.offset, 0x0102);
+        assert_eq!(packet.data, &[10, 20, 30]);
+    }
+
+    #[test]
+    fn rejects_short_and_unknown_packets() {
+        assert!(parse_packet(&[]).is_err());
+        assert!(parse_packet(&[config::PROTOCOL_HEADER, 0]).is_err());
+        assert!(parse_packet(&[config::SEQUENCED_DATA_HEADER, 0, 0, 1, 0]).is_err());
+        assert!(parse_packet(&[0xEE, 0, 0, 1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn parses_sequenced_packet_with_priority() {
+        let data = [
+            config::SEQUENCED_DATA_HEADER,
+            config::DATA_FLAG_PRIORITY,
+            7,
+            0x12,
+            0x34,
+            0x00,
+            0x05,
+            1,
+            2,
+            3,
+        ];
+        let packet = parse_packet(&data).unwrap();
+        assert_eq!(packet.sequence, Some(0x1234));
+        assert_eq!(packet.offset, 5);
+        assert_eq!(packet.data, &[1, 2, 3]);
+        assert_eq!(packet_priority(&data), 7);
+        assert_eq!(packet_priority(&[config::PROTOCOL_HEADER, 0, 0]), 0);
+    }
+
+    #[test]
+    fn checks_crc_of_sequenced_packet() {
+        let mut data = [0u8; 11];
+        data[..7].copy_from_slice(&[
+            config::SEQUENCED_DATA_HEADER,
+            config::DATA_FLAG_CRC32,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            42,
+        ]);
+        let crc = crate::crc::crc32(&data[..7]);
+        data[7..].copy_from_slice(&crc.to_be_bytes());
+
+        let packet = parse_packet(&data).unwrap();
+        assert_eq!(packet.sequence, Some(1));
+        assert_eq!(packet.data, &[42]);
+
+        data[6] = 43;
+        assert!(parse_packet(&data).is_err());
+    }
+
+    #[test]
+    fn writes_pixels_in_channel_order() {
+        let mut out = [0; 4];
+        PixelFormat::Grbw.write_pixel([1, 2, 3, 4], &mut out);
+        assert_eq!(out, [2, 1, 3, 4]);
+        PixelFormat::Rgbw.write_pixel([1, 2, 3, 4], &mut out);
+        assert_eq!(out, [1, 2, 3, 4]);
+
+        let mut out = [0; 3];
+        PixelFormat::Grb.write_pixel([10, 20, 30, 0], &mut out);
+        assert_eq!(out, [20, 10, 30]);
+        PixelFormat::Rgb.write_pixel([10, 20, 250, 40], &mut out);
+        assert_eq!(out, [50, 60, 255]);
+    }
+
+    #[test]
+    fn parses_fragment() {
+        let fragment =
+            parse_fragment(&[config::FRAGMENT_HEADER, 0x00, 0x09, 1, 3, 0x01, 0x00, 0xAA]).unwrap();
+        assert_eq!(fragment.frame_id, 9);
+        assert_eq!(fragment.index, 1);
+        assert_eq!(fragment.count, 3);
+        assert_eq!(fragment.byte_offset, 256);
+        assert_eq!(fragment.data, &[0xAA]);
+        assert!(parse_fragment(&[config::FRAGMENT_HEADER, 0, 0, 0, 1, 0, 0]).is_err());
+    }
+
+    fn fragment(
+        frame_id: u16,
+        index: u8,
+        count: u8,
+        offset: usize,
+        data: &[u8],
+    ) -> FrameFragment<'_> {
+        FrameFragment {
+            frame_id,
+            index,
+            count,
+            byte_offset: offset as u16,
+            data,
+        }
+    }
+
+    #[test]
+    fn reassembles_a_full_strip() {
+        let frame: alloc::vec::Vec<u8> = (0..config::MAX_FRAME_BYTES).map(|i| i as u8).collect();
+        let chunks: alloc::vec::Vec<&[u8]> = frame.chunks(1400).collect();
+        let count = chunks.len() as u8;
+        let mut assembler = FrameAssembler::default();
+
+        // Fragments may arrive in any order
+        for index in (1..count).rev() {
+            let offset = index as usize * 1400;
+            let data = chunks[index as usize];
+            assert!(
+                assembler
+                    .push(&fragment(1, index, count, offset, data))
+                    .is_none()
+            );
+        }
+        let complete = assembler.push(&fragment(1, 0, count, 0, chunks[0]));
+        assert_eq!(complete, Some(frame.as_slice()));
+    }
+
+    #[test]
+    fn ignores_duplicates_of_a_completed_frame() {
+        let mut assembler = FrameAssembler::default();
+        assert!(assembler.push(&fragment(1, 0, 2, 0, &[1, 2])).is_none());
+        assert!(assembler.push(&fragment(1, 1, 2, 2, &[3, 4])).is_some());
+
+        assert!(assembler.push(&fragment(1, 1, 2, 2, &[3, 4])).is_none());
+        assert!(assembler.push(&fragment(2, 0, 1, 0, &[5, 6])).is_some());
+        assert_eq!(assembler.incomplete_frames, 0);
+    }
+
+    #[test]
+    fn counts_abandoned_frames() {
+        let mut assembler = FrameAssembler::default();
+        assert!(assembler.push(&fragment(1, 0, 2, 0, &[1, 2])).is_none());
+        assert!(assembler.push(&fragment(2, 0, 1, 0, &[5, 6])).is_some());
+        assert!(assembler.push(&fragment(1, 1, 2, 2, &[3, 4])).is_none());
+        assert_eq!(assembler.incomplete_frames, 1);
+    }
+}
@@ -0,0 +1,730 @@
+//! Wi-Fi provisioning module
+//!
+//! Boards without credentials (or that repeatedly fail to join their network)
+//! open an access point with a captive portal, so end users can pick the
+//! network from a phone instead of building the firmware with `WIFI_SSID` set.
+//!
+//! While the access point is up, three tasks serve it:
+//!
+//! - a minimal DHCP server leasing `192.168.4.100`-`107` to clients
+//! - a DNS responder resolving every name to the board, which makes phones
+//!   and laptops pop up the portal on their own
+//! - an HTTP server with the setup form; submitted credentials are stored in
+//!   flash and the board restarts to join the network (`POST /reboot` only
+//!   restarts it)
+//!
+//! The portal also serves the settings page (`/settings`, see `web_config`).
+//!
+//! The same tasks also serve the optional configuration access point, which
+//! stays up next to the station connection (see `wifi::config_ap_enabled`).
+//!
+//! Networks are scanned just before the access point opens (station mode is
+//! needed for that); the form offers them in a picker and `GET /scan` returns
+//! them as JSON for apps.
+
+pub use crate::config_store::WifiCredentials;
+use crate::println;
+use crate::wifi::{MAX_SCAN_RESULTS, ScanResult};
+use crate::{BoardError, config, config_store};
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use embassy_net::{
+    IpAddress, IpEndpoint, Ipv4Address, Stack,
+    tcp::TcpSocket,
+    udp::{PacketMetadata, UdpSocket},
+};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_io_async::Write;
+use embedded_storage::{ReadStorage, Storage};
+use esp_storage::FlashStorage;
+use heapless::{String, Vec};
+
+/// Address of the board on its access point network (/24)
+pub const AP_ADDRESS: Ipv4Address = Ipv4Address::new(192, 168, 4, 1);
+
+/// Last octet of the first address leased to clients
+const LEASE_POOL_START: u8 = 100;
+
+/// Clients served by the DHCP server at once
+const MAX_LEASES: usize = 8;
+
+/// Lease time handed out to clients (seconds)
+const LEASE_TIME_SECS: u32 = 3600;
+
+/// Flash record magic of the networks stored before the configuration store
+const RECORD_MAGIC: [u8; 4] = *b"WCRD";
+
+/// Flash record: [magic:4][ssid len][ssid:32][password len][password:64][crc32:4]
+const RECORD_SIZE: usize = 4 + 1 + 32 + 1 + 64 + 4;
+
+/// Flash space per network slot
+const RECORD_SLOT: usize = 128;
+
+/// Networks tried: the provisioned ones plus the build-time one
+pub const MAX_NETWORKS: usize = config::MAX_STORED_NETWORKS + 1;
+
+/// Largest portal request accepted (headers and form body)
+const MAX_REQUEST_SIZE: usize = 1024;
+
+/// Flash record of the device name stored before the configuration store: [magic:4][len][name:63][crc32:4]
+const NAME_RECORD_MAGIC: [u8; 4] = *b"NAME";
+const NAME_RECORD_SIZE: usize = 4 + 1 + 63 + 4;
+
+/// Flash offset of the device name record, after the network slots
+const NAME_RECORD_OFFSET: u32 =
+    config::CREDENTIALS_FLASH_OFFSET + (config::MAX_STORED_NETWORKS * RECORD_SLOT) as u32;
+
+/// Uptime (seconds) of the last portal request, 0 if none yet
+static LAST_PORTAL_REQUEST: AtomicU32 = AtomicU32::new(0);
+
+/// Networks offered in the portal's picker
+static SCAN_RESULTS: critical_section::Mutex<RefCell<Vec<ScanResult, MAX_SCAN_RESULTS>>> =
+    critical_section::Mutex::new(RefCell::new(Vec::new()));
+
+/// Whether the board is in provisioning mode
+static PROVISIONING_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+impl WifiCredentials {
+    /// Networks to try, in priority order: the provisioned ones, then the
+    /// build-time `WIFI_SSID` / `WIFI_PASSWORD`
+    pub fn configured() -> Vec<Self, MAX_NETWORKS> {
+        let mut networks: Vec<Self, MAX_NETWORKS> = Self::load().into_iter().collect();
+        if let Some(built_in) = Self::new(config::WIFI_SSID, config::WIFI_PASSWORD)
+            && !networks.iter().any(|n| n.ssid == built_in.ssid)
+        {
+            let _ = networks.push(built_in);
+        }
+        networks
+    }
+
+    /// The provisioned networks, in priority order
+    pub fn load() -> Vec<Self, { config::MAX_STORED_NETWORKS }> {
+        config_store::get().networks
+    }
+
+    /// Store these credentials as the highest priority network
+    ///
+    /// A stored network with the same SSID is replaced; with every slot taken
+    /// the lowest priority one is dropped.
+    pub fn store(&self) -> Result<(), BoardError> {
+        config_store::update(|stored| {
+            stored.add_network(self.clone());
+            Ok(())
+        })
+    }
+}
+
+/// Networks provisioned before the configuration store, for its one-time import
+pub(crate) fn legacy_networks() -> Vec<WifiCredentials, { config::MAX_STORED_NETWORKS }> {
+    (0..config::MAX_STORED_NETWORKS)
+        .filter_map(read_legacy_slot)
+        .collect()
+}
+
+/// Read one network slot of the old per-network records
+fn read_legacy_slot(slot: usize) -> Option<WifiCredentials> {
+    let mut record = [0u8; RECORD_SIZE];
+    FlashStorage::new()
+        .read(
+            config::CREDENTIALS_FLASH_OFFSET + (slot * RECORD_SLOT) as u32,
+            &mut record,
+        )
+        .ok()?;
+
+    let (body, crc) = record.split_at(RECORD_SIZE - 4);
+    if body[..4] != RECORD_MAGIC
+        || crate::crc::crc32(body) != u32::from_be_bytes(crc.try_into().ok()?)
+    {
+        return None;
+    }
+
+    let ssid_len = (body[4] as usize).min(32);
+    let password_len = (body[37] as usize).min(64);
+    let ssid = core::str::from_utf8(&body[5..5 + ssid_len]).ok()?;
+    let password = core::str::from_utf8(&body[38..38 + password_len]).ok()?;
+    WifiCredentials::new(ssid, password)
+}
+
+/// Device name provisioned before the configuration store, for its one-time import
+pub(crate) fn legacy_device_name() -> Option<String<63>> {
+    let mut record = [0u8; NAME_RECORD_SIZE];
+    FlashStorage::new()
+        .read(NAME_RECORD_OFFSET, &mut record)
+        .ok()?;
+
+    let (body, crc) = record.split_at(NAME_RECORD_SIZE - 4);
+    if body[..4] != NAME_RECORD_MAGIC
+        || crate::crc::crc32(body) != u32::from_be_bytes(crc.try_into().ok()?)
+    {
+        return None;
+    }
+    let len = (body[4] as usize).min(63);
+    let name = core::str::from_utf8(&body[5..5 + len]).ok()?;
+    String::try_from(name).ok().filter(|name| !name.is_empty())
+}
+
+/// Erase the records kept by older firmware, so a reset store doesn't import them again
+pub(crate) fn clear_legacy_records() -> Result<(), BoardError> {
+    let len = NAME_RECORD_OFFSET + NAME_RECORD_SIZE as u32 - config::CREDENTIALS_FLASH_OFFSET;
+    let mut flash = FlashStorage::new();
+    let mut offset = config::CREDENTIALS_FLASH_OFFSET;
+    while offset < config::CREDENTIALS_FLASH_OFFSET + len {
+        flash
+            .write(offset, &[0u8; RECORD_SLOT])
+            .map_err(|_| BoardError::StorageError)?;
+        offset += RECORD_SLOT as u32;
+    }
+    Ok(())
+}
+
+/// The provisioned device name, if any
+pub fn load_device_name() -> Option<String<63>> {
+    config_store::device_name()
+}
+
+/// Store the device name (mDNS instance name)
+pub fn store_device_name(name: &str) -> Result<(), BoardError> {
+    if name.is_empty() {
+        return Err(BoardError::ProtocolError);
+    }
+    config_store::update(|stored| {
+        stored.device_name = String::try_from(name).map_err(|_| BoardError::ProtocolError)?;
+        Ok(())
+    })
+}
+
+/// Store provisioned credentials and restart to join the network
+///
+/// Only returns if the credentials couldn't be stored.
+pub async fn apply(credentials: &WifiCredentials) -> Result<(), BoardError> {
+    credentials.store()?;
+    // New credentials deserve a normal boot, even from safe mode
+    crate::crash_log::clear_failed_boots();
+    println!(
+        "[PROV] Credentials for '{}' saved - restarting",
+        credentials.ssid.as_str()
+    );
+    Timer::after(Duration::from_secs(2)).await;
+    esp_hal::system::software_reset();
+}
+
+/// Remember the networks found before the access point opened, for the picker
+pub fn set_scan_results(results: Vec<ScanResult, MAX_SCAN_RESULTS>) {
+    critical_section::with(|cs| *SCAN_RESULTS.borrow_ref_mut(cs) = results);
+}
+
+/// Networks found by the last scan
+pub fn scan_results() -> Vec<ScanResult, MAX_SCAN_RESULTS> {
+    critical_section::with(|cs| SCAN_RESULTS.borrow_ref(cs).clone())
+}
+
+/// Mark provisioning mode as entered or left
+pub fn set_active(active: bool) {
+    PROVISIONING_ACTIVE.store(active, Ordering::Relaxed);
+}
+
+/// Whether the board is in provisioning mode
+pub fn is_active() -> bool {
+    PROVISIONING_ACTIVE.load(Ordering::Relaxed)
+}
+
+/// SSID of the setup access point: `ambient-setup-<last 3 MAC bytes>`
+pub fn ap_ssid() -> String<32> {
+    mac_ssid("ambient-setup")
+}
+
+/// SSID of the configuration access point: `ambient-config-<last 3 MAC bytes>`
+pub fn config_ap_ssid() -> String<32> {
+    mac_ssid("ambient-config")
+}
+
+fn mac_ssid(prefix: &str) -> String<32> {
+    let mac = esp_hal::efuse::Efuse::read_base_mac_address();
+    let mut ssid = String::new();
+    let _ = core::fmt::write(
+        &mut ssid,
+        format_args!("{}-{:02x}{:02x}{:02x}", prefix, mac[3], mac[4], mac[5]),
+    );
+    ssid
+}
+
+/// Record a request from a provisioning client (web portal or BLE app)
+pub fn mark_portal_activity() {
+    LAST_PORTAL_REQUEST.store(Instant::now().as_secs().max(1) as u32, Ordering::Relaxed);
+}
+
+/// Time since the portal last served a request (None if it never has)
+pub fn portal_idle_time() -> Option<Duration> {
+    match LAST_PORTAL_REQUEST.load(Ordering::Relaxed) {
+        0 => None,
+        secs => Some(Duration::from_secs(
+            Instant::now().as_secs().saturating_sub(secs as u64),
+        )),
+    }
+}
+
+/// DHCP message types (option 53)
+const DHCP_DISCOVER: u8 = 1;
+const DHCP_OFFER: u8 = 2;
+const DHCP_REQUEST: u8 = 3;
+const DHCP_ACK: u8 = 5;
+
+/// Offset of the options in a DHCP message, after the magic cookie
+const DHCP_OPTIONS: usize = 240;
+const DHCP_MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+/// Find a DHCP option's value
+fn dhcp_option(options: &[u8], code: u8) -> Option<&[u8]> {
+    let mut pos = 0;
+    while let Some(&option) = options.get(pos) {
+        match option {
+            0 => pos += 1,
+            255 => return None,
+            _ => {
+                let len = *options.get(pos + 1)? as usize;
+                let value = options.get(pos + 2..pos + 2 + len)?;
+                if option == code {
+                    return Some(value);
+                }
+                pos += 2 + len;
+            }
+        }
+    }
+    None
+}
+
+/// Build an OFFER or ACK for a client's DISCOVER or REQUEST
+fn dhcp_reply(request: &[u8], reply_type: u8, address: Ipv4Address) -> Vec<u8, 300> {
+    let server = AP_ADDRESS.octets();
+    let mut reply = Vec::new();
+
+    let _ = reply.extend_from_slice(&[2, 1, 6, 0]); // BOOTREPLY, Ethernet, 6-byte MAC
+    let _ = reply.extend_from_slice(&request[4..8]); // Transaction ID
+    let _ = reply.extend_from_slice(&[0, 0]); // Seconds
+    let _ = reply.extend_from_slice(&request[10..12]); // Flags
+    let _ = reply.extend_from_slice(&[0; 4]); // Client address
+    let _ = reply.extend_from_slice(&address.octets()); // Your address
+    let _ = reply.extend_from_slice(&server); // Server address
+    let _ = reply.extend_from_slice(&[0; 4]); // Relay address
+    let _ = reply.extend_from_slice(&request[28..44]); // Client hardware address
+    let _ = reply.resize(DHCP_OPTIONS - 4, 0); // Server name and boot file
+    let _ = reply.extend_from_slice(&DHCP_MAGIC_COOKIE);
+
+    let _ = reply.extend_from_slice(&[53, 1, reply_type]);
+    let _ = reply.extend_from_slice(&[54, 4]);
+    let _ = reply.extend_from_slice(&server);
+    let _ = reply.extend_from_slice(&[51, 4]);
+    let _ = reply.extend_from_slice(&LEASE_TIME_SECS.to_be_bytes());
+    let _ = reply.extend_from_slice(&[1, 4, 255, 255, 255, 0]); // Subnet mask
+    let _ = reply.extend_from_slice(&[3, 4]); // Router
+    let _ = reply.extend_from_slice(&server);
+    let _ = reply.extend_from_slice(&[6, 4]); // DNS server: the captive responder
+    let _ = reply.extend_from_slice(&server);
+    let _ = reply.push(255);
+    reply
+}
+
+/// DHCP server task for the setup access point
+#[embassy_executor::task]
+pub async fn dhcp_server_task(stack: &'static Stack<'static>) {
+    let mut rx_buffer = [0; 1024];
+    let mut tx_buffer = [0; 1024];
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut socket = UdpSocket::new(
+        *stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    if let Err(e) = socket.bind(67) {
+        println!("[PROV] DHCP bind failed: {:?}", e);
+        return;
+    }
+
+    // Client MAC per lease slot
+    let mut leases: Vec<[u8; 6], MAX_LEASES> = Vec::new();
+    let mut buffer = [0u8; 576];
+    let broadcast = IpEndpoint::new(IpAddress::v4(255, 255, 255, 255), 68);
+
+    loop {
+        crate::memory::checkpoint(crate::memory::Subsystem::Provisioning);
+
+        let Ok((len, _)) = socket.recv_from(&mut buffer).await else {
+            continue;
+        };
+        let request = &buffer[..len];
+        if len < DHCP_OPTIONS || request[0] != 1 || request[236..240] != DHCP_MAGIC_COOKIE {
+            continue;
+        }
+
+        let reply_type = match dhcp_option(&request[DHCP_OPTIONS..], 53) {
+            Some([DHCP_DISCOVER]) => DHCP_OFFER,
+            Some([DHCP_REQUEST]) => DHCP_ACK,
+            _ => continue,
+        };
+
+        let mut mac = [0u8; 6];
+        mac.copy_from_slice(&request[28..34]);
+        let slot = match leases.iter().position(|lease| *lease == mac) {
+            Some(slot) => slot,
+            None => {
+                if leases.push(mac).is_err() {
+                    println!("[PROV] DHCP pool exhausted");
+                    continue;
+                }
+                leases.len() - 1
+            }
+        };
+
+        let [a, b, c, _] = AP_ADDRESS.octets();
+        let address = Ipv4Address::new(a, b, c, LEASE_POOL_START + slot as u8);
+        let reply = dhcp_reply(request, reply_type, address);
+        socket.send_to(&reply, broadcast).await.ok();
+        if reply_type == DHCP_ACK {
+            println!("[PROV] Leased {} to a setup client", address);
+        }
+    }
+}
+
+/// Captive DNS task: answers every A query with the board's address
+#[embassy_executor::task]
+pub async fn captive_dns_task(stack: &'static Stack<'static>) {
+    let mut rx_buffer = [0; 1024];
+    let mut tx_buffer = [0; 1024];
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut socket = UdpSocket::new(
+        *stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    if let Err(e) = socket.bind(53) {
+        println!("[PROV] DNS bind failed: {:?}", e);
+        return;
+    }
+
+    let mut buffer = [0u8; 512];
+    loop {
+        crate::memory::checkpoint(crate::memory::Subsystem::Provisioning);
+
+        let Ok((len, endpoint)) = socket.recv_from(&mut buffer).await else {
+            continue;
+        };
+        if let Some(reply) = captive_dns_reply(&buffer[..len]) {
+            socket.send_to(&reply, endpoint.endpoint).await.ok();
+        }
+    }
+}
+
+/// Reply to a DNS query, resolving any A question to `AP_ADDRESS`
+fn captive_dns_reply(query: &[u8]) -> Option<Vec<u8, 512>> {
+    // Standard queries with exactly one question
+    if query.len() < 12 || query[2] & 0xF8 != 0 || query[4..6] != [0, 1] {
+        return None;
+    }
+
+    // Skip the question name
+    let mut pos = 12;
+    loop {
+        let len = *query.get(pos)? as usize;
+        pos += 1;
+        if len == 0 {
+            break;
+        }
+        if len > 63 {
+            return None;
+        }
+        pos += len;
+    }
+    let question = query.get(12..pos + 4)?;
+    let is_a = question[question.len() - 4..question.len() - 2] == [0, 1];
+
+    let mut reply = Vec::new();
+    let _ = reply.extend_from_slice(&query[..2]); // Transaction ID
+    let _ = reply.extend_from_slice(&[0x81, 0x80]); // Response, recursion available
+    let _ = reply.extend_from_slice(&[0, 1, 0, is_a as u8, 0, 0, 0, 0]);
+    let _ = reply.extend_from_slice(question);
+    if is_a {
+        let _ = reply.extend_from_slice(&[0xC0, 12, 0, 1, 0, 1]); // Name pointer, A, IN
+        let _ = reply.extend_from_slice(&60u32.to_be_bytes()); // TTL
+        let _ = reply.extend_from_slice(&[0, 4]);
+        let _ = reply.extend_from_slice(&AP_ADDRESS.octets());
+    }
+    Some(reply)
+}
+
+const SETUP_PAGE_HEAD: &str = concat!(
+    "<!DOCTYPE html><html><head><meta charset=\"utf-8\">",
+    "<meta name=\"viewport\" content=\"width=device-width,initial-scale=1\">",
+    "<title>Ambient light setup</title></head><body>",
+    "<h1>Ambient light setup</h1>",
+    "<form method=\"post\" action=\"/save\">",
+);
+
+/// Free-text SSID entry, used alone when the scan found nothing
+const SSID_INPUT: &str =
+    "<p><label>Network name<br><input name=\"ssid\" maxlength=\"32\" required></label></p>";
+
+/// Free-text entry for a network missing from the picker
+const SSID_OTHER_INPUT: &str =
+    "<p><label>Other network<br><input name=\"ssid_other\" maxlength=\"32\"></label></p>";
+
+const SETUP_PAGE_TAIL: &str = concat!(
+    "<p><label>Password<br><input name=\"password\" type=\"password\" maxlength=\"64\"></label></p>",
+    "<p><button>Save and connect</button></p>",
+    "</form>",
+    "<p><a href=\"/settings\">More settings</a></p>",
+    "<form method=\"post\" action=\"/reboot\"><p><button>Restart board</button></p></form>",
+    "</body></html>"
+);
+
+const SAVED_PAGE: &str = concat!(
+    "<!DOCTYPE html><html><head><meta charset=\"utf-8\">",
+    "<meta name=\"viewport\" content=\"width=device-width,initial-scale=1\">",
+    "<title>Ambient light setup</title></head><body>",
+    "<h1>Saved</h1><p>The board restarts and joins the network.</p>",
+    "</body></html>"
+);
+
+pub(crate) const REBOOT_PAGE: &str = concat!(
+    "<!DOCTYPE html><html><head><meta charset=\"utf-8\">",
+    "<meta name=\"viewport\" content=\"width=device-width,initial-scale=1\">",
+    "<title>Ambient light setup</title></head><body>",
+    "<h1>Restarting</h1><p>The board restarts now.</p>",
+    "</body></html>"
+);
+
+/// Append text with the characters significant in HTML and JSON strings escaped
+pub(crate) fn push_escaped(out: &mut alloc::string::String, text: &str, json: bool) {
+    for c in text.chars() {
+        match (c, json) {
+            ('"', true) => out.push_str("\\\""),
+            ('\\', true) => out.push_str("\\\\"),
+            (c, true) if c.is_control() => {
+                let _ = core::fmt::write(out, format_args!("\\u{:04x}", c as u32));
+            }
+            ('&', false) => out.push_str("&amp;"),
+            ('<', false) => out.push_str("&lt;"),
+            ('>', false) => out.push_str("&gt;"),
+            ('"', false) => out.push_str("&quot;"),
+            (c, _) => out.push(c),
+        }
+    }
+}
+
+/// The setup form, with a picker of the scanned networks
+fn setup_page() -> alloc::string::String {
+    let networks = scan_results();
+    let mut page = alloc::string::String::from(SETUP_PAGE_HEAD);
+
+    if networks.is_empty() {
+        page.push_str(SSID_INPUT);
+    } else {
+        page.push_str("<p><label>Network<br><select name=\"ssid\">");
+        for network in &networks {
+            page.push_str("<option value=\"");
+            push_escaped(&mut page, &network.ssid, false);
+            page.push_str("\">");
+            push_escaped(&mut page, &network.ssid, false);
+            let _ = core::fmt::write(
+                &mut page,
+                format_args!(
+                    " ({} dBm, {})</option>",
+                    network.rssi,
+                    crate::wifi::auth_method_name(network.auth_method)
+                ),
+            );
+        }
+        page.push_str("<option value=\"\">Other...</option></select></label></p>");
+        page.push_str(SSID_OTHER_INPUT);
+    }
+
+    page.push_str(SETUP_PAGE_TAIL);
+    page
+}
+
+/// Scan results as JSON: `[{"ssid":..,"rssi":..,"auth":..,"channel":..}]`
+fn scan_json() -> alloc::string::String {
+    let mut json = alloc::string::String::from("[");
+    for (i, network) in scan_results().iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push_str("{\"ssid\":\"");
+        push_escaped(&mut json, &network.ssid, true);
+        let _ = core::fmt::write(
+            &mut json,
+            format_args!(
+                "\",\"rssi\":{},\"auth\":\"{}\",\"channel\":{}}}",
+                network.rssi,
+                crate::wifi::auth_method_name(network.auth_method),
+                network.channel
+            ),
+        );
+    }
+    json.push(']');
+    json
+}
+
+/// Decode a value from an `application/x-www-form-urlencoded` body
+pub(crate) fn form_value<const N: usize>(body: &[u8], key: &str) -> Option<String<N>> {
+    let encoded = body.split(|&b| b == b'&').find_map(|pair| {
+        let eq = pair.iter().position(|&b| b == b'=')?;
+        (&pair[..eq] == key.as_bytes()).then(|| &pair[eq + 1..])
+    })?;
+
+    let mut decoded: Vec<u8, N> = Vec::new();
+    let mut bytes = encoded.iter();
+    while let Some(&byte) = bytes.next() {
+        let byte = match byte {
+            b'+' => b' ',
+            b'%' => {
+                let hex = [*bytes.next()?, *bytes.next()?];
+                u8::from_str_radix(core::str::from_utf8(&hex).ok()?, 16).ok()?
+            }
+            byte => byte,
+        };
+        decoded.push(byte).ok()?;
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Read an HTTP request (headers and body) into `request`
+///
+/// Returns the header length and the length including the body.
+pub(crate) async fn read_request(
+    socket: &mut TcpSocket<'_>,
+    request: &mut [u8],
+) -> Result<(usize, usize), BoardError> {
+    let mut len = 0;
+
+    // Headers, then the form body if there is one
+    let header_end = loop {
+        if let Some(end) = request[..len].windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+        if len == request.len() {
+            return Err(BoardError::ProtocolError);
+        }
+        match socket.read(&mut request[len..]).await {
+            Ok(0) | Err(_) => return Err(BoardError::ProtocolError),
+            Ok(n) => len += n,
+        }
+    };
+    let content_length = crate::websocket::header_value(&request[..header_end], "Content-Length")
+        .and_then(|value| core::str::from_utf8(value).ok()?.parse::<usize>().ok())
+        .unwrap_or(0);
+    let request_end = header_end + content_length;
+    if request_end > request.len() {
+        return Err(BoardError::ProtocolError);
+    }
+    while len < request_end {
+        match socket.read(&mut request[len..request_end]).await {
+            Ok(0) | Err(_) => return Err(BoardError::ProtocolError),
+            Ok(n) => len += n,
+        }
+    }
+    Ok((header_end, request_end))
+}
+
+/// Send a complete `200 OK` response
+pub(crate) async fn write_response(
+    socket: &mut TcpSocket<'_>,
+    content_type: &str,
+    page: &str,
+) -> Result<(), BoardError> {
+    let mut head = String::<128>::new();
+    let _ = core::fmt::write(
+        &mut head,
+        format_args!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            content_type,
+            page.len()
+        ),
+    );
+    socket
+        .write_all(head.as_bytes())
+        .await
+        .map_err(|_| BoardError::ProtocolError)?;
+    socket
+        .write_all(page.as_bytes())
+        .await
+        .map_err(|_| BoardError::ProtocolError)?;
+    socket.flush().await.map_err(|_| BoardError::ProtocolError)
+}
+
+/// Serve one portal request; returns credentials once the form was submitted
+async fn serve_portal(socket: &mut TcpSocket<'_>) -> Result<Option<WifiCredentials>, BoardError> {
+    let mut request = [0u8; MAX_REQUEST_SIZE];
+    let (header_end, request_end) = read_request(socket, &mut request).await?;
+    mark_portal_activity();
+
+    let submitted = if request.starts_with(b"POST /save ") {
+        let body = &request[header_end..request_end];
+        form_value::<32>(body, "ssid")
+            .filter(|ssid| !ssid.is_empty())
+            .or_else(|| form_value::<32>(body, "ssid_other"))
+            .and_then(|ssid| {
+                let password = form_value::<64>(body, "password").unwrap_or_default();
+                WifiCredentials::new(&ssid, &password)
+            })
+    } else {
+        None
+    };
+    let reboot = request.starts_with(b"POST /reboot ");
+
+    // Every other path gets the form, so OS captive-portal probes open it
+    let (content_type, page) = if submitted.is_some() {
+        ("text/html", alloc::string::String::from(SAVED_PAGE))
+    } else if reboot {
+        ("text/html", alloc::string::String::from(REBOOT_PAGE))
+    } else if request.starts_with(b"GET /scan ") {
+        ("application/json", scan_json())
+    } else if let Some(response) =
+        crate::web_config::respond(&request[..header_end], &request[header_end..request_end])
+    {
+        response
+    } else {
+        ("text/html", setup_page())
+    };
+    write_response(socket, content_type, &page).await?;
+
+    if reboot {
+        println!("[PROV] Reboot requested from the portal");
+        crate::state_machine::send_event(crate::state_machine::SystemEvent::RebootRequested);
+    }
+    Ok(submitted)
+}
+
+/// Captive portal HTTP task
+#[embassy_executor::task]
+pub async fn portal_http_task(stack: &'static Stack<'static>) {
+    let mut rx_buffer = [0; 1536];
+    let mut tx_buffer = [0; 1536];
+
+    loop {
+        let mut socket = TcpSocket::new(*stack, &mut rx_buffer, &mut tx_buffer);
+        socket.set_timeout(Some(Duration::from_secs(10)));
+
+        if let Err(e) = socket.accept(80).await {
+            println!("[PROV] Accept failed: {:?}", e);
+            continue;
+        }
+        crate::memory::checkpoint(crate::memory::Subsystem::Provisioning);
+
+        let submitted = serve_portal(&mut socket).await;
+        socket.close();
+        socket.flush().await.ok();
+
+        if let Ok(Some(credentials)) = submitted
+            && let Err(e) = apply(&credentials).await
+        {
+            println!("[PROV] Failed to save credentials: {:?}", e);
+        }
+        socket.abort();
+    }
+}
@@ -0,0 +1,117 @@
+//! Boot-time self-test
+//!
+//! Before the network comes up, the state machine's `SelfTest` state checks
+//! that the LED strip driver came up and that the heap can still serve a
+//! sizeable allocation. The LED task reports the RMT channel set-up (and, with
+//! `config::SELF_TEST_LED_SWEEP`, a short sweep over the strip) as it starts,
+//! and the self-test waits for that report. A failure stops the boot in `HardwareError`: the status LEDs
+//! blink long-short when the strip works, and the failure is kept in the crash
+//! log either way.
+
+use crate::BoardError;
+use crate::config;
+use crate::led_control::UniversalDriverBoard;
+use crate::println;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU8, Ordering};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use esp_hal::rmt::TxChannelAsync;
+
+/// Brightness of each channel during the sweep
+const SWEEP_LEVEL: u8 = 32;
+
+/// How long each channel stays lit during the sweep
+const SWEEP_STEP_MS: u32 = 150;
+
+/// Self-test failures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Failure {
+    /// The RMT peripheral or channel couldn't be configured
+    Rmt = 1,
+    /// Writing to the strip failed during the sweep
+    Strip = 2,
+    /// The heap couldn't serve the probe allocation
+    Heap = 3,
+}
+
+impl Failure {
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(Failure::Rmt),
+            2 => Some(Failure::Strip),
+            3 => Some(Failure::Heap),
+            _ => None,
+        }
+    }
+}
+
+/// Hardware failure found while bringing up peripherals (0 = none)
+static HARDWARE_FAILURE: AtomicU8 = AtomicU8::new(0);
+
+/// Signalled once the strip checks are done, or won't run
+static STRIP_CHECKED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Report a hardware failure found during peripheral set-up
+pub fn record_failure(failure: Failure) {
+    println!("[SELFTEST] {:?} check failed", failure);
+    HARDWARE_FAILURE.store(failure as u8, Ordering::Relaxed);
+}
+
+/// Report that the LED task has brought up the strip, or that it won't start
+pub fn finish_strip_check() {
+    STRIP_CHECKED.signal(());
+}
+
+/// Light each color channel of the strip in turn, then clear it
+///
+/// Takes a fraction of a second; called by the LED task before it starts
+/// rendering.
+pub async fn led_sweep<TX: TxChannelAsync>(
+    board: &mut UniversalDriverBoard<TX>,
+) -> Result<(), BoardError> {
+    let mut frame = vec![0u8; crate::config_store::led_count() * config::BYTES_PER_LED];
+    for channel in 0..config::BYTES_PER_LED {
+        for (i, byte) in frame.iter_mut().enumerate() {
+            *byte = if i % config::BYTES_PER_LED == channel {
+                SWEEP_LEVEL
+            } else {
+                0
+            };
+        }
+        board.forward_raw_stream(&frame).await?;
+        embassy_time::Timer::after_millis(SWEEP_STEP_MS as u64).await;
+    }
+    frame.fill(0);
+    board.forward_raw_stream(&frame).await
+}
+
+/// Run the self-test, including the failures reported during set-up
+pub async fn run() -> Result<(), Failure> {
+    STRIP_CHECKED.wait().await;
+    if let Some(failure) = Failure::from_code(HARDWARE_FAILURE.load(Ordering::Relaxed)) {
+        return Err(failure);
+    }
+
+    let mut probe: Vec<u8> = Vec::new();
+    if probe
+        .try_reserve_exact(config::SELF_TEST_HEAP_PROBE_BYTES)
+        .is_err()
+    {
+        println!(
+            "[SELFTEST] Couldn't allocate {} bytes ({} free)",
+            config::SELF_TEST_HEAP_PROBE_BYTES,
+            esp_alloc::HEAP.free()
+        );
+        return Err(Failure::Heap);
+    }
+    drop(probe);
+
+    println!(
+        "[SELFTEST] Passed ({} bytes heap free)",
+        esp_alloc::HEAP.free()
+    );
+    Ok(())
+}
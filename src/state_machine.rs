@@ -1,12 +1,97 @@
 //! 系统状态机模块
 //!
 //! 管理ESP32固件的所有系统状态，包括网络连接、服务通信、LED渲染等
+//!
+//! 状态机只由状态机任务持有。其他任务通过 `send_event` 把事件放入队列，
+//! 并通过 `current_state` / `is_network_degraded` 读取最近发布的状态，无需加锁。
+//! 需要在状态变化时得到通知的模块（遥测、日志、MQTT）用 `register_observer`
+//! 注册观察者，无需轮询。
+
+use crate::BoardError;
+use crate::config;
+use crate::led_status::LedStatus;
+use crate::println;
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Instant};
+
+/// 事件队列容量，足以容纳状态机任务两次循环之间的事件
+const EVENT_QUEUE_SIZE: usize = 32;
+
+/// 发往状态机任务的事件队列
+static EVENTS: Channel<CriticalSectionRawMutex, SystemEvent, EVENT_QUEUE_SIZE> = Channel::new();
+
+/// 最近发布的系统状态
+static CURRENT_STATE: AtomicU8 = AtomicU8::new(SystemState::SystemInit as u8);
+
+/// 最近发布的信号弱标志
+static NETWORK_DEGRADED: AtomicBool = AtomicBool::new(false);
+
+/// 向状态机任务发送事件（队列满时丢弃）
+pub fn send_event(event: SystemEvent) {
+    if EVENTS.try_send(event).is_err() {
+        println!("[STATE] Event queue full, dropped {:?}", event);
+    }
+}
+
+/// 取出一个待处理事件（仅供状态机任务使用）
+pub fn try_receive_event() -> Option<SystemEvent> {
+    EVENTS.try_receive().ok()
+}
+
+/// 获取最近发布的系统状态
+pub fn current_state() -> SystemState {
+    SystemState::from_code(CURRENT_STATE.load(Ordering::Relaxed)).unwrap_or(SystemState::SystemInit)
+}
+
+/// 检查最近发布的WiFi信号是否偏弱
+pub fn is_network_degraded() -> bool {
+    NETWORK_DEGRADED.load(Ordering::Relaxed)
+}
+
+/// 一次状态转换
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateChange {
+    pub from: SystemState,
+    pub to: SystemState,
+    /// 触发转换的事件，强制转换时为 None
+    pub event: Option<SystemEvent>,
+}
+
+/// 状态转换观察者
+///
+/// 在状态机任务中同步调用，应立即返回（例如只发出信号或写入队列）。
+pub type TransitionObserver = fn(&StateChange);
+
+/// 可注册的观察者数量上限
+pub const MAX_OBSERVERS: usize = 8;
 
-use crate::led_control::LedStatus;
-use esp_println::println;
+/// 已注册的观察者
+static OBSERVERS: critical_section::Mutex<
+    RefCell<heapless::Vec<TransitionObserver, MAX_OBSERVERS>>,
+> = critical_section::Mutex::new(RefCell::new(heapless::Vec::new()));
+
+/// 注册状态转换观察者，之后的每次状态转换都会通知它
+pub fn register_observer(observer: TransitionObserver) -> Result<(), BoardError> {
+    critical_section::with(|cs| OBSERVERS.borrow_ref_mut(cs).push(observer))
+        .map_err(|_| BoardError::SystemError)
+}
+
+/// 通知所有观察者（在锁外调用，观察者可以再注册或发送事件）
+fn notify_observers(change: &StateChange) {
+    let observers = critical_section::with(|cs| OBSERVERS.borrow_ref(cs).clone());
+    for observer in observers {
+        observer(change);
+    }
+}
 
 /// 系统状态枚举 - 简化版本
+///
+/// 声明顺序即 0x01 心跳回复中的状态码，新增状态只能追加在末尾
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
 pub enum SystemState {
     // 初始化状态
     SystemInit,
@@ -31,6 +116,49 @@ pub enum SystemState {
 
     // 恢复状态
     Reconnecting,
+
+    // 配网状态 - 热点和配网页面已开启
+    Provisioning,
+
+    // 固件更新状态 - 下载中 / 写入并切换分区中，LED数据暂停
+    OtaDownloading,
+    OtaApplying,
+
+    // 服务发现状态 - mDNS 响应器启动失败 / 退避后重试中，UDP 服务照常运行
+    MdnsFailed,
+    MdnsRetrying,
+
+    // 启动自检状态 - 检查堆内存和LED驱动，失败则停在硬件错误状态
+    SelfTest,
+    HardwareError,
+}
+
+impl SystemState {
+    /// 由 0x01 心跳回复中的状态码还原状态
+    pub fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            0 => SystemState::SystemInit,
+            1 => SystemState::WiFiConnecting,
+            2 => SystemState::DHCPRequesting,
+            3 => SystemState::NetworkReady,
+            4 => SystemState::UDPStarting,
+            5 => SystemState::UDPListening,
+            6 => SystemState::Operational,
+            7 => SystemState::UDPTimeout,
+            8 => SystemState::WiFiError,
+            9 => SystemState::DHCPError,
+            10 => SystemState::UDPError,
+            11 => SystemState::Reconnecting,
+            12 => SystemState::Provisioning,
+            13 => SystemState::OtaDownloading,
+            14 => SystemState::OtaApplying,
+            15 => SystemState::MdnsFailed,
+            16 => SystemState::MdnsRetrying,
+            17 => SystemState::SelfTest,
+            18 => SystemState::HardwareError,
+            _ => return None,
+        })
+    }
 }
 
 /// 系统事件枚举 - 简化版本
@@ -58,6 +186,30 @@ pub enum SystemEvent {
     WiFiConnectionFailed,
     RecoveryRequested,
     StateTimeout,
+
+    // 配网事件 - 无可用凭据或连接多次失败
+    ProvisioningRequested,
+
+    // 信号事件 - RSSI 低于阈值 / 恢复
+    NetworkDegraded,
+    NetworkRecovered,
+
+    // 固件更新事件 - 开始下载 / 下载校验完成 / 失败或中止
+    OtaStarted,
+    OtaDownloaded,
+    OtaFailed,
+
+    // 远程重启请求
+    RebootRequested,
+
+    // 服务发现事件 - mDNS 响应器启动失败 / 开始重试 / 启动成功
+    MdnsFailed,
+    MdnsRetrying,
+    MdnsStarted,
+
+    // 启动自检事件
+    SelfTestPassed,
+    SelfTestFailed,
 }
 
 /// 状态转换结果
@@ -98,8 +250,281 @@ pub enum Action {
     LogError(SystemState),
     /// 重置重试计数
     ResetRetryCount,
+    /// 开启配网热点
+    StartProvisioning,
+    /// 执行启动自检
+    RunSelfTest,
+    /// 软件复位重启
+    Reboot,
+}
+
+/// 转换条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Guard {
+    /// 无条件
+    Always,
+    /// 重试次数已用完
+    RetriesExhausted,
+}
+
+/// 状态转换表中的一条规则
+///
+/// `from` / `events` 为空时匹配任意状态 / 事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransitionRule {
+    pub from: &'static [SystemState],
+    pub events: &'static [SystemEvent],
+    pub guard: Guard,
+    pub transition: StateTransition,
+}
+
+impl TransitionRule {
+    /// 无条件规则
+    const fn new(
+        from: &'static [SystemState],
+        events: &'static [SystemEvent],
+        transition: StateTransition,
+    ) -> Self {
+        Self {
+            from,
+            events,
+            guard: Guard::Always,
+            transition,
+        }
+    }
+
+    /// 仅在重试次数用完后生效
+    const fn when_retries_exhausted(self) -> Self {
+        Self {
+            guard: Guard::RetriesExhausted,
+            ..self
+        }
+    }
+
+    /// 检查规则是否适用于当前状态和事件
+    pub fn matches(&self, state: SystemState, event: SystemEvent, retries_exhausted: bool) -> bool {
+        (self.from.is_empty() || self.from.contains(&state))
+            && (self.events.is_empty() || self.events.contains(&event))
+            && match self.guard {
+                Guard::Always => true,
+                Guard::RetriesExhausted => retries_exhausted,
+            }
+    }
 }
 
+/// 匹配任意状态 / 事件
+const ANY_STATE: &[SystemState] = &[];
+const ANY_EVENT: &[SystemEvent] = &[];
+
+/// 状态转换表 - 按顺序匹配，第一条匹配的规则生效，无匹配时保持当前状态
+pub const TRANSITION_TABLE: &[TransitionRule] = {
+    use StateTransition::{Stay, Transition, TransitionWithReset};
+    use SystemEvent as E;
+    use SystemState as S;
+
+    &[
+        // 系统启动流程 - 先自检，通过后连接WiFi；硬件错误需要重启
+        TransitionRule::new(
+            &[S::SystemInit],
+            &[E::SystemStarted],
+            Transition(S::SelfTest),
+        ),
+        TransitionRule::new(
+            &[S::SelfTest],
+            &[E::SelfTestPassed],
+            Transition(S::WiFiConnecting),
+        ),
+        TransitionRule::new(
+            &[S::SelfTest],
+            &[E::SelfTestFailed],
+            Transition(S::HardwareError),
+        ),
+        TransitionRule::new(&[S::SelfTest, S::HardwareError], ANY_EVENT, Stay),
+        // WiFi连接流程 - WiFi连接成功后进行DHCP，重试用完后进入错误状态
+        TransitionRule::new(
+            &[S::WiFiConnecting],
+            &[E::WiFiConnected],
+            TransitionWithReset(S::DHCPRequesting),
+        ),
+        TransitionRule::new(
+            &[S::WiFiConnecting],
+            &[E::WiFiConnectionFailed],
+            Transition(S::WiFiError),
+        )
+        .when_retries_exhausted(),
+        TransitionRule::new(&[S::WiFiConnecting], &[E::WiFiConnectionFailed], Stay),
+        TransitionRule::new(
+            &[S::WiFiConnecting],
+            &[E::StateTimeout],
+            Transition(S::WiFiError),
+        ),
+        // DHCP流程 - 获取IP后启动UDP服务
+        TransitionRule::new(
+            &[S::DHCPRequesting],
+            &[E::DHCPSuccess],
+            TransitionWithReset(S::NetworkReady),
+        ),
+        TransitionRule::new(
+            &[S::DHCPRequesting],
+            &[E::DHCPFailed],
+            Transition(S::DHCPError),
+        )
+        .when_retries_exhausted(),
+        TransitionRule::new(&[S::DHCPRequesting], &[E::DHCPFailed], Stay),
+        TransitionRule::new(
+            &[S::DHCPRequesting],
+            &[E::StateTimeout],
+            Transition(S::DHCPError),
+        ),
+        // 网络就绪后启动UDP服务
+        TransitionRule::new(
+            &[S::NetworkReady],
+            &[E::UDPServerStarted],
+            Transition(S::UDPStarting),
+        ),
+        TransitionRule::new(
+            &[S::NetworkReady],
+            &[E::StateTimeout],
+            Transition(S::UDPError),
+        ),
+        // UDP服务启动
+        TransitionRule::new(
+            &[S::UDPStarting],
+            &[E::UDPServerStarted],
+            TransitionWithReset(S::UDPListening),
+        ),
+        TransitionRule::new(
+            &[S::UDPStarting],
+            &[E::UDPServerFailed, E::StateTimeout],
+            Transition(S::UDPError),
+        ),
+        // UDP监听状态 - 收到0x01消息表示正常
+        TransitionRule::new(
+            &[S::UDPListening],
+            &[E::ConnectionCheckReceived],
+            TransitionWithReset(S::Operational),
+        ),
+        TransitionRule::new(
+            &[S::UDPListening],
+            &[E::UDPTimeout],
+            Transition(S::UDPTimeout),
+        ),
+        // 正常运行状态 - 处理LED数据 / 重置UDP超时计时器时保持运行状态
+        TransitionRule::new(
+            &[S::Operational],
+            &[E::LEDDataReceived, E::ConnectionCheckReceived],
+            Stay,
+        ),
+        TransitionRule::new(
+            &[S::Operational],
+            &[E::UDPTimeout],
+            Transition(S::UDPTimeout),
+        ),
+        // UDP超时处理
+        TransitionRule::new(
+            &[S::UDPTimeout],
+            &[E::ConnectionCheckReceived],
+            TransitionWithReset(S::Operational),
+        ),
+        // 固件更新流程 - 网络可用时才能开始，失败或超时后恢复服务
+        TransitionRule::new(
+            &[
+                S::NetworkReady,
+                S::UDPStarting,
+                S::UDPListening,
+                S::Operational,
+                S::UDPTimeout,
+                S::UDPError,
+            ],
+            &[E::OtaStarted],
+            Transition(S::OtaDownloading),
+        ),
+        TransitionRule::new(
+            &[S::OtaDownloading],
+            &[E::OtaDownloaded],
+            Transition(S::OtaApplying),
+        ),
+        TransitionRule::new(
+            &[S::OtaDownloading, S::OtaApplying],
+            &[E::OtaFailed, E::StateTimeout],
+            TransitionWithReset(S::UDPListening),
+        ),
+        TransitionRule::new(
+            &[S::OtaDownloading, S::OtaApplying],
+            &[E::WiFiDisconnected],
+            Transition(S::Reconnecting),
+        ),
+        TransitionRule::new(&[S::OtaDownloading, S::OtaApplying], ANY_EVENT, Stay),
+        // 服务发现流程 - UDP 服务可用后 mDNS 失败则转入失败状态，退避重试，成功后恢复监听
+        TransitionRule::new(
+            &[S::UDPListening, S::Operational, S::UDPTimeout],
+            &[E::MdnsFailed],
+            Transition(S::MdnsFailed),
+        ),
+        TransitionRule::new(
+            &[S::MdnsFailed],
+            &[E::MdnsRetrying],
+            Transition(S::MdnsRetrying),
+        ),
+        TransitionRule::new(
+            &[S::MdnsRetrying],
+            &[E::MdnsFailed],
+            Transition(S::MdnsFailed),
+        ),
+        TransitionRule::new(
+            &[S::MdnsFailed, S::MdnsRetrying],
+            &[E::MdnsStarted],
+            TransitionWithReset(S::UDPListening),
+        ),
+        // 配网流程 - 保存凭据后重启；有凭据时可重新尝试连接
+        TransitionRule::new(
+            &[S::Provisioning],
+            &[E::RecoveryRequested],
+            TransitionWithReset(S::WiFiConnecting),
+        ),
+        TransitionRule::new(&[S::Provisioning], ANY_EVENT, Stay),
+        TransitionRule::new(
+            ANY_STATE,
+            &[E::ProvisioningRequested],
+            Transition(S::Provisioning),
+        ),
+        // WiFi断开处理 - 需要重置DHCP
+        TransitionRule::new(
+            ANY_STATE,
+            &[E::WiFiDisconnected],
+            Transition(S::Reconnecting),
+        ),
+        // 重连流程 - WiFi重连后重新DHCP
+        TransitionRule::new(
+            &[S::Reconnecting],
+            &[E::WiFiConnected],
+            Transition(S::DHCPRequesting),
+        ),
+        // 错误恢复
+        TransitionRule::new(
+            &[S::WiFiError],
+            &[E::RecoveryRequested],
+            Transition(S::WiFiConnecting),
+        ),
+        TransitionRule::new(
+            &[S::DHCPError],
+            &[E::RecoveryRequested],
+            Transition(S::DHCPRequesting),
+        ),
+        TransitionRule::new(
+            &[S::UDPError, S::UDPTimeout],
+            &[E::RecoveryRequested],
+            Transition(S::UDPStarting),
+        ),
+    ]
+};
+
+/// 单次 update 最多产生的动作数
+pub const MAX_ACTIONS: usize = 4;
+
+/// update 返回的动作列表，不占用堆内存
+pub type Actions = heapless::Vec<Action, MAX_ACTIONS>;
+
 /// 错误上下文信息
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ErrorContext {
@@ -112,13 +537,21 @@ pub struct ErrorContext {
 pub struct SystemStateMachine {
     current_state: SystemState,
     previous_state: Option<SystemState>,
-    state_entry_time: u64,
+    state_entry_time: Instant,
     retry_count: u32,
     error_context: Option<ErrorContext>,
     max_retries: u32,
     mdns_started: bool, // Track if mDNS has been started
     monitor_counter: u32,
     monitor_interval: u32,
+    network_degraded: bool, // WiFi信号弱
+    reboot_requested: bool,
+}
+
+impl Default for SystemStateMachine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SystemStateMachine {
@@ -127,13 +560,15 @@ impl SystemStateMachine {
         Self {
             current_state: SystemState::SystemInit,
             previous_state: None,
-            state_entry_time: 0,
+            state_entry_time: Instant::now(),
             retry_count: 0,
             error_context: None,
             max_retries: 3,
             mdns_started: false,
             monitor_counter: 0,
             monitor_interval: 50, // Monitor every 50 state machine cycles
+            network_degraded: false,
+            reboot_requested: false,
         }
     }
 
@@ -152,6 +587,25 @@ impl SystemStateMachine {
         self.retry_count
     }
 
+    /// 获取在当前状态停留的时间
+    pub fn time_in_state(&self) -> Duration {
+        self.state_entry_time.elapsed()
+    }
+
+    /// 各状态的最长停留时间，超时后产生 StateTimeout 事件
+    fn state_timeout(state: SystemState) -> Option<Duration> {
+        let limit_ms = match state {
+            SystemState::WiFiConnecting => config::WIFI_CONNECTING_STATE_TIMEOUT_MS,
+            SystemState::DHCPRequesting => config::DHCP_STATE_TIMEOUT_MS,
+            SystemState::NetworkReady | SystemState::UDPStarting => {
+                config::SERVICE_START_STATE_TIMEOUT_MS
+            }
+            SystemState::OtaDownloading | SystemState::OtaApplying => config::OTA_STATE_TIMEOUT_MS,
+            _ => 0,
+        };
+        (limit_ms > 0).then(|| Duration::from_millis(limit_ms as u64))
+    }
+
     /// 获取对应的LED状态
     pub fn get_led_status(&self) -> LedStatus {
         match self.current_state {
@@ -167,32 +621,85 @@ impl SystemStateMachine {
             SystemState::DHCPError => LedStatus::NetworkError,
             SystemState::UDPError => LedStatus::ServiceError,
             SystemState::Reconnecting => LedStatus::Reconnecting,
+            SystemState::Provisioning => LedStatus::Provisioning,
+            SystemState::OtaDownloading => LedStatus::OtaDownloading,
+            SystemState::OtaApplying => LedStatus::OtaApplying,
+            SystemState::MdnsFailed => LedStatus::DiscoveryError,
+            SystemState::MdnsRetrying => LedStatus::MDNSAdvertising,
+            SystemState::SelfTest => LedStatus::HardwareInit,
+            SystemState::HardwareError => LedStatus::HardwareError,
         }
     }
 
     /// 处理系统事件
     pub fn handle_event(&mut self, event: SystemEvent) -> StateTransition {
+        // 信号质量不改变状态，只记录下来供状态上报
+        match event {
+            SystemEvent::NetworkDegraded => {
+                println!("[STATE] Network degraded (weak signal)");
+                self.network_degraded = true;
+            }
+            SystemEvent::NetworkRecovered => {
+                println!("[STATE] Network signal recovered");
+                self.network_degraded = false;
+            }
+            SystemEvent::WiFiDisconnected => self.network_degraded = false,
+            // 重启不改变状态，由下一次 update 产生 Reboot 动作
+            SystemEvent::RebootRequested => {
+                println!("[STATE] Reboot requested");
+                self.reboot_requested = true;
+            }
+            _ => {}
+        }
+
         let transition = self.get_state_transition(self.current_state, event);
 
         match transition {
             StateTransition::Transition(new_state) => {
-                self.transition_to_state(new_state);
+                self.transition_to_state(new_state, Some(event));
             }
             StateTransition::TransitionWithReset(new_state) => {
                 self.retry_count = 0;
-                self.transition_to_state(new_state);
+                self.transition_to_state(new_state, Some(event));
             }
             StateTransition::Stay => {
                 // 保持当前状态，可能需要更新重试计数
             }
         }
 
+        self.publish();
         transition
     }
 
+    /// 发布当前状态，供其他任务读取
+    fn publish(&self) {
+        CURRENT_STATE.store(self.current_state as u8, Ordering::Relaxed);
+        NETWORK_DEGRADED.store(self.network_degraded, Ordering::Relaxed);
+    }
+
     /// 状态机更新，返回需要执行的动作
-    pub fn update(&mut self) -> alloc::vec::Vec<Action> {
-        let mut actions = alloc::vec::Vec::new();
+    pub fn update(&mut self) -> Actions {
+        let mut actions = Actions::new();
+
+        // 重启优先于其他动作
+        if core::mem::take(&mut self.reboot_requested) {
+            actions.push(Action::Reboot).ok();
+            return actions;
+        }
+
+        // 状态超时检查 - 超时状态转入对应的错误状态
+        if Self::state_timeout(self.current_state)
+            .is_some_and(|limit| self.time_in_state() >= limit)
+        {
+            println!(
+                "[STATE] {:?} timed out after {}s",
+                self.current_state,
+                self.time_in_state().as_secs()
+            );
+            self.handle_event(SystemEvent::StateTimeout);
+            // 没有超时转换的状态重新计时
+            self.state_entry_time = Instant::now();
+        }
 
         // Check if this is the first time entering this state
         let is_state_entry = self.previous_state != Some(self.current_state);
@@ -205,38 +712,46 @@ impl SystemStateMachine {
         // 根据当前状态生成相应的动作
         match self.current_state {
             SystemState::SystemInit => {
-                actions.push(Action::UpdateLEDStatus(LedStatus::Starting));
+                actions
+                    .push(Action::UpdateLEDStatus(LedStatus::Starting))
+                    .ok();
             }
 
             SystemState::WiFiConnecting => {
-                actions.push(Action::UpdateLEDStatus(LedStatus::WiFiConnecting));
-                actions.push(Action::StartWiFiConnection);
+                actions
+                    .push(Action::UpdateLEDStatus(LedStatus::WiFiConnecting))
+                    .ok();
+                actions.push(Action::StartWiFiConnection).ok();
             }
 
             SystemState::DHCPRequesting => {
-                actions.push(Action::UpdateLEDStatus(LedStatus::DHCPRequesting));
-                actions.push(Action::StartDHCPRequest);
+                actions
+                    .push(Action::UpdateLEDStatus(LedStatus::DHCPRequesting))
+                    .ok();
+                actions.push(Action::StartDHCPRequest).ok();
             }
 
             SystemState::NetworkReady => {
-                actions.push(Action::UpdateLEDStatus(LedStatus::NetworkReady));
-                actions.push(Action::StartNetworkServices);
+                actions
+                    .push(Action::UpdateLEDStatus(LedStatus::NetworkReady))
+                    .ok();
+                actions.push(Action::StartNetworkServices).ok();
             }
 
             SystemState::UDPStarting => {
-                actions.push(Action::StartUDPServer);
+                actions.push(Action::StartUDPServer).ok();
             }
 
             SystemState::UDPListening => {
                 // Start mDNS service only once when first entering this state
                 if is_state_entry && !self.mdns_started {
-                    actions.push(Action::StartMDNSService);
+                    actions.push(Action::StartMDNSService).ok();
                     self.mdns_started = true;
                 }
                 // Monitor connection periodically, not every cycle
                 self.monitor_counter += 1;
                 if self.monitor_counter >= self.monitor_interval {
-                    actions.push(Action::MonitorConnection);
+                    actions.push(Action::MonitorConnection).ok();
                     self.monitor_counter = 0;
                 }
             }
@@ -245,48 +760,107 @@ impl SystemStateMachine {
                 // Monitor connection periodically, not every cycle
                 self.monitor_counter += 1;
                 if self.monitor_counter >= self.monitor_interval {
-                    actions.push(Action::MonitorConnection);
+                    actions.push(Action::MonitorConnection).ok();
                     self.monitor_counter = 0;
                 }
-                actions.push(Action::ProcessLEDData);
+                actions.push(Action::ProcessLEDData).ok();
             }
 
             SystemState::UDPTimeout => {
-                actions.push(Action::UpdateLEDStatus(LedStatus::ServiceError));
-                actions.push(Action::LogError(self.current_state));
+                actions
+                    .push(Action::UpdateLEDStatus(LedStatus::ServiceError))
+                    .ok();
+                actions.push(Action::LogError(self.current_state)).ok();
                 if self.retry_count < self.max_retries {
-                    actions.push(Action::RestartServices);
+                    actions.push(Action::RestartServices).ok();
                 }
             }
 
             // 错误状态处理
             SystemState::WiFiError => {
-                actions.push(Action::UpdateLEDStatus(LedStatus::WiFiError));
-                actions.push(Action::LogError(self.current_state));
+                actions
+                    .push(Action::UpdateLEDStatus(LedStatus::WiFiError))
+                    .ok();
+                actions.push(Action::LogError(self.current_state)).ok();
                 if self.retry_count < self.max_retries {
-                    actions.push(Action::SystemRecover);
+                    actions.push(Action::SystemRecover).ok();
                 }
             }
 
             SystemState::DHCPError => {
-                actions.push(Action::UpdateLEDStatus(LedStatus::NetworkError));
-                actions.push(Action::LogError(self.current_state));
+                actions
+                    .push(Action::UpdateLEDStatus(LedStatus::NetworkError))
+                    .ok();
+                actions.push(Action::LogError(self.current_state)).ok();
                 if self.retry_count < self.max_retries {
-                    actions.push(Action::SystemRecover);
+                    actions.push(Action::SystemRecover).ok();
                 }
             }
 
             SystemState::UDPError => {
-                actions.push(Action::UpdateLEDStatus(LedStatus::ServiceError));
-                actions.push(Action::LogError(self.current_state));
+                actions
+                    .push(Action::UpdateLEDStatus(LedStatus::ServiceError))
+                    .ok();
+                actions.push(Action::LogError(self.current_state)).ok();
                 if self.retry_count < self.max_retries {
-                    actions.push(Action::RestartServices);
+                    actions.push(Action::RestartServices).ok();
                 }
             }
 
             SystemState::Reconnecting => {
-                actions.push(Action::UpdateLEDStatus(LedStatus::Reconnecting));
-                actions.push(Action::SystemRecover);
+                actions
+                    .push(Action::UpdateLEDStatus(LedStatus::Reconnecting))
+                    .ok();
+                actions.push(Action::StartWiFiConnection).ok();
+            }
+
+            SystemState::Provisioning => {
+                actions
+                    .push(Action::UpdateLEDStatus(LedStatus::Provisioning))
+                    .ok();
+                if is_state_entry {
+                    actions.push(Action::StartProvisioning).ok();
+                }
+            }
+
+            SystemState::OtaDownloading => {
+                actions
+                    .push(Action::UpdateLEDStatus(LedStatus::OtaDownloading))
+                    .ok();
+            }
+
+            SystemState::OtaApplying => {
+                actions
+                    .push(Action::UpdateLEDStatus(LedStatus::OtaApplying))
+                    .ok();
+            }
+
+            SystemState::MdnsFailed | SystemState::MdnsRetrying => {
+                actions
+                    .push(Action::UpdateLEDStatus(self.get_led_status()))
+                    .ok();
+                // UDP 服务仍在运行，继续定期监控连接
+                self.monitor_counter += 1;
+                if self.monitor_counter >= self.monitor_interval {
+                    actions.push(Action::MonitorConnection).ok();
+                    self.monitor_counter = 0;
+                }
+            }
+
+            SystemState::SelfTest => {
+                actions
+                    .push(Action::UpdateLEDStatus(LedStatus::HardwareInit))
+                    .ok();
+                if is_state_entry {
+                    actions.push(Action::RunSelfTest).ok();
+                }
+            }
+
+            SystemState::HardwareError => {
+                actions
+                    .push(Action::UpdateLEDStatus(LedStatus::HardwareError))
+                    .ok();
+                actions.push(Action::LogError(self.current_state)).ok();
             }
         }
 
@@ -297,12 +871,15 @@ impl SystemStateMachine {
     }
 
     /// 内部状态转换逻辑
-    fn transition_to_state(&mut self, new_state: SystemState) {
+    fn transition_to_state(&mut self, new_state: SystemState, event: Option<SystemEvent>) {
         if new_state != self.current_state {
             // Only print critical state changes
             match new_state {
                 SystemState::Operational => println!("[STATE] System operational"),
-                SystemState::WiFiError | SystemState::DHCPError | SystemState::UDPError => {
+                SystemState::WiFiError
+                | SystemState::DHCPError
+                | SystemState::UDPError
+                | SystemState::HardwareError => {
                     println!("[STATE] Error state: {:?}", new_state);
                 }
                 SystemState::UDPListening => {
@@ -311,118 +888,30 @@ impl SystemStateMachine {
                 _ => {} // Silent for normal transitions
             }
 
+            let change = StateChange {
+                from: self.current_state,
+                to: new_state,
+                event,
+            };
             self.previous_state = Some(self.current_state);
             self.current_state = new_state;
-            self.state_entry_time = 0; // 在实际实现中应该使用真实时间
+            self.state_entry_time = Instant::now();
+            self.publish();
+            notify_observers(&change);
         }
     }
 
-    /// 获取状态转换规则 - 简化版本
+    /// 按转换表查找事件对应的状态转换
     fn get_state_transition(
         &self,
         current_state: SystemState,
         event: SystemEvent,
     ) -> StateTransition {
-        match (current_state, event) {
-            // 系统启动流程
-            (SystemState::SystemInit, SystemEvent::SystemStarted) => {
-                StateTransition::Transition(SystemState::WiFiConnecting)
-            }
-
-            // WiFi连接流程 - WiFi连接成功后进行DHCP
-            (SystemState::WiFiConnecting, SystemEvent::WiFiConnected) => {
-                StateTransition::TransitionWithReset(SystemState::DHCPRequesting)
-            }
-            (SystemState::WiFiConnecting, SystemEvent::WiFiConnectionFailed) => {
-                if self.retry_count < self.max_retries {
-                    StateTransition::Stay // 继续重试
-                } else {
-                    StateTransition::Transition(SystemState::WiFiError)
-                }
-            }
-            (SystemState::WiFiConnecting, SystemEvent::StateTimeout) => {
-                StateTransition::Transition(SystemState::WiFiError)
-            }
-
-            // DHCP流程 - 获取IP后启动UDP服务
-            (SystemState::DHCPRequesting, SystemEvent::DHCPSuccess) => {
-                StateTransition::TransitionWithReset(SystemState::NetworkReady)
-            }
-            (SystemState::DHCPRequesting, SystemEvent::DHCPFailed) => {
-                if self.retry_count < self.max_retries {
-                    StateTransition::Stay // 继续重试DHCP
-                } else {
-                    StateTransition::Transition(SystemState::DHCPError)
-                }
-            }
-            (SystemState::DHCPRequesting, SystemEvent::StateTimeout) => {
-                StateTransition::Transition(SystemState::DHCPError)
-            }
-
-            // 网络就绪后启动UDP服务
-            (SystemState::NetworkReady, SystemEvent::UDPServerStarted) => {
-                StateTransition::Transition(SystemState::UDPStarting)
-            }
-
-            // UDP服务启动
-            (SystemState::UDPStarting, SystemEvent::UDPServerStarted) => {
-                StateTransition::TransitionWithReset(SystemState::UDPListening)
-            }
-            (SystemState::UDPStarting, SystemEvent::UDPServerFailed) => {
-                StateTransition::Transition(SystemState::UDPError)
-            }
-
-            // UDP监听状态 - 收到0x01消息表示正常
-            (SystemState::UDPListening, SystemEvent::ConnectionCheckReceived) => {
-                StateTransition::TransitionWithReset(SystemState::Operational)
-            }
-            (SystemState::UDPListening, SystemEvent::UDPTimeout) => {
-                StateTransition::Transition(SystemState::UDPTimeout)
-            }
-
-            // 正常运行状态
-            (SystemState::Operational, SystemEvent::LEDDataReceived) => {
-                StateTransition::Stay // 处理LED数据但保持运行状态
-            }
-            (SystemState::Operational, SystemEvent::ConnectionCheckReceived) => {
-                StateTransition::Stay // 重置UDP超时计时器
-            }
-            (SystemState::Operational, SystemEvent::UDPTimeout) => {
-                StateTransition::Transition(SystemState::UDPTimeout)
-            }
-
-            // UDP超时处理
-            (SystemState::UDPTimeout, SystemEvent::ConnectionCheckReceived) => {
-                StateTransition::TransitionWithReset(SystemState::Operational)
-            }
-
-            // WiFi断开处理 - 需要重置DHCP
-            (_, SystemEvent::WiFiDisconnected) => {
-                StateTransition::Transition(SystemState::Reconnecting)
-            }
-
-            // 重连流程
-            (SystemState::Reconnecting, SystemEvent::WiFiConnected) => {
-                StateTransition::Transition(SystemState::DHCPRequesting) // WiFi重连后重新DHCP
-            }
-
-            // 错误恢复
-            (SystemState::WiFiError, SystemEvent::RecoveryRequested) => {
-                StateTransition::Transition(SystemState::WiFiConnecting)
-            }
-            (SystemState::DHCPError, SystemEvent::RecoveryRequested) => {
-                StateTransition::Transition(SystemState::DHCPRequesting)
-            }
-            (SystemState::UDPError, SystemEvent::RecoveryRequested) => {
-                StateTransition::Transition(SystemState::UDPStarting)
-            }
-            (SystemState::UDPTimeout, SystemEvent::RecoveryRequested) => {
-                StateTransition::Transition(SystemState::UDPStarting)
-            }
-
-            // 默认情况：保持当前状态
-            _ => StateTransition::Stay,
-        }
+        let retries_exhausted = self.retry_count >= self.max_retries;
+        TRANSITION_TABLE
+            .iter()
+            .find(|rule| rule.matches(current_state, event, retries_exhausted))
+            .map_or(StateTransition::Stay, |rule| rule.transition)
     }
 
     /// 检查是否需要重试
@@ -468,6 +957,7 @@ impl SystemStateMachine {
                 | SystemState::DHCPError
                 | SystemState::UDPError
                 | SystemState::UDPTimeout
+                | SystemState::HardwareError
         )
     }
 
@@ -481,9 +971,15 @@ impl SystemStateMachine {
 
     /// 强制转换到指定状态（用于紧急情况）
     pub fn force_transition(&mut self, new_state: SystemState) {
-        self.transition_to_state(new_state);
+        self.transition_to_state(new_state, None);
         self.retry_count = 0;
         self.error_context = None;
+        self.publish();
+    }
+
+    /// 检查WiFi信号是否偏弱
+    pub fn is_network_degraded(&self) -> bool {
+        self.network_degraded
     }
 
     /// 标记 mDNS 服务已启动
@@ -491,3 +987,216 @@ impl SystemStateMachine {
         self.mdns_started = true;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 依次处理事件，返回最终状态
+    fn run(machine: &mut SystemStateMachine, events: &[SystemEvent]) -> SystemState {
+        for &event in events {
+            machine.handle_event(event);
+        }
+        machine.get_current_state()
+    }
+
+    /// 所有状态，按状态码顺序
+    fn all_states() -> impl Iterator<Item = SystemState> {
+        (0..=u8::MAX).map_while(SystemState::from_code)
+    }
+
+    #[test]
+    fn state_codes_round_trip() {
+        for (code, state) in all_states().enumerate() {
+            assert_eq!(state as usize, code);
+        }
+        assert_eq!(
+            all_states().count(),
+            SystemState::HardwareError as usize + 1
+        );
+    }
+
+    #[test]
+    fn boots_to_operational() {
+        let mut machine = SystemStateMachine::new();
+        let state = run(
+            &mut machine,
+            &[
+                SystemEvent::SystemStarted,
+                SystemEvent::SelfTestPassed,
+                SystemEvent::WiFiConnected,
+                SystemEvent::DHCPSuccess,
+                SystemEvent::UDPServerStarted,
+                SystemEvent::UDPServerStarted,
+                SystemEvent::ConnectionCheckReceived,
+                SystemEvent::LEDDataReceived,
+            ],
+        );
+        assert_eq!(state, SystemState::Operational);
+        assert!(machine.is_operational());
+    }
+
+    #[test]
+    fn connection_failures_retry_until_exhausted() {
+        let mut machine = SystemStateMachine::new();
+        run(
+            &mut machine,
+            &[SystemEvent::SystemStarted, SystemEvent::SelfTestPassed],
+        );
+
+        while machine.should_retry() {
+            let transition = machine.handle_event(SystemEvent::WiFiConnectionFailed);
+            assert_eq!(transition, StateTransition::Stay);
+            machine.increment_retry();
+        }
+        let state = run(&mut machine, &[SystemEvent::WiFiConnectionFailed]);
+        assert_eq!(state, SystemState::WiFiError);
+        assert!(machine.is_error_state());
+
+        let state = run(&mut machine, &[SystemEvent::RecoveryRequested]);
+        assert_eq!(state, SystemState::WiFiConnecting);
+    }
+
+    #[test]
+    fn self_test_failure_stops_the_boot() {
+        let mut machine = SystemStateMachine::new();
+        let state = run(&mut machine, &[SystemEvent::SystemStarted]);
+        assert_eq!(state, SystemState::SelfTest);
+        assert!(machine.update().contains(&Action::RunSelfTest));
+
+        let state = run(
+            &mut machine,
+            &[
+                SystemEvent::SelfTestFailed,
+                SystemEvent::ProvisioningRequested,
+                SystemEvent::RecoveryRequested,
+            ],
+        );
+        assert_eq!(state, SystemState::HardwareError);
+        assert!(machine.is_error_state());
+        assert!(!machine.update().contains(&Action::SystemRecover));
+    }
+
+    #[test]
+    fn disconnect_leads_to_reconnecting() {
+        let mut machine = SystemStateMachine::new();
+        machine.force_transition(SystemState::Operational);
+        let state = run(&mut machine, &[SystemEvent::WiFiDisconnected]);
+        assert_eq!(state, SystemState::Reconnecting);
+        let state = run(&mut machine, &[SystemEvent::WiFiConnected]);
+        assert_eq!(state, SystemState::DHCPRequesting);
+    }
+
+    #[test]
+    fn ota_ignores_unrelated_events() {
+        let mut machine = SystemStateMachine::new();
+        machine.force_transition(SystemState::Operational);
+        let state = run(
+            &mut machine,
+            &[
+                SystemEvent::OtaStarted,
+                SystemEvent::ProvisioningRequested,
+                SystemEvent::UDPTimeout,
+            ],
+        );
+        assert_eq!(state, SystemState::OtaDownloading);
+
+        let state = run(&mut machine, &[SystemEvent::OtaDownloaded]);
+        assert_eq!(state, SystemState::OtaApplying);
+        let state = run(&mut machine, &[SystemEvent::OtaFailed]);
+        assert_eq!(state, SystemState::UDPListening);
+    }
+
+    #[test]
+    fn mdns_failure_recovers_to_listening() {
+        let mut machine = SystemStateMachine::new();
+        machine.force_transition(SystemState::Operational);
+        let state = run(
+            &mut machine,
+            &[
+                SystemEvent::MdnsFailed,
+                SystemEvent::ConnectionCheckReceived,
+            ],
+        );
+        assert_eq!(state, SystemState::MdnsFailed);
+        assert_eq!(machine.get_led_status(), LedStatus::DiscoveryError);
+
+        let state = run(
+            &mut machine,
+            &[SystemEvent::MdnsRetrying, SystemEvent::MdnsStarted],
+        );
+        assert_eq!(state, SystemState::UDPListening);
+    }
+
+    #[test]
+    fn observers_see_transitions() {
+        static SEEN: AtomicBool = AtomicBool::new(false);
+        fn observer(change: &StateChange) {
+            if change.to == SystemState::OtaApplying && change.event.is_none() {
+                assert_eq!(change.from, SystemState::SystemInit);
+                SEEN.store(true, Ordering::Relaxed);
+            }
+        }
+
+        register_observer(observer).unwrap();
+        SystemStateMachine::new().force_transition(SystemState::OtaApplying);
+        assert!(SEEN.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn provisioning_holds_until_recovery() {
+        let mut machine = SystemStateMachine::new();
+        run(
+            &mut machine,
+            &[SystemEvent::SystemStarted, SystemEvent::SelfTestPassed],
+        );
+        let state = run(
+            &mut machine,
+            &[
+                SystemEvent::ProvisioningRequested,
+                SystemEvent::WiFiDisconnected,
+            ],
+        );
+        assert_eq!(state, SystemState::Provisioning);
+        let state = run(&mut machine, &[SystemEvent::RecoveryRequested]);
+        assert_eq!(state, SystemState::WiFiConnecting);
+    }
+
+    #[test]
+    fn every_state_is_reachable() {
+        for state in all_states().filter(|&s| s != SystemState::SystemInit) {
+            let reachable = TRANSITION_TABLE.iter().any(|rule| {
+                matches!(
+                    rule.transition,
+                    StateTransition::Transition(to) | StateTransition::TransitionWithReset(to)
+                        if to == state
+                )
+            });
+            assert!(reachable, "{:?} has no incoming transition", state);
+        }
+    }
+
+    #[test]
+    fn reboot_preempts_other_actions() {
+        let mut machine = SystemStateMachine::new();
+        machine.force_transition(SystemState::UDPError);
+        machine.handle_event(SystemEvent::RebootRequested);
+        assert_eq!(machine.update().as_slice(), &[Action::Reboot]);
+
+        let actions = machine.update();
+        assert!(actions.contains(&Action::LogError(SystemState::UDPError)));
+        assert!(actions.contains(&Action::RestartServices));
+    }
+
+    #[test]
+    fn mdns_starts_once() {
+        let mut machine = SystemStateMachine::new();
+        machine.force_transition(SystemState::UDPListening);
+        assert!(machine.update().contains(&Action::StartMDNSService));
+
+        machine.force_transition(SystemState::Operational);
+        machine.update();
+        machine.force_transition(SystemState::UDPListening);
+        assert!(!machine.update().contains(&Action::StartMDNSService));
+    }
+}
@@ -0,0 +1,98 @@
+//! Chip temperature monitoring
+//!
+//! Boards mounted behind a TV get little airflow, and a chip running hot is
+//! usually the first sign before the radio starts dropping out. The internal
+//! sensor is sampled every `config::TEMPERATURE_SAMPLE_INTERVAL_MS`; the
+//! reading goes out with keepalive replies and the MQTT state, and crossing
+//! `config::TEMPERATURE_WARNING_C` sets the overheating flag until the chip
+//! cools back to `config::TEMPERATURE_RECOVERED_C`.
+//!
+//! The reading is the die temperature, which runs well above the air around
+//! the board. Only the ESP32-C3 and ESP32-C6 sensors are supported; on other
+//! chips no reading is ever available.
+
+use core::sync::atomic::{AtomicBool, AtomicI8, Ordering};
+
+/// Stored while no reading is available
+const UNKNOWN: i8 = i8::MIN;
+
+/// Last smoothed reading (°C), `UNKNOWN` before the first sample
+static LAST_CELSIUS: AtomicI8 = AtomicI8::new(UNKNOWN);
+
+/// Set while the chip is above the warning threshold
+static OVERHEATING: AtomicBool = AtomicBool::new(false);
+
+/// Chip temperature (°C) at the last sample
+pub fn last_celsius() -> Option<i8> {
+    match LAST_CELSIUS.load(Ordering::Relaxed) {
+        UNKNOWN => None,
+        celsius => Some(celsius),
+    }
+}
+
+/// Whether the chip is running above `config::TEMPERATURE_WARNING_C`
+pub fn is_overheating() -> bool {
+    OVERHEATING.load(Ordering::Relaxed)
+}
+
+/// Wire encoding of the reading: signed °C, 0x80 when unknown
+pub fn wire_byte() -> u8 {
+    LAST_CELSIUS.load(Ordering::Relaxed) as u8
+}
+
+#[cfg(any(feature = "esp32c3", feature = "esp32c6"))]
+pub use sensor::temperature_task;
+
+#[cfg(any(feature = "esp32c3", feature = "esp32c6"))]
+mod sensor {
+    use super::{LAST_CELSIUS, OVERHEATING};
+    use crate::config;
+    use crate::println;
+    use core::sync::atomic::Ordering;
+    use embassy_time::{Duration, Timer};
+    use esp_hal::tsens::TemperatureSensor;
+
+    /// Weight of a new sample in the running average; single readings jump
+    /// by a degree or two
+    const SMOOTHING: f32 = 0.25;
+
+    /// Round to whole degrees (no `f32::round` without std)
+    fn whole_degrees(celsius: f32) -> i8 {
+        if celsius >= 0.0 {
+            (celsius + 0.5) as i8
+        } else {
+            (celsius - 0.5) as i8
+        }
+    }
+
+    /// Temperature sampling task
+    #[embassy_executor::task]
+    pub async fn temperature_task(sensor: TemperatureSensor<'static>) {
+        // The sensor needs a few hundred microseconds after power-up
+        Timer::after(Duration::from_millis(1)).await;
+
+        let mut average: Option<f32> = None;
+        loop {
+            let reading = sensor.get_temperature().to_celsius();
+            let smoothed = average.map_or(reading, |a| a + (reading - a) * SMOOTHING);
+            average = Some(smoothed);
+
+            let celsius = whole_degrees(smoothed);
+            LAST_CELSIUS.store(celsius, Ordering::Relaxed);
+
+            let overheating = OVERHEATING.load(Ordering::Relaxed);
+            if !overheating && celsius >= config::TEMPERATURE_WARNING_C {
+                println!("[TEMP] ⚠️ Chip running hot: {}°C", celsius);
+                OVERHEATING.store(true, Ordering::Relaxed);
+            } else if overheating && celsius <= config::TEMPERATURE_RECOVERED_C {
+                println!("[TEMP] Chip cooled down: {}°C", celsius);
+                OVERHEATING.store(false, Ordering::Relaxed);
+            }
+
+            Timer::after(Duration::from_millis(
+                config::TEMPERATURE_SAMPLE_INTERVAL_MS as u64,
+            ))
+            .await;
+        }
+    }
+}
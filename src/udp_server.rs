@@ -1,25 +1,626 @@
 //! UDP communication server module
 //!
-//! Handles UDP socket creation, packet reception, and protocol parsing.
+//! Handles UDP socket creation, packet reception and dispatch; the packet
+//! formats themselves are decoded by `protocol`.
 
+use crate::println;
+use crate::protocol::{self, FrameAssembler};
 use crate::{BoardError, config};
 use embassy_net::{
-    Stack,
+    IpAddress, IpEndpoint, Stack,
     udp::{PacketMetadata, UdpSocket},
 };
-use esp_println::println;
+use embassy_time::Instant;
 use heapless::Vec;
 
-/// Maximum UDP packet size for LED data
-const MAX_PACKET_SIZE: usize = 4096;
+pub use crate::protocol::{LedPacket, MAX_PACKET_SIZE};
 
-/// UDP packet structure for LED data
-#[derive(Debug)]
-pub struct LedPacket {
-    /// LED start offset (16-bit big-endian)
-    pub offset: u16,
-    /// LED color data (RGB or RGBW)
-    pub data: Vec<u8, MAX_PACKET_SIZE>,
+/// Socket receive buffer size, room for a couple of maximum-size packets
+const RX_BUFFER_SIZE: usize = 2 * MAX_PACKET_SIZE;
+
+/// Socket transmit buffer size (replies, and chain forwarding of up to a whole packet)
+const TX_BUFFER_SIZE: usize = MAX_PACKET_SIZE;
+
+/// Socket and packet buffers of the UDP server
+///
+/// These live in a static rather than on the task stack, so raising
+/// `config::MAX_LEDS` (and with it `MAX_PACKET_SIZE`) grows `.bss` instead of
+/// risking a stack overflow.
+pub struct UdpBuffers {
+    rx_meta: [PacketMetadata; 16],
+    rx: [u8; RX_BUFFER_SIZE],
+    tx_meta: [PacketMetadata; 16],
+    tx: [u8; TX_BUFFER_SIZE],
+    packet: [u8; MAX_PACKET_SIZE],
+}
+
+impl UdpBuffers {
+    pub const fn new() -> Self {
+        Self {
+            rx_meta: [PacketMetadata::EMPTY; 16],
+            rx: [0; RX_BUFFER_SIZE],
+            tx_meta: [PacketMetadata::EMPTY; 16],
+            tx: [0; TX_BUFFER_SIZE],
+            packet: [0; MAX_PACKET_SIZE],
+        }
+    }
+}
+
+impl Default for UdpBuffers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sequence number tracking for sequenced LED data packets
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SequenceTracker {
+    last: Option<u16>,
+    /// Sequenced packets accepted
+    pub received: u32,
+    /// Packets missing from gaps in the sequence
+    pub lost: u32,
+    /// Out-of-date (reordered or duplicated) packets skipped
+    pub stale: u32,
+}
+
+impl SequenceTracker {
+    /// Record a sequence number, returning `false` if the packet is out of date
+    pub fn accept(&mut self, sequence: u16) -> bool {
+        if let Some(last) = self.last {
+            let delta = sequence.wrapping_sub(last) as i16;
+            if delta <= 0 && delta > -(config::SEQUENCE_RESYNC_WINDOW as i16) {
+                self.stale = self.stale.saturating_add(1);
+                return false;
+            }
+            if delta > 1 {
+                self.lost = self.lost.saturating_add(delta as u32 - 1);
+            }
+            // Larger backwards jumps mean the sender restarted - resync silently
+        }
+
+        self.last = Some(sequence);
+        self.received = self.received.saturating_add(1);
+        true
+    }
+
+    /// Forget the last sequence number (e.g. after the sender went away)
+    pub fn resync(&mut self) {
+        self.last = None;
+    }
+
+    /// Encode the counters and the per-sender delivery statistics as a
+    /// sequence statistics response:
+    /// `[header][received u32][lost u32][stale u32][sender count]`, then per
+    /// sender `[family (4 IPv4, 6 IPv6)][address][received u32][lost u32]`
+    /// `[reordered u32][duplicates u32][stalls u32]`
+    pub fn to_response(&self, senders: &SenderLossTable) -> Vec<u8, SEQUENCE_STATS_MAX> {
+        let mut response = Vec::new();
+        let _ = response.push(config::SEQUENCE_STATS_HEADER);
+        for value in [self.received, self.lost, self.stale] {
+            let _ = response.extend_from_slice(&value.to_be_bytes());
+        }
+
+        let _ = response.push(senders.senders.len() as u8);
+        for sender in &senders.senders {
+            match sender.source {
+                IpAddress::Ipv4(addr) => {
+                    let _ = response.push(4);
+                    let _ = response.extend_from_slice(&addr.octets());
+                }
+                IpAddress::Ipv6(addr) => {
+                    let _ = response.push(6);
+                    let _ = response.extend_from_slice(&addr.octets());
+                }
+            }
+            for value in [
+                sender.received,
+                sender.lost,
+                sender.reordered,
+                sender.duplicates,
+                sender.stalls,
+            ] {
+                let _ = response.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        response
+    }
+}
+
+/// Senders tracked by the loss statistics at once
+const MAX_LOSS_SENDERS: usize = 4;
+
+/// Largest sequence statistics response
+pub const SEQUENCE_STATS_MAX: usize = 14 + MAX_LOSS_SENDERS * (1 + 16 + 20);
+
+/// Sequence numbers behind the newest one remembered per sender, to tell a
+/// late packet from a duplicate
+const LOSS_WINDOW: u16 = 64;
+
+/// Delivery statistics of one sender, from the sequence numbers of its packets
+///
+/// Unlike [`SequenceTracker`], which decides what gets rendered, this only
+/// observes: a packet that arrives after a later one counts as reordered
+/// instead of lost, and a pause in an otherwise gapless stream counts as a
+/// stall. Gaps point at the network, stalls at the sender itself (e.g. a
+/// screen capture hiccup).
+#[derive(Debug, Clone, Copy)]
+pub struct SenderLoss {
+    /// Sender address
+    pub source: IpAddress,
+    highest: u16,
+    /// Bit n set: sequence number `highest - n` arrived
+    seen: u64,
+    last_seen: Instant,
+    /// Sequenced packets received, duplicates excluded
+    pub received: u32,
+    /// Sequence numbers that never arrived
+    pub lost: u32,
+    /// Packets that arrived after a later one
+    pub reordered: u32,
+    /// Packets that arrived twice
+    pub duplicates: u32,
+    /// Pauses of at least `config::SENDER_STALL_MS` with no packet missing
+    pub stalls: u32,
+}
+
+impl SenderLoss {
+    fn new(source: IpAddress, sequence: u16, now: Instant) -> Self {
+        Self {
+            source,
+            highest: sequence,
+            seen: 1,
+            last_seen: now,
+            received: 1,
+            lost: 0,
+            reordered: 0,
+            duplicates: 0,
+            stalls: 0,
+        }
+    }
+
+    fn record(&mut self, sequence: u16, now: Instant) {
+        let idle_ms = now.duration_since(self.last_seen).as_millis();
+        self.last_seen = now;
+
+        // A stream that went quiet for longer than the source lock timeout has
+        // ended; the next one may start anywhere
+        if idle_ms >= config::SOURCE_LOCK_TIMEOUT_MS as u64 {
+            self.highest = sequence;
+            self.seen = 1;
+            self.received = self.received.saturating_add(1);
+            return;
+        }
+
+        let delta = sequence.wrapping_sub(self.highest) as i16;
+        if delta > 0 {
+            let delta = delta as u16;
+            if delta == 1 && idle_ms >= config::SENDER_STALL_MS as u64 {
+                self.stalls = self.stalls.saturating_add(1);
+            }
+            // Provisionally lost; a late arrival takes it back
+            self.lost = self.lost.saturating_add(delta as u32 - 1);
+            self.seen = if delta >= LOSS_WINDOW {
+                0
+            } else {
+                self.seen << delta
+            };
+            self.seen |= 1;
+            self.highest = sequence;
+            self.received = self.received.saturating_add(1);
+        } else if delta.unsigned_abs() < LOSS_WINDOW {
+            let bit = 1u64 << delta.unsigned_abs();
+            if self.seen & bit != 0 {
+                self.duplicates = self.duplicates.saturating_add(1);
+            } else {
+                self.seen |= bit;
+                self.reordered = self.reordered.saturating_add(1);
+                self.lost = self.lost.saturating_sub(1);
+                self.received = self.received.saturating_add(1);
+            }
+        } else if delta.unsigned_abs() >= config::SEQUENCE_RESYNC_WINDOW {
+            // The sender restarted its sequence
+            self.highest = sequence;
+            self.seen = 1;
+            self.received = self.received.saturating_add(1);
+        } else {
+            // Too late to tell a reordered packet from a duplicate
+            self.reordered = self.reordered.saturating_add(1);
+        }
+    }
+}
+
+/// Per-sender delivery statistics (see [`SenderLoss`])
+#[derive(Debug, Clone, Default)]
+pub struct SenderLossTable {
+    senders: Vec<SenderLoss, MAX_LOSS_SENDERS>,
+}
+
+impl SenderLossTable {
+    /// Record a sequenced packet from `source`
+    pub fn record(&mut self, source: IpAddress, sequence: u16) {
+        let now = Instant::now();
+        if let Some(sender) = self.senders.iter_mut().find(|s| s.source == source) {
+            sender.record(sequence, now);
+            return;
+        }
+        // Evict the longest-idle sender when the table is full
+        if self.senders.is_full()
+            && let Some(oldest) = (0..self.senders.len()).min_by_key(|&i| self.senders[i].last_seen)
+        {
+            self.senders.swap_remove(oldest);
+        }
+        let _ = self.senders.push(SenderLoss::new(source, sequence, now));
+    }
+
+    /// Statistics of each tracked sender
+    pub fn senders(&self) -> &[SenderLoss] {
+        &self.senders
+    }
+}
+
+/// What the UDP server does once the keepalive grace period is exhausted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepaliveTimeoutAction {
+    /// Only report `UDPTimeout` to the state machine
+    StateOnly,
+    /// Report `UDPTimeout` and blank the LED strip until keepalives resume
+    BlankStrip,
+}
+
+/// Keepalive (0x01) supervision settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepaliveConfig {
+    /// Time without a keepalive that counts as one miss
+    pub timeout_ms: u32,
+    /// Consecutive misses tolerated before `action` is taken
+    pub max_missed: u8,
+    /// Action taken once the grace period is exhausted
+    pub action: KeepaliveTimeoutAction,
+}
+
+impl KeepaliveConfig {
+    /// Settings from the config store
+    pub fn stored() -> Self {
+        let stored = crate::config_store::get();
+        Self {
+            timeout_ms: stored.keepalive_timeout_ms,
+            max_missed: stored.keepalive_max_missed,
+            action: if stored.keepalive_blank_on_timeout {
+                KeepaliveTimeoutAction::BlankStrip
+            } else {
+                KeepaliveTimeoutAction::StateOnly
+            },
+        }
+    }
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: config::KEEPALIVE_TIMEOUT_MS,
+            max_missed: config::KEEPALIVE_MAX_MISSED,
+            action: if config::KEEPALIVE_BLANK_ON_TIMEOUT {
+                KeepaliveTimeoutAction::BlankStrip
+            } else {
+                KeepaliveTimeoutAction::StateOnly
+            },
+        }
+    }
+}
+
+/// Per-sender flood protection settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitConfig {
+    /// Sustained packets per second per sender (0 disables the limiter)
+    pub packets_per_sec: u32,
+    /// Packets a sender may burst above the sustained rate
+    pub burst: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            packets_per_sec: config::RATE_LIMIT_PACKETS_PER_SEC,
+            burst: config::RATE_LIMIT_BURST,
+        }
+    }
+}
+
+/// Senders tracked by the rate limiter at once
+const MAX_RATE_LIMIT_SOURCES: usize = 4;
+
+/// Token bucket of one sender (tokens are scaled by 1000)
+#[derive(Debug, Clone, Copy)]
+struct RateBucket {
+    source: IpAddress,
+    milli_tokens: u64,
+    last_refill: Instant,
+}
+
+/// Per-sender token bucket rate limiter
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Vec<RateBucket, MAX_RATE_LIMIT_SOURCES>,
+    /// Packets dropped for exceeding the rate
+    pub dropped: u32,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Charge one packet to `source`, returning `false` if it must be dropped
+    pub fn allow(&mut self, source: IpAddress) -> bool {
+        if self.config.packets_per_sec == 0 {
+            return true;
+        }
+
+        let now = Instant::now();
+        let capacity = self.config.burst.max(1) as u64 * 1000;
+
+        let index = match self.buckets.iter().position(|b| b.source == source) {
+            Some(index) => index,
+            None => {
+                // Evict the longest-idle sender when the table is full
+                if self.buckets.is_full()
+                    && let Some(oldest) =
+                        (0..self.buckets.len()).min_by_key(|&i| self.buckets[i].last_refill)
+                {
+                    self.buckets.swap_remove(oldest);
+                }
+                let _ = self.buckets.push(RateBucket {
+                    source,
+                    milli_tokens: capacity,
+                    last_refill: now,
+                });
+                self.buckets.len() - 1
+            }
+        };
+
+        let bucket = &mut self.buckets[index];
+        let elapsed_us = now.duration_since(bucket.last_refill).as_micros();
+        let refill = elapsed_us * self.config.packets_per_sec as u64 / 1000;
+        bucket.milli_tokens = (bucket.milli_tokens + refill).min(capacity);
+        bucket.last_refill = now;
+
+        if bucket.milli_tokens >= 1000 {
+            bucket.milli_tokens -= 1000;
+            true
+        } else {
+            self.dropped = self.dropped.saturating_add(1);
+            false
+        }
+    }
+}
+
+/// Outcome of checking a data packet against the source lock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceDecision {
+    /// Sent by the current owner at the active priority
+    Accepted,
+    /// The lock was free or idle, or the packet outranks the active stream,
+    /// and the sender became the owner
+    Acquired,
+    /// Another sender or a higher-priority stream owns the lock
+    Rejected,
+}
+
+/// Binds the LED data stream to a single sender
+///
+/// The first sender to stream data owns the lock until it has been idle for
+/// the timeout or another sender claims it with a takeover packet. A stream
+/// with a higher priority (e.g. a notification flash) preempts the owner and
+/// holds the lock until it has been silent for the priority timeout.
+#[derive(Debug, Clone, Copy)]
+pub struct SourceLock {
+    owner: Option<IpAddress>,
+    priority: u8,
+    last_seen: Instant,
+    timeout_ms: u32,
+    priority_timeout_ms: u32,
+    /// Data packets ignored because another stream owned the lock
+    pub rejected: u32,
+}
+
+impl SourceLock {
+    pub fn new(timeout_ms: u32, priority_timeout_ms: u32) -> Self {
+        Self {
+            owner: None,
+            priority: 0,
+            last_seen: Instant::now(),
+            timeout_ms,
+            priority_timeout_ms,
+            rejected: 0,
+        }
+    }
+
+    /// Current owner of the data stream
+    pub fn owner(&self) -> Option<IpAddress> {
+        self.owner
+    }
+
+    /// Priority of the active stream
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// Check a data packet from `source` carrying `priority`
+    pub fn accept(&mut self, source: IpAddress, priority: u8) -> SourceDecision {
+        let now = Instant::now();
+        let timeout_ms = if self.priority > 0 {
+            self.priority_timeout_ms
+        } else {
+            self.timeout_ms
+        };
+        let expired = now.duration_since(self.last_seen).as_millis() > timeout_ms as u64;
+
+        if self.owner == Some(source) && priority == self.priority {
+            self.last_seen = now;
+            return SourceDecision::Accepted;
+        }
+
+        if self.owner.is_none() || expired || priority > self.priority {
+            self.owner = Some(source);
+            self.priority = priority;
+            self.last_seen = now;
+            return SourceDecision::Acquired;
+        }
+
+        self.rejected = self.rejected.saturating_add(1);
+        SourceDecision::Rejected
+    }
+
+    /// Hand the stream to `source` unconditionally, at the base priority
+    pub fn claim(&mut self, source: IpAddress) {
+        self.owner = Some(source);
+        self.priority = 0;
+        self.last_seen = Instant::now();
+    }
+}
+
+/// Link health counters of the UDP server
+#[derive(Debug, Clone, Copy)]
+pub struct UdpStats {
+    /// Packets received (before any filtering)
+    pub packets_received: u32,
+    pub bytes_received: u32,
+    /// Packets that failed to decode (bad format, CRC or authentication)
+    pub parse_errors: u32,
+    /// Frames handed to the LED task
+    pub frames_committed: u32,
+    /// Receive rate over the last full second
+    pub bytes_per_sec: u32,
+    /// Sender of the most recent packet
+    pub last_sender: Option<IpEndpoint>,
+    window_start: Instant,
+    window_bytes: u32,
+}
+
+impl Default for UdpStats {
+    fn default() -> Self {
+        Self {
+            packets_received: 0,
+            bytes_received: 0,
+            parse_errors: 0,
+            frames_committed: 0,
+            bytes_per_sec: 0,
+            last_sender: None,
+            window_start: Instant::now(),
+            window_bytes: 0,
+        }
+    }
+}
+
+impl UdpStats {
+    /// Record a received packet
+    pub fn record_packet(&mut self, len: usize, sender: IpEndpoint) {
+        self.packets_received = self.packets_received.saturating_add(1);
+        self.bytes_received = self.bytes_received.wrapping_add(len as u32);
+        self.window_bytes = self.window_bytes.saturating_add(len as u32);
+        self.last_sender = Some(sender);
+        self.update_rate();
+    }
+
+    /// Roll the bytes/sec window once a second has passed
+    pub fn update_rate(&mut self) {
+        let elapsed_ms = self.window_start.elapsed().as_millis();
+        if elapsed_ms >= 1000 {
+            self.bytes_per_sec = (self.window_bytes as u64 * 1000 / elapsed_ms) as u32;
+            self.window_bytes = 0;
+            self.window_start = Instant::now();
+        }
+    }
+
+    /// Encode as a UDP statistics response:
+    /// `[header][received u32][bytes u32][parse errors u32][committed u32][dropped u32]`
+    /// `[bytes/sec u32][last sender: family (0 none, 4 IPv4, 6 IPv6)][address][port:2]`
+    /// `[render fps x100 u32][frame fps x100 u32][encode avg us u32][encode max us u32]`
+    /// `[transmit avg us u32][transmit max us u32]`
+    pub fn to_response(
+        &self,
+        dropped: u32,
+        pipeline: crate::led_control::PipelineStats,
+    ) -> Vec<u8, 72> {
+        let mut response = Vec::new();
+        let _ = response.push(config::UDP_STATS_HEADER);
+        for value in [
+            self.packets_received,
+            self.bytes_received,
+            self.parse_errors,
+            self.frames_committed,
+            dropped,
+            self.bytes_per_sec,
+        ] {
+            let _ = response.extend_from_slice(&value.to_be_bytes());
+        }
+
+        match self.last_sender {
+            Some(endpoint) => {
+                match endpoint.addr {
+                    IpAddress::Ipv4(addr) => {
+                        let _ = response.push(4);
+                        let _ = response.extend_from_slice(&addr.octets());
+                    }
+                    IpAddress::Ipv6(addr) => {
+                        let _ = response.push(6);
+                        let _ = response.extend_from_slice(&addr.octets());
+                    }
+                }
+                let _ = response.extend_from_slice(&endpoint.port.to_be_bytes());
+            }
+            None => {
+                let _ = response.push(0);
+            }
+        }
+
+        for value in [
+            pipeline.render_fps_x100,
+            pipeline.frame_fps_x100,
+            pipeline.encode_avg_us,
+            pipeline.encode_max_us,
+            pipeline.transmit_avg_us,
+            pipeline.transmit_max_us,
+        ] {
+            let _ = response.extend_from_slice(&value.to_be_bytes());
+        }
+        response
+    }
+}
+
+/// Snapshot of the UDP server counters, for readers outside its task
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UdpCounters {
+    pub packets_received: u32,
+    pub bytes_received: u32,
+    pub parse_errors: u32,
+    pub frames_committed: u32,
+    /// Packets discarded before reaching the frame buffer
+    pub dropped: u32,
+    /// Sequence numbers missing from the stream
+    pub sequence_lost: u32,
+}
+
+/// Counters as of the last pass of the packet loop
+static COUNTERS: critical_section::Mutex<core::cell::RefCell<UdpCounters>> =
+    critical_section::Mutex::new(core::cell::RefCell::new(UdpCounters {
+        packets_received: 0,
+        bytes_received: 0,
+        parse_errors: 0,
+        frames_committed: 0,
+        dropped: 0,
+        sequence_lost: 0,
+    }));
+
+/// UDP server counters, updated at least every 100 ms while the server runs
+pub fn counters() -> UdpCounters {
+    critical_section::with(|cs| *COUNTERS.borrow_ref(cs))
 }
 
 /// UDP server for receiving LED data packets
@@ -27,6 +628,20 @@ pub struct UdpServer<'a> {
     port: u16,
     is_bound: bool,
     stack: Option<&'a Stack<'a>>,
+    buffers: Option<&'a mut UdpBuffers>,
+    multicast_group: Option<IpAddress>,
+    keepalive: KeepaliveConfig,
+    sequence: SequenceTracker,
+    sender_loss: SenderLossTable,
+    assembler: FrameAssembler,
+    rate_limiter: RateLimiter,
+    cipher: Option<crate::crypto::PacketCipher>,
+    replay_guard: crate::crypto::ReplayGuard,
+    replay_marks: Option<crate::crypto::MarkStore>,
+    stats: UdpStats,
+    source_lock: SourceLock,
+    device_info: Option<crate::device_info::DeviceInfo>,
+    chain_next_hop: Option<IpEndpoint>,
 }
 
 impl<'a> UdpServer<'a> {
@@ -36,6 +651,23 @@ impl<'a> UdpServer<'a> {
             port: 0,
             is_bound: false,
             stack: None,
+            buffers: None,
+            multicast_group: None,
+            keepalive: KeepaliveConfig::default(),
+            sequence: SequenceTracker::default(),
+            sender_loss: SenderLossTable::default(),
+            assembler: FrameAssembler::default(),
+            rate_limiter: RateLimiter::new(RateLimitConfig::default()),
+            cipher: None,
+            replay_guard: crate::crypto::ReplayGuard::default(),
+            replay_marks: None,
+            stats: UdpStats::default(),
+            source_lock: SourceLock::new(
+                config::SOURCE_LOCK_TIMEOUT_MS,
+                config::PRIORITY_TIMEOUT_MS,
+            ),
+            device_info: None,
+            chain_next_hop: None,
         }
     }
 
@@ -44,6 +676,97 @@ impl<'a> UdpServer<'a> {
         self.stack = Some(stack);
     }
 
+    /// Provide the socket and packet buffers (required before listening)
+    pub fn set_buffers(&mut self, buffers: &'a mut UdpBuffers) {
+        self.buffers = Some(buffers);
+    }
+
+    /// Also accept LED data sent to this multicast group (joined on start)
+    pub fn set_multicast_group(&mut self, group: Option<IpAddress>) {
+        self.multicast_group = group;
+    }
+
+    /// Set the keepalive timeout, grace period and timeout action
+    pub fn set_keepalive_config(&mut self, keepalive: KeepaliveConfig) {
+        self.keepalive = keepalive;
+    }
+
+    /// Get the active keepalive configuration
+    pub fn get_keepalive_config(&self) -> KeepaliveConfig {
+        self.keepalive
+    }
+
+    /// Set the per-sender rate limit
+    pub fn set_rate_limit_config(&mut self, rate_limit: RateLimitConfig) {
+        self.rate_limiter = RateLimiter::new(rate_limit);
+    }
+
+    /// Get the number of packets dropped by the rate limiter
+    pub fn get_rate_limited_count(&self) -> u32 {
+        self.rate_limiter.dropped
+    }
+
+    /// Require ChaCha20-Poly1305 encrypted packets with this pre-shared key
+    pub fn set_encryption_key(&mut self, key: &[u8; 32]) {
+        self.cipher = Some(crate::crypto::PacketCipher::new(key));
+        let (marks, guard) = crate::crypto::MarkStore::load();
+        self.replay_marks = Some(marks);
+        self.replay_guard = guard;
+    }
+
+    /// Get the link health counters
+    pub fn get_stats(&self) -> UdpStats {
+        self.stats
+    }
+
+    /// Packets discarded before reaching the frame buffer (rate limited,
+    /// locked out, replayed or stale)
+    pub fn dropped_packet_count(&self) -> u32 {
+        self.rate_limiter
+            .dropped
+            .saturating_add(self.source_lock.rejected)
+            .saturating_add(self.replay_guard.rejected)
+            .saturating_add(self.sequence.stale)
+    }
+
+    /// Publish the counters for `counters()`
+    fn publish_counters(&self) {
+        let snapshot = UdpCounters {
+            packets_received: self.stats.packets_received,
+            bytes_received: self.stats.bytes_received,
+            parse_errors: self.stats.parse_errors,
+            frames_committed: self.stats.frames_committed,
+            dropped: self.dropped_packet_count(),
+            sequence_lost: self.sequence.lost,
+        };
+        critical_section::with(|cs| *COUNTERS.borrow_ref_mut(cs) = snapshot);
+    }
+
+    /// Get the number of data packets ignored by the source lock
+    pub fn get_source_rejected_count(&self) -> u32 {
+        self.source_lock.rejected
+    }
+
+    /// Set the device information returned for info queries
+    pub fn set_device_info(&mut self, device_info: crate::device_info::DeviceInfo) {
+        self.device_info = Some(device_info);
+    }
+
+    /// Forward LEDs beyond this board's window to the next board in a chain
+    pub fn set_chain_next_hop(&mut self, next_hop: Option<IpEndpoint>) {
+        self.chain_next_hop = next_hop;
+    }
+
+    /// Get the sequence statistics of sequenced LED data packets
+    pub fn get_sequence_stats(&self) -> SequenceTracker {
+        self.sequence
+    }
+
+    /// Get the per-sender delivery statistics of sequenced LED data packets
+    pub fn get_sender_loss(&self) -> &SenderLossTable {
+        &self.sender_loss
+    }
+
     /// Bind to the specified port and start listening
     pub fn bind(&mut self, port: u16) -> Result<(), BoardError> {
         if self.stack.is_none() {
@@ -61,15 +784,12 @@ impl<'a> UdpServer<'a> {
     /// Start UDP server and listen for packets (async)
     pub async fn start_listening(
         &mut self,
-        led_data_sender: &embassy_sync::channel::Sender<
+        led_data_sender: &crate::led_control::LedDataSignal,
+        led_mode_sender: &embassy_sync::channel::Sender<
             'static,
             embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
-            crate::led_control::LedData,
-            4,
-        >,
-        state_machine: &embassy_sync::mutex::Mutex<
-            embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
-            crate::state_machine::SystemStateMachine,
+            crate::led_control::LedMode,
+            2,
         >,
     ) -> Result<(), BoardError> {
         if !self.is_bound {
@@ -77,19 +797,14 @@ impl<'a> UdpServer<'a> {
         }
 
         let stack = self.stack.ok_or(BoardError::UdpError)?;
-
-        // Create UDP socket buffers
-        let mut rx_buffer = [0; 4096];
-        let mut tx_buffer = [0; 4096];
-        let mut rx_meta = [PacketMetadata::EMPTY; 16];
-        let mut tx_meta = [PacketMetadata::EMPTY; 16];
-        let mut socket = UdpSocket::new(
-            *stack,
-            &mut rx_meta,
-            &mut rx_buffer,
-            &mut tx_meta,
-            &mut tx_buffer,
-        );
+        let UdpBuffers {
+            rx_meta,
+            rx,
+            tx_meta,
+            tx,
+            packet,
+        } = self.buffers.take().ok_or(BoardError::UdpError)?;
+        let mut socket = UdpSocket::new(*stack, rx_meta, rx, tx_meta, tx);
 
         // Bind to the configured port
         match socket.bind(self.port) {
@@ -102,8 +817,17 @@ impl<'a> UdpServer<'a> {
             }
         }
 
+        // Let one sender feed several boards with a single multicast stream
+        if let Some(group) = self.multicast_group {
+            stack.wait_config_up().await;
+            match stack.join_multicast_group(group) {
+                Ok(_) => println!("[UDP] Joined LED data multicast group {}", group),
+                Err(e) => println!("[UDP] Failed to join multicast group {}: {:?}", group, e),
+            }
+        }
+
         // Start packet reception loop
-        self.packet_loop(&mut socket, led_data_sender, state_machine)
+        self.packet_loop(&mut socket, packet, led_data_sender, led_mode_sender)
             .await
     }
 
@@ -111,48 +835,169 @@ impl<'a> UdpServer<'a> {
     async fn packet_loop(
         &mut self,
         socket: &mut UdpSocket<'_>,
-        led_data_sender: &embassy_sync::channel::Sender<
+        buffer: &mut [u8; MAX_PACKET_SIZE],
+        led_data_sender: &crate::led_control::LedDataSignal,
+        led_mode_sender: &embassy_sync::channel::Sender<
             'static,
             embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
-            crate::led_control::LedData,
-            4,
-        >,
-        state_machine: &embassy_sync::mutex::Mutex<
-            embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
-            crate::state_machine::SystemStateMachine,
+            crate::led_control::LedMode,
+            2,
         >,
     ) -> Result<(), BoardError> {
-        use embassy_time::{Duration, Instant};
+        use embassy_time::Duration;
 
-        let mut buffer = [0u8; MAX_PACKET_SIZE];
         let mut last_connection_check = Instant::now();
-        let connection_timeout = Duration::from_secs(30); // 30秒超时
+        let mut missed_keepalives: u8 = 0;
+        // Mode to restore once keepalives resume, while the strip is blanked
+        let mut blanked_mode: Option<crate::led_control::LedMode> = None;
 
-        // Batch state machine events to reduce lock contention
-        let mut pending_events = heapless::Vec::<crate::state_machine::SystemEvent, 8>::new();
-        let mut last_state_update = Instant::now();
-        let state_update_interval = Duration::from_millis(100); // Update state machine every 100ms
+        // Zone updates arriving within one frame interval are merged into this
+        // framebuffer and committed to the LED task as a single frame
+        let mut frame = alloc::vec::Vec::<u8>::new();
+        let mut forward = alloc::vec::Vec::<u8>::new();
+        let next_hop = self.chain_next_hop;
+        let mut frame_dirty_since: Option<Instant> = None;
+        let frame_interval = Duration::from_millis(config::LED_FRAME_INTERVAL_MS);
+
+        // Who gets firmware update progress reports, and when the last one went out
+        let mut ota_subscriber: Option<IpEndpoint> = None;
+        let mut last_ota_report = Instant::now();
+
+        // When the rate limiter was last reported, so floods don't flood the log too
+        let mut last_rate_limit_log: Option<Instant> = None;
+        // When a keepalive timeout was last reported
+        let mut last_timeout_log: Option<Instant> = None;
 
         loop {
-            // 使用超时接收数据
-            match embassy_time::with_timeout(
-                Duration::from_millis(100), // Reduced timeout for more responsive state updates
-                socket.recv_from(&mut buffer),
-            )
-            .await
+            crate::memory::checkpoint(crate::memory::Subsystem::Udp);
+            crate::watchdog::heartbeat(crate::watchdog::Task::Udp);
+            self.publish_counters();
+
+            // Report update progress until the update ends, with a last report
+            // of how it ended
+            if let Some(subscriber) = ota_subscriber
+                && last_ota_report.elapsed().as_millis() >= config::OTA_PROGRESS_INTERVAL_MS
             {
+                let progress = crate::ota::OtaProgress::read();
+                socket
+                    .send_to(&progress.to_response(), subscriber)
+                    .await
+                    .ok();
+                last_ota_report = Instant::now();
+                if !progress.is_active() && !crate::ota::is_in_progress() {
+                    ota_subscriber = None;
+                }
+            }
+
+            // Wake up in time to commit a pending frame
+            let recv_timeout = match frame_dirty_since {
+                Some(since) => frame_interval
+                    .checked_sub(Instant::now().duration_since(since))
+                    .unwrap_or(Duration::from_ticks(0)),
+                None => Duration::from_millis(100), // Reduced timeout for more responsive state updates
+            };
+
+            // 使用超时接收数据
+            match embassy_time::with_timeout(recv_timeout, socket.recv_from(buffer)).await {
                 Ok(Ok((len, endpoint))) => {
+                    let mut len = len;
+                    self.stats.record_packet(len, endpoint.endpoint);
+
+                    // An empty datagram carries no header; `buffer[0]` would still
+                    // hold the previous packet's
+                    if len == 0 {
+                        continue;
+                    }
+
+                    // Drop floods before doing any work on them, decryption included
+                    // (clear keepalives are always served)
+                    if !protocol::is_connection_check(&buffer[..len])
+                        && !self.rate_limiter.allow(endpoint.endpoint.addr)
+                    {
+                        let now = Instant::now();
+                        if last_rate_limit_log
+                            .is_none_or(|last| now.duration_since(last) > Duration::from_secs(10))
+                        {
+                            println!(
+                                "[UDP] ⚠️ Rate limiting {} ({} packets dropped so far)",
+                                endpoint.endpoint.addr, self.rate_limiter.dropped
+                            );
+                            last_rate_limit_log = Some(now);
+                        }
+                        continue;
+                    }
+
+                    // With a pre-shared key, only keepalives, device info queries and
+                    // discovery probes may be sent in the clear
+                    if let Some(ref cipher) = self.cipher {
+                        if buffer[0] == config::ENCRYPTED_HEADER {
+                            match cipher.open(&mut buffer[..len]) {
+                                Ok((nonce, plaintext)) => {
+                                    // Captured packets must not be accepted a second time
+                                    if !self.replay_guard.accept(nonce) {
+                                        continue;
+                                    }
+                                    if let Some(ref mut marks) = self.replay_marks {
+                                        marks.save(&mut self.replay_guard);
+                                    }
+                                    len = plaintext.len();
+                                    buffer.copy_within(plaintext, 0);
+                                    if len == 0 {
+                                        continue;
+                                    }
+                                }
+                                Err(_) => {
+                                    // Wrong key or tampered packet
+                                    self.stats.parse_errors =
+                                        self.stats.parse_errors.saturating_add(1);
+                                    continue;
+                                }
+                            }
+                        } else if !(len == 1
+                            && matches!(
+                                buffer[0],
+                                config::CONNECTION_CHECK_HEADER | config::DEVICE_INFO_HEADER
+                            ))
+                            && !protocol::is_discovery_probe(&buffer[..len])
+                        {
+                            continue;
+                        }
+                    }
+
                     // Check if this is a connection check packet
-                    if Self::is_connection_check(&buffer[..len]) {
+                    if protocol::is_connection_check(&buffer[..len]) {
                         // 更新最后收到连接检查的时间
                         last_connection_check = Instant::now();
+                        missed_keepalives = 0;
 
-                        // Queue state machine event instead of immediate lock
-                        let _ = pending_events
-                            .push(crate::state_machine::SystemEvent::ConnectionCheckReceived);
+                        // Restore the mode the strip had before it was blanked on timeout
+                        if let Some(mode) = blanked_mode.take() {
+                            let _ = led_mode_sender.try_send(mode);
+                        }
 
-                        // Send connection response (echo back 0x01)
-                        let response = [config::CONNECTION_CHECK_HEADER];
+                        crate::state_machine::send_event(
+                            crate::state_machine::SystemEvent::ConnectionCheckReceived,
+                        );
+
+                        // Send connection response: 0x01 + system state, active mode, drops,
+                        // signal strength, the weak-signal flag, chip temperature and the
+                        // overheating flag
+                        let system_state = crate::state_machine::current_state();
+                        let degraded = crate::state_machine::is_network_degraded();
+                        let [d0, d1, d2, d3] = self.dropped_packet_count().to_be_bytes();
+                        let response = [
+                            config::CONNECTION_CHECK_HEADER,
+                            system_state as u8,
+                            crate::led_control::active_mode() as u8,
+                            d0,
+                            d1,
+                            d2,
+                            d3,
+                            crate::wifi::last_rssi().unwrap_or(0) as u8,
+                            degraded as u8,
+                            crate::temperature::wire_byte(),
+                            crate::temperature::is_overheating() as u8,
+                        ];
                         socket.send_to(&response, endpoint.endpoint).await.ok();
                         continue; // Skip LED packet processing
                     }
@@ -162,29 +1007,307 @@ impl<'a> UdpServer<'a> {
                         continue; // Skip processing this packet entirely
                     }
 
+                    // Answer device info queries so senders can auto-configure
+                    if len == 1 && buffer[0] == config::DEVICE_INFO_HEADER {
+                        if let Some(ref device_info) = self.device_info {
+                            let response = device_info.to_response();
+                            socket.send_to(&response, endpoint.endpoint).await.ok();
+                        }
+                        continue;
+                    }
+
+                    // Answer broadcast discovery probes for networks that filter mDNS
+                    if protocol::is_discovery_probe(&buffer[..len]) {
+                        if let Some(ref device_info) = self.device_info {
+                            let instance_name = crate::mdns::current_config().instance_name;
+                            let response =
+                                device_info.to_discovery_response(self.port, &instance_name);
+                            socket.send_to(&response, endpoint.endpoint).await.ok();
+                        }
+                        continue;
+                    }
+
+                    // Rename the mDNS instance so boards can be told apart
+                    if len > 0 && buffer[0] == config::INSTANCE_NAME_HEADER {
+                        let status = match core::str::from_utf8(&buffer[1..len]) {
+                            Ok(name) => crate::mdns::set_instance_name(name).is_err() as u8,
+                            Err(_) => 1,
+                        };
+                        let instance_name = crate::mdns::current_config().instance_name;
+                        let mut response = Vec::<u8, 65>::new();
+                        let _ = response.push(config::INSTANCE_NAME_HEADER);
+                        let _ = response.push(status);
+                        let _ = response.extend_from_slice(instance_name.as_bytes());
+                        socket.send_to(&response, endpoint.endpoint).await.ok();
+                        continue;
+                    }
+
+                    // Echo the sender's timestamp with the receive-to-render latency estimate
+                    if len == 9 && buffer[0] == config::PING_HEADER {
+                        let mut response = [0u8; 13];
+                        response[..9].copy_from_slice(&buffer[..9]);
+                        response[9..].copy_from_slice(
+                            &crate::led_control::render_latency_us().to_be_bytes(),
+                        );
+                        socket.send_to(&response, endpoint.endpoint).await.ok();
+                        continue;
+                    }
+
+                    // Queue a GPIO toggle diagnostic and report whether it was accepted
+                    if buffer[0] == config::DIAGNOSTICS_GPIO_HEADER {
+                        let status = crate::diagnostics::submit_gpio_toggle(&buffer[..len]);
+                        let response = [config::DIAGNOSTICS_GPIO_HEADER, status as u8];
+                        socket.send_to(&response, endpoint.endpoint).await.ok();
+                        continue;
+                    }
+
+                    // Set the global brightness and acknowledge the applied value
+                    if len == 2 && buffer[0] == config::BRIGHTNESS_HEADER {
+                        crate::led_control::set_saved_brightness(buffer[1]);
+                        let response = [
+                            config::BRIGHTNESS_HEADER,
+                            crate::led_control::global_brightness(),
+                        ];
+                        socket.send_to(&response, endpoint.endpoint).await.ok();
+                        continue;
+                    }
+
+                    // Blank the strip (0x00) or re-enable it (0x01) and acknowledge
+                    if len == 2 && buffer[0] == config::POWER_HEADER {
+                        crate::led_control::set_strip_enabled(buffer[1] != 0);
+                        let response = [
+                            config::POWER_HEADER,
+                            crate::led_control::is_strip_enabled() as u8,
+                        ];
+                        socket.send_to(&response, endpoint.endpoint).await.ok();
+                        continue;
+                    }
+
+                    // Open (0x01) or close (0x00) a throughput benchmark window
+                    if len == 2 && buffer[0] == config::BENCHMARK_HEADER {
+                        if buffer[1] == 0x01 {
+                            crate::benchmark::start();
+                            println!("[UDP] Benchmark started");
+                            let response = [config::BENCHMARK_HEADER, 0x01];
+                            socket.send_to(&response, endpoint.endpoint).await.ok();
+                        } else {
+                            let stats = crate::benchmark::stop();
+                            println!(
+                                "[UDP] Benchmark: {} packets, {} frames rendered in {}ms",
+                                stats.packets, stats.frames_rendered, stats.elapsed_ms
+                            );
+                            socket
+                                .send_to(&stats.to_response(), endpoint.endpoint)
+                                .await
+                                .ok();
+                        }
+                        continue;
+                    }
+
+                    // Select an LED mode explicitly, or return to auto-switching
+                    if len == 2 && buffer[0] == config::MODE_HEADER {
+                        let status = Self::handle_mode_command(buffer[1], led_mode_sender);
+                        let response = [config::MODE_HEADER, status, buffer[1]];
+                        socket.send_to(&response, endpoint.endpoint).await.ok();
+                        continue;
+                    }
+
+                    // Report heap/stack usage and high-water marks
+                    if len == 1 && buffer[0] == config::MEMORY_STATS_HEADER {
+                        let response = crate::memory::stats().to_response();
+                        socket.send_to(&response, endpoint.endpoint).await.ok();
+                        continue;
+                    }
+
+                    // Restart the board through the state machine
+                    if len == 5
+                        && buffer[0] == config::REBOOT_HEADER
+                        && &buffer[1..5] == config::REBOOT_MAGIC
+                    {
+                        println!("[UDP] Reboot requested by {}", endpoint.endpoint);
+                        socket
+                            .send_to(&[config::REBOOT_HEADER, 0x00], endpoint.endpoint)
+                            .await
+                            .ok();
+                        crate::state_machine::send_event(
+                            crate::state_machine::SystemEvent::RebootRequested,
+                        );
+                        continue;
+                    }
+
+                    // Download and switch to new firmware: [0x1F][url...], no URL for
+                    // the configured one; reply [0x1F][status]
+                    if buffer[0] == config::OTA_HEADER {
+                        let status = crate::ota::request_update(&buffer[1..len]);
+                        println!(
+                            "[UDP] Firmware update requested by {}: {:?}",
+                            endpoint.endpoint, status
+                        );
+                        if status == crate::ota::OtaStatus::Started {
+                            ota_subscriber = Some(endpoint.endpoint);
+                            last_ota_report = Instant::now();
+                        }
+                        socket
+                            .send_to(&[config::OTA_HEADER, status as u8], endpoint.endpoint)
+                            .await
+                            .ok();
+                        continue;
+                    }
+
+                    // Report update progress now, and every
+                    // `OTA_PROGRESS_INTERVAL_MS` while an update runs
+                    if len == 1 && buffer[0] == config::OTA_PROGRESS_HEADER {
+                        let progress = crate::ota::OtaProgress::read();
+                        socket
+                            .send_to(&progress.to_response(), endpoint.endpoint)
+                            .await
+                            .ok();
+                        if crate::ota::is_in_progress() {
+                            ota_subscriber = Some(endpoint.endpoint);
+                            last_ota_report = Instant::now();
+                        }
+                        continue;
+                    }
+
+                    // Report the running firmware and partition
+                    if len == 1 && buffer[0] == config::FIRMWARE_INFO_HEADER {
+                        let response = crate::ota::FirmwareInfo::read().to_response();
+                        socket.send_to(&response, endpoint.endpoint).await.ok();
+                        continue;
+                    }
+
+                    // Read or persist a stored setting
+                    if buffer[0] == config::CONFIG_HEADER {
+                        let response = self.handle_config_request(&buffer[..len]);
+                        socket.send_to(&response, endpoint.endpoint).await.ok();
+                        continue;
+                    }
+
+                    // Report why the board last reset and its last error
+                    if len == 1 && buffer[0] == config::BOOT_REPORT_HEADER {
+                        let response = crate::crash_log::to_response();
+                        socket.send_to(&response, endpoint.endpoint).await.ok();
+                        continue;
+                    }
+
+                    // Report link health counters
+                    if len == 1 && buffer[0] == config::UDP_STATS_HEADER {
+                        self.stats.update_rate();
+                        let response = self.stats.to_response(
+                            self.dropped_packet_count(),
+                            crate::led_control::pipeline_stats(),
+                        );
+                        socket.send_to(&response, endpoint.endpoint).await.ok();
+                        continue;
+                    }
+
+                    if len == 1 && buffer[0] == config::SEQUENCE_STATS_HEADER {
+                        let response = self.sequence.to_response(&self.sender_loss);
+                        socket.send_to(&response, endpoint.endpoint).await.ok();
+                        continue;
+                    }
+
+                    // Hand the data stream to this sender on request
+                    if len == 1 && buffer[0] == config::SOURCE_TAKEOVER_HEADER {
+                        let source = endpoint.endpoint.addr;
+                        if self.source_lock.owner() != Some(source) {
+                            println!("[UDP] Data source taken over by {}", source);
+                            self.source_lock.claim(source);
+                            self.sequence.resync();
+                            self.assembler.reset();
+                        }
+                        let response = [config::SOURCE_TAKEOVER_HEADER, 0x00];
+                        socket.send_to(&response, endpoint.endpoint).await.ok();
+                        continue;
+                    }
+
+                    // Only the sender owning the lock may stream LED data
+                    if matches!(
+                        buffer[0],
+                        config::PROTOCOL_HEADER
+                            | config::SEQUENCED_DATA_HEADER
+                            | config::FRAGMENT_HEADER
+                    ) {
+                        let priority = protocol::packet_priority(&buffer[..len]);
+                        match self.source_lock.accept(endpoint.endpoint.addr, priority) {
+                            SourceDecision::Accepted => {}
+                            SourceDecision::Acquired => {
+                                println!(
+                                    "[UDP] Data source locked to {} (priority {})",
+                                    endpoint.endpoint.addr, priority
+                                );
+                                self.sequence.resync();
+                                self.assembler.reset();
+                            }
+                            SourceDecision::Rejected => continue,
+                        }
+                    }
+
+                    // Reassemble frames too large for a single datagram
+                    if buffer[0] == config::FRAGMENT_HEADER {
+                        let Ok(fragment) = protocol::parse_fragment(&buffer[..len]) else {
+                            self.stats.parse_errors = self.stats.parse_errors.saturating_add(1);
+                            continue;
+                        };
+                        if let Some(complete) = self.assembler.push(&fragment) {
+                            Self::apply_zone(
+                                socket,
+                                next_hop,
+                                &mut forward,
+                                &mut frame,
+                                0,
+                                complete,
+                            )
+                            .await;
+                            if frame_dirty_since.is_none() {
+                                frame_dirty_since = Some(Instant::now());
+                            }
+                        }
+                        continue;
+                    }
+
                     // Process LED data packets
-                    match Self::parse_packet(&buffer[..len]) {
+                    let received_at = Instant::now();
+                    match protocol::parse_packet(&buffer[..len]) {
                         Ok(packet) => {
-                            // Create LED data and send to LED task
-                            let led_data = crate::led_control::LedData {
-                                data: packet.data.to_vec(),
-                                timestamp: embassy_time::Instant::now(),
-                            };
-
-                            // Send LED data to LED task via channel
-                            match led_data_sender.try_send(led_data) {
-                                Ok(_) => {
-                                    // Queue state machine event instead of immediate lock
-                                    let _ = pending_events
-                                        .push(crate::state_machine::SystemEvent::LEDDataReceived);
+                            // Skip packets older than the newest one already applied
+                            if let Some(sequence) = packet.sequence {
+                                self.sender_loss.record(endpoint.endpoint.addr, sequence);
+                                let lost_before = self.sequence.lost;
+                                let accepted = self.sequence.accept(sequence);
+
+                                // Acknowledge stale packets too so the sender stops retrying
+                                if packet.flags & config::DATA_FLAG_ACK != 0 {
+                                    let [hi, lo] = sequence.to_be_bytes();
+                                    let status = if accepted { 0 } else { 1 };
+                                    let ack = [config::DATA_ACK_HEADER, hi, lo, status];
+                                    socket.send_to(&ack, endpoint.endpoint).await.ok();
                                 }
-                                Err(_) => {
-                                    // Channel full or other error - silent handling
+
+                                if !accepted {
+                                    continue;
                                 }
+                                crate::benchmark::record_lost(self.sequence.lost - lost_before);
+                            }
+
+                            // Merge the zone into the pending frame
+                            Self::apply_zone(
+                                socket,
+                                next_hop,
+                                &mut forward,
+                                &mut frame,
+                                packet.offset,
+                                packet.data,
+                            )
+                            .await;
+                            if frame_dirty_since.is_none() {
+                                frame_dirty_since = Some(Instant::now());
                             }
+                            crate::benchmark::record_packet(len, received_at);
                         }
                         Err(_) => {
                             // Silent error - invalid packets are common
+                            self.stats.parse_errors = self.stats.parse_errors.saturating_add(1);
                         }
                     }
                 }
@@ -194,50 +1317,81 @@ impl<'a> UdpServer<'a> {
                 Err(_) => {
                     // 超时 - 检查是否需要触发超时事件
                     let now = Instant::now();
-                    if now.duration_since(last_connection_check) > connection_timeout {
-                        static mut LAST_TIMEOUT_LOG: Option<Instant> = None;
-                        let should_log = unsafe {
-                            LAST_TIMEOUT_LOG.map_or(true, |last| {
-                                now.duration_since(last) > Duration::from_secs(30)
-                            })
-                        };
+                    // Re-read each time, as config packets can change it
+                    let keepalive = self.keepalive;
+                    let connection_timeout = Duration::from_millis(keepalive.timeout_ms as u64);
+                    let keepalive_missed =
+                        now.duration_since(last_connection_check) > connection_timeout;
+                    if keepalive_missed {
+                        missed_keepalives = missed_keepalives.saturating_add(1);
 
-                        if should_log {
+                        // 重置超时计时器
+                        last_connection_check = now;
+                    }
+
+                    // Only act once the configured grace period is exhausted
+                    if keepalive_missed && missed_keepalives > keepalive.max_missed {
+                        if last_timeout_log
+                            .is_none_or(|last| now.duration_since(last) > Duration::from_secs(30))
+                        {
                             println!(
                                 "[UDP] ⚠️ Connection check timeout - no 0x01 message received for {} seconds",
-                                connection_timeout.as_secs()
+                                connection_timeout.as_secs() * missed_keepalives as u64
                             );
-                            unsafe {
-                                LAST_TIMEOUT_LOG = Some(now);
-                            }
+                            last_timeout_log = Some(now);
                         }
 
-                        // Queue timeout event
-                        let _ = pending_events.push(crate::state_machine::SystemEvent::UDPTimeout);
+                        crate::state_machine::send_event(
+                            crate::state_machine::SystemEvent::UDPTimeout,
+                        );
 
-                        // 重置超时计时器
-                        last_connection_check = now;
+                        // The sender may restart its sequence when it comes back
+                        self.sequence.resync();
+
+                        if keepalive.action == KeepaliveTimeoutAction::BlankStrip
+                            && blanked_mode.is_none()
+                        {
+                            blanked_mode = Some(crate::led_control::active_mode());
+                            let _ = led_mode_sender.try_send(crate::led_control::LedMode::Off);
+                        }
                     }
                 }
             }
 
-            // Process batched state machine events periodically
-            let now = Instant::now();
-            if !pending_events.is_empty()
-                && now.duration_since(last_state_update) >= state_update_interval
+            // Commit the merged frame once per frame interval
+            if let Some(since) = frame_dirty_since
+                && Instant::now().duration_since(since) >= frame_interval
             {
-                let mut sm = state_machine.lock().await;
-                for event in pending_events.iter() {
-                    sm.handle_event(*event);
+                // Snapshot the frame into a recycled buffer to avoid a fresh allocation
+                let mut data = crate::led_control::take_recycled_frame().unwrap_or_default();
+                data.clear();
+                if data.try_reserve(frame.len()).is_err() {
+                    // Out of heap: drop this frame rather than panic
+                    crate::memory::note_alloc_failure(frame.len());
+                    frame_dirty_since = None;
+                    continue;
                 }
-                pending_events.clear();
-                last_state_update = now;
+                data.extend_from_slice(&frame);
+                let led_data = crate::led_control::LedData {
+                    data,
+                    timestamp: since,
+                };
+
+                // Hand the frame to the LED task, replacing any frame not yet rendered
+                led_data_sender.signal(led_data);
+                crate::benchmark::record_commit();
+                self.stats.frames_committed = self.stats.frames_committed.saturating_add(1);
+
+                crate::state_machine::send_event(
+                    crate::state_machine::SystemEvent::LEDDataReceived,
+                );
+                frame_dirty_since = None;
             }
         }
     }
 
     /// Receive and parse a UDP packet (legacy method - now deprecated)
-    pub fn receive_packet(&mut self) -> Result<Option<LedPacket>, BoardError> {
+    pub fn receive_packet(&mut self) -> Result<Option<LedPacket<'_>>, BoardError> {
         // This method is now deprecated in favor of start_listening()
         // which provides proper async UDP reception
         Ok(None)
@@ -253,48 +1407,183 @@ impl<'a> UdpServer<'a> {
         self.port
     }
 
-    /// Check if packet is a connection check packet
-    pub fn is_connection_check(data: &[u8]) -> bool {
-        data.len() == 1 && data[0] == config::CONNECTION_CHECK_HEADER
-    }
+    /// Merge a zone addressed by absolute LED offset into the pending frame
+    ///
+    /// The part inside this board's window (`config::CHAIN_LED_OFFSET` onwards)
+    /// is rebased to the board's first LED. With a next hop configured the
+    /// window is `config_store::led_count()` LEDs long and the LEDs beyond it are
+    /// re-sent downstream as a 0x02 packet, still at their absolute offset.
+    async fn apply_zone(
+        socket: &mut UdpSocket<'_>,
+        next_hop: Option<IpEndpoint>,
+        forward: &mut alloc::vec::Vec<u8>,
+        frame: &mut alloc::vec::Vec<u8>,
+        offset: u16,
+        data: &[u8],
+    ) {
+        let bytes_per_led = config::BYTES_PER_LED;
+        let window_start = config::CHAIN_LED_OFFSET as usize;
+        let window_end = match next_hop {
+            Some(_) => window_start + crate::config_store::led_count(),
+            None => usize::MAX,
+        };
+        let zone_start = offset as usize;
+        let zone_end = zone_start + data.len().div_ceil(bytes_per_led);
 
-    /// Parse raw packet data according to protocol specification
-    pub fn parse_packet(data: &[u8]) -> Result<LedPacket, BoardError> {
-        // 解析数据包，不打印详细的数据内容
+        let local_start = zone_start.max(window_start);
+        let local_end = zone_end.min(window_end);
+        if local_start < local_end {
+            let from = (local_start - zone_start) * bytes_per_led;
+            let to = ((local_end - zone_start) * bytes_per_led).min(data.len());
+            Self::write_zone(frame, (local_start - window_start) as u16, &data[from..to]);
+        }
 
-        // Connection check packets should be handled before calling this function
-        if Self::is_connection_check(data) {
-            return Err(BoardError::ProtocolError);
+        let Some(next_hop) = next_hop else {
+            return;
+        };
+        if zone_end <= window_end {
+            return;
         }
+        let forward_start = zone_start.max(window_end);
+        let Ok(forward_offset) = u16::try_from(forward_start) else {
+            return;
+        };
+        forward.clear();
+        forward.push(config::PROTOCOL_HEADER);
+        forward.extend_from_slice(&forward_offset.to_be_bytes());
+        forward.extend_from_slice(&data[(forward_start - zone_start) * bytes_per_led..]);
+        socket.send_to(forward, next_hop).await.ok();
+    }
 
-        if data.len() < 3 {
-            return Err(BoardError::ProtocolError);
+    /// Write a zone update into the framebuffer at its LED offset
+    ///
+    /// The framebuffer grows to fit the zone, capped at `config::MAX_LEDS`.
+    fn write_zone(frame: &mut alloc::vec::Vec<u8>, offset: u16, data: &[u8]) {
+        let max_len = config::MAX_FRAME_BYTES;
+        let start = offset as usize * config::BYTES_PER_LED;
+        if start >= max_len {
+            return;
         }
 
-        // Check protocol header
-        if data[0] != config::PROTOCOL_HEADER {
-            return Err(BoardError::ProtocolError);
+        let end = (start + data.len()).min(max_len);
+        if frame.len() < end {
+            frame.resize(end, 0);
         }
+        frame[start..end].copy_from_slice(&data[..end - start]);
+    }
 
-        // Parse offset (16-bit big-endian)
-        let offset = u16::from_be_bytes([data[1], data[2]]);
+    /// Merge a data packet into `frame` and hand the whole frame to the LED task
+    ///
+    /// Used by the stream transports (USB serial, WebSocket), which render each
+    /// packet as soon as it arrives instead of batching per frame interval.
+    pub fn render_packet(
+        frame: &mut alloc::vec::Vec<u8>,
+        data: &[u8],
+        led_data_sender: &crate::led_control::LedDataSignal,
+    ) -> Result<(), BoardError> {
+        let packet = protocol::parse_packet(data)?;
+        Self::write_zone(frame, packet.offset, packet.data);
 
-        // Extract LED data
-        let led_data = &data[3..];
-        let mut data_vec = Vec::new();
+        let mut data = crate::led_control::take_recycled_frame().unwrap_or_default();
+        data.clear();
+        data.extend_from_slice(frame);
+        led_data_sender.signal(crate::led_control::LedData {
+            data,
+            timestamp: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Answer a config get/set packet
+    ///
+    /// Reply: [0x1E][status][key][current value...]. Brightness, the device
+    /// name and the keepalive settings apply at once; the other settings are picked up by the next frame,
+    /// the device info reply or, for the UDP port, a restart. Key 0x00 exports
+    /// or imports the whole configuration.
+    fn handle_config_request(
+        &mut self,
+        data: &[u8],
+    ) -> Vec<u8, { 3 + crate::config_store::RECORD_CAPACITY }> {
+        use crate::protocol::{ConfigKey, ConfigRequest, ConfigStatus};
 
-        for &byte in led_data {
-            if data_vec.push(byte).is_err() {
-                return Err(BoardError::ProtocolError);
+        let key = data.get(2).copied().unwrap_or(0);
+        let status = match protocol::parse_config_request(data) {
+            Ok(ConfigRequest::Get(_)) => ConfigStatus::Ok,
+            Ok(ConfigRequest::Set(key, value)) => {
+                match crate::config_store::update(|stored| stored.set_value(key, value)) {
+                    Ok(()) => {
+                        let stored = crate::config_store::get();
+                        if matches!(key, ConfigKey::Brightness | ConfigKey::All) {
+                            crate::led_control::set_global_brightness(stored.brightness);
+                        }
+                        if key == ConfigKey::MaxBrightness {
+                            crate::led_control::set_global_brightness(
+                                crate::led_control::global_brightness(),
+                            );
+                        }
+                        if matches!(key, ConfigKey::DeviceName | ConfigKey::All) {
+                            crate::mdns::set_instance_name(&stored.device_name).ok();
+                        }
+                        if matches!(
+                            key,
+                            ConfigKey::LedCount | ConfigKey::PixelFormat | ConfigKey::All
+                        ) {
+                            self.device_info = Some(crate::device_info::DeviceInfo::read());
+                        }
+                        if matches!(
+                            key,
+                            ConfigKey::KeepaliveTimeout
+                                | ConfigKey::KeepaliveMaxMissed
+                                | ConfigKey::KeepaliveAction
+                                | ConfigKey::All
+                        ) {
+                            self.keepalive = KeepaliveConfig::stored();
+                        }
+                        println!("[UDP] Config key {:?} updated", key);
+                        ConfigStatus::Ok
+                    }
+                    Err(BoardError::StorageError) => ConfigStatus::StorageFailed,
+                    Err(_) => ConfigStatus::InvalidValue,
+                }
             }
+            Err(status) => status,
+        };
+
+        let mut response = Vec::new();
+        let _ = response.extend_from_slice(&[config::CONFIG_HEADER, status as u8, key]);
+        if let Some(key) = ConfigKey::from_code(key) {
+            let _ = response.extend_from_slice(&crate::config_store::get().value(key));
         }
+        response
+    }
 
-        // LED数据解析完成，不打印数据长度
+    /// Apply a mode command code, returning the reply status
+    /// (0 = applied, 1 = unknown mode, 2 = mode queue full)
+    pub(crate) fn handle_mode_command(
+        code: u8,
+        led_mode_sender: &embassy_sync::channel::Sender<
+            'static,
+            embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+            crate::led_control::LedMode,
+            2,
+        >,
+    ) -> u8 {
+        if code == config::MODE_AUTO {
+            crate::led_control::set_auto_mode_switch(true);
+            println!("[UDP] Mode auto-switching restored");
+            return 0;
+        }
 
-        Ok(LedPacket {
-            offset,
-            data: data_vec,
-        })
+        let Some(mode) = crate::led_control::LedMode::from_code(code) else {
+            return 1;
+        };
+        if led_mode_sender.try_send(mode).is_err() {
+            return 2;
+        }
+        // An explicitly selected mode sticks until the next mode command
+        crate::led_control::set_auto_mode_switch(false);
+        println!("[UDP] Mode set to {:?}", mode);
+        0
     }
 }
 
@@ -0,0 +1,135 @@
+//! USB-Serial-JTAG data input module
+//!
+//! Accepts LED data over the C3's built-in USB serial device, so the board can
+//! be driven tethered (no network latency) or used as a development loopback.
+//!
+//! The byte stream carries ordinary 0x02/0x05 data packets, each wrapped in a
+//! frame: `[0xA5][length:2][packet][crc32:4]`, with the CRC over the packet.
+//! Every packet is rendered as soon as it arrives.
+
+use crate::led_control::LedDataSignal;
+use crate::println;
+use crate::udp_server::{MAX_PACKET_SIZE, UdpServer};
+use alloc::vec::Vec;
+use embedded_io_async::Read;
+use esp_hal::Async;
+use esp_hal::usb_serial_jtag::UsbSerialJtagRx;
+
+/// Start-of-frame marker
+pub const FRAME_SYNC: u8 = 0xA5;
+
+/// Largest packet accepted inside a frame
+const MAX_FRAME_PACKET: usize = MAX_PACKET_SIZE + 16;
+
+/// Parser position within a serial frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseState {
+    Sync,
+    LengthHigh,
+    LengthLow(u8),
+    Packet,
+    Crc,
+}
+
+/// Incremental parser for framed packets on a byte stream
+#[derive(Debug)]
+pub struct SerialFramer {
+    state: ParseState,
+    expected: usize,
+    packet: Vec<u8>,
+    crc: [u8; 4],
+    crc_len: usize,
+    /// Frames dropped for a bad length or CRC
+    pub errors: u32,
+}
+
+impl Default for SerialFramer {
+    fn default() -> Self {
+        Self {
+            state: ParseState::Sync,
+            expected: 0,
+            packet: Vec::new(),
+            crc: [0; 4],
+            crc_len: 0,
+            errors: 0,
+        }
+    }
+}
+
+impl SerialFramer {
+    /// Feed one byte, returning the packet once a valid frame has arrived
+    pub fn push(&mut self, byte: u8) -> Option<&[u8]> {
+        self.state = match self.state {
+            ParseState::Sync if byte == FRAME_SYNC => ParseState::LengthHigh,
+            ParseState::Sync => ParseState::Sync,
+            ParseState::LengthHigh => ParseState::LengthLow(byte),
+            ParseState::LengthLow(high) => {
+                let length = u16::from_be_bytes([high, byte]) as usize;
+                if length == 0 || length > MAX_FRAME_PACKET {
+                    self.errors = self.errors.saturating_add(1);
+                    ParseState::Sync
+                } else {
+                    self.expected = length;
+                    self.packet.clear();
+                    ParseState::Packet
+                }
+            }
+            ParseState::Packet => {
+                self.packet.push(byte);
+                if self.packet.len() < self.expected {
+                    ParseState::Packet
+                } else {
+                    self.crc_len = 0;
+                    ParseState::Crc
+                }
+            }
+            ParseState::Crc => {
+                self.crc[self.crc_len] = byte;
+                self.crc_len += 1;
+                if self.crc_len < self.crc.len() {
+                    ParseState::Crc
+                } else {
+                    self.state = ParseState::Sync;
+                    if crate::crc::crc32(&self.packet) != u32::from_be_bytes(self.crc) {
+                        self.errors = self.errors.saturating_add(1);
+                        return None;
+                    }
+                    return Some(&self.packet);
+                }
+            }
+        };
+        None
+    }
+}
+
+/// USB serial receiver task
+#[embassy_executor::task]
+pub async fn usb_serial_task(
+    mut rx: UsbSerialJtagRx<'static, Async>,
+    led_data_sender: &'static LedDataSignal,
+) {
+    println!("[USB] Accepting framed LED data on USB serial");
+
+    let mut framer = SerialFramer::default();
+    let mut frame: Vec<u8> = Vec::new();
+    let mut buffer = [0u8; 64];
+
+    loop {
+        crate::memory::checkpoint(crate::memory::Subsystem::UsbSerial);
+
+        let len = match rx.read(&mut buffer).await {
+            Ok(len) => len,
+            Err(e) => {
+                println!("[USB] Read error: {:?}", e);
+                continue;
+            }
+        };
+
+        for &byte in &buffer[..len] {
+            let Some(packet) = framer.push(byte) else {
+                continue;
+            };
+            let _ = UdpServer::render_packet(&mut frame, packet, led_data_sender);
+        }
+    }
+}
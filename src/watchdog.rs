@@ -0,0 +1,97 @@
+//! Hardware watchdog supervisor
+//!
+//! The LED task, the UDP server loop and the state machine loop report a
+//! heartbeat on every iteration. A supervisor task feeds the TIMG0 watchdog
+//! only while each of them has reported recently, so a hung task resets the
+//! board cleanly instead of leaving the strip frozen on its last frame. A
+//! stalled executor stops the supervisor too, which the watchdog catches the
+//! same way.
+//!
+//! Tasks are only supervised once they have reported for the first time, as
+//! the UDP server waits for the network before it starts looping.
+
+use crate::config;
+use crate::println;
+use core::sync::atomic::{AtomicU32, Ordering};
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::peripherals::TIMG0;
+use esp_hal::timer::timg::{MwdtStage, MwdtStageAction, Wdt};
+
+/// Supervised tasks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Task {
+    Led = 0,
+    Udp = 1,
+    StateMachine = 2,
+}
+
+/// Number of supervised tasks
+const TASK_COUNT: usize = 3;
+
+impl Task {
+    /// All tasks in id order
+    const ALL: [Task; TASK_COUNT] = [Task::Led, Task::Udp, Task::StateMachine];
+
+    /// Longest gap between heartbeats before the task counts as hung
+    fn stall_limit(self) -> Duration {
+        let ms = match self {
+            Task::Led | Task::Udp => config::WATCHDOG_TASK_STALL_MS,
+            // Provisioning and connection rounds hold the loop for a while
+            Task::StateMachine => config::WATCHDOG_STATE_MACHINE_STALL_MS,
+        };
+        Duration::from_millis(ms as u64)
+    }
+}
+
+/// Last heartbeat per task, in milliseconds since boot (0 = not started)
+static HEARTBEATS: [AtomicU32; TASK_COUNT] = [const { AtomicU32::new(0) }; TASK_COUNT];
+
+/// How often the supervisor checks the heartbeats and feeds the watchdog
+const FEED_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// Report that `task` is making progress
+///
+/// Cheap enough to call once per task loop iteration.
+pub fn heartbeat(task: Task) {
+    let now = (Instant::now().as_millis() as u32).max(1);
+    HEARTBEATS[task as usize].store(now, Ordering::Relaxed);
+}
+
+/// The first supervised task that stopped reporting, if any
+fn stalled_task() -> Option<Task> {
+    let now = Instant::now().as_millis() as u32;
+    Task::ALL.into_iter().find(|&task| {
+        let last = HEARTBEATS[task as usize].load(Ordering::Relaxed);
+        last != 0 && now.wrapping_sub(last) as u64 > task.stall_limit().as_millis()
+    })
+}
+
+/// Watchdog supervisor task
+#[embassy_executor::task]
+pub async fn watchdog_task(mut wdt: Wdt<TIMG0<'static>>) {
+    wdt.set_timeout(
+        MwdtStage::Stage0,
+        esp_hal::time::Duration::from_millis(config::WATCHDOG_TIMEOUT_MS as u64),
+    );
+    wdt.set_stage_action(MwdtStage::Stage0, MwdtStageAction::ResetSystem);
+    wdt.enable();
+    println!(
+        "[WDT] Watchdog enabled ({}ms timeout)",
+        config::WATCHDOG_TIMEOUT_MS
+    );
+
+    loop {
+        match stalled_task() {
+            None => wdt.feed(),
+            Some(task) => {
+                // Stop feeding and let the watchdog reset the board
+                println!("[WDT] {:?} task stalled - waiting for watchdog reset", task);
+                loop {
+                    Timer::after(FEED_INTERVAL).await;
+                }
+            }
+        }
+        Timer::after(FEED_INTERVAL).await;
+    }
+}
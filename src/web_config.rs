@@ -0,0 +1,268 @@
+//! Web configuration UI
+//!
+//! A settings page for adjusting the board without rebuilding it: strip
+//! length, pixel order, brightness and its limit, gamma, white balance, an
+//! extra Wi-Fi network and the device name. It is served on the station network at `config::HTTP_PORT`
+//! and at `/settings` on the setup and configuration access points.
+//!
+//! `GET /firmware` returns the running version, build, partition and update
+//! state as JSON for fleet tooling, and `GET /metrics` the board's health for
+//! Prometheus (see `metrics`).
+//!
+//! Saved settings go through the configuration store, all or nothing. The
+//! brightness and name apply at once; the strip length, pixel order and color
+//! correction with the next frame; a new network is tried on the next connection attempt (the
+//! page has a restart button for that).
+
+use crate::color_correction::GAMMA_RANGE;
+use crate::config_store::{self, WifiCredentials};
+use crate::println;
+use crate::protocol::PixelFormat;
+use crate::provisioning::{form_value, push_escaped};
+use crate::{BoardError, config};
+use alloc::string::String;
+use embassy_net::{Stack, tcp::TcpSocket};
+use embassy_time::Duration;
+
+/// Largest request accepted (headers and form body)
+const MAX_REQUEST_SIZE: usize = 1024;
+
+/// Pixel formats with the build's bytes per LED, offered in the picker
+const PIXEL_FORMATS: [PixelFormat; 4] = [
+    PixelFormat::Rgb,
+    PixelFormat::Grb,
+    PixelFormat::Rgbw,
+    PixelFormat::Grbw,
+];
+
+/// White balance channels, in stored order
+const WHITE_BALANCE_FIELDS: [&str; 4] = ["red", "green", "blue", "white"];
+
+const SETTINGS_PAGE_HEAD: &str = concat!(
+    "<!DOCTYPE html><html><head><meta charset=\"utf-8\">",
+    "<meta name=\"viewport\" content=\"width=device-width,initial-scale=1\">",
+    "<title>Ambient light settings</title></head><body>",
+    "<h1>Ambient light settings</h1>",
+);
+
+const SETTINGS_PAGE_TAIL: &str = concat!(
+    "<p><button>Save</button></p>",
+    "</form>",
+    "<form method=\"post\" action=\"/reboot\"><p><button>Restart board</button></p></form>",
+    "</body></html>"
+);
+
+/// Answer `GET /settings` with the form, `POST /settings` by saving it,
+/// `GET /firmware` with the running firmware as JSON and `GET /metrics` with
+/// the Prometheus metrics
+///
+/// Returns the content type and body, or None for other requests.
+pub(crate) fn respond(request: &[u8], body: &[u8]) -> Option<(&'static str, String)> {
+    if request.starts_with(b"POST /settings ") {
+        let notice = match save(body) {
+            Ok(()) => "Saved.",
+            Err(BoardError::StorageError) => "The settings couldn't be written to flash.",
+            Err(_) => "Some values were out of range - nothing was saved.",
+        };
+        Some(("text/html", settings_page(Some(notice))))
+    } else if request.starts_with(b"GET /settings ") {
+        Some(("text/html", settings_page(None)))
+    } else if request.starts_with(b"GET /firmware ") {
+        Some((
+            "application/json",
+            crate::ota::FirmwareInfo::read().to_json(),
+        ))
+    } else if request.starts_with(b"GET /metrics ") {
+        Some((crate::metrics::CONTENT_TYPE, crate::metrics::render()))
+    } else {
+        None
+    }
+}
+
+/// The settings form, filled in with the stored values
+fn settings_page(notice: Option<&str>) -> String {
+    let stored = config_store::get();
+    let mut page = String::from(SETTINGS_PAGE_HEAD);
+    if let Some(notice) = notice {
+        page.push_str("<p><strong>");
+        page.push_str(notice);
+        page.push_str("</strong></p>");
+    }
+    page.push_str("<form method=\"post\" action=\"/settings\">");
+
+    let _ = core::fmt::write(
+        &mut page,
+        format_args!(
+            "<p><label>LEDs on the strip<br><input name=\"led_count\" type=\"number\" \
+             min=\"1\" max=\"{}\" value=\"{}\" required></label></p>",
+            config::MAX_LEDS,
+            stored.led_count
+        ),
+    );
+
+    page.push_str("<p><label>Pixel order<br><select name=\"pixel_format\">");
+    for format in PIXEL_FORMATS
+        .iter()
+        .filter(|f| f.bytes_per_led() == config::BYTES_PER_LED)
+    {
+        let selected = if *format == stored.pixel_format {
+            " selected"
+        } else {
+            ""
+        };
+        let _ = core::fmt::write(
+            &mut page,
+            format_args!(
+                "<option value=\"{}\"{}>{}</option>",
+                *format as u8,
+                selected,
+                format.name()
+            ),
+        );
+    }
+    page.push_str("</select></label></p>");
+
+    let _ = core::fmt::write(
+        &mut page,
+        format_args!(
+            "<p><label>Brightness (0-255)<br><input name=\"brightness\" type=\"number\" \
+             min=\"0\" max=\"255\" value=\"{}\" required></label></p>\
+             <p><label>Brightness limit (0-255)<br><input name=\"max_brightness\" \
+             type=\"number\" min=\"0\" max=\"255\" value=\"{}\" required></label></p>",
+            stored.brightness, stored.max_brightness
+        ),
+    );
+
+    let _ = core::fmt::write(
+        &mut page,
+        format_args!(
+            "<p><label>Gamma in tenths (10 = off, 22 = 2.2)<br><input name=\"gamma\" \
+             type=\"number\" min=\"{}\" max=\"{}\" value=\"{}\" required></label></p>",
+            GAMMA_RANGE.start(),
+            GAMMA_RANGE.end(),
+            stored.gamma
+        ),
+    );
+    page.push_str("<p>White balance (0-255 per channel)<br>");
+    for (name, scale) in WHITE_BALANCE_FIELDS.iter().zip(stored.white_balance) {
+        let _ = core::fmt::write(
+            &mut page,
+            format_args!(
+                "<label>{} <input name=\"wb_{}\" type=\"number\" min=\"0\" max=\"255\" \
+                 value=\"{}\" required></label> ",
+                name, name, scale
+            ),
+        );
+    }
+    page.push_str("</p>");
+
+    page.push_str(
+        "<p><label>Device name (empty for the default)<br>\
+         <input name=\"name\" maxlength=\"63\" value=\"",
+    );
+    push_escaped(&mut page, &stored.device_name, false);
+    page.push_str("\"></label></p>");
+
+    page.push_str("<p>Saved networks: ");
+    if stored.networks.is_empty() {
+        page.push_str("none");
+    }
+    for (i, network) in stored.networks.iter().enumerate() {
+        if i > 0 {
+            page.push_str(", ");
+        }
+        push_escaped(&mut page, &network.ssid, false);
+    }
+    page.push_str(
+        "</p><p><label>Add network (optional)<br>\
+         <input name=\"ssid\" maxlength=\"32\"></label></p>\
+         <p><label>Password<br>\
+         <input name=\"password\" type=\"password\" maxlength=\"64\"></label></p>",
+    );
+
+    page.push_str(SETTINGS_PAGE_TAIL);
+    page
+}
+
+/// Store a submitted settings form and apply what takes effect at once
+fn save(body: &[u8]) -> Result<(), BoardError> {
+    fn number<T: core::str::FromStr>(body: &[u8], key: &str) -> Result<T, BoardError> {
+        form_value::<8>(body, key)
+            .and_then(|value| value.trim().parse().ok())
+            .ok_or(BoardError::ProtocolError)
+    }
+
+    config_store::update(|stored| {
+        stored.led_count = number(body, "led_count")?;
+        stored.pixel_format = PixelFormat::from_code(number(body, "pixel_format")?)
+            .ok_or(BoardError::ProtocolError)?;
+        stored.brightness = number(body, "brightness")?;
+        stored.max_brightness = number(body, "max_brightness")?;
+        stored.gamma = number(body, "gamma")?;
+        for (i, name) in WHITE_BALANCE_FIELDS.iter().enumerate() {
+            let mut key = String::from("wb_");
+            key.push_str(name);
+            stored.white_balance[i] = number(body, &key)?;
+        }
+        stored.device_name = form_value(body, "name").ok_or(BoardError::ProtocolError)?;
+
+        let ssid = form_value::<32>(body, "ssid").unwrap_or_default();
+        if !ssid.is_empty() {
+            let password = form_value::<64>(body, "password").unwrap_or_default();
+            let network =
+                WifiCredentials::new(&ssid, &password).ok_or(BoardError::ProtocolError)?;
+            stored.add_network(network);
+        }
+        Ok(())
+    })?;
+
+    let stored = config_store::get();
+    crate::led_control::set_global_brightness(stored.brightness);
+    crate::mdns::set_instance_name(&stored.device_name).ok();
+    println!("[WEB] Settings saved");
+    Ok(())
+}
+
+/// Settings page HTTP task on the station network
+///
+/// Every path but `/reboot` shows the settings page.
+#[embassy_executor::task]
+pub async fn web_config_task(stack: &'static Stack<'static>, port: u16) {
+    let mut rx_buffer = [0; 1536];
+    let mut tx_buffer = [0; 1536];
+    println!("[WEB] Settings page on port {}", port);
+
+    loop {
+        let mut socket = TcpSocket::new(*stack, &mut rx_buffer, &mut tx_buffer);
+        socket.set_timeout(Some(Duration::from_secs(10)));
+
+        if let Err(e) = socket.accept(port).await {
+            println!("[WEB] Accept failed: {:?}", e);
+            continue;
+        }
+        crate::memory::checkpoint(crate::memory::Subsystem::WebConfig);
+
+        let mut request = [0u8; MAX_REQUEST_SIZE];
+        if let Ok((header_end, request_end)) =
+            crate::provisioning::read_request(&mut socket, &mut request).await
+        {
+            let reboot = request.starts_with(b"POST /reboot ");
+            let (content_type, page) = if reboot {
+                ("text/html", String::from(crate::provisioning::REBOOT_PAGE))
+            } else {
+                let (head, body) = request[..request_end].split_at(header_end);
+                respond(head, body).unwrap_or_else(|| ("text/html", settings_page(None)))
+            };
+            let sent = crate::provisioning::write_response(&mut socket, content_type, &page).await;
+            if reboot && sent.is_ok() {
+                println!("[WEB] Reboot requested from the settings page");
+                crate::state_machine::send_event(
+                    crate::state_machine::SystemEvent::RebootRequested,
+                );
+            }
+        }
+        socket.close();
+        socket.flush().await.ok();
+        socket.abort();
+    }
+}
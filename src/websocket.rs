@@ -0,0 +1,244 @@
+//! WebSocket streaming module
+//!
+//! Browser-based senders can't emit UDP, so this task offers a WebSocket
+//! endpoint accepting the same data packets. Each binary message carries one
+//! ordinary 0x02/0x05 packet and is rendered as soon as it arrives. One client
+//! is served at a time.
+
+use crate::BoardError;
+use crate::led_control::LedDataSignal;
+use crate::println;
+use crate::udp_server::{MAX_PACKET_SIZE, UdpServer};
+use alloc::vec::Vec;
+use embassy_net::{Stack, tcp::TcpSocket};
+use embassy_time::Duration;
+use embedded_io_async::{Read, Write};
+use sha1::{Digest, Sha1};
+
+/// GUID appended to the client key in the opening handshake (RFC 6455)
+const HANDSHAKE_GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest HTTP upgrade request accepted
+const MAX_REQUEST_SIZE: usize = 1024;
+
+/// Largest message payload accepted
+const MAX_MESSAGE_SIZE: usize = MAX_PACKET_SIZE + 16;
+
+/// Connections idle for this long are dropped
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// Find a header value in an HTTP request (header names are case-insensitive)
+pub(crate) fn header_value<'r>(request: &'r [u8], name: &str) -> Option<&'r [u8]> {
+    request.split(|&b| b == b'\n').skip(1).find_map(|line| {
+        let colon = line.iter().position(|&b| b == b':')?;
+        if !line[..colon].eq_ignore_ascii_case(name.as_bytes()) {
+            return None;
+        }
+        Some(line[colon + 1..].trim_ascii())
+    })
+}
+
+/// Standard base64 encoding of a SHA-1 digest
+fn base64_encode(input: &[u8; 20]) -> [u8; 28] {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut output = [b'='; 28];
+    for (chunk, out) in input.chunks(3).zip(output.chunks_mut(4)) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, b[0], b[1], b[2]]);
+        for (i, slot) in out.iter_mut().enumerate().take(chunk.len() + 1) {
+            *slot = ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3F];
+        }
+    }
+    output
+}
+
+/// `Sec-WebSocket-Accept` value for a client key
+fn accept_key(client_key: &[u8]) -> [u8; 28] {
+    let mut hasher = Sha1::new();
+    hasher.update(client_key);
+    hasher.update(HANDSHAKE_GUID);
+    base64_encode(&hasher.finalize().into())
+}
+
+/// Read the HTTP upgrade request and answer it
+async fn handshake(socket: &mut TcpSocket<'_>) -> Result<(), BoardError> {
+    let mut request = [0u8; MAX_REQUEST_SIZE];
+    let mut len = 0;
+    while !request[..len].ends_with(b"\r\n\r\n") {
+        if len == request.len() {
+            return Err(BoardError::ProtocolError);
+        }
+        match socket.read(&mut request[len..]).await {
+            Ok(0) | Err(_) => return Err(BoardError::WebSocketError),
+            Ok(n) => len += n,
+        }
+    }
+
+    let request = &request[..len];
+    let upgrade = header_value(request, "Upgrade");
+    let Some(key) = header_value(request, "Sec-WebSocket-Key") else {
+        return Err(BoardError::ProtocolError);
+    };
+    if !upgrade.is_some_and(|u| u.eq_ignore_ascii_case(b"websocket")) {
+        socket
+            .write_all(b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n")
+            .await
+            .ok();
+        return Err(BoardError::ProtocolError);
+    }
+
+    let accept = accept_key(key);
+    let parts: [&[u8]; 3] = [
+        b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: ",
+        &accept,
+        b"\r\n\r\n",
+    ];
+    for part in parts {
+        socket
+            .write_all(part)
+            .await
+            .map_err(|_| BoardError::WebSocketError)?;
+    }
+    Ok(())
+}
+
+/// Send an unmasked control frame
+async fn send_control(
+    socket: &mut TcpSocket<'_>,
+    opcode: u8,
+    payload: &[u8],
+) -> Result<(), BoardError> {
+    // Control frame payloads are at most 125 bytes
+    let payload = &payload[..payload.len().min(125)];
+    socket
+        .write_all(&[0x80 | opcode, payload.len() as u8])
+        .await
+        .map_err(|_| BoardError::WebSocketError)?;
+    socket
+        .write_all(payload)
+        .await
+        .map_err(|_| BoardError::WebSocketError)
+}
+
+/// Serve one client until it closes or errors
+async fn serve(
+    socket: &mut TcpSocket<'_>,
+    led_data_sender: &'static LedDataSignal,
+) -> Result<(), BoardError> {
+    handshake(socket).await?;
+    println!("[WS] Client connected");
+
+    let mut frame: Vec<u8> = Vec::new();
+    let mut payload: Vec<u8> = Vec::new();
+
+    loop {
+        crate::memory::checkpoint(crate::memory::Subsystem::WebSocket);
+
+        let mut head = [0u8; 2];
+        socket
+            .read_exact(&mut head)
+            .await
+            .map_err(|_| BoardError::WebSocketError)?;
+        let fin = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0F;
+        let masked = head[1] & 0x80 != 0;
+
+        let len = match head[1] & 0x7F {
+            126 => {
+                let mut ext = [0u8; 2];
+                socket
+                    .read_exact(&mut ext)
+                    .await
+                    .map_err(|_| BoardError::WebSocketError)?;
+                u16::from_be_bytes(ext) as usize
+            }
+            127 => {
+                let mut ext = [0u8; 8];
+                socket
+                    .read_exact(&mut ext)
+                    .await
+                    .map_err(|_| BoardError::WebSocketError)?;
+                u64::from_be_bytes(ext).min(usize::MAX as u64) as usize
+            }
+            len => len as usize,
+        };
+
+        // Clients must mask their frames; fragmented messages aren't supported
+        if !masked || !fin || len > MAX_MESSAGE_SIZE {
+            send_control(socket, OPCODE_CLOSE, &1002u16.to_be_bytes())
+                .await
+                .ok();
+            return Err(BoardError::ProtocolError);
+        }
+
+        let mut mask = [0u8; 4];
+        socket
+            .read_exact(&mut mask)
+            .await
+            .map_err(|_| BoardError::WebSocketError)?;
+        payload.resize(len, 0);
+        socket
+            .read_exact(&mut payload)
+            .await
+            .map_err(|_| BoardError::WebSocketError)?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        match opcode {
+            OPCODE_BINARY => {
+                let _ = UdpServer::render_packet(&mut frame, &payload, led_data_sender);
+            }
+            OPCODE_PING => send_control(socket, OPCODE_PONG, &payload).await?,
+            OPCODE_CLOSE => {
+                send_control(socket, OPCODE_CLOSE, &payload).await.ok();
+                return Ok(());
+            }
+            // Text and pong frames are ignored
+            _ => {}
+        }
+    }
+}
+
+/// WebSocket server task
+#[embassy_executor::task]
+pub async fn websocket_task(
+    stack: &'static Stack<'static>,
+    led_data_sender: &'static LedDataSignal,
+    port: u16,
+) {
+    stack.wait_config_up().await;
+    println!("[WS] Listening for WebSocket clients on port {}", port);
+
+    let mut rx_buffer = [0; 4096];
+    let mut tx_buffer = [0; 256];
+
+    loop {
+        let mut socket = TcpSocket::new(*stack, &mut rx_buffer, &mut tx_buffer);
+        socket.set_timeout(Some(IDLE_TIMEOUT));
+
+        if let Err(e) = socket.accept(port).await {
+            println!("[WS] Accept failed: {:?}", e);
+            continue;
+        }
+
+        match serve(&mut socket, led_data_sender).await {
+            Ok(()) => println!("[WS] Client disconnected"),
+            Err(e) => println!("[WS] Connection closed: {:?}", e),
+        }
+
+        socket.close();
+        socket.flush().await.ok();
+        socket.abort();
+    }
+}
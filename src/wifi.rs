@@ -1,15 +1,59 @@
-//! WiFi module for ESP32-C3 board
+//! WiFi module for the board
 //!
 //! Handles WiFi network connection using esp-wifi 0.14.1 with embassy-net DHCP
 
+use crate::println;
+use crate::state_machine::SystemEvent;
 use crate::{BoardError, config};
 use alloc::string::{String, ToString};
-use embassy_net::Stack;
-use embassy_time::{Duration, Timer};
-use esp_println::println;
-use esp_wifi::wifi::{AuthMethod, ClientConfiguration, WifiController};
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicI8, Ordering};
+use embassy_net::{Ipv6Address, Stack};
+use embassy_time::{Duration, Instant};
+use esp_hal::rng::Rng;
+use esp_wifi::config::PowerSaveMode;
+use esp_wifi::wifi::{
+    AccessPointConfiguration, AccessPointInfo, AuthMethod, ClientConfiguration, Configuration,
+    ScanConfig, WifiController, WifiEvent,
+};
 use heapless::Vec;
 
+/// Whether the configuration access point runs alongside the station
+pub fn config_ap_enabled() -> bool {
+    (8..=63).contains(&config::CONFIG_AP_PASSWORD.len())
+}
+
+/// The low-profile configuration access point: WPA2, one client, and hidden
+/// unless configured otherwise. It shares the station's channel.
+///
+/// Safe mode keeps the open setup access point up in its place.
+fn config_access_point() -> Option<AccessPointConfiguration> {
+    if crate::crash_log::is_safe_mode() {
+        return Some(AccessPointConfiguration {
+            ssid: crate::provisioning::ap_ssid().as_str().into(),
+            auth_method: AuthMethod::None,
+            ..Default::default()
+        });
+    }
+    if !config_ap_enabled() {
+        return None;
+    }
+    // The driver takes SSIDs of up to 32 bytes and WPA2 passphrases of 8-63
+    let ssid = crate::provisioning::config_ap_ssid();
+    let password = config::CONFIG_AP_PASSWORD;
+    if ssid.len() > 32 || !(password.is_empty() || (8..=63).contains(&password.len())) {
+        return None;
+    }
+    Some(AccessPointConfiguration {
+        ssid: ssid.as_str().into(),
+        ssid_hidden: config::CONFIG_AP_HIDDEN,
+        auth_method: AuthMethod::WPA2Personal,
+        password: password.into(),
+        max_connections: 1,
+        ..Default::default()
+    })
+}
+
 /// DHCP configuration information
 #[derive(Debug, Clone)]
 pub struct DhcpInfo {
@@ -19,10 +63,122 @@ pub struct DhcpInfo {
     pub dns_servers: Vec<[u8; 4], 3>,
 }
 
+/// Networks kept from a scan
+pub const MAX_SCAN_RESULTS: usize = 16;
+
+/// A network found by `WiFiManager::scan`
+#[derive(Debug, Clone)]
+pub struct ScanResult {
+    pub ssid: heapless::String<32>,
+    /// Signal strength (dBm)
+    pub rssi: i8,
+    pub auth_method: AuthMethod,
+    pub channel: u8,
+    /// Access point with the strongest signal
+    pub bssid: [u8; 6],
+}
+
+impl ScanResult {
+    /// Convert a driver scan entry, under the given SSID (None when empty or too long)
+    fn from_info(ap: &AccessPointInfo, ssid: &str) -> Option<Self> {
+        if ssid.is_empty() {
+            return None;
+        }
+        Some(Self {
+            ssid: heapless::String::try_from(ssid).ok()?,
+            rssi: ap.signal_strength,
+            auth_method: ap.auth_method.unwrap_or(AuthMethod::None),
+            channel: ap.channel,
+            bssid: ap.bssid,
+        })
+    }
+}
+
+/// Short lowercase name of an authentication method
+pub fn auth_method_name(auth_method: AuthMethod) -> &'static str {
+    match auth_method {
+        AuthMethod::None => "open",
+        AuthMethod::WEP => "wep",
+        AuthMethod::WPA => "wpa",
+        AuthMethod::WPA2Personal => "wpa2",
+        AuthMethod::WPAWPA2Personal => "wpa/wpa2",
+        AuthMethod::WPA2Enterprise => "wpa2-enterprise",
+        AuthMethod::WPA3Personal => "wpa3",
+        AuthMethod::WPA2WPA3Personal => "wpa2/wpa3",
+        _ => "other",
+    }
+}
+
+/// Last sampled signal strength (dBm), 0 when unknown
+static LAST_RSSI: AtomicI8 = AtomicI8::new(0);
+
+/// Signal strength of the joined access point at the last sample
+pub fn last_rssi() -> Option<i8> {
+    match LAST_RSSI.load(Ordering::Relaxed) {
+        0 => None,
+        rssi => Some(rssi),
+    }
+}
+
+/// Authentication method from its `auth_method_name`, None for `auto` or unknown names
+pub fn parse_auth_method(name: &str) -> Option<AuthMethod> {
+    [
+        AuthMethod::None,
+        AuthMethod::WEP,
+        AuthMethod::WPA,
+        AuthMethod::WPA2Personal,
+        AuthMethod::WPAWPA2Personal,
+        AuthMethod::WPA2Enterprise,
+        AuthMethod::WPA3Personal,
+        AuthMethod::WPA2WPA3Personal,
+    ]
+    .into_iter()
+    .find(|&auth_method| auth_method_name(auth_method) == name)
+}
+
+/// Modem-sleep mode from its name: `off`, `min` or `max`
+pub fn parse_power_save(name: &str) -> Option<PowerSaveMode> {
+    match name {
+        "off" => Some(PowerSaveMode::None),
+        "min" => Some(PowerSaveMode::Minimum),
+        "max" => Some(PowerSaveMode::Maximum),
+        _ => None,
+    }
+}
+
+/// SSID of the network last joined, for telemetry
+static ACTIVE_SSID: critical_section::Mutex<RefCell<heapless::String<32>>> =
+    critical_section::Mutex::new(RefCell::new(heapless::String::new()));
+
+fn set_active_ssid(ssid: &str) {
+    critical_section::with(|cs| {
+        let mut active = ACTIVE_SSID.borrow_ref_mut(cs);
+        active.clear();
+        let _ = active.push_str(ssid);
+    });
+}
+
+/// SSID of the network the station joined (empty when it hasn't)
+pub fn active_ssid() -> heapless::String<32> {
+    critical_section::with(|cs| ACTIVE_SSID.borrow_ref(cs).clone())
+}
+
+/// Credentials and auth method of the joined network
+#[derive(Debug, Clone)]
+struct JoinedNetwork {
+    ssid: heapless::String<32>,
+    password: heapless::String<64>,
+    auth_method: AuthMethod,
+}
+
 /// WiFi manager for handling network connectivity with real DHCP
 pub struct WiFiManager<'a> {
     controller: WifiController<'a>,
     is_connected: bool,
+    /// Signal below `RSSI_DEGRADED_DBM` and not yet recovered
+    signal_degraded: bool,
+    /// Network of the last successful `connect`, for roaming
+    joined: Option<JoinedNetwork>,
     stack: Option<Stack<'a>>, // Embassy-net stack for real DHCP
 }
 
@@ -32,6 +188,8 @@ impl<'a> WiFiManager<'a> {
         Self {
             controller,
             is_connected: false,
+            signal_degraded: false,
+            joined: None,
             stack: None,
         }
     }
@@ -42,47 +200,403 @@ impl<'a> WiFiManager<'a> {
     }
 
     /// Connect to WiFi network (async)
+    ///
+    /// Start-up and association are awaited on the controller's events, so the
+    /// executor keeps running other tasks meanwhile.
     pub async fn connect(&mut self, ssid: &str, password: &str) -> Result<(), BoardError> {
+        let fallback = if password.is_empty() {
+            AuthMethod::None
+        } else {
+            AuthMethod::WPA2Personal
+        };
+        let pinned = parse_auth_method(config::WIFI_AUTH_METHOD);
+        let network = if config::WIFI_HIDDEN_SSID {
+            self.probe_hidden(ssid).await
+        } else if pinned.is_none() {
+            self.find_network(ssid).await
+        } else {
+            None
+        };
+        let auth_method = pinned
+            .or(network.as_ref().map(|n| n.auth_method))
+            .unwrap_or(fallback);
+        // A pinned BSSID wins; a hidden network is joined on the access point
+        // its probe found
+        let target = match config::WIFI_BSSID {
+            Some(bssid) => Some((bssid, None)),
+            None => network
+                .filter(|_| config::WIFI_HIDDEN_SSID)
+                .map(|n| (n.bssid, Some(n.channel))),
+        };
+        // Open networks take no password, even if one was provisioned
+        let password = if auth_method == AuthMethod::None {
+            ""
+        } else {
+            password
+        };
+
+        self.join(ssid, password, auth_method, target).await?;
+        self.signal_degraded = false;
+        self.joined = Some(JoinedNetwork {
+            ssid: heapless::String::try_from(ssid).map_err(|_| BoardError::WiFiError)?,
+            password: heapless::String::try_from(password).map_err(|_| BoardError::WiFiError)?,
+            auth_method,
+        });
+        Ok(())
+    }
+
+    /// Associate with a network, optionally a specific access point (BSSID and channel)
+    async fn join(
+        &mut self,
+        ssid: &str,
+        password: &str,
+        auth_method: AuthMethod,
+        target: Option<([u8; 6], Option<u8>)>,
+    ) -> Result<(), BoardError> {
+        if ssid.len() > 32 || password.len() > 64 {
+            return Err(BoardError::WiFiError);
+        }
         let client_config = ClientConfiguration {
-            ssid: ssid.try_into().map_err(|_| BoardError::WiFiError)?,
-            password: password.try_into().map_err(|_| BoardError::WiFiError)?,
-            auth_method: AuthMethod::WPA2Personal,
+            ssid: ssid.into(),
+            password: password.into(),
+            auth_method,
+            bssid: target.map(|(bssid, _)| bssid),
+            channel: target.and_then(|(_, channel)| channel),
+        };
+
+        // Keep the configuration access point up next to the station if enabled
+        let configuration = match config_access_point() {
+            Some(ap_config) => Configuration::Mixed(client_config, ap_config),
+            None => Configuration::Client(client_config),
+        };
+        self.controller
+            .set_configuration(&configuration)
+            .map_err(|_| BoardError::WiFiError)?;
+
+        if !self.controller.is_started().unwrap_or(false) {
+            self.controller
+                .start_async()
+                .await
+                .map_err(|_| BoardError::WiFiError)?;
+        }
+
+        // Modem sleep delays received frames until the next beacon, which shows
+        // up as frame jitter on some access points
+        let power_save = parse_power_save(config::WIFI_POWER_SAVE).unwrap_or(PowerSaveMode::None);
+        if let Err(e) = self.set_power_save(power_save) {
+            println!("[WIFI] Failed to set power save mode: {:?}", e);
+        }
+
+        // Resolves on the StaConnected / StaDisconnected event
+        let timeout = Duration::from_millis(config::WIFI_CONNECT_TIMEOUT_MS as u64);
+        match embassy_time::with_timeout(timeout, self.controller.connect_async()).await {
+            Ok(Ok(())) => {
+                self.is_connected = true;
+                set_active_ssid(ssid);
+                println!("[WIFI] Successfully connected to WiFi network: {}", ssid);
+
+                // Try to get DHCP IP address
+                self.update_dhcp_ip();
+
+                Ok(())
+            }
+            Ok(Err(e)) => {
+                println!(
+                    "[WIFI] Failed to connect to WiFi network '{}': {:?}",
+                    ssid, e
+                );
+                Err(BoardError::WiFiError)
+            }
+            Err(_) => {
+                println!(
+                    "[WIFI] Timed out connecting to WiFi network '{}' after {}s",
+                    ssid,
+                    timeout.as_secs()
+                );
+                // Abandon the pending attempt before the next retry
+                self.controller.disconnect_async().await.ok();
+                Err(BoardError::WiFiError)
+            }
+        }
+    }
+
+    /// Bring up the open setup access point, replacing station mode
+    pub async fn start_access_point(&mut self, ssid: &str) -> Result<(), BoardError> {
+        if ssid.len() > 32 {
+            return Err(BoardError::WiFiError);
+        }
+        let ap_config = AccessPointConfiguration {
+            ssid: ssid.into(),
+            auth_method: AuthMethod::None,
             ..Default::default()
         };
 
+        if self.controller.is_started().unwrap_or(false) {
+            self.controller
+                .stop_async()
+                .await
+                .map_err(|_| BoardError::WiFiError)?;
+        }
+        self.is_connected = false;
+        set_active_ssid("");
+
         self.controller
-            .set_configuration(&esp_wifi::wifi::Configuration::Client(client_config))
+            .set_configuration(&Configuration::AccessPoint(ap_config))
             .map_err(|_| BoardError::WiFiError)?;
+        self.controller
+            .start_async()
+            .await
+            .map_err(|_| BoardError::WiFiError)?;
+        println!("[WIFI] Setup access point '{}' started", ssid);
+        Ok(())
+    }
+
+    /// Take the setup access point down so `connect` can use station mode again
+    pub async fn stop_access_point(&mut self) -> Result<(), BoardError> {
+        self.controller
+            .stop_async()
+            .await
+            .map_err(|_| BoardError::WiFiError)
+    }
+
+    /// Start station mode without joining a network, for sniffing
+    pub async fn start_station(&mut self) -> Result<(), BoardError> {
+        if self.controller.is_started().unwrap_or(false) {
+            self.controller
+                .stop_async()
+                .await
+                .map_err(|_| BoardError::WiFiError)?;
+        }
+        self.is_connected = false;
+        set_active_ssid("");
 
-        self.controller.start().map_err(|_| BoardError::WiFiError)?;
         self.controller
-            .connect()
+            .set_configuration(&Configuration::Client(ClientConfiguration::default()))
+            .map_err(|_| BoardError::WiFiError)?;
+        self.controller
+            .start_async()
+            .await
+            .map_err(|_| BoardError::WiFiError)
+    }
+
+    /// Scan for networks, strongest first
+    ///
+    /// Hidden networks are left out and each SSID is listed once, with its
+    /// strongest access point. Needs station mode, so it fails while the setup
+    /// access point is up.
+    pub async fn scan(&mut self) -> Result<Vec<ScanResult, MAX_SCAN_RESULTS>, BoardError> {
+        if !self.controller.is_started().unwrap_or(false) {
+            self.start_station().await?;
+        }
+
+        let found = self
+            .controller
+            .scan_n_async(MAX_SCAN_RESULTS * 2)
+            .await
             .map_err(|_| BoardError::WiFiError)?;
 
-        // Wait for connection with async delays
-        let mut attempts = 0;
-        const MAX_ATTEMPTS: u32 = 50;
+        let mut results: Vec<ScanResult, MAX_SCAN_RESULTS> = Vec::new();
+        for ap in &found {
+            let Some(network) = ScanResult::from_info(ap, ap.ssid.as_str()) else {
+                continue;
+            };
+            match results.iter_mut().find(|r| r.ssid == network.ssid) {
+                Some(known) if network.rssi > known.rssi => *known = network,
+                Some(_) => {}
+                None => {
+                    let _ = results.push(network);
+                }
+            }
+        }
+        results.sort_unstable_by_key(|r| core::cmp::Reverse(r.rssi));
+
+        println!("[WIFI] Scan found {} networks", results.len());
+        Ok(results)
+    }
 
-        while !self.controller.is_connected().unwrap_or(false) && attempts < MAX_ATTEMPTS {
-            attempts += 1;
-            // Async delay instead of busy waiting
-            Timer::after(Duration::from_millis(200)).await;
+    /// Find a network by scanning, to learn its authentication method
+    ///
+    /// None if the network wasn't found (it may be hidden or out of range).
+    async fn find_network(&mut self, ssid: &str) -> Option<ScanResult> {
+        let network = self
+            .scan()
+            .await
+            .ok()?
+            .into_iter()
+            .find(|n| n.ssid == ssid)?;
+        println!(
+            "[WIFI] '{}' uses {}",
+            ssid,
+            auth_method_name(network.auth_method)
+        );
+        Some(network)
+    }
+
+    /// Find a hidden network with a probe naming it
+    ///
+    /// Hidden access points leave their SSID out of beacons, so a plain scan
+    /// never lists them; they do answer probe requests carrying the SSID.
+    async fn probe_hidden(&mut self, ssid: &str) -> Option<ScanResult> {
+        let network = self
+            .access_points(ssid)
+            .await
+            .ok()?
+            .into_iter()
+            .max_by_key(|n| n.rssi)?;
+        println!(
+            "[WIFI] Hidden network '{}' answered on channel {} ({})",
+            ssid,
+            network.channel,
+            auth_method_name(network.auth_method)
+        );
+        Some(network)
+    }
+
+    /// Every access point answering for an SSID (mesh nodes, repeaters)
+    ///
+    /// Uses a probe naming the SSID, so hidden networks answer too.
+    async fn access_points(
+        &mut self,
+        ssid: &str,
+    ) -> Result<alloc::vec::Vec<ScanResult>, BoardError> {
+        if !self.controller.is_started().unwrap_or(false) {
+            self.start_station().await?;
         }
 
-        if self.controller.is_connected().unwrap_or(false) {
-            self.is_connected = true;
-            println!("[WIFI] Successfully connected to WiFi network: {}", ssid);
+        let found = self
+            .controller
+            .scan_with_config_async(ScanConfig {
+                ssid: Some(ssid),
+                show_hidden: true,
+                ..Default::default()
+            })
+            .await
+            .map_err(|_| BoardError::WiFiError)?;
+        Ok(found
+            .iter()
+            .filter_map(|ap| ScanResult::from_info(ap, ssid))
+            .collect())
+    }
 
-            // Try to get DHCP IP address
-            self.update_dhcp_ip();
+    /// Re-associate with a clearly stronger access point of the same network
+    ///
+    /// Meant for weak-signal periods on mesh networks, where the station would
+    /// otherwise stay on a distant node. Returns whether it moved. Does nothing
+    /// with `config::WIFI_BSSID` pinned. An error means the station is no
+    /// longer associated.
+    pub async fn roam(&mut self) -> Result<bool, BoardError> {
+        if config::WIFI_BSSID.is_some() || !self.is_connected() {
+            return Ok(false);
+        }
+        let Some(network) = self.joined.clone() else {
+            return Ok(false);
+        };
+        let Some((current_bssid, current_rssi)) = Self::current_access_point() else {
+            return Ok(false);
+        };
+
+        let Some(best) = self
+            .access_points(&network.ssid)
+            .await?
+            .into_iter()
+            .max_by_key(|n| n.rssi)
+        else {
+            return Ok(false);
+        };
+        if best.bssid == current_bssid
+            || (best.rssi as i16) < current_rssi as i16 + config::ROAM_MIN_IMPROVEMENT_DB as i16
+        {
+            return Ok(false);
+        }
+
+        println!(
+            "[WIFI] Roaming to {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x} on channel {} ({} dBm, was {} dBm)",
+            best.bssid[0],
+            best.bssid[1],
+            best.bssid[2],
+            best.bssid[3],
+            best.bssid[4],
+            best.bssid[5],
+            best.channel,
+            best.rssi,
+            current_rssi
+        );
+        self.controller.disconnect_async().await.ok();
+        self.is_connected = false;
+        self.join(
+            &network.ssid,
+            &network.password,
+            network.auth_method,
+            Some((best.bssid, Some(best.channel))),
+        )
+        .await?;
+        Ok(true)
+    }
+
+    /// Whether the signal is below `RSSI_DEGRADED_DBM` and hasn't recovered yet
+    pub fn is_signal_degraded(&self) -> bool {
+        self.signal_degraded
+    }
+
+    /// BSSID and signal strength of the joined access point
+    fn current_access_point() -> Option<([u8; 6], i8)> {
+        use esp_wifi_sys::include::{esp_wifi_sta_get_ap_info, wifi_ap_record_t};
+
+        // esp-wifi has no safe wrapper for this
+        let mut info: wifi_ap_record_t = unsafe { core::mem::zeroed() };
+        (unsafe { esp_wifi_sta_get_ap_info(&mut info) } == 0).then_some((info.bssid, info.rssi))
+    }
 
-            Ok(())
+    /// Set the modem-sleep (power save) mode
+    pub fn set_power_save(&mut self, mode: PowerSaveMode) -> Result<(), BoardError> {
+        self.controller
+            .set_power_saving(mode)
+            .map_err(|_| BoardError::WiFiError)
+    }
+
+    /// Sample the signal strength of the joined access point
+    ///
+    /// The sample is kept for `last_rssi`; returns None while disconnected.
+    pub fn sample_rssi(&mut self) -> Option<i8> {
+        let rssi = if self.is_connected() {
+            Self::current_access_point().map(|(_, rssi)| rssi)
         } else {
-            println!(
-                "[WIFI] Failed to connect to WiFi network '{}' after {} attempts",
-                ssid, attempts
-            );
-            Err(BoardError::WiFiError)
+            None
+        };
+        LAST_RSSI.store(rssi.unwrap_or(0), Ordering::Relaxed);
+        rssi
+    }
+
+    /// Sample the signal and report crossing the weak-signal thresholds
+    ///
+    /// Returns `NetworkDegraded` once the signal drops below `RSSI_DEGRADED_DBM`
+    /// and `NetworkRecovered` once it climbs back to `RSSI_RECOVERED_DBM`.
+    pub fn monitor_signal(&mut self) -> Option<SystemEvent> {
+        let rssi = self.sample_rssi()?;
+
+        if !self.signal_degraded && rssi < config::RSSI_DEGRADED_DBM {
+            println!("[WIFI] Weak signal: {} dBm", rssi);
+            self.signal_degraded = true;
+            Some(SystemEvent::NetworkDegraded)
+        } else if self.signal_degraded && rssi >= config::RSSI_RECOVERED_DBM {
+            println!("[WIFI] Signal recovered: {} dBm", rssi);
+            self.signal_degraded = false;
+            Some(SystemEvent::NetworkRecovered)
+        } else {
+            None
+        }
+    }
+
+    /// Tune the radio to a channel (station must not be associated)
+    pub fn set_channel(&mut self, channel: u8) -> Result<(), BoardError> {
+        use esp_wifi_sys::include::{
+            esp_wifi_set_channel, wifi_second_chan_t_WIFI_SECOND_CHAN_NONE,
+        };
+
+        // esp-wifi has no safe wrapper for this
+        match unsafe { esp_wifi_set_channel(channel, wifi_second_chan_t_WIFI_SECOND_CHAN_NONE) } {
+            0 => Ok(()),
+            _ => Err(BoardError::WiFiError),
         }
     }
 
@@ -129,40 +643,51 @@ impl<'a> WiFiManager<'a> {
         None
     }
 
+    /// Get the configured IPv6 address, if any
+    pub fn get_ipv6_address(&self) -> Option<Ipv6Address> {
+        if !self.is_connected {
+            return None;
+        }
+        self.stack
+            .as_ref()
+            .and_then(|stack| stack.config_v6())
+            .map(|config| config.address.address())
+    }
+
     /// Get detailed DHCP configuration information
     pub fn get_dhcp_info(&self) -> Option<DhcpInfo> {
-        if let Some(ref stack) = self.stack {
-            if let Some(config) = stack.config_v4() {
-                let ip = config.address.address().octets();
-                let subnet_mask = config.address.prefix_len();
-
-                // Convert prefix length to subnet mask
-                let mask_value = (!0u32) << (32 - subnet_mask);
-                let mask = [
-                    (mask_value >> 24) as u8,
-                    (mask_value >> 16) as u8,
-                    (mask_value >> 8) as u8,
-                    mask_value as u8,
-                ];
-
-                let mut dns_servers = Vec::new();
-                // Add DNS servers from config if available
-                for dns in config.dns_servers.iter() {
-                    let _ = dns_servers.push(dns.octets());
-                }
-
-                // If no DNS servers, add default
-                if dns_servers.is_empty() {
-                    let _ = dns_servers.push([8, 8, 8, 8]); // Google DNS as fallback
-                }
+        if let Some(ref stack) = self.stack
+            && let Some(config) = stack.config_v4()
+        {
+            let ip = config.address.address().octets();
+            let subnet_mask = config.address.prefix_len();
+
+            // Convert prefix length to subnet mask
+            let mask_value = (!0u32) << (32 - subnet_mask);
+            let mask = [
+                (mask_value >> 24) as u8,
+                (mask_value >> 16) as u8,
+                (mask_value >> 8) as u8,
+                mask_value as u8,
+            ];
+
+            let mut dns_servers = Vec::new();
+            // Add DNS servers from config if available
+            for dns in config.dns_servers.iter() {
+                let _ = dns_servers.push(dns.octets());
+            }
 
-                return Some(DhcpInfo {
-                    ip_address: ip,
-                    subnet_mask: mask,
-                    gateway: config.gateway.map(|gw| gw.octets()),
-                    dns_servers,
-                });
+            // If no DNS servers, add default
+            if dns_servers.is_empty() {
+                let _ = dns_servers.push([8, 8, 8, 8]); // Google DNS as fallback
             }
+
+            return Some(DhcpInfo {
+                ip_address: ip,
+                subnet_mask: mask,
+                gateway: config.gateway.map(|gw| gw.octets()),
+                dns_servers,
+            });
         }
         None
     }
@@ -215,23 +740,148 @@ impl<'a> WiFiManager<'a> {
     }
 
     /// Monitor WiFi connection status
-    pub fn monitor_connection(&mut self) -> Result<(), BoardError> {
+    ///
+    /// Returns the state machine event for a change since the last check.
+    pub fn monitor_connection(&mut self) -> Option<SystemEvent> {
         let current_status = self.controller.is_connected().unwrap_or(false);
 
         if self.is_connected && !current_status {
             println!("[WIFI] WiFi connection lost!");
             self.is_connected = false;
             // Note: Embassy-net stack will handle IP cleanup automatically
+            Some(SystemEvent::WiFiDisconnected)
         } else if !self.is_connected && current_status {
             println!("[WIFI] WiFi connection restored!");
             self.is_connected = true;
 
             // Update DHCP IP when connection is restored
             self.update_dhcp_ip();
+            Some(SystemEvent::WiFiConnected)
+        } else {
+            None
         }
+    }
 
-        Ok(())
+    /// Wait for the station to lose its association
+    ///
+    /// Driven by the controller's StaDisconnected event. Never resolves while
+    /// disconnected; reconnecting goes through `connect`.
+    pub async fn wait_for_link_change(&mut self) -> SystemEvent {
+        if !self.is_connected {
+            return core::future::pending().await;
+        }
+
+        // A drop that happened before we started waiting has already fired
+        if self.controller.is_connected().unwrap_or(false) {
+            self.controller
+                .wait_for_event(WifiEvent::StaDisconnected)
+                .await;
+        }
+
+        println!("[WIFI] WiFi connection lost!");
+        self.is_connected = false;
+        SystemEvent::WiFiDisconnected
+    }
+}
+
+/// Exponential backoff with jitter between failed connection rounds
+///
+/// The first attempt after a success is immediate. Each failure doubles the
+/// wait, starting at `WIFI_RECONNECT_INTERVAL_MS` and capped at
+/// `WIFI_RECONNECT_MAX_INTERVAL_MS`, with ±20% jitter so boards sharing an
+/// access point don't retry in lockstep.
+pub struct ReconnectBackoff {
+    rng: Rng,
+    failures: u32,
+    next_attempt: Option<Instant>,
+}
+
+impl ReconnectBackoff {
+    pub fn new(rng: Rng) -> Self {
+        Self {
+            rng,
+            failures: 0,
+            next_attempt: None,
+        }
+    }
+
+    /// Whether the next attempt is due
+    pub fn ready(&self) -> bool {
+        self.next_attempt.is_none_or(|at| Instant::now() >= at)
+    }
+
+    /// Schedule the next attempt after a failed round, returning the wait
+    pub fn record_failure(&mut self) -> Duration {
+        let base = config::WIFI_RECONNECT_INTERVAL_MS as u64;
+        let interval = base
+            .saturating_mul(1 << self.failures.min(16))
+            .min(config::WIFI_RECONNECT_MAX_INTERVAL_MS as u64);
+        // Uniform in [-20%, +20%]
+        let spread = interval * 2 / 5;
+        let jitter = (self.rng.random() as u64 % (spread + 1)) as i64 - (spread / 2) as i64;
+        let wait = Duration::from_millis(interval.saturating_add_signed(jitter));
+
+        self.failures = self.failures.saturating_add(1);
+        self.next_attempt = Some(Instant::now() + wait);
+        wait
+    }
+
+    /// Forget past failures after a successful connection
+    pub fn reset(&mut self) {
+        self.failures = 0;
+        self.next_attempt = None;
+    }
+}
+
+/// Host name sent in DHCP requests (option 12)
+///
+/// `config::DHCP_HOSTNAME`, else derived from the provisioned device name or
+/// the default mDNS instance name, reduced to lowercase letters, digits and
+/// hyphens so routers accept it.
+pub fn dhcp_hostname(mac: &[u8; 6]) -> heapless::String<32> {
+    let name = if config::DHCP_HOSTNAME.is_empty() {
+        crate::provisioning::load_device_name()
+            .unwrap_or_else(|| crate::mdns::default_instance_name(mac))
+    } else {
+        heapless::String::try_from(config::DHCP_HOSTNAME).unwrap_or_default()
+    };
+
+    let mut hostname = heapless::String::new();
+    for c in name.chars() {
+        let c = if c.is_ascii_alphanumeric() {
+            c.to_ascii_lowercase()
+        } else {
+            '-'
+        };
+        // Collapse runs of separators and skip leading ones
+        if c == '-' && hostname.chars().last().is_none_or(|last| last == '-') {
+            continue;
+        }
+        if hostname.push(c).is_err() {
+            break;
+        }
     }
+    while hostname.ends_with('-') {
+        hostname.pop();
+    }
+    if hostname.is_empty() {
+        let _ = hostname.push_str(config::MDNS_HOSTNAME);
+    }
+    hostname
+}
+
+/// IPv6 link-local address derived from a MAC address (modified EUI-64)
+pub fn link_local_ipv6(mac: [u8; 6]) -> Ipv6Address {
+    Ipv6Address::new(
+        0xfe80,
+        0,
+        0,
+        0,
+        u16::from_be_bytes([mac[0] ^ 0x02, mac[1]]),
+        u16::from_be_bytes([mac[2], 0xff]),
+        u16::from_be_bytes([0xfe, mac[3]]),
+        u16::from_be_bytes([mac[4], mac[5]]),
+    )
 }
 
 /// Create WiFi configuration from environment variables
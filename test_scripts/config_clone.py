@@ -0,0 +1,61 @@
+#!/usr/bin/env python3
+"""
+Export the stored configuration of a board to a file, or import it into another one
+
+Usage:
+    python3 config_clone.py export 192.168.1.100 board.cfg
+    python3 config_clone.py import 192.168.1.101 board.cfg
+
+The file is the board's own configuration record (versioned, CRC-checked). It
+lists the Wi-Fi networks without their passwords; a board keeps the passwords it
+already has for those networks.
+"""
+
+import socket
+import sys
+
+BOARD_PORT = 23042
+CONFIG_HEADER = 0x1E
+OP_GET = 0x00
+OP_SET = 0x01
+KEY_ALL = 0x00
+
+STATUS = {0: "ok", 1: "unknown key", 2: "invalid value", 3: "storage failed"}
+
+
+def request(ip, message):
+    """Send a config packet and return (status, payload)"""
+    sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+    sock.settimeout(3.0)
+    sock.sendto(message, (ip, BOARD_PORT))
+    response, _ = sock.recvfrom(2048)
+    sock.close()
+    if len(response) < 3 or response[0] != CONFIG_HEADER:
+        raise RuntimeError(f"Unexpected response: {response.hex()}")
+    return response[1], response[3:]
+
+
+def main():
+    if len(sys.argv) != 4 or sys.argv[1] not in ("export", "import"):
+        print(__doc__)
+        sys.exit(1)
+    command, ip, path = sys.argv[1:]
+
+    if command == "export":
+        status, blob = request(ip, bytes([CONFIG_HEADER, OP_GET, KEY_ALL]))
+        if status != 0:
+            sys.exit(f"❌ Export failed: {STATUS.get(status, status)}")
+        with open(path, "wb") as f:
+            f.write(blob)
+        print(f"✅ Exported {len(blob)} bytes from {ip} to {path}")
+    else:
+        with open(path, "rb") as f:
+            blob = f.read()
+        status, _ = request(ip, bytes([CONFIG_HEADER, OP_SET, KEY_ALL]) + blob)
+        if status != 0:
+            sys.exit(f"❌ Import failed: {STATUS.get(status, status)}")
+        print(f"✅ Imported {path} into {ip} - restart the board to apply the LED count and port")
+
+
+if __name__ == "__main__":
+    main()
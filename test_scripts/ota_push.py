@@ -0,0 +1,81 @@
+#!/usr/bin/env python3
+"""
+Push a firmware image to a board over the local network
+
+Usage:
+    python3 ota_push.py 192.168.1.100 board-rs.bin
+
+The image is the app binary from `espflash save-image`. An interrupted push
+picks up where it stopped when run again with the same image.
+"""
+
+import socket
+import struct
+import sys
+import zlib
+
+PUSH_PORT = 23044
+CHUNK_SIZE = 1024
+MAX_RESENDS = 5
+
+STATUS = {
+    0: "ok",
+    1: "resend",
+    2: "busy",
+    3: "invalid",
+    4: "flash error",
+    5: "image CRC mismatch",
+    6: "complete",
+}
+
+
+def receive_reply(sock):
+    """Read a [status][next offset:4] reply"""
+    reply = b""
+    while len(reply) < 5:
+        part = sock.recv(5 - len(reply))
+        if not part:
+            raise RuntimeError("Connection closed by the board")
+        reply += part
+    return reply[0], struct.unpack(">I", reply[1:])[0]
+
+
+def main():
+    if len(sys.argv) != 3:
+        print(__doc__)
+        sys.exit(1)
+    ip, path = sys.argv[1:]
+    with open(path, "rb") as f:
+        image = f.read()
+    image_crc = zlib.crc32(image)
+
+    sock = socket.create_connection((ip, PUSH_PORT), timeout=10.0)
+    sock.sendall(b"OTAP" + struct.pack(">II", len(image), image_crc))
+    status, offset = receive_reply(sock)
+    if status != 0:
+        sys.exit(f"❌ Push refused: {STATUS.get(status, status)}")
+    if offset:
+        print(f"Resuming at {offset} of {len(image)} bytes")
+
+    resends = 0
+    while True:
+        data = image[offset : offset + CHUNK_SIZE]
+        sock.sendall(struct.pack(">IH", offset, len(data)) + data + struct.pack(">I", zlib.crc32(data)))
+        status, offset = receive_reply(sock)
+        if status == 6:
+            print(f"\n✅ {len(image)} bytes pushed - the board restarts into the new firmware")
+            break
+        if status == 1:
+            resends += 1
+            if resends > MAX_RESENDS:
+                sys.exit("\n❌ Too many resends")
+            continue
+        if status != 0:
+            sys.exit(f"\n❌ Push failed: {STATUS.get(status, status)}")
+        resends = 0
+        print(f"\r{offset * 100 // len(image)}% ({offset} / {len(image)} bytes)", end="", flush=True)
+    sock.close()
+
+
+if __name__ == "__main__":
+    main()